@@ -31,29 +31,48 @@ extern crate aleph_quickjs as qjs;
 
 use std::env::{current_dir, current_exe};
 use std::io;
+use std::io::Read;
 use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
 
 use aleph_alloc::instrumentation::{
     IAllocationCategory, Instrumented, is_instrumentation_enabled, system,
 };
 use aleph_nstr::NStr;
 use aleph_target::{Architecture, BuildType, Platform};
+use aleph_vfs::directory_layer::DirectoryLayer;
+use aleph_vfs::metadata::FileType;
+use aleph_vfs::{IRouterExt, LayerDesc, Router};
 use camino::{Utf8Path, Utf8PathBuf};
 use thiserror::Error;
 
+/// Runs the JavaScript config scripts found in the 'configs' folder.
+///
+/// Every '.js' file at the top level of the folder is treated as a config script and is evaluated
+/// as an ES module, in alphabetical order. Scripts can `import` shared code from other files in
+/// the folder. Shared modules should be kept in a sub-folder (i.e. 'configs/common/') so they are
+/// not also run as config scripts themselves.
 pub struct ConfigRunner {
     runtime: qjs::Runtime,
 
     context: Option<qjs::Context>,
 
-    /// The directory we will load config scripts from
-    config_dir: Utf8PathBuf,
+    /// A vfs with the directory we load config scripts from mounted at '/configs'. Shared with the
+    /// runtime's module loader so configs can import other modules from the same folder.
+    vfs: Arc<Router>,
 
     /// A reference to the quickjs object that stores the config
     config_object: Option<qjs::RefValue>,
+
+    /// The maximum amount of time a single config script is allowed to run for
+    time_budget: Option<Duration>,
 }
 
 impl ConfigRunner {
+    /// The default value for [`ConfigRunner::set_time_budget`].
+    pub const DEFAULT_TIME_BUDGET: Duration = Duration::from_secs(5);
+
     pub fn new() -> io::Result<Self> {
         Config::with(|| {
             let runtime = if is_instrumentation_enabled() {
@@ -65,35 +84,46 @@ impl ConfigRunner {
             let context = runtime.new_context().unwrap();
             let config_object = context.new_object().unwrap();
             let config_dir = find_folder_in_search_path("configs")?;
+            let vfs = Router::new([LayerDesc {
+                mount_name: "configs",
+                layer: DirectoryLayer::new(config_dir),
+            }])?;
+            let vfs = Arc::new(vfs);
+            runtime.set_module_loader(qjs::RouterModuleLoader::new(vfs.clone()));
 
             let out = Self {
                 runtime,
                 context: Some(context),
-                config_dir,
+                vfs,
                 config_object: Some(config_object),
+                time_budget: Some(Self::DEFAULT_TIME_BUDGET),
             };
             Ok(out)
         })
     }
 
+    /// Sets the maximum amount of time each config script is allowed to run for. A script that runs
+    /// past its budget is interrupted and fails with [`RunConfigError::Js`]. [`None`] allows
+    /// scripts to run forever.
+    pub fn set_time_budget(&mut self, budget: Option<Duration>) {
+        self.time_budget = budget;
+    }
+
     pub fn run_all_configs(&mut self) -> Result<(), RunConfigError> {
         Config::with(|| {
             // Collect all .js config files in the config directory into a list
             let mut items = Vec::new();
-            for item in self.config_dir.read_dir_utf8()? {
-                let item = item?;
-
+            for item in self.vfs.read_dir("/configs")? {
                 // Skip non js files
-                if !item.file_name().ends_with(".js") {
+                if !item.name().ends_with(".js") {
                     continue;
                 }
 
-                let file_type = item.file_type()?;
-                if !file_type.is_file() && !file_type.is_symlink() {
+                if item.file_type() != FileType::File {
                     continue;
                 }
 
-                items.push(item.file_name().to_string());
+                items.push(item.name().to_string());
             }
 
             // Sort in alphabetical order to get a stable config run order
@@ -113,7 +143,9 @@ impl ConfigRunner {
             let script = self.load_config_script(name)?;
             let script_nstr = NStr::from_str(&script).unwrap();
 
-            let filename = format!("{name}\0");
+            // The filename is the script's full vfs path so relative imports resolve against the
+            // configs folder.
+            let filename = format!("/configs/{name}\0");
             let filename = NStr::from_str(&filename).unwrap();
 
             // We create a new context for every script so they don't polute eachother's global
@@ -132,38 +164,47 @@ impl ConfigRunner {
             // Provide the 'Environment' object which contains info about the build+platform+arch
             Self::setup_global_environment(&context)?;
 
-            // And finally we evaluate the script. The script is expected to write the config into
-            // the 'Configs' global.
+            // And finally we evaluate the script as a module. The script is expected to write the
+            // config into the 'Configs' global.
+            //
+            // The script runs under our time budget so a script stuck in a loop fails instead of
+            // hanging the whole process.
             log::trace!("Running {filename}");
-            let _ = context
-                .eval(script_nstr, filename, qjs::raw::JSEvalFlags::STRICT)
-                .unwrap();
+            if let Some(budget) = self.time_budget {
+                let budget = qjs::ExecutionBudget::timeout(budget);
+                self.runtime.set_execution_budget(budget);
+            }
+            let result = context.eval_module(script_nstr, filename).map(drop);
+            self.runtime.clear_interrupt_handler();
+            result?;
 
             Ok(())
         })
     }
 
-    pub fn finalize(mut self) -> serde_json::Map<String, serde_json::Value> {
+    /// Reads the final config object out of the JS runtime, applying any command line overrides,
+    /// and tears the runtime down.
+    ///
+    /// Fails with [`RunConfigError::Deserialize`] if the config object can't be represented as
+    /// JSON, e.g. because it contains itself.
+    pub fn finalize(
+        mut self,
+    ) -> Result<serde_json::Map<String, serde_json::Value>, RunConfigError> {
         Config::with(|| {
             let context = self.context.as_ref().unwrap();
             let config_object = self.config_object.as_ref().unwrap();
-            let json = context.to_json(config_object).unwrap();
-            let mut json = match json {
-                serde_json::Value::Null
-                | serde_json::Value::Bool(_)
-                | serde_json::Value::Number(_)
-                | serde_json::Value::String(_)
-                | serde_json::Value::Array(_) => panic!("Unexpected serde_json::Value type"),
-                serde_json::Value::Object(v) => v,
-            };
-            Self::command_line_overrides(&mut json);
+            let json: Result<serde_json::Map<String, serde_json::Value>, _> =
+                context.deserialize(config_object);
 
             // Destroy the context and force a GC to clean up as best we can
             drop(self.config_object.take());
             drop(self.context.take());
+            self.runtime.clear_module_loader();
             self.runtime.gc();
 
-            json
+            let mut json = json?;
+            Self::command_line_overrides(&mut json);
+            Ok(json)
         })
     }
 }
@@ -283,14 +324,19 @@ impl ConfigRunner {
 impl ConfigRunner {
     /// Internal function for loading the config script under the given name from the script folder.
     fn load_config_script(&self, name: &str) -> Result<String, RunConfigError> {
-        let config = self.config_dir.join(name);
+        let config = format!("/configs/{name}");
 
         // Check if the config file exists
-        if !config.is_file() {
-            return Err(RunConfigError::NoConfig);
+        match self.vfs.metadata(config.as_str()) {
+            Ok(v) if v.is_file() => {}
+            Ok(_) => return Err(RunConfigError::NoConfig),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Err(RunConfigError::NoConfig),
+            Err(e) => return Err(e.into()),
         }
 
-        let mut string = std::fs::read_to_string(config)?;
+        let file = self.vfs.open(config.as_str())?;
+        let mut string = String::new();
+        file.reader().read_to_string(&mut string)?;
         string.push('\0');
         Ok(string)
     }
@@ -304,6 +350,9 @@ pub enum RunConfigError {
     #[error("A JS error occured: {0}")]
     Js(String),
 
+    #[error("Failed to read the config object: {0}")]
+    Deserialize(#[from] qjs::SerdeError),
+
     #[error("No config with the given name was found")]
     NoConfig,
 }
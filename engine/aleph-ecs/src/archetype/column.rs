@@ -33,14 +33,23 @@ use std::ptr::NonNull;
 
 use aleph_alloc::alloc::{Allocator, Global};
 
+use crate::tick::ComponentTicks;
+
 /// Basic 'stretchy buffer' data structure. Provides an aligned buffer allocated in multiples of
 /// some element type.
 ///
 /// Does not manage object life times. This just manages a growable byte buffer.
+///
+/// Alongside the element buffer the column manages a second buffer with a [`ComponentTicks`] for
+/// each element, which is used to implement change detection. The tick buffer always has the same
+/// count as the element buffer and is moved in lockstep with it.
 pub struct Column<A: Allocator = Global> {
     /// Pointer to the buffer that the column manages.
     ptr: NonNull<u8>,
 
+    /// Pointer to the change ticks buffer that the column manages.
+    ticks: NonNull<ComponentTicks>,
+
     /// The layout of a single element in the column. The API allocates in multiples of this layout.
     element_layout: Layout,
 
@@ -58,6 +67,7 @@ impl<A: Allocator> Column<A> {
     pub const fn new_in(element_layout: Layout, allocator: A) -> Self {
         Self {
             ptr: NonNull::dangling(),
+            ticks: NonNull::dangling(),
             element_layout,
             count: 0,
             allocator,
@@ -83,6 +93,37 @@ impl<A: Allocator> Column<A> {
         unsafe { Some(self.ptr.byte_add(i * self.element_layout.size())) }
     }
 
+    /// Gets the column's change ticks buffer pointer, returning [`None`] if the column is currently
+    /// empty.
+    pub const fn ticks(&self) -> Option<NonNull<ComponentTicks>> {
+        if self.count > 0 {
+            Some(self.ticks)
+        } else {
+            None
+        }
+    }
+
+    /// Get a pointer to the change ticks of the `i'th` element of the column, returning [`None`] if
+    /// the index is out of bounds.
+    pub const fn get_ticks_at_index(&self, i: usize) -> Option<NonNull<ComponentTicks>> {
+        if i >= self.count {
+            return None;
+        }
+
+        // Safety: The tick buffer is always valid for self.count elements, same as the element
+        //         buffer.
+        unsafe { Some(self.ticks.add(i)) }
+    }
+
+    /// Get the change ticks for every element the column has allocated space for.
+    ///
+    /// Ticks for elements that haven't been written yet are zeroed.
+    pub fn ticks_mut(&mut self) -> &mut [ComponentTicks] {
+        // Safety: The tick buffer is a zero-initialized allocation valid for self.count elements,
+        //         and an all-zero ComponentTicks is valid. The mutable borrow makes this exclusive.
+        unsafe { NonNull::slice_from_raw_parts(self.ticks, self.count).as_mut() }
+    }
+
     /// Resize the column so it has space for 'new_count' elements.
     ///
     /// This is a wrapper over [`Column::grow_to_fit`] and [`Column::shrink_to_fit`] that selects
@@ -104,23 +145,16 @@ impl<A: Allocator> Column<A> {
             return;
         }
 
-        let new_layout = self.layout_for_count(new_count).unwrap();
-        let buffer = if self.count == 0 {
-            match self.allocator.allocate_zeroed(new_layout) {
-                Ok(v) => v,
-                Err(_) => handle_alloc_error(new_layout),
-            }
-        } else {
-            let old_layout = self.layout_for_count(self.count).unwrap();
-            unsafe {
-                match self.allocator.grow_zeroed(self.ptr, old_layout, new_layout) {
-                    Ok(v) => v,
-                    Err(_) => handle_alloc_error(new_layout),
-                }
-            }
-        };
+        // Safety: Both buffers are valid allocations for self.count elements, or dangling if
+        //         self.count is 0.
+        unsafe {
+            let old_count = self.count;
+            self.ptr = self.grow_buffer(self.ptr, self.element_layout, old_count, new_count);
+            self.ticks = self
+                .grow_buffer(self.ticks.cast(), TICKS_LAYOUT, old_count, new_count)
+                .cast();
+        }
         self.count = new_count;
-        self.ptr = buffer.cast();
     }
 
     /// Request the column shrink to only provide enough space for 'new_count' elements.
@@ -132,32 +166,20 @@ impl<A: Allocator> Column<A> {
             return;
         }
 
-        let old_layout = self.layout_for_count(self.count).unwrap();
-        let buffer = if new_count == 0 {
-            // There's no situation where we can reach this code with self.count == 0, so we don't
-            // need to handle the case where we don't have a valid allocation. We only need to
-            // concern ourselves with deallocating an existing allocation.
-            unsafe {
-                self.allocator.deallocate(self.ptr, old_layout);
-            }
-            NonNull::dangling()
-        } else {
-            // There's no situation where we can reach this code with self.count == 0, so we don't
-            // need to handle the case where we must create the initial allocation. We only need to
-            // concern ourselves with shrinking an existing allocation.
-            let new_layout = self.layout_for_count(new_count).unwrap();
-            let old_layout = self.layout_for_count(self.count).unwrap();
-            let buffer = unsafe {
-                match self.allocator.shrink(self.ptr, old_layout, new_layout) {
-                    Ok(v) => v,
-                    Err(_) => handle_alloc_error(new_layout),
-                }
-            };
-            buffer.cast()
-        };
-
+        // There's no situation where we can reach this code with self.count == 0, so we don't
+        // need to handle the case where we don't have a valid allocation. We only need to concern
+        // ourselves with shrinking or deallocating the existing allocations.
+        unsafe {
+            let old_count = self.count;
+            self.ptr = self
+                .shrink_buffer(self.ptr, self.element_layout, old_count, new_count)
+                .unwrap_or(NonNull::dangling());
+            self.ticks = self
+                .shrink_buffer(self.ticks.cast(), TICKS_LAYOUT, old_count, new_count)
+                .map(NonNull::cast)
+                .unwrap_or(NonNull::dangling());
+        }
         self.count = new_count;
-        self.ptr = buffer;
     }
 
     /// Gets the 'element_layout' the column was created with.
@@ -169,25 +191,26 @@ impl<A: Allocator> Column<A> {
     /// element other than the last will move the element at the end of the list into the place of
     /// the element to be removed.
     ///
+    /// The column doesn't track how many of its elements are live, so the caller must provide the
+    /// number of live elements in 'len'. The column's allocation is left untouched.
+    ///
     /// # Warning
     ///
     /// Does not drop the element, that is the caller's responsibility.
-    pub fn swap_remove(&mut self, index: usize) {
+    pub fn swap_remove(&mut self, index: usize, len: usize) {
         assert!(
-            index < self.count,
-            "Index '{index}' is out of bounds of column."
+            len <= self.count,
+            "Length '{len}' is out of bounds of column."
         );
+        assert!(index < len, "Index '{index}' is out of bounds of column.");
 
-        let last_index = self.count - 1; // Can't be 0 here so can't underflow.
-        if index == last_index {
-            // If we swap remove on the last element is just a pop operation.
-            self.pop();
-        } else {
-            // Otherwise we copy the last element over the top of the one we want to remove.
+        let last_index = len - 1; // Can't be 0 here so can't underflow.
+        if index != last_index {
+            // We copy the last element over the top of the one we want to remove.
             let remove_offset = index * self.element_layout.size();
             let last_offset = last_index * self.element_layout.size();
 
-            // Safety: Bounds checked by the above assert. Column must always have an allocation
+            // Safety: Bounds checked by the above asserts. Column must always have an allocation
             //         in self.ptr valid for at least self.count elements. Mutable borrow means we
             //         have valid access and this branch is only taken if index != last index so
             //         the copy is always non-overlapping.
@@ -195,11 +218,12 @@ impl<A: Allocator> Column<A> {
                 let remove = self.ptr.add(remove_offset);
                 let last = self.ptr.add(last_offset);
                 remove.copy_from_nonoverlapping(last, self.element_layout.size());
-            };
 
-            // Decrement count to 'pop' the swapped element off the end. It's impossible for count
-            // to == 0 if we reach this code so this can't underflow.
-            self.count -= 1;
+                // The ticks follow the element they belong to
+                self.ticks
+                    .add(index)
+                    .write(self.ticks.add(last_index).read());
+            };
         }
     }
 
@@ -215,23 +239,83 @@ impl<A: Allocator> Column<A> {
         self.count = self.count.saturating_sub(1);
     }
 
-    /// Project the single element layout on self to provide a [`Layout`] for an array of 'count'
-    /// elements.
+    /// Grows a buffer of 'old_count' elements of 'element_layout' to fit 'new_count' elements,
+    /// zeroing the new space. A new buffer is allocated if 'old_count' is 0.
+    ///
+    /// # Safety
+    ///
+    /// 'ptr' must be an allocation made by this column's allocator for 'old_count' elements, if
+    /// 'old_count' is not 0.
+    unsafe fn grow_buffer(
+        &self,
+        ptr: NonNull<u8>,
+        element_layout: Layout,
+        old_count: usize,
+        new_count: usize,
+    ) -> NonNull<u8> {
+        let new_layout = layout_for_count(element_layout, new_count).unwrap();
+        let buffer = if old_count == 0 {
+            self.allocator.allocate_zeroed(new_layout)
+        } else {
+            let old_layout = layout_for_count(element_layout, old_count).unwrap();
+            unsafe { self.allocator.grow_zeroed(ptr, old_layout, new_layout) }
+        };
+        match buffer {
+            Ok(v) => v.cast(),
+            Err(_) => handle_alloc_error(new_layout),
+        }
+    }
+
+    /// Shrinks a buffer of 'old_count' elements of 'element_layout' to fit 'new_count' elements.
+    /// The buffer is freed, returning [`None`], if 'new_count' is 0.
     ///
-    /// If 'count' is too large and produces an allocation size too large this will return an error.
-    fn layout_for_count(&self, count: usize) -> Result<Layout, LayoutError> {
-        let size = self.element_layout.size();
-        let size = size.saturating_mul(count);
-        Layout::from_size_align(size, self.element_layout.align())
+    /// # Safety
+    ///
+    /// 'ptr' must be an allocation made by this column's allocator for 'old_count' elements, and
+    /// 'old_count' must not be 0.
+    unsafe fn shrink_buffer(
+        &self,
+        ptr: NonNull<u8>,
+        element_layout: Layout,
+        old_count: usize,
+        new_count: usize,
+    ) -> Option<NonNull<u8>> {
+        let old_layout = layout_for_count(element_layout, old_count).unwrap();
+        if new_count == 0 {
+            unsafe {
+                self.allocator.deallocate(ptr, old_layout);
+            }
+            return None;
+        }
+
+        let new_layout = layout_for_count(element_layout, new_count).unwrap();
+        match unsafe { self.allocator.shrink(ptr, old_layout, new_layout) } {
+            Ok(v) => Some(v.cast()),
+            Err(_) => handle_alloc_error(new_layout),
+        }
     }
 }
 
+/// The layout of a single element in a column's change tick buffer.
+const TICKS_LAYOUT: Layout = Layout::new::<ComponentTicks>();
+
+/// Project a single element layout to provide a [`Layout`] for an array of 'count' elements.
+///
+/// If 'count' is too large and produces an allocation size too large this will return an error.
+fn layout_for_count(element_layout: Layout, count: usize) -> Result<Layout, LayoutError> {
+    let size = element_layout.size();
+    let size = size.saturating_mul(count);
+    Layout::from_size_align(size, element_layout.align())
+}
+
 impl<A: Allocator> Drop for Column<A> {
     fn drop(&mut self) {
         if self.count > 0 {
             unsafe {
-                self.allocator
-                    .deallocate(self.ptr, self.layout_for_count(self.count).unwrap())
+                let layout = layout_for_count(self.element_layout, self.count).unwrap();
+                self.allocator.deallocate(self.ptr, layout);
+                let layout = layout_for_count(TICKS_LAYOUT, self.count).unwrap();
+                self.allocator.deallocate(self.ticks.cast(), layout);
             }
         }
     }
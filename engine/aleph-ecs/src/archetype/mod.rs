@@ -276,7 +276,7 @@ impl Archetype {
 
         // Now perform the swap-remove operation on each component column in the archetype.
         for column in self.columns.iter_mut() {
-            column.swap_remove(index);
+            column.swap_remove(index, self.len);
         }
 
         self.len -= 1;
@@ -0,0 +1,187 @@
+//
+//
+// This file is a part of Aleph
+//
+// https://github.com/nathanvoglsam/aleph
+//
+// MIT License
+//
+// Copyright (c) 2020 Aleph Engine
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+#[cfg(test)]
+mod tests;
+
+use crate::component::Component;
+use crate::entity::EntityHandle;
+use crate::world::World;
+use crate::world::insertion::{RustEntityInsertionInfo, SingleEntityInsertionInfo};
+
+/// Type alias for a single recorded command.
+type BoxedCommand = Box<dyn FnOnce(&mut World) + Send + Sync>;
+
+/// A buffer that records structural changes to a [`World`] so they can be applied later.
+///
+/// Structural changes (spawning or removing entities, adding or removing components) need
+/// exclusive access to the world. That makes it impossible to spawn or despawn entities while
+/// iterating a query, or from a system that runs in parallel with other systems. A
+/// [`CommandBuffer`] records the changes instead, and they are applied in the order they were
+/// recorded by calling [`CommandBuffer::apply`] at a sync point where exclusive access is
+/// available.
+///
+/// Each thread, or system, should record into its own buffer. Buffers are [`Send`] so they can be
+/// collected at the sync point.
+///
+/// # Failures
+///
+/// Commands are validated when they are applied, not when they are recorded. An earlier command
+/// may invalidate the entity a later command refers to. Commands that refer to an entity that is
+/// no longer live are silently skipped, matching the `None`/`Err` results of the equivalent
+/// [`World`] functions.
+#[derive(Default)]
+pub struct CommandBuffer {
+    commands: Vec<BoxedCommand>,
+}
+
+impl CommandBuffer {
+    /// Constructs a new, empty command buffer.
+    pub const fn new() -> Self {
+        Self {
+            commands: Vec::new(),
+        }
+    }
+
+    /// Returns the number of recorded commands that are waiting to be applied.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    /// Returns whether there are no commands waiting to be applied.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    /// Records an arbitrary command. The function will be called with exclusive access to the
+    /// world when the buffer is applied.
+    ///
+    /// This can be used to express changes that depend on the result of another change, like
+    /// adding components to an entity spawned by the same command.
+    #[inline]
+    pub fn push(&mut self, command: impl FnOnce(&mut World) + Send + Sync + 'static) {
+        self.commands.push(Box::new(command));
+    }
+
+    /// Records a [`World::spawn_entity`] command.
+    #[inline]
+    pub fn spawn_entity(&mut self) {
+        self.push(|world| {
+            world.spawn_entity();
+        });
+    }
+
+    /// Records a [`World::insert`] command, spawning a single entity with the given components.
+    #[inline]
+    pub fn insert<T>(&mut self, components: T)
+    where
+        T: RustEntityInsertionInfo + SingleEntityInsertionInfo + Send + Sync + 'static,
+    {
+        self.push(move |world| {
+            world.insert(components);
+        });
+    }
+
+    /// Records a [`World::bulk_insert`] command, spawning a batch of entities with the given
+    /// component columns.
+    #[inline]
+    pub fn bulk_insert<T: RustEntityInsertionInfo + Send + Sync + 'static>(
+        &mut self,
+        components: T,
+    ) {
+        self.push(move |world| {
+            world.bulk_insert(components);
+        });
+    }
+
+    /// Records a [`World::add_component`] command.
+    #[inline]
+    pub fn add_component<T: Component>(&mut self, entity: EntityHandle, v: T) {
+        self.push(move |world| {
+            let _ = world.add_component(entity, v);
+        });
+    }
+
+    /// Records a [`World::remove_component`] command. The removed component is dropped.
+    #[inline]
+    pub fn remove_component<T: Component>(&mut self, entity: EntityHandle) {
+        self.push(move |world| {
+            world.remove_component::<T>(entity);
+        });
+    }
+
+    /// Records a [`World::remove_entity`] command.
+    #[inline]
+    pub fn remove_entity(&mut self, entity: EntityHandle) {
+        self.push(move |world| {
+            world.remove_entity(entity);
+        });
+    }
+
+    /// Records a [`World::remove_entity_recursive`] command.
+    #[inline]
+    pub fn remove_entity_recursive(&mut self, entity: EntityHandle) {
+        self.push(move |world| {
+            world.remove_entity_recursive(entity);
+        });
+    }
+
+    /// Records a [`World::set_parent`] command. The command is skipped if it would form a cycle.
+    #[inline]
+    pub fn set_parent(&mut self, child: EntityHandle, parent: EntityHandle) {
+        self.push(move |world| {
+            let _ = world.set_parent(child, parent);
+        });
+    }
+
+    /// Records a [`World::remove_parent`] command.
+    #[inline]
+    pub fn remove_parent(&mut self, child: EntityHandle) {
+        self.push(move |world| {
+            world.remove_parent(child);
+        });
+    }
+
+    /// Applies all the recorded commands to the world, in the order they were recorded. The buffer
+    /// will be empty afterward, but keeps its allocation so it can be reused.
+    pub fn apply(&mut self, world: &mut World) {
+        for command in self.commands.drain(..) {
+            command(world);
+        }
+    }
+
+    /// Moves all the commands recorded in 'other' to the end of this buffer, leaving 'other'
+    /// empty.
+    #[inline]
+    pub fn append(&mut self, other: &mut CommandBuffer) {
+        self.commands.append(&mut other.commands);
+    }
+}
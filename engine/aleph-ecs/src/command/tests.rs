@@ -0,0 +1,126 @@
+//
+//
+// This file is a part of Aleph
+//
+// https://github.com/nathanvoglsam/aleph
+//
+// MIT License
+//
+// Copyright (c) 2020 Aleph Engine
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use crate::command::CommandBuffer;
+use crate::register_component;
+use crate::world::World;
+use crate::world::query::{Read, Write};
+
+#[derive(Clone, Default, PartialEq, Debug)]
+struct Health(u32);
+register_component!(Health);
+
+#[derive(Clone, Default, PartialEq, Debug)]
+struct Dead;
+register_component!(Dead);
+
+#[test]
+fn deferred_structural_changes_test() {
+    let mut world = World::new();
+    let ids = world.bulk_insert(([Health(10), Health(0), Health(0), Health(5)],));
+
+    // Structural changes can be recorded while the world is mutably borrowed by a query
+    let mut commands = CommandBuffer::new();
+    for (id, health) in world.query_mut::<Write<Health>>() {
+        if health.0 == 0 {
+            commands.add_component(id, Dead);
+        } else {
+            health.0 -= 1;
+            commands.insert((Health(health.0 * 10),));
+        }
+    }
+    assert_eq!(commands.len(), 4);
+    assert_eq!(world.len(), 4);
+    assert!(!world.has_component::<Dead>(ids[1]));
+
+    commands.apply(&mut world);
+    assert!(commands.is_empty());
+    assert_eq!(world.len(), 6);
+    assert!(world.has_component::<Dead>(ids[1]));
+    assert!(world.has_component::<Dead>(ids[2]));
+    assert!(!world.has_component::<Dead>(ids[0]));
+
+    let mut spawned: Vec<_> = world
+        .query::<Read<Health>>()
+        .map(|(_, v)| v.0)
+        .filter(|&v| v >= 40)
+        .collect();
+    spawned.sort();
+    assert_eq!(spawned, vec![40, 90]);
+
+    for id in ids.iter().copied() {
+        if world.has_component::<Dead>(id) {
+            commands.remove_entity(id);
+        }
+    }
+    commands.remove_component::<Health>(ids[0]);
+    commands.apply(&mut world);
+    assert_eq!(world.len(), 4);
+    assert!(!world.is_live(ids[1]));
+    assert!(!world.is_live(ids[2]));
+    assert!(!world.has_component::<Health>(ids[0]));
+}
+
+#[test]
+fn deferred_command_order_test() {
+    let mut world = World::new();
+    let id = world.insert((Health(1),));
+
+    // Commands apply in order, so later commands see the effect of earlier ones. Commands on
+    // entities that are no longer live are skipped.
+    let mut commands = CommandBuffer::new();
+    commands.add_component(id, Health(2));
+    commands.push(move |world| {
+        world.get_component_mut::<Health>(id).unwrap().0 *= 10;
+    });
+    commands.remove_entity(id);
+    commands.add_component(id, Dead);
+
+    // Buffers recorded on other threads can be merged together before applying them
+    let mut other = std::thread::scope(|s| {
+        s.spawn(|| {
+            let mut commands = CommandBuffer::new();
+            commands.bulk_insert(([Health(3), Health(4)],));
+            commands.spawn_entity();
+            commands
+        })
+        .join()
+        .unwrap()
+    });
+    commands.push(move |world| {
+        assert!(!world.is_live(id));
+    });
+    commands.append(&mut other);
+    assert!(other.is_empty());
+
+    commands.apply(&mut world);
+    assert!(!world.is_live(id));
+    assert_eq!(world.len(), 3);
+    assert_eq!(world.query::<Read<Health>>().count(), 2);
+}
@@ -76,6 +76,14 @@ pub unsafe trait Component: Sized + Send + Sync + 'static {
 #[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug)]
 pub struct ComponentId(pub(crate) u32);
 
+impl ComponentId {
+    /// Returns the raw integer value of the ID.
+    #[inline(always)]
+    pub const fn as_u32(self) -> u32 {
+        self.0
+    }
+}
+
 /// FFI portable type description table. Contains all the information exposed by [`Component`]
 /// wrapped in a neat little struct that can be safely sent across FFI boundaries.
 #[derive(Clone, Hash, Debug)]
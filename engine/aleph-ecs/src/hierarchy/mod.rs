@@ -0,0 +1,282 @@
+//
+//
+// This file is a part of Aleph
+//
+// https://github.com/nathanvoglsam/aleph
+//
+// MIT License
+//
+// Copyright (c) 2020 Aleph Engine
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+//!
+//! Parent/child relationships between entities.
+//!
+//! A relationship is stored as a pair of components. The child has a [`Parent`] component that
+//! refers to its parent, and the parent has a [`Children`] component that lists all of its
+//! children. An entity with no children has no [`Children`] component, and an entity with no
+//! parent has no [`Parent`] component, so both can be used as query filters to find leaves and
+//! roots.
+//!
+//! The two components must always agree with each other, so they can only be changed through the
+//! [`World`] hierarchy API ([`World::set_parent`] and [`World::remove_parent`]). Removing an entity
+//! with [`World::remove_entity`] also keeps the hierarchy consistent. The entity is removed from
+//! its parent's children, and the entity's own children become roots.
+//!
+//! Removing a [`Parent`] or [`Children`] component directly with [`World::remove_component`] will
+//! leave the other half of the relationship behind, and should be avoided.
+
+#[cfg(test)]
+mod tests;
+
+use std::ops::Deref;
+
+use thiserror::Error;
+
+use crate::entity::EntityHandle;
+use crate::register_component;
+use crate::snapshot::{SerializableComponent, SnapshotError, SnapshotReader, SnapshotWriter};
+use crate::world::World;
+
+/// Component attached to an entity that has a parent. Stores the handle of the parent entity.
+///
+/// Managed by [`World::set_parent`] and [`World::remove_parent`].
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug)]
+pub struct Parent(pub(crate) EntityHandle);
+
+impl Parent {
+    /// Returns the handle of the parent entity.
+    #[inline]
+    pub fn get(&self) -> EntityHandle {
+        self.0
+    }
+}
+
+register_component!(Parent);
+
+/// Component attached to an entity that has at least one child. Stores the handles of all the
+/// children, in the order they were attached.
+///
+/// Managed by [`World::set_parent`] and [`World::remove_parent`].
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Children(pub(crate) Vec<EntityHandle>);
+
+impl Children {
+    /// Returns the handles of all the child entities.
+    #[inline]
+    pub fn as_slice(&self) -> &[EntityHandle] {
+        &self.0
+    }
+}
+
+impl Deref for Children {
+    type Target = [EntityHandle];
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+register_component!(Children);
+
+impl SerializableComponent for Parent {
+    fn serialize(&self, writer: &mut SnapshotWriter) {
+        writer.write_entity(self.0);
+    }
+
+    fn deserialize(reader: &mut SnapshotReader) -> Result<Self, SnapshotError> {
+        Ok(Parent(reader.read_entity()?))
+    }
+}
+
+impl SerializableComponent for Children {
+    fn serialize(&self, writer: &mut SnapshotWriter) {
+        writer.write_u32(self.0.len() as u32);
+        for child in self.0.iter().copied() {
+            writer.write_entity(child);
+        }
+    }
+
+    fn deserialize(reader: &mut SnapshotReader) -> Result<Self, SnapshotError> {
+        let len = reader.read_u32()? as usize;
+        let mut children = Vec::new();
+        for _ in 0..len {
+            children.push(reader.read_entity()?);
+        }
+        Ok(Children(children))
+    }
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum HierarchyError {
+    #[error("The entity handle is not live")]
+    InvalidEntity,
+
+    #[error("The parent is the child itself, or one of the child's descendants")]
+    Cycle,
+}
+
+/// Iterator over all the descendants of an entity, created by [`World::descendants`].
+///
+/// Entities are yielded depth first, and a parent is always yielded before its children. This is
+/// the order transforms need to be propagated in.
+pub struct Descendants<'a> {
+    world: &'a World,
+    stack: Vec<EntityHandle>,
+}
+
+impl<'a> Iterator for Descendants<'a> {
+    type Item = EntityHandle;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let next = self.stack.pop()?;
+        self.stack
+            .extend(self.world.children(next).iter().rev().copied());
+        Some(next)
+    }
+}
+
+impl World {
+    /// Makes 'parent' the parent of 'child'. If 'child' already has a parent it is detached from
+    /// the old parent first.
+    ///
+    /// Returns `Err` if either handle is invalid, or if 'parent' is 'child' or one of its
+    /// descendants as that would form a cycle.
+    pub fn set_parent(
+        &mut self,
+        child: EntityHandle,
+        parent: EntityHandle,
+    ) -> Result<(), HierarchyError> {
+        if !self.is_live(child) || !self.is_live(parent) {
+            return Err(HierarchyError::InvalidEntity);
+        }
+        if child == parent || self.is_descendant_of(parent, child) {
+            return Err(HierarchyError::Cycle);
+        }
+        if self.parent(child) == Some(parent) {
+            return Ok(());
+        }
+
+        self.remove_parent(child);
+
+        // Both handles were checked to be live above, so neither of these can fail
+        let _ = self.add_component(child, Parent(parent));
+        match self.get_component_mut::<Children>(parent) {
+            Some(children) => children.0.push(child),
+            None => {
+                let _ = self.add_component(parent, Children(vec![child]));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Detaches 'child' from its parent, making it a root.
+    ///
+    /// Returns the handle of the old parent, or `None` if 'child' had no parent or the handle is
+    /// invalid.
+    pub fn remove_parent(&mut self, child: EntityHandle) -> Option<EntityHandle> {
+        let parent = self.remove_component::<Parent>(child)?.0;
+        self.remove_child_from_parent(parent, child);
+        Some(parent)
+    }
+
+    /// Returns the parent of the given entity, or `None` if it has no parent or the handle is
+    /// invalid.
+    #[inline]
+    pub fn parent(&self, entity: EntityHandle) -> Option<EntityHandle> {
+        self.get_component_ref::<Parent>(entity).map(Parent::get)
+    }
+
+    /// Returns the children of the given entity. Returns an empty slice if it has no children or
+    /// the handle is invalid.
+    #[inline]
+    pub fn children(&self, entity: EntityHandle) -> &[EntityHandle] {
+        self.get_component_ref::<Children>(entity)
+            .map(Children::as_slice)
+            .unwrap_or(&[])
+    }
+
+    /// Returns an iterator over all the descendants of the given entity, not including the entity
+    /// itself. See [`Descendants`] for the iteration order.
+    pub fn descendants(&self, entity: EntityHandle) -> Descendants<'_> {
+        let stack = self.children(entity).iter().rev().copied().collect();
+        Descendants { world: self, stack }
+    }
+
+    /// Returns whether 'entity' is a descendant of 'ancestor', by walking up the parents of
+    /// 'entity'.
+    pub fn is_descendant_of(&self, entity: EntityHandle, ancestor: EntityHandle) -> bool {
+        let mut current = self.parent(entity);
+        while let Some(v) = current {
+            if v == ancestor {
+                return true;
+            }
+            current = self.parent(v);
+        }
+        false
+    }
+
+    /// Removes the given entity and all of its descendants from the world.
+    ///
+    /// Returns `None` if the handle was invalid and nothing was removed.
+    pub fn remove_entity_recursive(&mut self, entity: EntityHandle) -> Option<()> {
+        if !self.is_live(entity) {
+            return None;
+        }
+
+        let descendants: Vec<EntityHandle> = self.descendants(entity).collect();
+        for v in descendants.into_iter().rev() {
+            self.remove_entity(v);
+        }
+        self.remove_entity(entity)
+    }
+
+    /// Unlinks the given entity from the hierarchy in preparation for it being removed from the
+    /// world. The entity is removed from its parent's children and its own children become roots.
+    ///
+    /// The components on the entity itself are left untouched so it doesn't need to be moved to
+    /// another archetype right before being removed.
+    pub(crate) fn unlink_from_hierarchy(&mut self, entity: EntityHandle) {
+        if let Some(parent) = self.parent(entity) {
+            self.remove_child_from_parent(parent, entity);
+        }
+        if let Some(children) = self.get_component_ref::<Children>(entity) {
+            let children = children.0.clone();
+            for child in children {
+                let _ = self.remove_component::<Parent>(child);
+            }
+        }
+    }
+
+    /// Removes 'child' from the [`Children`] of 'parent', removing the component entirely if it
+    /// was the last child.
+    fn remove_child_from_parent(&mut self, parent: EntityHandle, child: EntityHandle) {
+        let Some(children) = self.get_component_mut::<Children>(parent) else {
+            return;
+        };
+        children.0.retain(|v| *v != child);
+        if children.0.is_empty() {
+            let _ = self.remove_component::<Children>(parent);
+        }
+    }
+}
@@ -0,0 +1,295 @@
+//
+//
+// This file is a part of Aleph
+//
+// https://github.com/nathanvoglsam/aleph
+//
+// MIT License
+//
+// Copyright (c) 2020 Aleph Engine
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use crate::entity::EntityHandle;
+use crate::hierarchy::{Children, HierarchyError, Parent};
+use crate::register_component;
+use crate::snapshot::SnapshotRegistry;
+use crate::world::World;
+use crate::world::query::{Has, Not, Read};
+
+#[derive(Clone, Default, PartialEq, Debug)]
+struct Name(&'static str);
+register_component!(Name);
+
+#[derive(Clone, Default, PartialEq, Debug)]
+struct TagA;
+register_component!(TagA);
+
+#[derive(Clone, Default, PartialEq, Debug)]
+struct TagB;
+register_component!(TagB);
+
+#[derive(Clone, Default, PartialEq, Debug)]
+struct TagC;
+register_component!(TagC);
+
+#[test]
+fn set_parent_test() {
+    let mut world = World::new();
+    let root = world.insert((Name("root"),));
+    let a = world.insert((Name("a"),));
+    let b = world.insert((Name("b"),));
+
+    world.set_parent(a, root).unwrap();
+    world.set_parent(b, root).unwrap();
+
+    assert_eq!(world.parent(a), Some(root));
+    assert_eq!(world.parent(b), Some(root));
+    assert_eq!(world.parent(root), None);
+    assert_eq!(world.children(root), &[a, b]);
+    assert!(world.children(a).is_empty());
+
+    // Setting the same parent again is a no-op
+    world.set_parent(a, root).unwrap();
+    assert_eq!(world.children(root), &[a, b]);
+
+    // Re-parenting detaches from the old parent
+    world.set_parent(b, a).unwrap();
+    assert_eq!(world.children(root), &[a]);
+    assert_eq!(world.children(a), &[b]);
+    assert_eq!(world.parent(b), Some(a));
+
+    // Roots and leaves can be found with queries
+    let roots: Vec<_> = world
+        .query::<(Read<Name>, Not<Parent>)>()
+        .map(|(_, (n, _))| n.0)
+        .collect();
+    assert_eq!(roots, vec!["root"]);
+    let leaves = world
+        .query::<(Read<Name>, Not<Children>, Has<Parent>)>()
+        .count();
+    assert_eq!(leaves, 1);
+}
+
+#[test]
+fn set_parent_errors_test() {
+    let mut world = World::new();
+    let a = world.spawn_entity();
+    let b = world.spawn_entity();
+    let c = world.spawn_entity();
+    world.set_parent(b, a).unwrap();
+    world.set_parent(c, b).unwrap();
+
+    assert_eq!(world.set_parent(a, a), Err(HierarchyError::Cycle));
+    assert_eq!(world.set_parent(a, c), Err(HierarchyError::Cycle));
+    assert_eq!(world.set_parent(a, b), Err(HierarchyError::Cycle));
+
+    let dead = world.spawn_entity();
+    world.remove_entity(dead);
+    assert_eq!(
+        world.set_parent(dead, a),
+        Err(HierarchyError::InvalidEntity)
+    );
+    assert_eq!(
+        world.set_parent(a, dead),
+        Err(HierarchyError::InvalidEntity)
+    );
+
+    // The failed calls must not have changed anything
+    assert_eq!(world.parent(a), None);
+    assert_eq!(world.children(a), &[b]);
+    assert_eq!(world.children(b), &[c]);
+}
+
+#[test]
+fn remove_parent_test() {
+    let mut world = World::new();
+    let parent = world.spawn_entity();
+    let child = world.spawn_entity();
+    world.set_parent(child, parent).unwrap();
+
+    assert_eq!(world.remove_parent(child), Some(parent));
+    assert_eq!(world.parent(child), None);
+    assert!(!world.has_component::<Children>(parent));
+    assert_eq!(world.remove_parent(child), None);
+}
+
+#[test]
+fn descendants_order_test() {
+    let mut world = World::new();
+    let root = world.spawn_entity();
+    let a = world.spawn_entity();
+    let a0 = world.spawn_entity();
+    let a1 = world.spawn_entity();
+    let b = world.spawn_entity();
+    let b0 = world.spawn_entity();
+
+    world.set_parent(a, root).unwrap();
+    world.set_parent(b, root).unwrap();
+    world.set_parent(a0, a).unwrap();
+    world.set_parent(a1, a).unwrap();
+    world.set_parent(b0, b).unwrap();
+
+    let descendants: Vec<_> = world.descendants(root).collect();
+    assert_eq!(descendants, vec![a, a0, a1, b, b0]);
+
+    let descendants: Vec<_> = world.descendants(b).collect();
+    assert_eq!(descendants, vec![b0]);
+
+    assert_eq!(world.descendants(b0).count(), 0);
+    assert!(world.is_descendant_of(a1, root));
+    assert!(!world.is_descendant_of(a1, b));
+}
+
+#[test]
+fn remove_entity_keeps_hierarchy_consistent_test() {
+    let mut world = World::new();
+    let root = world.spawn_entity();
+    let middle = world.spawn_entity();
+    let sibling = world.spawn_entity();
+    let leaf = world.spawn_entity();
+    world.set_parent(middle, root).unwrap();
+    world.set_parent(sibling, root).unwrap();
+    world.set_parent(leaf, middle).unwrap();
+
+    world.remove_entity(middle);
+
+    // Removed from the parent's children, and its children become roots
+    assert_eq!(world.children(root), &[sibling]);
+    assert_eq!(world.parent(leaf), None);
+    assert!(world.is_live(leaf));
+
+    // Removing the last child removes the component from the parent
+    world.remove_entity(sibling);
+    assert!(!world.has_component::<Children>(root));
+}
+
+#[test]
+fn remove_entity_recursive_test() {
+    let mut world = World::new();
+    let root = world.spawn_entity();
+    let outside = world.spawn_entity();
+    let a = world.spawn_entity();
+    let b = world.spawn_entity();
+    let c = world.spawn_entity();
+    world.set_parent(root, outside).unwrap();
+    world.set_parent(a, root).unwrap();
+    world.set_parent(b, a).unwrap();
+    world.set_parent(c, root).unwrap();
+
+    assert_eq!(world.remove_entity_recursive(root), Some(()));
+    assert!(!world.is_live(root));
+    assert!(!world.is_live(a));
+    assert!(!world.is_live(b));
+    assert!(!world.is_live(c));
+    assert!(world.is_live(outside));
+    assert!(world.children(outside).is_empty());
+    assert_eq!(world.len(), 1);
+
+    assert_eq!(world.remove_entity_recursive(root), None);
+}
+
+#[test]
+fn remove_matching_keeps_hierarchy_consistent_test() {
+    let mut world = World::new();
+    let root = world.insert((Name("root"),));
+    let a = world.spawn_entity();
+    let b = world.spawn_entity();
+    let c = world.insert((Name("c"),));
+    world.set_parent(a, root).unwrap();
+    world.set_parent(b, root).unwrap();
+    world.set_parent(c, a).unwrap();
+
+    world.remove_matching::<Has<Parent>>();
+
+    assert!(world.is_live(root));
+    assert!(!world.is_live(a));
+    assert!(!world.is_live(b));
+    assert!(!world.is_live(c));
+    assert!(!world.has_component::<Children>(root));
+    assert_eq!(world.len(), 1);
+}
+
+#[test]
+fn remove_matching_is_independent_of_archetype_order_test() {
+    fn add_tags(world: &mut World, entity: EntityHandle, tags: u32) {
+        if tags & 1 != 0 {
+            world.add_component(entity, TagA).unwrap();
+        }
+        if tags & 2 != 0 {
+            world.add_component(entity, TagB).unwrap();
+        }
+        if tags & 4 != 0 {
+            world.add_component(entity, TagC).unwrap();
+        }
+    }
+
+    // Removing the child moves the parent out of an archetype with `Children` and into one that
+    // matches the query. Archetypes are visited in hash set order, so build a parent for every
+    // combination of tags to have both archetype orders covered.
+    let mut world = World::new();
+    let mut parents = Vec::new();
+    for tags in 0..8 {
+        let parent = world.insert((Name("parent"),));
+        let child = world.insert((Name("child"),));
+        let bystander = world.insert((Name("bystander"),));
+        add_tags(&mut world, parent, tags);
+        add_tags(&mut world, child, tags);
+        add_tags(&mut world, bystander, tags);
+        world.set_parent(child, parent).unwrap();
+        parents.push(parent);
+    }
+
+    world.remove_matching::<(Read<Name>, Not<Children>)>();
+
+    // Only the parents didn't match when remove_matching was called
+    for parent in parents {
+        assert!(world.is_live(parent));
+        assert!(!world.has_component::<Children>(parent));
+    }
+    assert_eq!(world.len(), 8);
+}
+
+#[test]
+fn hierarchy_snapshot_test() {
+    let mut world = World::new();
+    let root = world.spawn_entity();
+    let a = world.spawn_entity();
+    let b = world.spawn_entity();
+    world.set_parent(a, root).unwrap();
+    world.set_parent(b, a).unwrap();
+
+    let registry = SnapshotRegistry::new();
+    let bytes = registry.save(&world);
+
+    let mut loaded = World::new();
+    let handles = registry.load(&mut loaded, &bytes).unwrap();
+    let roots: Vec<_> = handles
+        .iter()
+        .copied()
+        .filter(|v| loaded.parent(*v).is_none())
+        .collect();
+    assert_eq!(roots.len(), 1);
+
+    let descendants: Vec<_> = loaded.descendants(roots[0]).collect();
+    assert_eq!(descendants.len(), 2);
+    assert_eq!(loaded.parent(descendants[0]), Some(roots[0]));
+    assert_eq!(loaded.parent(descendants[1]), Some(descendants[0]));
+}
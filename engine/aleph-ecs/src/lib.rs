@@ -33,8 +33,12 @@ extern crate core;
 pub extern crate ctor;
 
 pub mod archetype;
+pub mod command;
 pub mod component;
 pub mod entity;
+pub mod hierarchy;
+pub mod snapshot;
+pub mod tick;
 pub mod type_layout;
 pub mod world;
 
@@ -44,7 +48,3 @@ pub struct Ecs;
 aleph_alloc::new_alloc_category!(Ecs, "01996aaa-df23-7790-ad3f-47f1b2420ee2");
 
 pub type EcsSystem = aleph_alloc::instrumentation::Instrumented<Ecs>;
-
-// TODO: CommandBuffers so that world modification commands can be queued by jobs and then resolved
-//       when the execution phase has completed. This completes the functionality of the ECS as
-//       currently it's not really possible to modify the world from within the task graph.
@@ -0,0 +1,706 @@
+//
+//
+// This file is a part of Aleph
+//
+// https://github.com/nathanvoglsam/aleph
+//
+// MIT License
+//
+// Copyright (c) 2020 Aleph Engine
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+//!
+//! Serialization of a [`World`] to and from a compact, versioned binary snapshot.
+//!
+//! Serialization is opt-in per component type. A type implements [`SerializableComponent`] and is
+//! registered with a [`SnapshotRegistry`], which handles saving and loading worlds. Components are
+//! identified in a snapshot by their [`ComponentDescription::name`], so a snapshot can be loaded by
+//! a different build of the program where the [`ComponentId`]s have been assigned differently.
+//!
+//! # Format
+//!
+//! All values are little endian.
+//!
+//! - The [`SNAPSHOT_MAGIC`] bytes, followed by the `u32` [`SNAPSHOT_VERSION`].
+//! - A `u32` count, followed by that many component names, sorted by name. Each name is a `u32`
+//!   byte length followed by the UTF-8 bytes.
+//! - A `u32` total entity count.
+//! - A `u32` archetype count, followed by that many archetypes. Each archetype is:
+//!   - A `u32` component count, followed by that many `u32` indices into the component name list.
+//!   - A `u32` entity count.
+//!   - For each component, a `u64` byte length followed by the serialized component of each entity
+//!     in the archetype.
+//!
+//! Entities are numbered in the order they appear in the snapshot, starting from 0. Entity handles
+//! stored inside components are written as these numbers, which are remapped to the newly
+//! allocated handles when the snapshot is loaded.
+//!
+//! [`ComponentDescription::name`]: crate::component::ComponentDescription::name
+
+#[cfg(test)]
+mod tests;
+
+use std::ptr::NonNull;
+
+use aleph_alloc::instrumentation::system;
+use aleph_alloc::{BHashMap, BVec};
+use aleph_gen_arena::HandleType;
+use thiserror::Error;
+
+use crate::EcsSystem;
+use crate::component::singleton::Singleton;
+use crate::component::{Component, ComponentId};
+use crate::entity::{EntityHandle, EntityLocation};
+use crate::hierarchy::{Children, Parent};
+use crate::tick::ComponentTicks;
+use crate::world::World;
+
+/// The magic bytes every snapshot starts with.
+pub const SNAPSHOT_MAGIC: [u8; 8] = *b"ALEPHECS";
+
+/// The current version of the snapshot format. Snapshots with any other version are rejected.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// Entity numbers of this value encode a handle that doesn't refer to an entity in the snapshot.
+const NULL_ENTITY: u32 = u32::MAX;
+
+/// The default for [`SnapshotRegistry::set_max_entities`].
+pub const DEFAULT_MAX_SNAPSHOT_ENTITIES: usize = 1 << 24;
+
+/// The interface a component type must implement to be serialized into a world snapshot.
+///
+/// Implementations should write their fields with the [`SnapshotWriter`] functions, and read them
+/// back in the same order with the matching [`SnapshotReader`] functions. Any [`EntityHandle`] the
+/// component stores must be written with [`SnapshotWriter::write_entity`] so it can be remapped.
+pub trait SerializableComponent: Component {
+    /// Writes the component into the snapshot.
+    fn serialize(&self, writer: &mut SnapshotWriter);
+
+    /// Reads a component previously written by [`SerializableComponent::serialize`].
+    fn deserialize(reader: &mut SnapshotReader) -> Result<Self, SnapshotError>;
+}
+
+impl SerializableComponent for Singleton {
+    fn serialize(&self, _writer: &mut SnapshotWriter) {}
+
+    fn deserialize(_reader: &mut SnapshotReader) -> Result<Self, SnapshotError> {
+        Ok(Singleton)
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum SnapshotError {
+    #[error("The data is not an ECS world snapshot")]
+    InvalidMagic,
+
+    #[error("Unsupported snapshot version {0}")]
+    UnsupportedVersion(u32),
+
+    #[error("Unexpected end of snapshot data")]
+    UnexpectedEof,
+
+    #[error("The snapshot contains component '{0}' that isn't registered for serialization")]
+    UnknownComponent(String),
+
+    #[error("The snapshot is malformed: {0}")]
+    Malformed(&'static str),
+}
+
+/// Type erased [`SerializableComponent`] functions for a single registered component type.
+struct SnapshotEntry {
+    name: &'static str,
+    id: ComponentId,
+    save: unsafe fn(NonNull<u8>, &mut SnapshotWriter),
+    load: fn(&mut SnapshotReader, usize) -> Result<BoxedColumn, SnapshotError>,
+}
+
+/// The set of component types that are serialized into world snapshots. Also implements saving
+/// and loading snapshots.
+///
+/// Components of types that aren't registered are skipped when saving. The entities themselves are
+/// always saved, even if none of their components are.
+pub struct SnapshotRegistry {
+    entries: BVec<SnapshotEntry, EcsSystem>,
+    by_id: BHashMap<ComponentId, usize, EcsSystem>,
+    by_name: BHashMap<&'static str, usize, EcsSystem>,
+
+    /// The most entities a snapshot may contain and still be loaded.
+    max_entities: usize,
+}
+
+impl Default for SnapshotRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SnapshotRegistry {
+    /// Constructs a new registry. The [`Singleton`] tag is always registered so singletons are
+    /// restored when a snapshot is loaded. The [`Parent`] and [`Children`] hierarchy components are
+    /// also always registered so both halves of a relationship are saved together.
+    pub fn new() -> Self {
+        let mut out = Self {
+            entries: BVec::new_in(system()),
+            by_id: BHashMap::new_in(system()),
+            by_name: BHashMap::new_in(system()),
+            max_entities: DEFAULT_MAX_SNAPSHOT_ENTITIES,
+        };
+        out.register::<Singleton>();
+        out.register::<Parent>();
+        out.register::<Children>();
+        out
+    }
+
+    /// Sets the most entities a snapshot may contain for [`SnapshotRegistry::load`] to accept it.
+    /// Defaults to [`DEFAULT_MAX_SNAPSHOT_ENTITIES`].
+    ///
+    /// Entities with no registered components, or only zero-sized ones, take up no space in a
+    /// snapshot. The size of the snapshot can't bound how many entities it spawns, so this does
+    /// instead.
+    pub fn set_max_entities(&mut self, max_entities: usize) -> &mut Self {
+        self.max_entities = max_entities;
+        self
+    }
+
+    /// Registers the component type `T` to be included in snapshots.
+    ///
+    /// # Panics
+    ///
+    /// Panics if another component type with the same name has already been registered.
+    pub fn register<T: SerializableComponent>(&mut self) -> &mut Self {
+        unsafe fn save<T: SerializableComponent>(ptr: NonNull<u8>, writer: &mut SnapshotWriter) {
+            unsafe { ptr.cast::<T>().as_ref().serialize(writer) }
+        }
+
+        fn load<T: SerializableComponent>(
+            reader: &mut SnapshotReader,
+            count: usize,
+        ) -> Result<BoxedColumn, SnapshotError> {
+            // The count hasn't been checked against the size of the data yet, so don't trust it
+            // for the allocation.
+            let mut column = BVec::with_capacity_in(count.min(reader.bytes.len()), system());
+            for _ in 0..count {
+                column.push(T::deserialize(reader)?);
+            }
+            Ok(Box::new(column))
+        }
+
+        if self.by_id.contains_key(&T::DESC.id) {
+            return self;
+        }
+
+        let name = T::DESC.name.to_str();
+        let index = self.entries.len();
+        let prev = self.by_name.insert(name, index);
+        assert!(prev.is_none(), "Component name '{name}' already registered");
+        self.by_id.insert(T::DESC.id, index);
+        self.entries.push(SnapshotEntry {
+            name,
+            id: T::DESC.id,
+            save: save::<T>,
+            load: load::<T>,
+        });
+        self
+    }
+
+    /// Saves all the entities in the world, and their registered components, into a snapshot.
+    ///
+    /// The output is deterministic. Saving two worlds built up by the same sequence of operations
+    /// produces identical snapshots.
+    pub fn save(&self, world: &World) -> Vec<u8> {
+        // Number every entity in the order they'll be written, so handles stored in components
+        // can be written as entity numbers.
+        let mut entities = BHashMap::new_in(system());
+        for archetype in world.archetypes.iter() {
+            for &entity in archetype.entity_ids_ref() {
+                let number = entities.len() as u32;
+                entities.insert(entity, number);
+            }
+        }
+
+        // Collect the registered components of each non-empty archetype, as (entry, column) pairs
+        // sorted by the component names so the output doesn't depend on component IDs.
+        let mut archetypes = Vec::new();
+        let mut used = vec![false; self.entries.len()];
+        for (i, archetype) in world.archetypes.iter().enumerate() {
+            if archetype.len() == 0 {
+                continue;
+            }
+            let mut components: Vec<(usize, usize)> = archetype
+                .type_layout
+                .iter()
+                .enumerate()
+                .filter_map(|(column, id)| Some((*self.by_id.get(id)?, column)))
+                .collect();
+            components.sort_by_key(|&(entry, _)| self.entries[entry].name);
+            for &(entry, _) in components.iter() {
+                used[entry] = true;
+            }
+            archetypes.push((i, components));
+        }
+
+        // The component name table, with the mapping from registry entry to table index
+        let mut names: Vec<usize> = (0..self.entries.len()).filter(|&v| used[v]).collect();
+        names.sort_by_key(|&v| self.entries[v].name);
+        let mut table_index = vec![0u32; self.entries.len()];
+        for (i, &entry) in names.iter().enumerate() {
+            table_index[entry] = i as u32;
+        }
+
+        let mut bytes = Vec::new();
+        let mut writer = SnapshotWriter {
+            bytes: &mut bytes,
+            entities: &entities,
+        };
+        writer.bytes.extend_from_slice(&SNAPSHOT_MAGIC);
+        writer.write_u32(SNAPSHOT_VERSION);
+        writer.write_u32(names.len() as u32);
+        for &entry in names.iter() {
+            writer.write_str(self.entries[entry].name);
+        }
+        writer.write_u32(entities.len() as u32);
+        writer.write_u32(archetypes.len() as u32);
+        for (archetype, components) in archetypes.iter() {
+            let archetype = &world.archetypes[*archetype];
+            writer.write_u32(components.len() as u32);
+            for &(entry, _) in components.iter() {
+                writer.write_u32(table_index[entry]);
+            }
+            writer.write_u32(archetype.len() as u32);
+            for &(entry, column) in components.iter() {
+                // Reserve space for the length of the column, and patch it once it's written
+                let length_offset = writer.bytes.len();
+                writer.write_u64(0);
+
+                let save = self.entries[entry].save;
+                let column = &archetype.columns[column];
+                for row in 0..archetype.len() {
+                    // Safety: 'row' is in bounds of the live entities of the archetype, and the
+                    //         column holds components of the type the entry was registered for.
+                    unsafe {
+                        let ptr = column.get_at_index(row).unwrap_unchecked();
+                        save(ptr, &mut writer);
+                    }
+                }
+
+                let length = (writer.bytes.len() - length_offset - 8) as u64;
+                writer.bytes[length_offset..length_offset + 8]
+                    .copy_from_slice(&length.to_le_bytes());
+            }
+        }
+
+        bytes
+    }
+
+    /// Loads all the entities in the snapshot into the world, alongside any existing entities.
+    ///
+    /// Returns the handles of the new entities, indexed by their entity number in the snapshot.
+    ///
+    /// Components that are registered as singletons in the snapshot replace the world's singletons
+    /// for the same component types.
+    ///
+    /// If an error is returned the world is left unchanged.
+    pub fn load(
+        &self,
+        world: &mut World,
+        bytes: &[u8],
+    ) -> Result<Vec<EntityHandle>, SnapshotError> {
+        // Read and validate the whole structure of the snapshot before touching the world, so the
+        // counts in it can be trusted.
+        let (entity_count, archetypes) = self.read_headers(bytes)?;
+        let new_entity_count = world.entities.len().saturating_add(entity_count);
+        if new_entity_count > u32::MAX as usize {
+            return Err(SnapshotError::Malformed("too many entities"));
+        }
+
+        // Allocate every handle up front so components can refer to any entity in the snapshot,
+        // whichever archetype it's stored in. The handles aren't placed in an archetype until all
+        // the components have been loaded.
+        let handles: Vec<EntityHandle> = (0..entity_count)
+            .map(|_| {
+                world.entities.alloc(EntityLocation {
+                    archetype: 0,
+                    row: 0,
+                })
+            })
+            .collect();
+
+        let columns = match self.load_columns(&archetypes, &handles) {
+            Ok(v) => v,
+            Err(e) => {
+                for &entity in handles.iter() {
+                    world.entities.free(entity);
+                }
+                return Err(e);
+            }
+        };
+
+        // Nothing can fail from here on, so the entities can be moved into the world
+        let mut next_entity = 0;
+        for (header, columns) in archetypes.iter().zip(columns) {
+            let entities = &handles[next_entity..next_entity + header.count];
+            next_entity += header.count;
+            self.insert_archetype(world, header, columns, entities);
+        }
+
+        Ok(handles)
+    }
+
+    /// Reads the snapshot's header and the header of every archetype in it, without loading any
+    /// components. Returns the total entity count and the archetypes.
+    fn read_headers<'a>(
+        &self,
+        bytes: &'a [u8],
+    ) -> Result<(usize, Vec<ArchetypeHeader<'a>>), SnapshotError> {
+        let mut reader = SnapshotReader {
+            bytes,
+            entities: &[],
+        };
+        if reader.read_array::<8>()? != SNAPSHOT_MAGIC {
+            return Err(SnapshotError::InvalidMagic);
+        }
+        let version = reader.read_u32()?;
+        if version != SNAPSHOT_VERSION {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+
+        let mut names = Vec::new();
+        for _ in 0..reader.read_u32()? {
+            let name = reader.read_str()?;
+            let entry = self
+                .by_name
+                .get(name)
+                .copied()
+                .ok_or_else(|| SnapshotError::UnknownComponent(name.to_string()))?;
+            names.push(entry);
+        }
+
+        let entity_count = reader.read_u32()? as usize;
+        if entity_count > self.max_entities {
+            return Err(SnapshotError::Malformed("too many entities"));
+        }
+
+        let mut archetypes = Vec::new();
+        let mut total = 0usize;
+        for _ in 0..reader.read_u32()? {
+            let mut components = Vec::new();
+            for _ in 0..reader.read_u32()? {
+                let name = reader.read_u32()? as usize;
+                let entry = names
+                    .get(name)
+                    .copied()
+                    .ok_or(SnapshotError::Malformed("component index out of bounds"))?;
+                if components.contains(&entry) {
+                    return Err(SnapshotError::Malformed("duplicate component in archetype"));
+                }
+                components.push(entry);
+            }
+
+            let count = reader.read_u32()? as usize;
+            total += count;
+            if total > entity_count {
+                return Err(SnapshotError::Malformed("entity count mismatch"));
+            }
+
+            let mut columns = Vec::with_capacity(components.len());
+            for _ in 0..components.len() {
+                let length = reader.read_u64()?;
+                let length = usize::try_from(length).map_err(|_| SnapshotError::UnexpectedEof)?;
+                columns.push(reader.read_slice(length)?);
+            }
+
+            archetypes.push(ArchetypeHeader {
+                components,
+                count,
+                columns,
+            });
+        }
+
+        if total != entity_count {
+            return Err(SnapshotError::Malformed("entity count mismatch"));
+        }
+        if !reader.bytes.is_empty() {
+            return Err(SnapshotError::Malformed("trailing data"));
+        }
+
+        Ok((entity_count, archetypes))
+    }
+
+    /// Deserializes the components of every archetype, with entity numbers remapped to the given
+    /// handles.
+    fn load_columns(
+        &self,
+        archetypes: &[ArchetypeHeader],
+        handles: &[EntityHandle],
+    ) -> Result<Vec<Vec<BoxedColumn>>, SnapshotError> {
+        let mut out = Vec::with_capacity(archetypes.len());
+        for header in archetypes.iter() {
+            let mut columns = Vec::with_capacity(header.components.len());
+            for (&entry, &bytes) in header.components.iter().zip(header.columns.iter()) {
+                let mut reader = SnapshotReader {
+                    bytes,
+                    entities: handles,
+                };
+                columns.push((self.entries[entry].load)(&mut reader, header.count)?);
+                if !reader.bytes.is_empty() {
+                    return Err(SnapshotError::Malformed(
+                        "component data was not fully read",
+                    ));
+                }
+            }
+            out.push(columns);
+        }
+        Ok(out)
+    }
+
+    /// Moves the loaded components of a single archetype into the world, placing the given
+    /// entities in the archetype's rows.
+    fn insert_archetype(
+        &self,
+        world: &mut World,
+        header: &ArchetypeHeader,
+        columns: Vec<BoxedColumn>,
+        entities: &[EntityHandle],
+    ) {
+        let ids = header.components.iter().map(|&v| self.entries[v].id);
+        let archetype = world
+            .find_archetype_for_component_set(ids)
+            .expect("Duplicate components are rejected when reading the headers");
+
+        let count = entities.len();
+        let base_row = world.archetypes[archetype].allocate_entities(count);
+        if count != 0 {
+            for (&entry, mut loaded) in header.components.iter().zip(columns) {
+                let id = self.entries[entry].id;
+                let column = world.components[id]
+                    .archetypes
+                    .get(&archetype)
+                    .unwrap()
+                    .column;
+                let column = &mut world.archetypes[archetype].columns[column];
+
+                // Safety: The rows were just allocated, and the column holds components of the
+                //         type the loaded column was read as.
+                unsafe {
+                    loaded.move_into(column.get_at_index(base_row).unwrap_unchecked());
+                }
+            }
+        }
+
+        // Every component of the new entities was just added.
+        let ticks = ComponentTicks::new(world.change_tick());
+        for column in world.archetypes[archetype].columns.iter_mut() {
+            column.ticks_mut()[base_row..base_row + count].fill(ticks);
+        }
+
+        world.archetypes[archetype].entity_handles[base_row..base_row + count]
+            .copy_from_slice(entities);
+        for (i, &entity) in entities.iter().enumerate() {
+            let location = world.entities.get_mut(entity).unwrap();
+            *location = EntityLocation {
+                archetype,
+                row: base_row + i,
+            };
+        }
+
+        // Entities with the singleton tag are the singleton for all their other components
+        let is_singleton = header
+            .components
+            .iter()
+            .any(|&v| self.entries[v].id == Singleton::DESC.id);
+        if let (true, Some(&entity)) = (is_singleton, entities.last()) {
+            for &entry in header.components.iter() {
+                let id = self.entries[entry].id;
+                if id != Singleton::DESC.id {
+                    world.components[id].singleton = Some(entity);
+                }
+            }
+        }
+    }
+}
+
+/// An archetype read from the headers of a snapshot, before any of its components are loaded.
+struct ArchetypeHeader<'a> {
+    /// The registry entries of the archetype's components
+    components: Vec<usize>,
+
+    /// The number of entities in the archetype
+    count: usize,
+
+    /// The serialized data of each component, in the same order as 'components'
+    columns: Vec<&'a [u8]>,
+}
+
+/// A type erased column of components loaded from a snapshot, waiting to be moved into the world.
+trait LoadedColumn {
+    /// Moves all the components into the memory at 'dst', leaving the column empty.
+    ///
+    /// # Safety
+    ///
+    /// 'dst' must be valid to write every component in the column.
+    unsafe fn move_into(&mut self, dst: NonNull<u8>);
+}
+
+type BoxedColumn = Box<dyn LoadedColumn>;
+
+impl<T: Component> LoadedColumn for BVec<T, EcsSystem> {
+    unsafe fn move_into(&mut self, dst: NonNull<u8>) {
+        unsafe {
+            dst.cast::<T>()
+                .copy_from_nonoverlapping(NonNull::new_unchecked(self.as_mut_ptr()), self.len());
+            self.set_len(0);
+        }
+    }
+}
+
+/// Writes the contents of a component into a world snapshot. See [`SerializableComponent`].
+pub struct SnapshotWriter<'a> {
+    bytes: &'a mut Vec<u8>,
+    entities: &'a BHashMap<EntityHandle, u32, EcsSystem>,
+}
+
+/// Reads the contents of a component from a world snapshot. See [`SerializableComponent`].
+pub struct SnapshotReader<'a> {
+    bytes: &'a [u8],
+    entities: &'a [EntityHandle],
+}
+
+macro_rules! impl_primitives {
+    ($(($t: ident, $write: ident, $read: ident)),+) => {
+        impl SnapshotWriter<'_> {
+            $(
+                #[doc = concat!("Writes a `", stringify!($t), "` into the snapshot.")]
+                #[inline]
+                pub fn $write(&mut self, v: $t) {
+                    self.bytes.extend_from_slice(&v.to_le_bytes());
+                }
+            )+
+        }
+
+        impl SnapshotReader<'_> {
+            $(
+                #[doc = concat!("Reads a `", stringify!($t), "` from the snapshot.")]
+                #[inline]
+                pub fn $read(&mut self) -> Result<$t, SnapshotError> {
+                    Ok($t::from_le_bytes(self.read_array()?))
+                }
+            )+
+        }
+    };
+}
+
+impl_primitives!(
+    (u8, write_u8, read_u8),
+    (u16, write_u16, read_u16),
+    (u32, write_u32, read_u32),
+    (u64, write_u64, read_u64),
+    (i8, write_i8, read_i8),
+    (i16, write_i16, read_i16),
+    (i32, write_i32, read_i32),
+    (i64, write_i64, read_i64),
+    (f32, write_f32, read_f32),
+    (f64, write_f64, read_f64)
+);
+
+impl SnapshotWriter<'_> {
+    /// Writes a `bool` into the snapshot.
+    #[inline]
+    pub fn write_bool(&mut self, v: bool) {
+        self.write_u8(v as u8);
+    }
+
+    /// Writes a length prefixed byte array into the snapshot.
+    #[inline]
+    pub fn write_bytes(&mut self, v: &[u8]) {
+        self.write_u32(u32::try_from(v.len()).expect("Byte array too long for a snapshot"));
+        self.bytes.extend_from_slice(v);
+    }
+
+    /// Writes a length prefixed string into the snapshot.
+    #[inline]
+    pub fn write_str(&mut self, v: &str) {
+        self.write_bytes(v.as_bytes());
+    }
+
+    /// Writes an entity handle into the snapshot. Handles to entities that are not live in the
+    /// world being saved are read back as dangling handles.
+    #[inline]
+    pub fn write_entity(&mut self, v: EntityHandle) {
+        let number = self.entities.get(&v).copied().unwrap_or(NULL_ENTITY);
+        self.write_u32(number);
+    }
+}
+
+impl<'a> SnapshotReader<'a> {
+    /// Reads a `bool` from the snapshot.
+    #[inline]
+    pub fn read_bool(&mut self) -> Result<bool, SnapshotError> {
+        match self.read_u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(SnapshotError::Malformed("invalid bool")),
+        }
+    }
+
+    /// Reads a length prefixed byte array from the snapshot.
+    #[inline]
+    pub fn read_bytes(&mut self) -> Result<&'a [u8], SnapshotError> {
+        let len = self.read_u32()? as usize;
+        self.read_slice(len)
+    }
+
+    /// Reads a length prefixed string from the snapshot.
+    #[inline]
+    pub fn read_str(&mut self) -> Result<&'a str, SnapshotError> {
+        let bytes = self.read_bytes()?;
+        std::str::from_utf8(bytes).map_err(|_| SnapshotError::Malformed("invalid UTF-8 string"))
+    }
+
+    /// Reads an entity handle from the snapshot, remapped to the handle of the entity spawned when
+    /// loading the snapshot.
+    #[inline]
+    pub fn read_entity(&mut self) -> Result<EntityHandle, SnapshotError> {
+        let number = self.read_u32()?;
+        if number == NULL_ENTITY {
+            return Ok(EntityHandle::dangling());
+        }
+        self.entities
+            .get(number as usize)
+            .copied()
+            .ok_or(SnapshotError::Malformed("entity number out of bounds"))
+    }
+
+    #[inline]
+    fn read_slice(&mut self, len: usize) -> Result<&'a [u8], SnapshotError> {
+        if len > self.bytes.len() {
+            return Err(SnapshotError::UnexpectedEof);
+        }
+        let (out, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(out)
+    }
+
+    #[inline]
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N], SnapshotError> {
+        let bytes = self.read_slice(N)?;
+        Ok(bytes.try_into().unwrap())
+    }
+}
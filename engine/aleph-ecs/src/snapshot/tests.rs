@@ -0,0 +1,368 @@
+//
+//
+// This file is a part of Aleph
+//
+// https://github.com/nathanvoglsam/aleph
+//
+// MIT License
+//
+// Copyright (c) 2020 Aleph Engine
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use aleph_gen_arena::HandleType;
+
+use crate::entity::EntityHandle;
+use crate::register_component;
+use crate::snapshot::{
+    SNAPSHOT_MAGIC, SNAPSHOT_VERSION, SerializableComponent, SnapshotError, SnapshotReader,
+    SnapshotRegistry, SnapshotWriter,
+};
+use crate::world::World;
+use crate::world::query::Read;
+
+#[derive(Clone, PartialEq, Debug)]
+struct Transform {
+    pub x: f32,
+    pub y: f64,
+}
+register_component!(Transform);
+
+impl SerializableComponent for Transform {
+    fn serialize(&self, writer: &mut SnapshotWriter) {
+        writer.write_f32(self.x);
+        writer.write_f64(self.y);
+    }
+
+    fn deserialize(reader: &mut SnapshotReader) -> Result<Self, SnapshotError> {
+        Ok(Self {
+            x: reader.read_f32()?,
+            y: reader.read_f64()?,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+struct Name(String);
+register_component!(Name);
+
+impl SerializableComponent for Name {
+    fn serialize(&self, writer: &mut SnapshotWriter) {
+        writer.write_str(&self.0);
+    }
+
+    fn deserialize(reader: &mut SnapshotReader) -> Result<Self, SnapshotError> {
+        Ok(Self(reader.read_str()?.to_string()))
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+struct Target {
+    pub entity: EntityHandle,
+    pub active: bool,
+}
+register_component!(Target);
+
+impl SerializableComponent for Target {
+    fn serialize(&self, writer: &mut SnapshotWriter) {
+        writer.write_entity(self.entity);
+        writer.write_bool(self.active);
+    }
+
+    fn deserialize(reader: &mut SnapshotReader) -> Result<Self, SnapshotError> {
+        Ok(Self {
+            entity: reader.read_entity()?,
+            active: reader.read_bool()?,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+struct Transient(u32);
+register_component!(Transient);
+
+fn registry() -> SnapshotRegistry {
+    let mut registry = SnapshotRegistry::new();
+    registry
+        .register::<Transform>()
+        .register::<Name>()
+        .register::<Target>();
+    registry
+}
+
+fn build_world() -> (World, Vec<EntityHandle>) {
+    let mut world = World::new();
+    let a = world.insert((Transform { x: 1.0, y: 2.0 }, Name("a".to_string())));
+    let b = world.insert((Transform { x: 3.0, y: 4.0 }, Transient(7)));
+    let dead = world.spawn_entity();
+    world.remove_entity(dead).unwrap();
+    let c = world.insert((
+        Name("c".to_string()),
+        Target {
+            entity: a,
+            active: true,
+        },
+    ));
+    world
+        .add_component(
+            a,
+            Target {
+                entity: c,
+                active: false,
+            },
+        )
+        .unwrap();
+    let d = world.insert((Target {
+        entity: dead,
+        active: true,
+    },));
+    let empty = world.spawn_entity();
+    world
+        .insert_singleton(Name("singleton".to_string()))
+        .unwrap();
+    (world, vec![a, b, c, d, empty])
+}
+
+#[test]
+fn snapshot_round_trip_test() {
+    let registry = registry();
+    let (world, _) = build_world();
+    let bytes = registry.save(&world);
+    assert_eq!(bytes[0..8], SNAPSHOT_MAGIC);
+    assert_eq!(bytes[8..12], SNAPSHOT_VERSION.to_le_bytes());
+
+    let mut loaded = World::new();
+    let existing = loaded.insert((Name("existing".to_string()),));
+    let handles = registry.load(&mut loaded, &bytes).unwrap();
+    assert_eq!(handles.len(), world.len());
+    assert_eq!(loaded.len(), world.len() + 1);
+    assert!(loaded.is_live(existing));
+
+    // Find the loaded entities by name, and check the handles stored in components were remapped
+    let find = |name: &str| {
+        loaded
+            .query::<Read<Name>>()
+            .find(|(_, v)| v.0 == name)
+            .map(|(id, _)| id)
+            .unwrap()
+    };
+    let a = find("a");
+    let c = find("c");
+    assert_eq!(
+        loaded.get_component_ref::<Transform>(a),
+        Some(&Transform { x: 1.0, y: 2.0 })
+    );
+    assert_eq!(
+        loaded.get_component_ref::<Target>(a),
+        Some(&Target {
+            entity: c,
+            active: false
+        })
+    );
+    assert_eq!(
+        loaded.get_component_ref::<Target>(c),
+        Some(&Target {
+            entity: a,
+            active: true
+        })
+    );
+
+    // Handles to entities that weren't live when saving don't resolve to anything
+    let (_, dangling) = loaded
+        .query::<Read<Target>>()
+        .find(|(id, _)| *id != a && *id != c)
+        .unwrap();
+    assert!(!loaded.is_live(dangling.entity));
+
+    // Unregistered components are skipped, but the entities are not
+    assert_eq!(loaded.query::<Read<Transient>>().count(), 0);
+    assert_eq!(loaded.query::<Read<Transform>>().count(), 2);
+
+    // Singletons are restored
+    assert_eq!(
+        loaded.get_singleton_ref::<Name>(),
+        Some(&Name("singleton".to_string()))
+    );
+}
+
+#[test]
+fn snapshot_deterministic_test() {
+    let registry = registry();
+    let (first, _) = build_world();
+    let (second, _) = build_world();
+    assert_eq!(registry.save(&first), registry.save(&second));
+
+    // Loading and saving a snapshot with a single archetype reproduces the same bytes
+    let mut world = World::new();
+    world.bulk_insert((
+        [Transform { x: 1.0, y: 1.0 }, Transform { x: 2.0, y: 2.0 }],
+        [Name("x".to_string()), Name("y".to_string())],
+    ));
+    let bytes = registry.save(&world);
+    let mut loaded = World::new();
+    registry.load(&mut loaded, &bytes).unwrap();
+    assert_eq!(registry.save(&loaded), bytes);
+}
+
+#[test]
+fn snapshot_error_test() {
+    let registry = registry();
+    let (world, _) = build_world();
+    let bytes = registry.save(&world);
+
+    let mut target = World::new();
+
+    let mut bad_magic = bytes.clone();
+    bad_magic[0] = b'X';
+    assert!(matches!(
+        registry.load(&mut target, &bad_magic),
+        Err(SnapshotError::InvalidMagic)
+    ));
+
+    let mut bad_version = bytes.clone();
+    bad_version[8..12].copy_from_slice(&(SNAPSHOT_VERSION + 1).to_le_bytes());
+    assert!(matches!(
+        registry.load(&mut target, &bad_version),
+        Err(SnapshotError::UnsupportedVersion(_))
+    ));
+
+    let mut partial = SnapshotRegistry::new();
+    partial.register::<Transform>();
+    assert!(matches!(
+        partial.load(&mut target, &bytes),
+        Err(SnapshotError::UnknownComponent(_))
+    ));
+
+    // Truncated data fails without leaving any partially loaded entities behind
+    for len in [12, bytes.len() / 2, bytes.len() - 1] {
+        assert!(matches!(
+            registry.load(&mut target, &bytes[..len]),
+            Err(SnapshotError::UnexpectedEof)
+        ));
+    }
+    assert_eq!(target.len(), 0);
+
+    let mut trailing = bytes.clone();
+    trailing.push(0);
+    assert!(matches!(
+        registry.load(&mut target, &trailing),
+        Err(SnapshotError::Malformed(_))
+    ));
+    assert_eq!(target.len(), 0);
+}
+
+/// Returns the offset of the total entity count in a snapshot, found by skipping the name table.
+fn entity_count_offset(bytes: &[u8]) -> usize {
+    let read_u32 =
+        |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+    let mut offset = 16;
+    for _ in 0..read_u32(12) {
+        offset += 4 + read_u32(offset) as usize;
+    }
+    offset
+}
+
+#[test]
+fn snapshot_corrupt_test() {
+    let registry = registry();
+    let (world, _) = build_world();
+    let bytes = registry.save(&world);
+
+    let mut target = World::new();
+    let existing = target
+        .insert_singleton(Target {
+            entity: EntityHandle::dangling(),
+            active: false,
+        })
+        .unwrap();
+
+    // Entity counts that don't match the archetypes are rejected, a huge count must not spawn
+    // anything before the archetypes are checked.
+    let offset = entity_count_offset(&bytes);
+    for count in [u32::MAX, world.len() as u32 - 1, world.len() as u32 + 1] {
+        let mut corrupt = bytes.clone();
+        corrupt[offset..offset + 4].copy_from_slice(&count.to_le_bytes());
+        assert!(matches!(
+            registry.load(&mut target, &corrupt),
+            Err(SnapshotError::Malformed(_))
+        ));
+        assert_eq!(target.len(), 1);
+    }
+
+    // A singleton whose component data is corrupt must not replace the world's singleton. The
+    // singleton's archetype holds only the singleton tag and a 'Target', so the last byte of the
+    // snapshot is the 'Target::active' bool.
+    let mut source = World::new();
+    source
+        .insert_singleton(Target {
+            entity: EntityHandle::dangling(),
+            active: true,
+        })
+        .unwrap();
+    let mut corrupt = registry.save(&source);
+    *corrupt.last_mut().unwrap() = 2;
+    assert!(matches!(
+        registry.load(&mut target, &corrupt),
+        Err(SnapshotError::Malformed(_))
+    ));
+    assert_eq!(target.len(), 1);
+    assert!(!target.get_singleton_ref::<Target>().unwrap().active);
+
+    // The uncorrupted snapshot does replace it
+    *corrupt.last_mut().unwrap() = 1;
+    let handles = registry.load(&mut target, &corrupt).unwrap();
+    assert_eq!(target.len(), 2);
+    assert!(target.get_singleton_ref::<Target>().unwrap().active);
+    assert!(target.is_live(existing));
+    assert!(target.is_live(handles[0]));
+}
+
+#[test]
+fn snapshot_entity_cap() {
+    let mut registry = registry();
+
+    // Entities with no components take up no space, so a tiny snapshot can claim any number of
+    // them. The layout is the header, an empty name table, the entity count, and a single
+    // archetype with no components followed by its entity count.
+    let mut source = World::new();
+    source.spawn_entity();
+    source.spawn_entity();
+    let bytes = registry.save(&source);
+    assert_eq!(bytes.len(), 32);
+
+    let mut corrupt = bytes.clone();
+    corrupt[16..20].copy_from_slice(&u32::MAX.to_le_bytes());
+    corrupt[28..32].copy_from_slice(&u32::MAX.to_le_bytes());
+    let mut target = World::new();
+    assert!(matches!(
+        registry.load(&mut target, &corrupt),
+        Err(SnapshotError::Malformed(_))
+    ));
+    assert_eq!(target.len(), 0);
+
+    // The cap can be adjusted to fit the snapshots a program expects
+    registry.set_max_entities(1);
+    assert!(matches!(
+        registry.load(&mut target, &bytes),
+        Err(SnapshotError::Malformed(_))
+    ));
+    registry.set_max_entities(2);
+    assert_eq!(registry.load(&mut target, &bytes).unwrap().len(), 2);
+}
@@ -0,0 +1,118 @@
+//
+//
+// This file is a part of Aleph
+//
+// https://github.com/nathanvoglsam/aleph
+//
+// MIT License
+//
+// Copyright (c) 2020 Aleph Engine
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+/// A point in time in an ECS world, used for change detection.
+///
+/// A [`crate::world::World`] keeps a 'current' tick which is stamped into [`ComponentTicks`]
+/// whenever a component is added or mutably accessed. Query filters compare the stamped ticks
+/// against a reference tick to find the components that changed since then.
+///
+/// # Wrapping
+///
+/// The tick advances far more often than once per frame: every run of a system with a query claims
+/// its own tick. A 32-bit counter could wrap within days, silently flipping the result of
+/// comparisons against old ticks, so ticks are a 64-bit counter instead. Even advancing a billion
+/// times a second it would take centuries to wrap, so old ticks never need to be clamped.
+/// Comparisons are still made relative to the current tick, and are correct for any tick less than
+/// `u64::MAX / 2` ticks older than the current tick.
+#[repr(transparent)]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Default, Debug)]
+pub struct Tick(pub u64);
+
+impl Tick {
+    /// Returns whether 'self' happened after 'last', from the point of view of 'current'.
+    ///
+    /// Ticks equal to 'last' are _not_ considered newer.
+    #[inline(always)]
+    pub const fn is_newer_than(self, last: Tick, current: Tick) -> bool {
+        let age = current.0.wrapping_sub(self.0);
+        let last_age = current.0.wrapping_sub(last.0);
+        age < last_age
+    }
+
+    /// Returns the tick that comes after 'self'.
+    #[inline(always)]
+    pub const fn next(self) -> Tick {
+        Tick(self.0.wrapping_add(1))
+    }
+}
+
+/// The change detection state of a single component instance.
+///
+/// Stored alongside every component in an archetype column.
+#[repr(C)]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Default, Debug)]
+pub struct ComponentTicks {
+    /// The tick the component was added to its entity.
+    pub added: Tick,
+
+    /// The tick the component was last mutably accessed, or added.
+    pub changed: Tick,
+}
+
+impl ComponentTicks {
+    /// Constructs the ticks for a component that was just added at the given tick.
+    #[inline(always)]
+    pub const fn new(tick: Tick) -> Self {
+        Self {
+            added: tick,
+            changed: tick,
+        }
+    }
+
+    /// Returns whether the component was added after 'last', from the point of view of 'current'.
+    #[inline(always)]
+    pub const fn is_added(&self, last: Tick, current: Tick) -> bool {
+        self.added.is_newer_than(last, current)
+    }
+
+    /// Returns whether the component was changed after 'last', from the point of view of
+    /// 'current'. Adding a component counts as changing it.
+    #[inline(always)]
+    pub const fn is_changed(&self, last: Tick, current: Tick) -> bool {
+        self.changed.is_newer_than(last, current)
+    }
+}
+
+/// The pair of ticks a query uses for change detection.
+///
+/// The [`crate::world::query::Added`] and [`crate::world::query::Changed`] filters match
+/// components added or changed after 'last_run', and a [`crate::world::query::Write`] term stamps
+/// the components it hands out with 'this_run'.
+///
+/// A scheduler keeps a separate 'last_run' for every system, so each system sees the changes made
+/// since _it_ last ran rather than since some world-wide reference point.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Default, Debug)]
+pub struct ChangeTicks {
+    /// The tick change detection filters compare component ticks against.
+    pub last_run: Tick,
+
+    /// The tick stamped into components that are mutably accessed.
+    pub this_run: Tick,
+}
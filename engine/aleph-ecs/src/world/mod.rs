@@ -33,8 +33,10 @@ pub mod query;
 #[cfg(test)]
 mod tests;
 
+use std::marker::PhantomData;
 use std::mem::MaybeUninit;
 use std::ptr::NonNull;
+use std::sync::atomic::{AtomicU64, Ordering};
 
 use aleph_alloc::instrumentation::system;
 use aleph_alloc::{BBox, BHashMap, BHashSet, BVec};
@@ -45,6 +47,8 @@ use crate::component::internal::{COMPONENTS, ComponentIdMap};
 use crate::component::singleton::Singleton;
 use crate::component::{Component, ComponentId};
 use crate::entity::{EntityHandle, EntityHandleArena, EntityLocation};
+use crate::hierarchy::{Children, Parent};
+use crate::tick::{ChangeTicks, ComponentTicks, Tick};
 use crate::type_layout::{TypeLayout, TypeLayoutBuf};
 use crate::world::component_index::{ComponentArchetypeRecord, ComponentIndex};
 use crate::world::insertion::{
@@ -177,6 +181,22 @@ use crate::world::query::{
 /// The list of components is constructed before `fn main()` is ever called. The IDs are assigned
 /// lazily, however in practice they will all be assigned on the first call to [`World::new`].
 ///
+/// # Change Detection
+///
+/// Every component stores the [`Tick`] it was added at, and the tick it was last mutably accessed
+/// at. Mutable access is anything that hands out a mutable reference, like
+/// [`World::get_component_mut`] or a [`query::Write`] query term, whether the reference is
+/// written through or not.
+///
+/// The [`query::Added`] and [`query::Changed`] query filters compare those ticks against a 'last
+/// run' tick to find components added or changed since then. Queries made directly on the world
+/// use the world's 'last change tick', and calling [`World::clear_trackers`] once per frame makes
+/// them match changes made within the last frame.
+///
+/// A scheduler should instead keep a last run tick for each system, and query with
+/// [`World::query_with_ticks`] and friends. Each system then sees the changes made since it last
+/// ran, regardless of when any other system ran. See [`ChangeTicks`].
+///
 pub struct World {
     /// Holds all the components that have been registered with the World
     pub(crate) components: ComponentIndex,
@@ -195,6 +215,15 @@ pub struct World {
 
     /// Holds the edges of the archetype graph. Maps component ID to the links.
     pub(crate) archetype_del_edges: BVec<ComponentIdMap<usize>, EcsSystem>,
+
+    /// The tick stamped into components when they are added or mutably accessed.
+    ///
+    /// Atomic so systems running in parallel, which only hold a shared reference to the world,
+    /// can claim their own tick with [`World::increment_change_tick`].
+    pub(crate) change_tick: AtomicU64,
+
+    /// The reference tick change detection filters compare component ticks against.
+    pub(crate) last_change_tick: Tick,
 }
 
 impl World {
@@ -229,6 +258,8 @@ impl World {
             archetypes,
             archetype_add_edges,
             archetype_del_edges,
+            change_tick: AtomicU64::new(1),
+            last_change_tick: Tick(0),
         };
 
         out
@@ -403,15 +434,111 @@ impl World {
 
     /// Returns `Some(ref)` if the given entity is both live, and has a component of the given type.
     /// `ref` will refer to the component of type `T` associated with the entity.
+    ///
+    /// This marks the component as changed.
     #[inline]
     pub fn get_component_mut<T: Component>(&mut self, entity: EntityHandle) -> Option<&mut T> {
         let ptr = self.raw_get_component(entity, T::DESC.id)?;
+        self.raw_mark_component_changed(entity, T::DESC.id)?;
 
         // Safety: The pointer should be valid for a single T, if raw_get_component is implemented
         //         correctly. We have an appropriate borrow to hand out a &T.
         unsafe { Some(ptr.cast::<T>().as_mut()) }
     }
 
+    /// Returns the change detection ticks of the given component on the given entity, if the
+    /// entity is live and has the component.
+    #[inline]
+    pub fn raw_get_component_ticks(
+        &self,
+        entity: EntityHandle,
+        component: ComponentId,
+    ) -> Option<ComponentTicks> {
+        let location = self.entities.get_ref(entity)?;
+        let ticks = self.get_ticks_at(component, location.archetype, location.row)?;
+
+        // Safety: The tick pointer is valid if get_ticks_at is implemented correctly.
+        unsafe { Some(ticks.read()) }
+    }
+
+    /// Marks the given component on the given entity as changed at the current change tick.
+    ///
+    /// The safe, generic interfaces do this automatically when handing out mutable references.
+    /// Code that writes to components through the raw pointer interfaces should call this to keep
+    /// change detection working.
+    ///
+    /// Returns `None` if the entity isn't live or doesn't have the component.
+    #[inline]
+    pub fn raw_mark_component_changed(
+        &mut self,
+        entity: EntityHandle,
+        component: ComponentId,
+    ) -> Option<()> {
+        let location = self.entities.get_ref(entity)?;
+        let ticks = self.get_ticks_at(component, location.archetype, location.row)?;
+
+        // Safety: The tick pointer is valid if get_ticks_at is implemented correctly, and we have
+        //         a mutable borrow of the world.
+        unsafe {
+            (*ticks.as_ptr()).changed = self.change_tick();
+        }
+        Some(())
+    }
+
+    /// Returns the world's current change tick. This is the tick stamped into components when
+    /// they are added or mutably accessed.
+    #[inline(always)]
+    pub fn change_tick(&self) -> Tick {
+        Tick(self.change_tick.load(Ordering::Relaxed))
+    }
+
+    /// Returns the world's last change tick. Change detection filters match components added or
+    /// changed after this tick.
+    #[inline(always)]
+    pub fn last_change_tick(&self) -> Tick {
+        self.last_change_tick
+    }
+
+    /// Overrides the world's last change tick.
+    ///
+    /// Code that wants to track changes over a different period than [`World::clear_trackers`]
+    /// can remember [`World::change_tick`], call [`World::increment_change_tick`] so later changes
+    /// get a newer tick, and later set the remembered tick here to find the changes made since.
+    #[inline(always)]
+    pub fn set_last_change_tick(&mut self, tick: Tick) {
+        self.last_change_tick = tick;
+    }
+
+    /// Returns the change detection ticks used by queries made directly on the world. Changes are
+    /// tracked from the world's last change tick up to its current change tick.
+    #[inline(always)]
+    pub fn change_ticks(&self) -> ChangeTicks {
+        ChangeTicks {
+            last_run: self.last_change_tick,
+            this_run: self.change_tick(),
+        }
+    }
+
+    /// Advances the world's current change tick, returning the previous value.
+    ///
+    /// Only needs a shared reference so a scheduler can claim a unique tick for each system run,
+    /// even for systems running in parallel.
+    #[inline(always)]
+    pub fn increment_change_tick(&self) -> Tick {
+        Tick(self.change_tick.fetch_add(1, Ordering::Relaxed))
+    }
+
+    /// Starts a new change detection period. The [`query::Added`] and [`query::Changed`] filters
+    /// in queries made directly on the world will only match components added or changed after
+    /// this call.
+    ///
+    /// Typically called once per frame, at the frame boundary. Queries made with explicit
+    /// [`ChangeTicks`], like those handed to systems by a scheduler, aren't affected.
+    #[inline]
+    pub fn clear_trackers(&mut self) {
+        self.last_change_tick = self.increment_change_tick();
+    }
+
     /// Find the index in the archetype table where the archetype for the given type layout can be
     /// found.
     ///
@@ -542,7 +669,13 @@ impl World {
     /// world.
     ///
     /// Returns `None` if the handle was invalid and no entity was successfully removed.
+    ///
+    /// If the entity is part of a hierarchy it is removed from its parent's [`Children`], and its
+    /// own children become roots. Use [`World::remove_entity_recursive`] to remove the children
+    /// too.
     pub fn remove_entity(&mut self, entity: EntityHandle) -> Option<()> {
+        self.unlink_from_hierarchy(entity);
+
         let location = self.entities.free(entity)?;
 
         unsafe {
@@ -599,11 +732,21 @@ impl World {
     pub fn query_one<Q: ReadOnlyComponentQuery>(
         &self,
         entity: EntityHandle,
+    ) -> Option<ComponentQueryItem<'_, Q>> {
+        self.query_one_with_ticks::<Q>(entity, self.change_ticks())
+    }
+
+    /// Like [`World::query_one`], but change detection filters use the given ticks instead of the
+    /// world's. See [`ChangeTicks`].
+    pub fn query_one_with_ticks<Q: ReadOnlyComponentQuery>(
+        &self,
+        entity: EntityHandle,
+        ticks: ChangeTicks,
     ) -> Option<ComponentQueryItem<'_, Q>> {
         let location = self.entities.get_ref(entity)?;
 
         // We check if the entity matches the query.
-        for c in Q::query_info() {
+        for c in Q::query_info().filter(|v| !v.optional) {
             let entity_has = self.components[c.id]
                 .archetypes
                 .get(&location.archetype)
@@ -616,8 +759,11 @@ impl World {
         }
 
         unsafe {
-            let fetch =
-                Q::Fetch::create_at(self, location.archetype, location.row).unwrap_unchecked();
+            let fetch = Q::Fetch::create_at(self, location.archetype, location.row, ticks)
+                .unwrap_unchecked();
+            if !fetch.matches() {
+                return None;
+            }
             Some(fetch.get())
         }
     }
@@ -642,6 +788,26 @@ impl World {
     pub fn query_one_mut<Q: ComponentQuery>(
         &mut self,
         entity: EntityHandle,
+    ) -> Option<ComponentQueryItem<'_, Q>> {
+        // Safety: We hold exclusive access to the whole world
+        unsafe { self.query_one_unchecked::<Q>(entity, self.change_ticks()) }
+    }
+
+    /// The unchecked form of [`World::query_one_mut`] that only requires shared access to the world.
+    ///
+    /// This exists so a scheduler can hand out mutable access to individual component types to
+    /// systems that run concurrently, without any of them holding exclusive access to the world.
+    /// Change detection uses the given ticks, see [`ChangeTicks`].
+    ///
+    /// # Safety
+    ///
+    /// For the lifetime of the returned item the caller must guarantee that no other reference to
+    /// a component type written by `Q` exists, and that no other reference to the world is used to
+    /// access a component type written by `Q` or to change the world's structure.
+    pub unsafe fn query_one_unchecked<Q: ComponentQuery>(
+        &self,
+        entity: EntityHandle,
+        ticks: ChangeTicks,
     ) -> Option<ComponentQueryItem<'_, Q>> {
         // First verify the entity is live before we do our more expensive validation
         let location = self.entities.get_ref(entity)?;
@@ -665,16 +831,17 @@ impl World {
         {
             let mut components = [ComponentId(0); 64];
 
+            // Filter terms don't access the component so they're allowed to alias other terms.
             let mut count = 0;
-            for (i, v) in Q::query_info().enumerate() {
-                count = usize::max(count, i);
+            for v in Q::query_info().filter(|v| !v.filter) {
                 let i = components
-                    .get_mut(i)
+                    .get_mut(count)
                     .expect("Must have less than 64 query items");
                 *i = v.id;
+                count += 1;
             }
 
-            let components = &mut components[0..=count];
+            let components = &mut components[0..count];
             components.sort_unstable();
             let _layout = TypeLayout::from_inner(&components)
                 .expect("Must be no duplicate components referenced in query");
@@ -685,7 +852,7 @@ impl World {
         // query to determine if we can yield a value or not.
         //
         // So instead of the set operations we just check the archetype
-        for c in Q::query_info() {
+        for c in Q::query_info().filter(|v| !v.optional) {
             let entity_has = self.components[c.id]
                 .archetypes
                 .get(&location.archetype)
@@ -701,8 +868,11 @@ impl World {
         //         as they all come from within the ECS world. The other constraint, on mutable
         //         references, is checked above.
         unsafe {
-            let fetch =
-                Q::Fetch::create_at(self, location.archetype, location.row).unwrap_unchecked();
+            let fetch = Q::Fetch::create_at(self, location.archetype, location.row, ticks)
+                .unwrap_unchecked();
+            if !fetch.matches() {
+                return None;
+            }
             Some(fetch.get())
         }
     }
@@ -727,12 +897,21 @@ impl World {
     /// duplicate component term check. We don't think it's worth having the extra machinery to
     /// skip the check here.
     pub fn query<Q: ReadOnlyComponentQuery>(&self) -> QueryRef<'_, Q> {
+        self.query_with_ticks::<Q>(self.change_ticks())
+    }
+
+    /// Like [`World::query`], but change detection filters use the given ticks instead of the
+    /// world's. See [`ChangeTicks`].
+    pub fn query_with_ticks<Q: ReadOnlyComponentQuery>(
+        &self,
+        ticks: ChangeTicks,
+    ) -> QueryRef<'_, Q> {
         let matches = self.find_query_matches::<Q>();
 
         unsafe {
             QueryRef::<Q> {
                 world: self,
-                inner: UnsafeQuery::new(matches.into_iter()),
+                inner: UnsafeQuery::new(matches.into_iter(), ticks),
             }
         }
     }
@@ -747,12 +926,32 @@ impl World {
     /// query term. This is to prevent unsound mutable aliasing by trying to read and write a
     /// component in the same query.
     pub fn query_mut<Q: ComponentQuery>(&mut self) -> QueryMut<'_, Q> {
+        // Safety: We hold exclusive access to the whole world
+        unsafe { self.query_mut_unchecked::<Q>(self.change_ticks()) }
+    }
+
+    /// The unchecked form of [`World::query_mut`] that only requires shared access to the world.
+    ///
+    /// This exists so a scheduler can hand out mutable access to individual component types to
+    /// systems that run concurrently, without any of them holding exclusive access to the world.
+    /// Change detection uses the given ticks, see [`ChangeTicks`].
+    ///
+    /// # Safety
+    ///
+    /// For the lifetime of the returned query the caller must guarantee that no other reference to
+    /// a component type written by `Q` exists, and that no other reference to the world is used to
+    /// access a component type written by `Q` or to change the world's structure.
+    pub unsafe fn query_mut_unchecked<Q: ComponentQuery>(
+        &self,
+        ticks: ChangeTicks,
+    ) -> QueryMut<'_, Q> {
         let matches = self.find_query_matches::<Q>();
 
         unsafe {
             QueryMut::<Q> {
                 world: self,
-                inner: UnsafeQuery::new(matches.into_iter()),
+                inner: UnsafeQuery::new(matches.into_iter(), ticks),
+                phantom: PhantomData,
             }
         }
     }
@@ -761,9 +960,26 @@ impl World {
     ///
     /// Queries match on types, so what this function does in practice is finds every _archetype_
     /// that matches `Q` and clears it, dropping all components and releasing all entity handles.
+    ///
+    /// Entities that are part of a hierarchy are removed one at a time with
+    /// [`World::remove_entity`] so the hierarchy is kept consistent.
     pub fn remove_matching<Q: ReadOnlyComponentQuery>(&mut self) {
         let matches = self.find_query_matches::<Q>();
+
+        // Unlinking the hierarchy moves the parents and children of removed entities between
+        // archetypes, possibly into or out of the matching ones. Snapshot the entities in the
+        // hierarchy first so the result doesn't depend on the order archetypes are visited.
+        let mut linked = Vec::new();
         for i in matches {
+            let archetype = &self.archetypes[i];
+            let layout = &archetype.type_layout;
+            if layout.contains_component_type(Parent::DESC.id)
+                || layout.contains_component_type(Children::DESC.id)
+            {
+                linked.extend_from_slice(archetype.entity_ids_ref());
+                continue;
+            }
+
             // First we free all the entity handles so the entities in the matching archetype will
             // become unavailable as they will be considered dead.
             //
@@ -776,6 +992,10 @@ impl World {
             // This also resets the length of the archetype to 0.
             self.clear_archetype(i);
         }
+
+        for entity in linked {
+            self.remove_entity(entity);
+        }
     }
 
     /// Insert a 'singleton' entity with the given component into the world. If one already exists
@@ -912,6 +1132,19 @@ impl World {
         Some(ptr)
     }
 
+    /// Returns a pointer to the change ticks of the given component in the given archetype row.
+    #[inline(always)]
+    pub(crate) fn get_ticks_at(
+        &self,
+        component: ComponentId,
+        archetype: usize,
+        row: usize,
+    ) -> Option<NonNull<ComponentTicks>> {
+        let arch = self.archetypes.get(archetype)?;
+        let column = self.entity_has_component(archetype, component)?.column;
+        arch.columns[column].get_ticks_at_index(row)
+    }
+
     #[inline]
     fn copy_component_from_to_archetype(
         &mut self,
@@ -931,8 +1164,10 @@ impl World {
             .get_disjoint_mut([src_archetype, dst_archetype])
             .ok()?;
 
-        let src_ptr = src_archetype.columns[src_column].get_at_index(src_row)?;
-        let dst_ptr = dst_archetype.columns[dst_column].get_at_index(dst_row)?;
+        let src_column = &src_archetype.columns[src_column];
+        let dst_column = &mut dst_archetype.columns[dst_column];
+        let src_ptr = src_column.get_at_index(src_row)?;
+        let dst_ptr = dst_column.get_at_index(dst_row)?;
 
         // Safety: Implementation should guarantee that if all of the above operations succeed then
         //         this access is valid
@@ -940,6 +1175,11 @@ impl World {
             dst_ptr.copy_from_nonoverlapping(src_ptr, component_info.desc.size);
         }
 
+        // Moving a component to another archetype doesn't count as a change, so the ticks move
+        // with it.
+        let ticks = unsafe { src_column.get_ticks_at_index(src_row)?.read() };
+        dst_column.ticks_mut()[dst_row] = ticks;
+
         Some(())
     }
 
@@ -1082,7 +1322,7 @@ impl World {
     }
 
     /// Given some set of component ids in 'types', validate that each type exists
-    fn find_archetype_for_component_set(
+    pub(crate) fn find_archetype_for_component_set(
         &mut self,
         types: impl ExactSizeIterator<Item = ComponentId>,
     ) -> Option<usize> {
@@ -1167,6 +1407,12 @@ impl World {
                 }
                 src_fn(dst_ptr, component_size);
             }
+
+            // Replacing a component counts as changing it, not adding it.
+            unsafe {
+                self.raw_mark_component_changed(entity, component)
+                    .unwrap_unchecked();
+            }
         } else {
             // If the entity does not already have a component of the given type then we must add
             // it by moving the entity to a different archetype.
@@ -1221,6 +1467,11 @@ impl World {
                     .entity_get_component(dst_archetype, dst_row, component)
                     .unwrap_unchecked();
                 src_fn(dst_ptr, component_size);
+
+                let dst_ticks = self
+                    .get_ticks_at(component, dst_archetype, dst_row)
+                    .unwrap_unchecked();
+                dst_ticks.write(ComponentTicks::new(self.change_tick()));
             }
 
             // And, finally, patch the location of the entity that we added the component to.
@@ -1366,6 +1617,12 @@ impl World {
             info.copy_into_columns(self, archetype_index, base_row, count);
         }
 
+        // Every component of the new entities was just added.
+        let ticks = ComponentTicks::new(self.change_tick());
+        for column in self.archetypes[archetype_index].columns.iter_mut() {
+            column.ticks_mut()[base_row..base_row + count].fill(ticks);
+        }
+
         // Once we've copied the component data we allocate entity handles for each new entity and
         // update the id back-reference entries in the archetype.
         let ids = &mut self.archetypes[archetype_index].entity_handles;
@@ -1410,13 +1667,21 @@ impl World {
             let mut required_count = 0;
             let mut denied_count = 0;
             for v in Q::query_info() {
-                matches[matches_count] = v.id;
-                matches_count += 1;
+                // Filter terms don't access the component so they're allowed to alias other terms.
+                if !v.filter {
+                    matches[matches_count] = v.id;
+                    matches_count += 1;
+                }
+
+                // Optional terms match archetypes with or without the component.
+                if v.optional {
+                    continue;
+                }
                 if v.required {
                     required[required_count] = v.id;
                     required_count += 1;
                 } else {
-                    denied[required_count] = v.id;
+                    denied[denied_count] = v.id;
                     denied_count += 1;
                 }
             }
@@ -1431,14 +1696,23 @@ impl World {
 
             // Now we can make the required and denied subset layouts.
             //
-            // These don't need to be de-duplicated or sorted!
+            // These don't need to be de-duplicated or sorted! A filter term may repeat a required
+            // component, which doesn't change the set of archetypes that match.
             let required = &mut required[0..required_count];
             let denied = &mut denied[0..denied_count];
 
             (required, denied)
         };
 
-        let mut required_archetypes = self.find_archetypes_with_components(required);
+        // A query made only of optional or denied terms requires nothing, so every archetype is a
+        // candidate rather than none of them.
+        let mut required_archetypes = if required.is_empty() {
+            let mut all = BHashSet::with_capacity_in(self.archetypes.len(), system());
+            all.extend(0..self.archetypes.len());
+            all
+        } else {
+            self.find_archetypes_with_components(required)
+        };
         let denied_archetypes = self.find_archetypes_with_components(denied);
         required_archetypes.retain(|v| !denied_archetypes.contains(v));
 
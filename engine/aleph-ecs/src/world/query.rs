@@ -31,10 +31,12 @@ use std::marker::PhantomData;
 use std::ptr::NonNull;
 
 use aleph_alloc::BHashSet;
+use rayon::prelude::*;
 
 use crate::EcsSystem;
 use crate::component::{Component, ComponentId};
 use crate::entity::EntityHandle;
+use crate::tick::{ChangeTicks, ComponentTicks, Tick};
 use crate::world::World;
 
 // =================================================================================================
@@ -70,7 +72,9 @@ pub unsafe trait Fetch<'a>: Clone + Sized {
     /// Constructs an instance of [`Fetch`] from the given archetype.
     ///
     /// Takes a pointer because borrow could mutable or shared depending on the implementation.
-    fn create_at(world: &World, archetype: usize, row: usize) -> Option<Self>;
+    ///
+    /// 'ticks' are the change detection ticks of the query, see [`ChangeTicks`].
+    fn create_at(world: &World, archetype: usize, row: usize, ticks: ChangeTicks) -> Option<Self>;
 
     /// Skip to the next item in the stream
     ///
@@ -78,11 +82,33 @@ pub unsafe trait Fetch<'a>: Clone + Sized {
     /// checking should be done externally.
     unsafe fn next(&mut self);
 
+    /// Skip forward 'n' items in the stream. Equivalent to calling [`Fetch::next`] 'n' times.
+    ///
+    /// The same bounds requirements as [`Fetch::next`] apply.
+    #[inline]
+    unsafe fn advance(&mut self, n: usize) {
+        for _ in 0..n {
+            unsafe { self.next() }
+        }
+    }
+
     /// Yields the item at the current position
     ///
     /// This *will* trigger UB if called when `Self` is out of bounds. To use this safely bounds
     /// checks must be implemented by users of this trait.
     unsafe fn get(&self) -> Self::Item;
+
+    /// Returns whether the item at the current position passes this fetch's per-entity filter.
+    /// Queries skip any entity where this returns false, and must call this before
+    /// [`Fetch::get`].
+    ///
+    /// Most fetches filter at the archetype level only, so the default always returns true.
+    ///
+    /// The same bounds requirements as [`Fetch::get`] apply.
+    #[inline(always)]
+    unsafe fn matches(&self) -> bool {
+        true
+    }
 }
 
 // =================================================================================================
@@ -100,8 +126,17 @@ pub struct ComponentQueryInfo {
     /// - `false` = negative bound, component must _not be_ present for the query to match.
     pub required: bool,
 
+    /// Flags if the component type is optional. Optional terms match archetypes both with and
+    /// without the component, so `required` is ignored when this is set.
+    pub optional: bool,
+
     /// Whether the query wants to be able to write to the component.
     pub mutable: bool,
+
+    /// Whether the term is a change detection filter. Filters only read the change ticks of the
+    /// component, never the component itself, so they may share a component type with another
+    /// term in the same query.
+    pub filter: bool,
 }
 
 /// Type of values yielded by a query
@@ -110,9 +145,15 @@ pub struct ComponentQueryInfo {
 pub type ComponentQueryItem<'a, Q> = <<Q as ComponentQuery>::Fetch as Fetch<'a>>::Item;
 
 /// Query parameter that declares a query wants to read the given component type `T`.
+///
+/// `Option<Read<T>>` can be used to optionally read `T`. It matches entities with or without the
+/// component, yielding `None` for entities that don't have it. Optional terms don't constrain which
+/// archetypes match, so a query needs at least one non-optional term to match anything.
 pub struct Read<T>(PhantomData<T>);
 
 /// Query parameter that declares a query wants to write the given component type `T`.
+///
+/// `Option<Write<T>>` can be used to optionally write `T`, see [`Read`].
 pub struct Write<T>(PhantomData<T>);
 
 /// Special query parameter that provides a positive bound on a component type. Use this to query
@@ -128,6 +169,19 @@ pub struct Has<T>(PhantomData<T>);
 /// Data for component `T` can not be accessed through this type.
 pub struct Not<T>(PhantomData<T>);
 
+/// Query filter that only matches entities where the component `T` was added since the query's
+/// last run tick. For queries made directly on the [`World`] that is the world's last change tick,
+/// see [`World::clear_trackers`]. A scheduler tracks a last run tick for each system instead.
+///
+/// Can be combined with another term for the same `T` to also access the component.
+pub struct Added<T>(PhantomData<T>);
+
+/// Query filter that only matches entities where the component `T` was mutably accessed, or
+/// added, since the query's last run tick. See [`Added`] for where that tick comes from.
+///
+/// Can be combined with another term for the same `T` to also access the component.
+pub struct Changed<T>(PhantomData<T>);
+
 /// Internal type that implements `Fetch` for shared references
 pub struct ComponentRead<T>(NonNull<T>);
 
@@ -140,13 +194,18 @@ impl<T> Clone for ComponentRead<T> {
 
 impl<T> Copy for ComponentRead<T> {}
 
-/// Internal type that implements `Fetch` for mutable references
-pub struct ComponentWrite<T>(NonNull<T>);
+/// Internal type that implements `Fetch` for mutable references. Marks each component it yields as
+/// changed.
+pub struct ComponentWrite<T> {
+    ptr: NonNull<T>,
+    ticks: NonNull<ComponentTicks>,
+    tick: Tick,
+}
 
 impl<T> Clone for ComponentWrite<T> {
     #[inline(always)]
     fn clone(&self) -> Self {
-        Self(self.0)
+        *self
     }
 }
 
@@ -164,6 +223,40 @@ impl<T> Clone for NoFetch<T> {
 
 impl<T> Copy for NoFetch<T> {}
 
+/// Internal type that implements `Fetch` for the [`Added`] filter
+pub struct ComponentAdded<T> {
+    ticks: NonNull<ComponentTicks>,
+    last: Tick,
+    current: Tick,
+    phantom: PhantomData<T>,
+}
+
+impl<T> Clone for ComponentAdded<T> {
+    #[inline(always)]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for ComponentAdded<T> {}
+
+/// Internal type that implements `Fetch` for the [`Changed`] filter
+pub struct ComponentChanged<T> {
+    ticks: NonNull<ComponentTicks>,
+    last: Tick,
+    current: Tick,
+    phantom: PhantomData<T>,
+}
+
+impl<T> Clone for ComponentChanged<T> {
+    #[inline(always)]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for ComponentChanged<T> {}
+
 // =================================================================================================
 // Query Implementations
 // =================================================================================================
@@ -176,7 +269,9 @@ impl<T: Component> ComponentQuery for Read<T> {
         std::iter::once(ComponentQueryInfo {
             id: T::DESC.id,
             required: true,
+            optional: false,
             mutable: false,
+            filter: false,
         })
     }
 }
@@ -191,7 +286,9 @@ impl<T: Component> ComponentQuery for Write<T> {
         std::iter::once(ComponentQueryInfo {
             id: T::DESC.id,
             required: true,
-            mutable: false,
+            optional: false,
+            mutable: true,
+            filter: false,
         })
     }
 }
@@ -204,7 +301,9 @@ impl<T: Component> ComponentQuery for Has<T> {
         std::iter::once(ComponentQueryInfo {
             id: T::DESC.id,
             required: true,
+            optional: false,
             mutable: false,
+            filter: false,
         })
     }
 }
@@ -219,13 +318,81 @@ impl<T: Component> ComponentQuery for Not<T> {
         std::iter::once(ComponentQueryInfo {
             id: T::DESC.id,
             required: false,
+            optional: false,
             mutable: false,
+            filter: false,
         })
     }
 }
 
 unsafe impl<T: Component> ReadOnlyComponentQuery for Not<T> {}
 
+impl<T: Component> ComponentQuery for Added<T> {
+    type Fetch = ComponentAdded<T>;
+
+    #[inline]
+    fn query_info() -> impl Iterator<Item = ComponentQueryInfo> {
+        std::iter::once(ComponentQueryInfo {
+            id: T::DESC.id,
+            required: true,
+            optional: false,
+            mutable: false,
+            filter: true,
+        })
+    }
+}
+
+unsafe impl<T: Component> ReadOnlyComponentQuery for Added<T> {}
+
+impl<T: Component> ComponentQuery for Changed<T> {
+    type Fetch = ComponentChanged<T>;
+
+    #[inline]
+    fn query_info() -> impl Iterator<Item = ComponentQueryInfo> {
+        std::iter::once(ComponentQueryInfo {
+            id: T::DESC.id,
+            required: true,
+            optional: false,
+            mutable: false,
+            filter: true,
+        })
+    }
+}
+
+unsafe impl<T: Component> ReadOnlyComponentQuery for Changed<T> {}
+
+impl<T: Component> ComponentQuery for Option<Read<T>> {
+    type Fetch = Option<ComponentRead<T>>;
+
+    #[inline]
+    fn query_info() -> impl Iterator<Item = ComponentQueryInfo> {
+        std::iter::once(ComponentQueryInfo {
+            id: T::DESC.id,
+            required: false,
+            optional: true,
+            mutable: false,
+            filter: false,
+        })
+    }
+}
+
+unsafe impl<T: Component> ReadOnlyComponentQuery for Option<Read<T>> {}
+
+impl<T: Component> ComponentQuery for Option<Write<T>> {
+    type Fetch = Option<ComponentWrite<T>>;
+
+    #[inline]
+    fn query_info() -> impl Iterator<Item = ComponentQueryInfo> {
+        std::iter::once(ComponentQueryInfo {
+            id: T::DESC.id,
+            required: false,
+            optional: true,
+            mutable: true,
+            filter: false,
+        })
+    }
+}
+
 // =================================================================================================
 // Fetch Implementations
 // =================================================================================================
@@ -234,7 +401,7 @@ unsafe impl<'a, T: Component> Fetch<'a> for ComponentRead<T> {
     type Item = &'a T;
 
     #[inline]
-    fn create_at(world: &World, archetype: usize, row: usize) -> Option<Self> {
+    fn create_at(world: &World, archetype: usize, row: usize, _: ChangeTicks) -> Option<Self> {
         let column = world.components[T::DESC.id]
             .archetypes
             .get(&archetype)?
@@ -250,6 +417,13 @@ unsafe impl<'a, T: Component> Fetch<'a> for ComponentRead<T> {
         }
     }
 
+    #[inline]
+    unsafe fn advance(&mut self, n: usize) {
+        unsafe {
+            self.0 = self.0.add(n);
+        }
+    }
+
     #[inline]
     unsafe fn get(&self) -> Self::Item {
         unsafe { self.0.as_ref() }
@@ -260,25 +434,43 @@ unsafe impl<'a, T: Component> Fetch<'a> for ComponentWrite<T> {
     type Item = &'a mut T;
 
     #[inline]
-    fn create_at(world: &World, archetype: usize, row: usize) -> Option<Self> {
+    fn create_at(world: &World, archetype: usize, row: usize, ticks: ChangeTicks) -> Option<Self> {
         let column = world.components[T::DESC.id]
             .archetypes
             .get(&archetype)?
             .column;
-        let ptr = world.archetypes[archetype].columns[column].get_at_index(row)?;
-        Some(Self(ptr.cast::<T>()))
+        let column = &world.archetypes[archetype].columns[column];
+        Some(Self {
+            ptr: column.get_at_index(row)?.cast::<T>(),
+            ticks: column.get_ticks_at_index(row)?,
+            tick: ticks.this_run,
+        })
     }
 
     #[inline(always)]
     unsafe fn next(&mut self) {
         unsafe {
-            self.0 = NonNull::new_unchecked(self.0.as_ptr().add(1));
+            self.ptr = NonNull::new_unchecked(self.ptr.as_ptr().add(1));
+            self.ticks = self.ticks.add(1);
+        }
+    }
+
+    #[inline(always)]
+    unsafe fn advance(&mut self, n: usize) {
+        unsafe {
+            self.ptr = self.ptr.add(n);
+            self.ticks = self.ticks.add(n);
         }
     }
 
     #[inline(always)]
     unsafe fn get(&self) -> Self::Item {
-        unsafe { &mut *self.0.as_ptr() }
+        unsafe {
+            // We can't tell if the caller will actually write through the reference, so handing
+            // out a mutable reference counts as a change.
+            (*self.ticks.as_ptr()).changed = self.tick;
+            &mut *self.ptr.as_ptr()
+        }
     }
 }
 
@@ -286,19 +478,128 @@ unsafe impl<'a, T: Component> Fetch<'a> for NoFetch<T> {
     type Item = ();
 
     #[inline]
-    fn create_at(_world: &World, _archetype: usize, _row: usize) -> Option<Self> {
+    fn create_at(_: &World, _: usize, _: usize, _: ChangeTicks) -> Option<Self> {
         Some(Self(Default::default()))
     }
 
     #[inline(always)]
     unsafe fn next(&mut self) {}
 
+    #[inline(always)]
+    unsafe fn advance(&mut self, _n: usize) {}
+
     #[inline(always)]
     unsafe fn get(&self) -> Self::Item {
         ()
     }
 }
 
+unsafe impl<'a, F: Fetch<'a>> Fetch<'a> for Option<F> {
+    type Item = Option<F::Item>;
+
+    #[inline]
+    fn create_at(world: &World, archetype: usize, row: usize, ticks: ChangeTicks) -> Option<Self> {
+        // Archetypes without the component yield a 'None' fetch rather than failing to match.
+        Some(F::create_at(world, archetype, row, ticks))
+    }
+
+    #[inline(always)]
+    unsafe fn next(&mut self) {
+        if let Some(fetch) = self {
+            unsafe { fetch.next() }
+        }
+    }
+
+    #[inline(always)]
+    unsafe fn advance(&mut self, n: usize) {
+        if let Some(fetch) = self {
+            unsafe { fetch.advance(n) }
+        }
+    }
+
+    #[inline(always)]
+    unsafe fn get(&self) -> Self::Item {
+        self.as_ref().map(|fetch| unsafe { fetch.get() })
+    }
+
+    #[inline(always)]
+    unsafe fn matches(&self) -> bool {
+        self.as_ref().is_none_or(|fetch| unsafe { fetch.matches() })
+    }
+}
+
+unsafe impl<'a, T: Component> Fetch<'a> for ComponentAdded<T> {
+    type Item = ();
+
+    #[inline]
+    fn create_at(world: &World, archetype: usize, row: usize, ticks: ChangeTicks) -> Option<Self> {
+        Some(Self {
+            ticks: world.get_ticks_at(T::DESC.id, archetype, row)?,
+            last: ticks.last_run,
+            current: ticks.this_run,
+            phantom: PhantomData,
+        })
+    }
+
+    #[inline(always)]
+    unsafe fn next(&mut self) {
+        unsafe {
+            self.ticks = self.ticks.add(1);
+        }
+    }
+
+    #[inline(always)]
+    unsafe fn advance(&mut self, n: usize) {
+        unsafe {
+            self.ticks = self.ticks.add(n);
+        }
+    }
+
+    #[inline(always)]
+    unsafe fn get(&self) -> Self::Item {}
+
+    #[inline(always)]
+    unsafe fn matches(&self) -> bool {
+        unsafe { self.ticks.as_ref().is_added(self.last, self.current) }
+    }
+}
+
+unsafe impl<'a, T: Component> Fetch<'a> for ComponentChanged<T> {
+    type Item = ();
+
+    #[inline]
+    fn create_at(world: &World, archetype: usize, row: usize, ticks: ChangeTicks) -> Option<Self> {
+        Some(Self {
+            ticks: world.get_ticks_at(T::DESC.id, archetype, row)?,
+            last: ticks.last_run,
+            current: ticks.this_run,
+            phantom: PhantomData,
+        })
+    }
+
+    #[inline(always)]
+    unsafe fn next(&mut self) {
+        unsafe {
+            self.ticks = self.ticks.add(1);
+        }
+    }
+
+    #[inline(always)]
+    unsafe fn advance(&mut self, n: usize) {
+        unsafe {
+            self.ticks = self.ticks.add(n);
+        }
+    }
+
+    #[inline(always)]
+    unsafe fn get(&self) -> Self::Item {}
+
+    #[inline(always)]
+    unsafe fn matches(&self) -> bool {
+        unsafe { self.ticks.as_ref().is_changed(self.last, self.current) }
+    }
+}
+
 // =================================================================================================
 // Tuple Impl Macro
 // =================================================================================================
@@ -310,8 +611,13 @@ macro_rules! impl_query_for_tuple {
             type Item = ($($t::Item,)+);
 
             #[inline]
-            fn create_at(world: &World, archetype: usize, row: usize) -> Option<Self> {
-                Some(($(<$t as Fetch>::create_at(world, archetype, row)?,)+))
+            fn create_at(
+                world: &World,
+                archetype: usize,
+                row: usize,
+                ticks: ChangeTicks,
+            ) -> Option<Self> {
+                Some(($(<$t as Fetch>::create_at(world, archetype, row, ticks)?,)+))
             }
 
             #[inline]
@@ -322,6 +628,14 @@ macro_rules! impl_query_for_tuple {
                 }
             }
 
+            #[inline]
+            unsafe fn advance(&mut self, n: usize) {
+                unsafe {
+                    let ($($t,)+) = self;
+                    ($($t.advance(n),)+);
+                }
+            }
+
             #[inline]
             unsafe fn get(&self) -> Self::Item {
                 unsafe {
@@ -329,6 +643,14 @@ macro_rules! impl_query_for_tuple {
                     ($($t.get(),)+)
                 }
             }
+
+            #[inline]
+            unsafe fn matches(&self) -> bool {
+                unsafe {
+                    let ($($t,)+) = self;
+                    true $(&& $t.matches())+
+                }
+            }
         }
 
         impl<$($t: ComponentQuery),+> ComponentQuery for ($($t,)+) {
@@ -381,9 +703,49 @@ impl<'world, Q: ReadOnlyComponentQuery> Iterator for QueryRef<'world, Q> {
     }
 }
 
+impl<'world, Q: ReadOnlyComponentQuery> QueryRef<'world, Q> {
+    /// Converts the query into an iterator over [`QueryChunk`]s, one for each matching archetype.
+    #[inline]
+    pub fn chunks(self) -> QueryChunks<'world, Q> {
+        QueryChunks {
+            world: self.world,
+            inner: self.inner,
+        }
+    }
+
+    /// Calls 'f' for every entity matched by the query, in parallel on the rayon thread pool.
+    ///
+    /// Entities are handed to the workers in batches of [`DEFAULT_PAR_BATCH_SIZE`] rows. The order
+    /// 'f' is called in is unspecified.
+    #[inline]
+    pub fn par_for_each<F>(self, f: F)
+    where
+        F: Fn(EntityHandle, ComponentQueryItem<'world, Q>) + Send + Sync,
+    {
+        par_for_each_in_batches(self.chunks(), DEFAULT_PAR_BATCH_SIZE, f)
+    }
+
+    /// Like [`QueryRef::par_for_each`], but with a custom number of rows per batch.
+    ///
+    /// # Panics
+    ///
+    /// Panics if 'batch_size' is zero.
+    #[inline]
+    pub fn par_for_each_batched<F>(self, batch_size: usize, f: F)
+    where
+        F: Fn(EntityHandle, ComponentQueryItem<'world, Q>) + Send + Sync,
+    {
+        par_for_each_in_batches(self.chunks(), batch_size, f)
+    }
+}
+
 pub struct QueryMut<'world, Q: ComponentQuery> {
-    pub(crate) world: &'world mut World,
+    pub(crate) world: &'world World,
     pub(crate) inner: UnsafeQuery<<BHashSet<usize, EcsSystem> as IntoIterator>::IntoIter, Q>,
+
+    /// A [`QueryMut`] logically holds exclusive access to the components it writes. The world is
+    /// only held by shared reference so [`World::query_mut_unchecked`] can construct one.
+    pub(crate) phantom: PhantomData<&'world mut World>,
 }
 
 impl<'world, Q: ComponentQuery> Iterator for QueryMut<'world, Q> {
@@ -398,15 +760,207 @@ impl<'world, Q: ComponentQuery> Iterator for QueryMut<'world, Q> {
     }
 }
 
+impl<'world, Q: ComponentQuery> QueryMut<'world, Q> {
+    /// Converts the query into an iterator over [`QueryChunk`]s, one for each matching archetype.
+    #[inline]
+    pub fn chunks(self) -> QueryChunks<'world, Q> {
+        QueryChunks {
+            world: self.world,
+            inner: self.inner,
+        }
+    }
+
+    /// Calls 'f' for every entity matched by the query, in parallel on the rayon thread pool.
+    ///
+    /// Entities are handed to the workers in batches of [`DEFAULT_PAR_BATCH_SIZE`] rows. The order
+    /// 'f' is called in is unspecified.
+    #[inline]
+    pub fn par_for_each<F>(self, f: F)
+    where
+        F: Fn(EntityHandle, ComponentQueryItem<'world, Q>) + Send + Sync,
+    {
+        par_for_each_in_batches(self.chunks(), DEFAULT_PAR_BATCH_SIZE, f)
+    }
+
+    /// Like [`QueryMut::par_for_each`], but with a custom number of rows per batch.
+    ///
+    /// # Panics
+    ///
+    /// Panics if 'batch_size' is zero.
+    #[inline]
+    pub fn par_for_each_batched<F>(self, batch_size: usize, f: F)
+    where
+        F: Fn(EntityHandle, ComponentQueryItem<'world, Q>) + Send + Sync,
+    {
+        par_for_each_in_batches(self.chunks(), batch_size, f)
+    }
+}
+
+/// The number of rows each rayon task iterates in [`QueryRef::par_for_each`] and
+/// [`QueryMut::par_for_each`].
+pub const DEFAULT_PAR_BATCH_SIZE: usize = 1024;
+
+/// Shared implementation of `par_for_each`. Splits every chunk into batches of at most
+/// 'batch_size' rows and iterates the batches on the rayon thread pool.
+fn par_for_each_in_batches<'world, Q, F>(chunks: QueryChunks<'world, Q>, batch_size: usize, f: F)
+where
+    Q: ComponentQuery,
+    F: Fn(EntityHandle, ComponentQueryItem<'world, Q>) + Send + Sync,
+{
+    assert_ne!(batch_size, 0, "batch_size must not be zero");
+
+    let mut batches = Vec::new();
+    for mut chunk in chunks {
+        while chunk.len() > batch_size {
+            let (head, tail) = chunk.split_at(batch_size);
+            batches.push(head);
+            chunk = tail;
+        }
+        batches.push(chunk);
+    }
+
+    batches
+        .into_par_iter()
+        .for_each(|batch| batch.for_each(|(id, item)| f(id, item)));
+}
+
+/// Iterator over the [`QueryChunk`]s of a query. Yields one chunk for each matching archetype that
+/// has at least one entity.
+///
+/// Created by [`QueryRef::chunks`] or [`QueryMut::chunks`].
+pub struct QueryChunks<'world, Q: ComponentQuery> {
+    world: &'world World,
+    inner: UnsafeQuery<<BHashSet<usize, EcsSystem> as IntoIterator>::IntoIter, Q>,
+}
+
+impl<'world, Q: ComponentQuery> Iterator for QueryChunks<'world, Q> {
+    type Item = QueryChunk<'world, Q>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let (ids, len, fetch) = self.inner.next_chunk(self.world)?;
+        Some(QueryChunk {
+            ids,
+            len,
+            fetch,
+            phantom: PhantomData,
+        })
+    }
+}
+
+/// A contiguous range of rows from a single archetype matched by a query. Iterating a chunk yields
+/// the same items as the query it came from would for those rows.
+///
+/// Chunks from the same query never overlap, so they can be sent to other threads and iterated in
+/// parallel, even for queries with mutable access. A chunk can be split with
+/// [`QueryChunk::split_at`] to spread a large archetype over more than one thread.
+pub struct QueryChunk<'world, Q: ComponentQuery> {
+    ids: NonNull<EntityHandle>,
+    len: usize,
+    fetch: Q::Fetch,
+    phantom: PhantomData<&'world World>,
+}
+
+// Safety: All components are Send + Sync, the world is borrowed for the whole lifetime of the chunk
+//         and no two chunks cover the same row. Mutable access from different chunks can never
+//         alias.
+unsafe impl<Q: ComponentQuery> Send for QueryChunk<'_, Q> {}
+
+impl<'world, Q: ComponentQuery> QueryChunk<'world, Q> {
+    /// Returns the number of rows remaining in the chunk. This is an upper bound on the number of
+    /// items the chunk will yield, as per-entity filters like [`Changed`] may skip some rows.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether there are no rows remaining in the chunk.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the handles of the entities in the remaining rows of the chunk.
+    #[inline]
+    pub fn entities(&self) -> &'world [EntityHandle] {
+        unsafe { std::slice::from_raw_parts(self.ids.as_ptr(), self.len) }
+    }
+
+    /// Splits the chunk in two at the given row. The first chunk contains the rows `[0, mid)` and
+    /// the second contains the rows `[mid, len)`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `mid > len`.
+    #[inline]
+    pub fn split_at(self, mid: usize) -> (Self, Self) {
+        assert!(mid <= self.len, "mid > len");
+
+        let mut tail_fetch = self.fetch.clone();
+
+        // Safety: 'mid' was bounds checked against the number of remaining rows above
+        let tail = unsafe {
+            tail_fetch.advance(mid);
+            Self {
+                ids: self.ids.add(mid),
+                len: self.len - mid,
+                fetch: tail_fetch,
+                phantom: PhantomData,
+            }
+        };
+        let head = Self {
+            ids: self.ids,
+            len: mid,
+            fetch: self.fetch,
+            phantom: PhantomData,
+        };
+        (head, tail)
+    }
+}
+
+impl<'world, Q: ComponentQuery> Iterator for QueryChunk<'world, Q> {
+    type Item = (EntityHandle, ComponentQueryItem<'world, Q>);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.len != 0 {
+            let out_id = self.ids;
+            let out_fetch = self.fetch.clone();
+
+            // Safety: 'len' tracks how many rows remain, so we never step past the archetype.
+            unsafe {
+                self.ids = self.ids.add(1);
+                self.fetch.next();
+            }
+            self.len -= 1;
+
+            // Safety: 'out_fetch' was in bounds when we cloned it.
+            unsafe {
+                if out_fetch.matches() {
+                    return Some((out_id.read(), out_fetch.get()));
+                }
+            }
+        }
+        None
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.len))
+    }
+}
+
 pub struct UnsafeQuery<I: Iterator<Item = usize>, Q: ComponentQuery> {
     matches: I,
+    ticks: ChangeTicks,
     state: QueryState<Q>,
 }
 
 impl<I: Iterator<Item = usize>, Q: ComponentQuery> UnsafeQuery<I, Q> {
-    pub(crate) unsafe fn new(matches: I) -> Self {
+    pub(crate) unsafe fn new(matches: I, ticks: ChangeTicks) -> Self {
         Self {
             matches,
+            ticks,
             state: QueryState::FindingArchetype,
         }
     }
@@ -441,14 +995,16 @@ impl<I: Iterator<Item = usize>, Q: ComponentQuery> UnsafeQuery<I, Q> {
                     // Safety: It's the caller's responsibility to ensure that all archetypes in
                     //         the 'matches' correctly match the filter 'Q'. This is enforced by
                     //         UnsafeQuery's constructor.
-                    let fetch = unsafe { Q::Fetch::create_at(world, next, 0).unwrap_unchecked() };
+                    let fetch = unsafe {
+                        Q::Fetch::create_at(world, next, 0, self.ticks).unwrap_unchecked()
+                    };
 
                     // Hand off to the next state in the chain
                     self.state = QueryState::IteratingArchetype(ids_start, ids_end, fetch);
                 }
 
                 QueryState::IteratingArchetype(ids, ids_end, fetch) => {
-                    if ids != ids_end {
+                    while ids != ids_end {
                         // We send out pointers for outer callers to deref so we don't need to
                         // handle borrows in this iterator logic.
                         let out_id = *ids;
@@ -462,7 +1018,12 @@ impl<I: Iterator<Item = usize>, Q: ComponentQuery> UnsafeQuery<I, Q> {
                             fetch.next();
                         }
 
-                        return Some((out_id, out_fetch));
+                        // Skip entities rejected by a per-entity filter, like change detection.
+                        //
+                        // Safety: 'out_fetch' was in bounds when we cloned it.
+                        if unsafe { out_fetch.matches() } {
+                            return Some((out_id, out_fetch));
+                        }
                     }
 
                     // If we reach here we have exhausted the archetype, so we try and find the
@@ -472,4 +1033,39 @@ impl<I: Iterator<Item = usize>, Q: ComponentQuery> UnsafeQuery<I, Q> {
             }
         }
     }
+
+    /// Returns the rows of the next matching archetype in one go, as a pointer to the entity IDs,
+    /// the number of rows and a fetch positioned at the first row. If the iterator is part way
+    /// through an archetype only the remaining rows are returned.
+    ///
+    /// Empty archetypes are skipped.
+    pub fn next_chunk(
+        &mut self,
+        world: &World,
+    ) -> Option<(NonNull<EntityHandle>, usize, Q::Fetch)> {
+        let state = std::mem::replace(&mut self.state, QueryState::FindingArchetype);
+        if let QueryState::IteratingArchetype(ids, ids_end, fetch) = state {
+            // Safety: Both pointers come from the same entity ID slice.
+            let len = unsafe { ids_end.offset_from(ids) } as usize;
+            if len != 0 {
+                return Some((ids, len, fetch));
+            }
+        }
+
+        loop {
+            let next = self.matches.next()?;
+            let archetype = &world.archetypes[next];
+            if archetype.len() == 0 {
+                continue;
+            }
+
+            let ids = NonNull::from_ref(archetype.entity_ids_ref()).cast::<EntityHandle>();
+
+            // Safety: See UnsafeQuery::next
+            let fetch =
+                unsafe { Q::Fetch::create_at(world, next, 0, self.ticks).unwrap_unchecked() };
+
+            return Some((ids, archetype.len(), fetch));
+        }
+    }
 }
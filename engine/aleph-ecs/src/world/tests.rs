@@ -29,10 +29,11 @@
 
 use std::sync::Arc;
 
+use crate::component::Component;
 use crate::component::singleton::Singleton;
 use crate::register_component;
 use crate::world::World;
-use crate::world::query::{Has, Read, Write};
+use crate::world::query::{Added, Changed, Has, Not, Read, Write};
 
 #[derive(Clone, Default, PartialEq, Debug)]
 struct Position {
@@ -383,6 +384,41 @@ fn remove_entity_array() {
     assert_eq!(world.len(), 0);
 }
 
+#[test]
+fn remove_entity_with_spare_capacity() {
+    let mut world = World::new();
+
+    // Three entities leaves a spare row in the archetype's columns. Removing an entity must move
+    // the last live row into the hole, not the last allocated row.
+    let ids = world.bulk_insert((
+        [
+            Position::new(1.0, 2.0),
+            Position::new(3.0, 4.0),
+            Position::new(5.0, 6.0),
+        ],
+        [Mesh::new(1), Mesh::new(2), Mesh::new(3)],
+    ));
+
+    assert!(world.remove_entity(ids[0]).is_some());
+    assert_eq!(
+        world.get_component_ref::<Position>(ids[1]),
+        Some(&Position::new(3.0, 4.0))
+    );
+    assert_eq!(
+        world.get_component_ref::<Position>(ids[2]),
+        Some(&Position::new(5.0, 6.0))
+    );
+    assert_eq!(world.get_component_ref::<Mesh>(ids[2]), Some(&Mesh::new(3)));
+
+    // The columns keep their allocation, so the archetype can grow back into it
+    let id = world.insert((Position::new(7.0, 8.0), Mesh::new(4)));
+    assert_eq!(
+        world.get_component_ref::<Position>(id),
+        Some(&Position::new(7.0, 8.0))
+    );
+    assert_eq!(world.query::<Read<Mesh>>().count(), 3);
+}
+
 #[test]
 fn add_remove_component_test() {
     let mut world = World::new();
@@ -724,3 +760,345 @@ fn singleton_api() {
     assert!(!world.has_component::<Position>(p_id));
     assert!(!world.has_component::<Scale>(s_id));
 }
+
+#[test]
+fn added_filter_test() {
+    let mut world = World::new();
+
+    let first = world.bulk_insert(([Position::new(1.0, 2.0), Position::new(3.0, 4.0)],));
+    let count = world.query::<(Read<Position>, Added<Position>)>().count();
+    assert_eq!(count, 2);
+
+    // Nothing is newer than the last change tick after clearing the trackers
+    world.clear_trackers();
+    assert_eq!(world.query::<Added<Position>>().count(), 0);
+
+    let second = world.insert((Position::new(5.0, 6.0),));
+    let added: Vec<_> = world.query::<Added<Position>>().map(|(id, _)| id).collect();
+    assert_eq!(added, vec![second]);
+
+    // Moving an entity to another archetype doesn't re-add its existing components
+    world.add_component(first[0], Scale::new(1.0, 1.0)).unwrap();
+    let added: Vec<_> = world.query::<Added<Position>>().map(|(id, _)| id).collect();
+    assert_eq!(added, vec![second]);
+    assert_eq!(
+        world.get_component_ref::<Position>(second),
+        Some(&Position::new(5.0, 6.0))
+    );
+    let added: Vec<_> = world.query::<Added<Scale>>().map(|(id, _)| id).collect();
+    assert_eq!(added, vec![first[0]]);
+
+    // Replacing an existing component is a change, not an add
+    world.clear_trackers();
+    world
+        .add_component(first[1], Position::new(0.0, 0.0))
+        .unwrap();
+    assert_eq!(world.query::<Added<Position>>().count(), 0);
+    let changed: Vec<_> = world
+        .query::<Changed<Position>>()
+        .map(|(id, _)| id)
+        .collect();
+    assert_eq!(changed, vec![first[1]]);
+}
+
+#[test]
+fn changed_filter_test() {
+    let mut world = World::new();
+
+    let ids = world.bulk_insert((
+        [
+            Position::new(1.0, 2.0),
+            Position::new(3.0, 4.0),
+            Position::new(5.0, 6.0),
+        ],
+        [Mesh::new(1), Mesh::new(2), Mesh::new(3)],
+    ));
+    world.clear_trackers();
+    assert_eq!(world.query::<Changed<Position>>().count(), 0);
+
+    // Both the single entity and query based mutable accessors mark changes
+    world.get_component_mut::<Position>(ids[0]).unwrap().x = 10.0;
+    world.query_one_mut::<Write<Position>>(ids[2]).unwrap().x = 20.0;
+    let changed: Vec<_> = world
+        .query::<(Read<Position>, Changed<Position>)>()
+        .map(|(id, (p, _))| (id, p.x))
+        .collect();
+    assert_eq!(changed.len(), 2);
+    assert!(changed.contains(&(ids[0], 10.0)));
+    assert!(changed.contains(&(ids[2], 20.0)));
+
+    // Filters apply per entity in query_one
+    assert!(world.query_one::<Changed<Position>>(ids[0]).is_some());
+    assert!(world.query_one::<Changed<Position>>(ids[1]).is_none());
+    assert!(world.query_one::<Changed<Mesh>>(ids[0]).is_none());
+
+    // A filter can be combined with a write to the same component. The filter is evaluated before
+    // the write marks the component as changed.
+    world.clear_trackers();
+    world.get_component_mut::<Mesh>(ids[1]).unwrap().a = 7;
+    let written: Vec<_> = world
+        .query_mut::<(Write<Mesh>, Changed<Mesh>)>()
+        .map(|(id, (m, _))| {
+            m.a += 1;
+            id
+        })
+        .collect();
+    assert_eq!(written, vec![ids[1]]);
+    assert_eq!(world.get_component_ref::<Mesh>(ids[1]), Some(&Mesh::new(8)));
+
+    // Iterating a write query marks everything it yields
+    world.clear_trackers();
+    for _ in world.query_mut::<(Write<Position>, Not<Scale>)>() {}
+    assert_eq!(world.query::<Changed<Position>>().count(), 3);
+    assert_eq!(world.query::<Changed<Mesh>>().count(), 0);
+}
+
+#[test]
+fn change_ticks_follow_entities() {
+    let mut world = World::new();
+
+    let ids = world.bulk_insert(([Position::new(1.0, 2.0), Position::new(3.0, 4.0)],));
+    let start = world.change_tick();
+    world.clear_trackers();
+
+    // Removing the first entity swaps the second into its row, the ticks must move with it
+    world.get_component_mut::<Position>(ids[1]).unwrap();
+    world.remove_entity(ids[0]).unwrap();
+    let changed: Vec<_> = world
+        .query::<Changed<Position>>()
+        .map(|(id, _)| id)
+        .collect();
+    assert_eq!(changed, vec![ids[1]]);
+
+    let ticks = world
+        .raw_get_component_ticks(ids[1], Position::DESC.id)
+        .unwrap();
+    assert_eq!(ticks.added, start);
+    assert_eq!(ticks.changed, world.change_tick());
+
+    // Custom change detection periods can be tracked with the tick accessors
+    let since = world.last_change_tick();
+    world.clear_trackers();
+    assert_eq!(world.query::<Changed<Position>>().count(), 0);
+    world.set_last_change_tick(since);
+    assert_eq!(world.query::<Changed<Position>>().count(), 1);
+}
+
+#[test]
+fn query_denied_after_many_required() {
+    let mut world = World::new();
+
+    let plain = world.insert((Position::new(1.0, 2.0), Scale::new(3.0, 4.0)));
+    let _denied = world.insert((Position::new(5.0, 6.0), Scale::new(7.0, 8.0), Mesh::new(1)));
+
+    // The denied term comes after more than one required term, so it must be recorded at the start
+    // of the denied set rather than at the index of the required set.
+    let matched: Vec<_> = world
+        .query::<(Read<Position>, Read<Scale>, Not<Mesh>)>()
+        .map(|(id, _)| id)
+        .collect();
+    assert_eq!(matched, vec![plain]);
+}
+
+#[test]
+fn change_ticks_survive_u32_overflow() {
+    let mut world = World::new();
+    let id = world.insert((Position::new(1.0, 2.0),));
+    let added = world.change_tick();
+
+    // Components added long ago must not look new once the tick passes the range of a u32. With
+    // 32-bit ticks the current tick would alias the tick the component was added at.
+    world
+        .change_tick
+        .store(added.0 + (1 << 32), std::sync::atomic::Ordering::Relaxed);
+    world.clear_trackers();
+    assert_eq!(world.query::<Added<Position>>().count(), 0);
+    assert_eq!(world.query::<Changed<Position>>().count(), 0);
+
+    world.get_component_mut::<Position>(id).unwrap();
+    assert_eq!(world.query::<Changed<Position>>().count(), 1);
+    assert_eq!(world.query::<Added<Position>>().count(), 0);
+}
+
+#[test]
+fn optional_query_test() {
+    let mut world = World::new();
+
+    let with = world.insert((Position::new(1.0, 2.0), Scale::new(3.0, 4.0)));
+    let without = world.insert((Position::new(5.0, 6.0),));
+    let unrelated = world.insert((Scale::new(7.0, 8.0),));
+
+    let mut results: Vec<_> = world
+        .query::<(Read<Position>, Option<Read<Scale>>)>()
+        .map(|(id, (p, s))| (id, p.x, s.map(|s| s.x)))
+        .collect();
+    results.sort_by_key(|v| v.0);
+    let mut expected = vec![(with, 1.0, Some(3.0)), (without, 5.0, None)];
+    expected.sort_by_key(|v| v.0);
+    assert_eq!(results, expected);
+
+    for (_, (_, scale)) in world.query_mut::<(Read<Position>, Option<Write<Scale>>)>() {
+        if let Some(scale) = scale {
+            scale.x *= 2.0;
+        }
+    }
+    assert_eq!(
+        world.get_component_ref::<Scale>(with),
+        Some(&Scale::new(6.0, 4.0))
+    );
+    assert_eq!(
+        world.get_component_ref::<Scale>(unrelated),
+        Some(&Scale::new(7.0, 8.0))
+    );
+
+    let (_, scale) = world
+        .query_one::<(Has<Position>, Option<Read<Scale>>)>(without)
+        .unwrap();
+    assert!(scale.is_none());
+    let (_, scale) = world
+        .query_one_mut::<(Has<Position>, Option<Write<Scale>>)>(with)
+        .unwrap();
+    assert_eq!(scale, Some(&mut Scale::new(6.0, 4.0)));
+    assert!(
+        world
+            .query_one::<(Has<Position>, Option<Read<Scale>>)>(unrelated)
+            .is_none()
+    );
+}
+
+#[test]
+fn optional_only_query_test() {
+    let mut world = World::new();
+
+    let with = world.insert((Position::new(1.0, 2.0), Scale::new(3.0, 4.0)));
+    let without = world.insert((Position::new(5.0, 6.0),));
+    let scale_only = world.insert((Scale::new(7.0, 8.0),));
+    let empty = world.spawn_entity();
+
+    // A query with no required terms matches every entity
+    let mut results: Vec<_> = world
+        .query::<Option<Read<Scale>>>()
+        .map(|(id, s)| (id, s.map(|s| s.x)))
+        .collect();
+    results.sort_by_key(|v| v.0);
+    let mut expected = vec![
+        (with, Some(3.0)),
+        (without, None),
+        (scale_only, Some(7.0)),
+        (empty, None),
+    ];
+    expected.sort_by_key(|v| v.0);
+    assert_eq!(results, expected);
+
+    for (_, (p, s)) in world.query_mut::<(Option<Write<Position>>, Option<Write<Scale>>)>() {
+        if let Some(p) = p {
+            p.x += 10.0;
+        }
+        if let Some(s) = s {
+            s.x += 10.0;
+        }
+    }
+    assert_eq!(
+        world.get_component_ref::<Position>(with),
+        Some(&Position::new(11.0, 2.0))
+    );
+    assert_eq!(
+        world.get_component_ref::<Scale>(scale_only),
+        Some(&Scale::new(17.0, 8.0))
+    );
+
+    // Denied terms still apply when nothing is required
+    let mut results: Vec<_> = world
+        .query::<(Option<Read<Position>>, Not<Scale>)>()
+        .map(|(id, _)| id)
+        .collect();
+    results.sort();
+    let mut expected = vec![without, empty];
+    expected.sort();
+    assert_eq!(results, expected);
+}
+
+#[test]
+#[should_panic]
+fn optional_query_duplicate_test() {
+    let mut world = World::new();
+    world.insert((Position::new(1.0, 2.0),));
+    for _ in world.query_mut::<(Write<Position>, Option<Read<Position>>)>() {}
+}
+
+#[test]
+fn par_for_each_test() {
+    let mut world = World::new();
+    let handles = world.bulk_insert((vec![Position::new(0.0, 1.0); 5000],));
+    let scaled = world.bulk_insert((
+        vec![Position::new(0.0, 2.0); 3000],
+        vec![Scale::new(2.0, 2.0); 3000],
+    ));
+
+    world
+        .query_mut::<(Write<Position>, Option<Read<Scale>>)>()
+        .par_for_each_batched(256, |_, (pos, scale)| {
+            pos.x = pos.y * scale.map_or(1.0, |v| v.x);
+        });
+    for v in handles.iter() {
+        assert_eq!(world.get_component_ref::<Position>(*v).unwrap().x, 1.0);
+    }
+    for v in scaled.iter() {
+        assert_eq!(world.get_component_ref::<Position>(*v).unwrap().x, 4.0);
+    }
+
+    let count = std::sync::atomic::AtomicUsize::new(0);
+    let sum = std::sync::Mutex::new(0.0f64);
+    world
+        .query::<(Read<Position>,)>()
+        .par_for_each(|_, (pos,)| {
+            count.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            *sum.lock().unwrap() += pos.x as f64;
+        });
+    assert_eq!(count.into_inner(), 8000);
+    assert_eq!(sum.into_inner().unwrap(), 5000.0 + 3000.0 * 4.0);
+}
+
+#[test]
+fn query_chunks_test() {
+    let mut world = World::new();
+    let a = world.bulk_insert((vec![Position::new(1.0, 1.0); 10],));
+    let b = world.bulk_insert((
+        vec![Position::new(2.0, 2.0); 4],
+        vec![Scale::new(1.0, 1.0); 4],
+    ));
+
+    // Empty matching archetypes are skipped
+    let empty = world.insert((Position::new(0.0, 0.0), Mesh::default()));
+    world.remove_entity(empty);
+
+    let mut chunks: Vec<_> = world.query::<(Read<Position>,)>().chunks().collect();
+    chunks.sort_by_key(|v| v.len());
+    assert_eq!(chunks.len(), 2);
+    assert_eq!(chunks[0].entities(), b.as_slice());
+    assert_eq!(chunks[1].entities(), a.as_slice());
+
+    let chunk = chunks.pop().unwrap();
+    let (head, tail) = chunk.split_at(3);
+    assert_eq!(head.entities(), &a[..3]);
+    assert_eq!(tail.entities(), &a[3..]);
+    let ids: Vec<_> = head.chain(tail).map(|(id, _)| id).collect();
+    assert_eq!(ids, a);
+
+    // A partially consumed query only yields the remaining rows of the current archetype
+    let mut query = world.query::<(Read<Position>, Has<Scale>)>();
+    query.next().unwrap();
+    let chunks: Vec<_> = query.chunks().collect();
+    assert_eq!(chunks.len(), 1);
+    assert_eq!(chunks[0].entities(), &b[1..]);
+
+    // Per-entity filters are still applied when iterating a chunk
+    world.clear_trackers();
+    world.get_component_mut::<Position>(a[7]).unwrap();
+    let changed: Vec<_> = world
+        .query::<(Read<Position>, Changed<Position>)>()
+        .chunks()
+        .flat_map(|v| v.map(|(id, _)| id))
+        .collect();
+    assert_eq!(changed, vec![a[7]]);
+}
@@ -47,6 +47,137 @@ mod transform {
         pub scale: aleph_math::Vec3,
     }
     ecs::register_component!(Transform);
+
+    impl Transform {
+        /// Constructs a new 'identity' transformation.
+        ///
+        /// This means position = (0,0,0), no rotation and (1,1,1) scale.
+        pub const fn identity() -> Self {
+            Self {
+                position: aleph_math::DVec3::new(0.0, 0.0, 0.0),
+                rotation: aleph_math::Rotor3::new(1.0, aleph_math::Bivec3::new(0.0, 0.0, 0.0)),
+                scale: aleph_math::Vec3::new(1.0, 1.0, 1.0),
+            }
+        }
+    }
+}
+
+mod local_transform {
+    use aleph_math::ToDouble;
+
+    use crate::components::Transform;
+
+    ///
+    /// This component stores an object's transform relative to its parent entity as position,
+    /// rotation and scale. An entity without a parent is relative to the world origin.
+    ///
+    /// The entity's [`Transform`] is computed from this component by [`propagate_transforms`], and
+    /// will be overwritten. An entity with a [`LocalTransform`] must also have a [`Transform`] for
+    /// the result to be written to.
+    ///
+    /// [`propagate_transforms`]: crate::components::propagate_transforms
+    ///
+    #[derive(Clone, PartialEq, Debug)]
+    #[repr(C)]
+    pub struct LocalTransform {
+        /// The position of the entity relative to its parent.
+        pub position: aleph_math::DVec3,
+
+        /// The rotation of the entity relative to its parent.
+        pub rotation: aleph_math::Rotor3,
+
+        /// The scale of the entity relative to its parent.
+        pub scale: aleph_math::Vec3,
+    }
+    ecs::register_component!(LocalTransform);
+
+    impl LocalTransform {
+        /// Constructs a new 'identity' transformation.
+        ///
+        /// This means position = (0,0,0), no rotation and (1,1,1) scale.
+        pub const fn identity() -> Self {
+            Self {
+                position: aleph_math::DVec3::new(0.0, 0.0, 0.0),
+                rotation: aleph_math::Rotor3::new(1.0, aleph_math::Bivec3::new(0.0, 0.0, 0.0)),
+                scale: aleph_math::Vec3::new(1.0, 1.0, 1.0),
+            }
+        }
+    }
+
+    impl Default for LocalTransform {
+        #[inline]
+        fn default() -> Self {
+            Self::identity()
+        }
+    }
+
+    impl Transform {
+        /// Computes the world transform of a child with the given local transform, where `self` is
+        /// the world transform of the child's parent.
+        ///
+        /// Scale is composed per-axis. A non-uniform parent scale combined with a rotated child
+        /// would produce shear, which can't be represented by a [`Transform`] and is discarded.
+        pub fn mul_local(&self, local: &LocalTransform) -> Transform {
+            let offset = self.scale.to_double() * local.position;
+            Transform {
+                position: self.position + self.rotation.to_double() * offset,
+                rotation: self.rotation * local.rotation,
+                scale: self.scale * local.scale,
+            }
+        }
+    }
+}
+
+mod transform_propagation {
+    use ecs::entity::EntityHandle;
+    use ecs::hierarchy::{Children, Parent};
+    use ecs::world::World;
+    use ecs::world::query::{Has, Not, Read, Write};
+
+    use crate::components::{LocalTransform, Transform};
+
+    ///
+    /// Computes the world space [`Transform`] of every entity with a [`LocalTransform`], following
+    /// the [`Parent`]/[`Children`] hierarchy from the roots down.
+    ///
+    /// Parents are always computed before their children. An entity with a [`Transform`] but no
+    /// [`LocalTransform`] keeps its [`Transform`] unchanged, and its children are placed relative
+    /// to it. Children of an entity with no [`Transform`] are placed relative to the world origin.
+    ///
+    pub fn propagate_transforms(world: &mut World) {
+        let query = world.query_mut::<(Read<LocalTransform>, Write<Transform>, Not<Parent>)>();
+        for (_, (local, transform, _)) in query {
+            transform.position = local.position;
+            transform.rotation = local.rotation;
+            transform.scale = local.scale;
+        }
+
+        let roots: Vec<EntityHandle> = world
+            .query::<(Has<Children>, Not<Parent>)>()
+            .map(|(entity, _)| entity)
+            .collect();
+        let mut descendants = Vec::new();
+        for root in roots {
+            descendants.clear();
+            descendants.extend(world.descendants(root));
+
+            for entity in descendants.iter().copied() {
+                let Some(local) = world.get_component_ref::<LocalTransform>(entity) else {
+                    continue;
+                };
+                let parent = world
+                    .parent(entity)
+                    .and_then(|v| world.get_component_ref::<Transform>(v))
+                    .cloned()
+                    .unwrap_or(Transform::identity());
+                let new = parent.mul_local(local);
+
+                if let Some(transform) = world.get_component_mut::<Transform>(entity) {
+                    *transform = new;
+                }
+            }
+        }
+    }
 }
 
 mod transform_history {
@@ -120,8 +251,13 @@ mod static_mesh {
     ecs::register_component!(StaticMesh);
 }
 
+#[cfg(test)]
+mod tests;
+
 pub use camera::Camera;
 pub use lights::PointLight;
+pub use local_transform::LocalTransform;
 pub use static_mesh::StaticMesh;
 pub use transform::Transform;
 pub use transform_history::TransformHistory;
+pub use transform_propagation::propagate_transforms;
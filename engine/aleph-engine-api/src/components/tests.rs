@@ -0,0 +1,129 @@
+//
+//
+// This file is a part of Aleph
+//
+// https://github.com/nathanvoglsam/aleph
+//
+// MIT License
+//
+// Copyright (c) 2020 Aleph Engine
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use std::f32::consts::FRAC_PI_2;
+
+use aleph_math::{DVec3, Rotor3, Vec3};
+use ecs::entity::EntityHandle;
+use ecs::world::World;
+
+use crate::components::{LocalTransform, Transform, propagate_transforms};
+
+fn spawn(world: &mut World, position: DVec3, rotation: Rotor3, scale: Vec3) -> EntityHandle {
+    let local = LocalTransform {
+        position,
+        rotation,
+        scale,
+    };
+    world.insert((local, Transform::identity()))
+}
+
+fn assert_position(world: &World, entity: EntityHandle, expected: DVec3) {
+    let position = world
+        .get_component_ref::<Transform>(entity)
+        .unwrap()
+        .position;
+    assert!(
+        (position - expected).mag() < 1.0e-5,
+        "expected {expected:?}, got {position:?}"
+    );
+}
+
+/// Builds a root scaled by 2, a child rotated a quarter turn in the XY plane and an unrotated
+/// grandchild, each offset by one unit along X from its parent.
+fn chain(world: &mut World) -> [EntityHandle; 3] {
+    let offset = DVec3::new(1.0, 0.0, 0.0);
+    let root = spawn(
+        world,
+        DVec3::new(10.0, 0.0, 0.0),
+        Rotor3::identity(),
+        Vec3::broadcast(2.0),
+    );
+    let child = spawn(
+        world,
+        offset,
+        Rotor3::from_rotation_xy(FRAC_PI_2),
+        Vec3::one(),
+    );
+    let grandchild = spawn(world, offset, Rotor3::identity(), Vec3::one());
+    world.set_parent(child, root).unwrap();
+    world.set_parent(grandchild, child).unwrap();
+    [root, child, grandchild]
+}
+
+#[test]
+fn propagate_transforms_chain() {
+    let mut world = World::new();
+    let [root, child, grandchild] = chain(&mut world);
+
+    propagate_transforms(&mut world);
+
+    // The child's offset is scaled by the root, and the grandchild's offset is also rotated by
+    // the child
+    assert_position(&world, root, DVec3::new(10.0, 0.0, 0.0));
+    assert_position(&world, child, DVec3::new(12.0, 0.0, 0.0));
+    assert_position(&world, grandchild, DVec3::new(12.0, 2.0, 0.0));
+
+    let transform = world.get_component_ref::<Transform>(grandchild).unwrap();
+    assert_eq!(transform.scale, Vec3::broadcast(2.0));
+    assert_eq!(transform.rotation, Rotor3::from_rotation_xy(FRAC_PI_2));
+}
+
+#[test]
+fn propagate_transforms_reparent() {
+    let mut world = World::new();
+    let [root, _, grandchild] = chain(&mut world);
+    propagate_transforms(&mut world);
+
+    // Now only scaled by the root, and no longer rotated
+    world.set_parent(grandchild, root).unwrap();
+    propagate_transforms(&mut world);
+    assert_position(&world, grandchild, DVec3::new(12.0, 0.0, 0.0));
+
+    // Without a parent the local transform is the world transform
+    world.remove_parent(grandchild);
+    propagate_transforms(&mut world);
+    assert_position(&world, grandchild, DVec3::new(1.0, 0.0, 0.0));
+    let transform = world.get_component_ref::<Transform>(grandchild).unwrap();
+    assert_eq!(transform.scale, Vec3::one());
+}
+
+#[test]
+fn propagate_transforms_despawn_parent() {
+    let mut world = World::new();
+    let [root, child, grandchild] = chain(&mut world);
+    propagate_transforms(&mut world);
+
+    // The grandchild becomes a root when its parent is removed
+    world.remove_entity(child);
+    propagate_transforms(&mut world);
+    assert_position(&world, root, DVec3::new(10.0, 0.0, 0.0));
+    assert_position(&world, grandchild, DVec3::new(1.0, 0.0, 0.0));
+    assert_eq!(world.parent(grandchild), None);
+}
@@ -0,0 +1,104 @@
+//
+//
+// This file is a part of Aleph
+//
+// https://github.com/nathanvoglsam/aleph
+//
+// MIT License
+//
+// Copyright (c) 2020 Aleph Engine
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use std::ops::{Deref, DerefMut};
+
+use ecs::command::CommandBuffer;
+use scheduler::{AccessDescriptor, SystemParam, SystemParamFetch, SystemParamState, TypedTable};
+
+use crate::schedule::WorldResource;
+
+/// A [`SystemParam`] that provides a [`CommandBuffer`] for recording structural changes to the
+/// ECS world from inside a system.
+///
+/// Each system gets its own buffer, so it doesn't conflict with any other system and can be used
+/// freely from systems that run in parallel. The buffer is applied to the [`WorldResource`] by the
+/// scheduler at the end of the stage the system runs in.
+pub struct Commands<'a> {
+    buffer: &'a mut CommandBuffer,
+}
+
+impl<'a> Deref for Commands<'a> {
+    type Target = CommandBuffer;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        self.buffer
+    }
+}
+
+impl<'a> DerefMut for Commands<'a> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.buffer
+    }
+}
+
+/// An internal type that owns the buffer [`Commands`] records into.
+pub struct CommandsState {
+    buffer: CommandBuffer,
+}
+
+impl<'a> SystemParam for Commands<'a> {
+    type Fetch = CommandsState;
+}
+
+unsafe impl SystemParamState for CommandsState {
+    #[inline]
+    fn init(_access: &mut dyn AccessDescriptor) -> Self {
+        // Recording commands doesn't touch the world, it's only accessed when the buffer is
+        // applied at the end of the stage. There's no access to declare.
+        Self {
+            buffer: CommandBuffer::new(),
+        }
+    }
+
+    #[inline]
+    fn apply(&mut self, resources: &mut TypedTable) {
+        if self.buffer.is_empty() {
+            return;
+        }
+
+        let world = resources
+            .get_mut::<WorldResource>()
+            .expect("Commands can't be applied without a WorldResource");
+        self.buffer.apply(&mut world.0);
+    }
+}
+
+impl<'a> SystemParamFetch<'a> for CommandsState {
+    type Item = Commands<'a>;
+
+    #[inline]
+    unsafe fn get_param(state: &'a mut Self, _resources: &'a TypedTable) -> Self::Item {
+        Commands {
+            buffer: &mut state.buffer,
+        }
+    }
+}
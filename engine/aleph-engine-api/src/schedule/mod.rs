@@ -27,9 +27,16 @@
 // SOFTWARE.
 //
 
+mod commands;
+mod query;
+#[cfg(test)]
+mod tests;
+
+pub use commands::{Commands, CommandsState};
 use ecs::world::World;
 use label::{Label, make_label};
 use object_system::unsafe_impl_iobject;
+pub use query::{Query, QueryState};
 
 pub struct WorldResource(pub World);
 unsafe_impl_iobject!(WorldResource, "019237c4-a130-7c61-9671-b99ba8563f22");
@@ -37,14 +44,31 @@ unsafe_impl_iobject!(WorldResource, "019237c4-a130-7c61-9671-b99ba8563f22");
 /// This enum provides a [`Label`] type that names the core engine execution stages.
 #[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
 pub enum CoreStage {
-    /// This stage runs at the very beginning of a frame and should be primarily used for collecting
-    /// input from the keyboard/mouse/controllers/etc...
+    /// This stage runs at the very beginning of a frame, at the frame boundary, and updates the
+    /// `Events` resources. It is an [`UpdateEventsStage`] and not a regular system stage, event
+    /// types are registered with it using [`Schedule::add_events`].
+    ///
+    /// [`UpdateEventsStage`]: crate::scheduler::UpdateEventsStage
+    /// [`Schedule::add_events`]: crate::scheduler::Schedule::add_events
+    UpdateEvents,
+
+    /// This stage runs directly after [CoreStage::UpdateEvents] and should be primarily used for
+    /// collecting input from the keyboard/mouse/controllers/etc...
     InputCollection,
 
-    /// This stage runs directly before [CoreStage::Update] and has the primary purpose of allowing
-    /// systems to setup engine state before the update stage.
+    /// This stage runs before [CoreStage::Update] and has the primary purpose of allowing systems to
+    /// setup engine state before the update stage.
     PreUpdate,
 
+    /// This stage runs directly after [CoreStage::PreUpdate] and runs its child schedule zero or
+    /// more times per frame at a fixed rate, driven by the engine's frame timer. It is a
+    /// [`FixedTimestep`] and not a regular system stage, stages and systems are added to the
+    /// schedule from [`FixedTimestep::schedule_mut`].
+    ///
+    /// [`FixedTimestep`]: crate::scheduler::FixedTimestep
+    /// [`FixedTimestep::schedule_mut`]: crate::scheduler::FixedTimestep::schedule_mut
+    FixedUpdate,
+
     /// This is the main update stage where the bulk of all gameplay code should be placed. Any
     /// other stage will be used almost exclusively by engine systems to make whatever happens in
     /// the update stage work
@@ -63,8 +87,10 @@ pub enum CoreStage {
 impl CoreStage {
     pub const fn to_label(self) -> Label {
         match self {
+            CoreStage::UpdateEvents => make_label!("aleph_ecs::CoreStage::UpdateEvents"),
             CoreStage::InputCollection => make_label!("aleph_ecs::CoreStage::InputCollection"),
             CoreStage::PreUpdate => make_label!("aleph_ecs::CoreStage::PreUpdate"),
+            CoreStage::FixedUpdate => make_label!("aleph_ecs::CoreStage::FixedUpdate"),
             CoreStage::Update => make_label!("aleph_ecs::CoreStage::Update"),
             CoreStage::PostUpdate => make_label!("aleph_ecs::CoreStage::PostUpdate"),
             CoreStage::Render => make_label!("aleph_ecs::CoreStage::Render"),
@@ -0,0 +1,179 @@
+//
+//
+// This file is a part of Aleph
+//
+// https://github.com/nathanvoglsam/aleph
+//
+// MIT License
+//
+// Copyright (c) 2020 Aleph Engine
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use std::marker::PhantomData;
+
+use ecs::entity::EntityHandle;
+use ecs::tick::{ChangeTicks, Tick};
+use ecs::world::World;
+use ecs::world::query::{
+    ComponentQuery, ComponentQueryInfo, ComponentQueryItem, QueryMut, QueryRef,
+    ReadOnlyComponentQuery,
+};
+use object_system::IObject;
+use scheduler::{AccessDescriptor, SystemParam, SystemParamFetch, SystemParamState, TypedTable};
+
+use crate::schedule::WorldResource;
+
+/// A [`SystemParam`] that provides access to the components matched by the query `Q` in the
+/// [`WorldResource`].
+///
+/// Unlike `ResMut<WorldResource>`, a [`Query`] only declares access to the component types named
+/// in `Q`. Systems with queries that don't write any component type the other reads or writes are
+/// free to run in parallel.
+///
+/// A [`Query`] can't change the structure of the world. Use [`crate::schedule::Commands`] to spawn
+/// or remove entities and components.
+///
+/// # Change Detection
+///
+/// Every system tracks its own last run tick, so the `Added` and `Changed` filters match the
+/// components added or changed since the system holding the [`Query`] last ran. Components a
+/// system writes are stamped with that run's tick, so a system never sees its own changes on its
+/// next run.
+pub struct Query<'w, Q: ComponentQuery> {
+    world: &'w World,
+    ticks: ChangeTicks,
+    phantom: PhantomData<Q>,
+}
+
+impl<'w, Q: ComponentQuery> Query<'w, Q> {
+    /// Returns an iterator over all the entities matched by the query. Only available for queries
+    /// made entirely of read-only terms.
+    #[inline]
+    pub fn iter(&self) -> QueryRef<'_, Q>
+    where
+        Q: ReadOnlyComponentQuery,
+    {
+        self.world.query_with_ticks::<Q>(self.ticks)
+    }
+
+    /// Returns an iterator over all the entities matched by the query.
+    #[inline]
+    pub fn iter_mut(&mut self) -> QueryMut<'_, Q> {
+        // Safety: The scheduler guarantees no other system accesses the component types written by
+        //         'Q' while this system runs, and borrowing 'self' mutably prevents overlapping
+        //         iterators from the same parameter.
+        unsafe { self.world.query_mut_unchecked::<Q>(self.ticks) }
+    }
+
+    /// Returns the query's components for a single entity, or `None` if the entity doesn't match
+    /// the query. Only available for queries made entirely of read-only terms.
+    #[inline]
+    pub fn get(&self, entity: EntityHandle) -> Option<ComponentQueryItem<'_, Q>>
+    where
+        Q: ReadOnlyComponentQuery,
+    {
+        self.world.query_one_with_ticks::<Q>(entity, self.ticks)
+    }
+
+    /// Returns the query's components for a single entity, or `None` if the entity doesn't match
+    /// the query.
+    #[inline]
+    pub fn get_mut(&mut self, entity: EntityHandle) -> Option<ComponentQueryItem<'_, Q>> {
+        // Safety: See Query::iter_mut
+        unsafe { self.world.query_one_unchecked::<Q>(entity, self.ticks) }
+    }
+
+    /// Calls 'f' for every entity matched by the query, in parallel on the rayon thread pool. See
+    /// [`QueryMut::par_for_each`].
+    #[inline]
+    pub fn par_for_each<F>(&mut self, f: F)
+    where
+        F: Fn(EntityHandle, ComponentQueryItem<'_, Q>) + Send + Sync,
+    {
+        self.iter_mut().par_for_each(f)
+    }
+}
+
+/// An internal type that declares the component access of a [`Query`], and tracks the tick the
+/// system holding it last ran at.
+pub struct QueryState<Q: ComponentQuery> {
+    last_run: Tick,
+    phantom: PhantomData<fn() -> Q>,
+}
+
+impl<'w, Q: ComponentQuery + 'static> SystemParam for Query<'w, Q> {
+    type Fetch = QueryState<Q>;
+}
+
+unsafe impl<Q: ComponentQuery + 'static> SystemParamState for QueryState<Q> {
+    fn init(access: &mut dyn AccessDescriptor) -> Self {
+        // Terms that deny a component only check which archetype an entity is in, they never look
+        // at the component itself.
+        let terms: Vec<ComponentQueryInfo> = Q::query_info()
+            .filter(|v| v.required || v.optional)
+            .collect();
+
+        // A component can appear in more than one term when a filter is combined with a write, so
+        // the write has to take precedence over the filter's read.
+        for v in terms.iter().filter(|v| v.mutable) {
+            access.writes_component_with_id(WorldResource::ID, v.id.as_u32());
+        }
+        for v in terms.iter().filter(|v| !v.mutable) {
+            let written = terms.iter().any(|w| w.mutable && w.id == v.id);
+            if !written {
+                access.reads_component_with_id(WorldResource::ID, v.id.as_u32());
+            }
+        }
+
+        // A world's change tick starts after zero, so everything already in the world counts as
+        // added on the first run.
+        Self {
+            last_run: Tick(0),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<'a, Q: ComponentQuery + 'static> SystemParamFetch<'a> for QueryState<Q> {
+    type Item = Query<'a, Q>;
+
+    #[inline]
+    unsafe fn get_param(state: &'a mut Self, resources: &'a TypedTable) -> Self::Item {
+        let world = resources
+            .get_ref::<WorldResource>()
+            .expect("Query can't be used without a WorldResource");
+
+        // Each run claims its own tick so changes made during this run are newer than the run
+        // before it, and older than the next.
+        let this_run = world.0.increment_change_tick();
+        let ticks = ChangeTicks {
+            last_run: state.last_run,
+            this_run,
+        };
+        state.last_run = this_run;
+
+        Query {
+            world: &world.0,
+            ticks,
+            phantom: PhantomData,
+        }
+    }
+}
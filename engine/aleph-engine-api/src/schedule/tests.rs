@@ -0,0 +1,275 @@
+//
+//
+// This file is a part of Aleph
+//
+// https://github.com/nathanvoglsam/aleph
+//
+// MIT License
+//
+// Copyright (c) 2020 Aleph Engine
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use std::ops::Range;
+
+use ecs::component::Component;
+use ecs::world::World;
+use ecs::world::query::{Added, Changed, Read, Write};
+use label::{Label, make_label};
+use object_system::{IObject, unsafe_impl_iobject};
+use scheduler::{DependencyReason, Res, ResMut, Schedule, SystemSchedule, TypedTable};
+
+use crate::components::{Camera, Transform};
+use crate::schedule::{Commands, Query, WorldResource};
+
+struct Frame(u32);
+unsafe_impl_iobject!(Frame, "019a0f4e-7b1c-7d2a-9e3f-4c5b6a7d8e90");
+
+#[derive(Default)]
+struct Seen(Vec<(usize, usize)>);
+unsafe_impl_iobject!(Seen, "019a0f4e-7b1c-7d2a-9e3f-4c5b6a7d8e91");
+
+fn two_stage_schedule() -> Schedule {
+    let mut schedule = Schedule::default();
+    schedule.add_stage(make_label!("tests::First"), SystemSchedule::default());
+    schedule.add_stage(make_label!("tests::Second"), SystemSchedule::default());
+    schedule
+}
+
+fn run_frames(schedule: &mut Schedule, resources: &mut TypedTable, frames: Range<u32>) {
+    for frame in frames {
+        resources.insert(Frame(frame));
+        schedule.run_once(&(), resources);
+    }
+}
+
+/// The edges between the parallel systems of the given stage, as (before, after, reasons).
+fn parallel_edges(stage: &mut SystemSchedule<()>) -> Vec<(Label, Label, Vec<DependencyReason>)> {
+    let channel = stage.introspect().parallel;
+    channel
+        .edges
+        .iter()
+        .map(|v| {
+            let before = channel.systems[v.before].label;
+            let after = channel.systems[v.after].label;
+            (before, after, v.reasons.clone())
+        })
+        .collect()
+}
+
+fn write_transform(_: Query<Write<Transform>>) {}
+
+fn write_camera(_: Query<Write<Camera>>) {}
+
+fn read_transform(_: Query<Read<Transform>>) {}
+
+fn read_world(_: Res<WorldResource>) {}
+
+fn write_world(_: ResMut<WorldResource>) {}
+
+#[test]
+fn queries_of_different_components_are_unordered() {
+    let mut stage = SystemSchedule::default();
+    stage
+        .add_system(make_label!("transform"), write_transform)
+        .add_system(make_label!("camera"), write_camera)
+        .add_system(make_label!("read_transform"), read_transform);
+
+    // Only the reader of the written component is ordered
+    let edges = parallel_edges(&mut stage);
+    assert_eq!(edges.len(), 1);
+    assert_eq!(edges[0].0, make_label!("transform"));
+    assert_eq!(edges[0].1, make_label!("read_transform"));
+}
+
+#[test]
+fn queries_of_the_same_component_are_ordered() {
+    let mut stage = SystemSchedule::default();
+    stage
+        .add_system(make_label!("a"), write_transform)
+        .add_system(make_label!("b"), write_transform);
+
+    let edges = parallel_edges(&mut stage);
+    assert_eq!(
+        edges,
+        [(
+            make_label!("a"),
+            make_label!("b"),
+            vec![DependencyReason::Component {
+                resource: WorldResource::ID,
+                component: Some(Transform::DESC.id.as_u32()),
+            }]
+        )]
+    );
+}
+
+#[test]
+fn world_resource_access_conflicts_with_queries() {
+    let whole_world = vec![DependencyReason::Component {
+        resource: WorldResource::ID,
+        component: None,
+    }];
+
+    // Reading the whole world conflicts with writing any component inside it
+    let mut stage = SystemSchedule::default();
+    stage
+        .add_system(make_label!("query"), write_transform)
+        .add_system(make_label!("world"), read_world);
+    let edges = parallel_edges(&mut stage);
+    assert_eq!(
+        edges,
+        [(
+            make_label!("query"),
+            make_label!("world"),
+            whole_world.clone()
+        )]
+    );
+
+    // Writing the whole world conflicts with reading any component inside it
+    let mut stage = SystemSchedule::default();
+    stage
+        .add_system(make_label!("world"), write_world)
+        .add_system(make_label!("query"), read_transform);
+    let edges = parallel_edges(&mut stage);
+    assert_eq!(
+        edges,
+        [(make_label!("world"), make_label!("query"), whole_world)]
+    );
+
+    // Reads never conflict
+    let mut stage = SystemSchedule::default();
+    stage
+        .add_system(make_label!("world"), read_world)
+        .add_system(make_label!("query"), read_transform);
+    assert!(parallel_edges(&mut stage).is_empty());
+}
+
+#[test]
+fn query_change_detection_is_per_system() {
+    // Only writes on the first frame
+    fn writer(frame: Res<Frame>, mut query: Query<Write<Transform>>) {
+        if frame.0 == 0 {
+            for (_, transform) in query.iter_mut() {
+                transform.scale.x = 2.0;
+            }
+        }
+    }
+
+    fn reader(
+        mut seen: ResMut<Seen>,
+        added: Query<(Read<Transform>, Added<Transform>)>,
+        changed: Query<(Read<Transform>, Changed<Transform>)>,
+    ) {
+        seen.0.push((added.iter().count(), changed.iter().count()));
+    }
+
+    let mut world = World::new();
+    world.insert((Transform::identity(),));
+    world.insert((Transform::identity(),));
+
+    let mut resources = TypedTable::new();
+    resources.insert(WorldResource(world));
+    resources.insert(Seen::default());
+
+    let mut schedule = two_stage_schedule();
+    schedule.add_system_to_stage(make_label!("tests::First"), make_label!("writer"), writer);
+    schedule.add_system_to_stage(make_label!("tests::Second"), make_label!("reader"), reader);
+
+    // The first frame sees the entities that already existed as added, and the first frame's
+    // writes. The second frame must see neither.
+    run_frames(&mut schedule, &mut resources, 0..2);
+    assert_eq!(resources.get_ref::<Seen>().unwrap().0, vec![(2, 2), (0, 0)]);
+
+    // Changes made outside the schedule are seen by the next run
+    let world = &mut resources.get_mut::<WorldResource>().unwrap().0;
+    world.insert((Transform::identity(),));
+    run_frames(&mut schedule, &mut resources, 2..3);
+    assert_eq!(resources.get_ref::<Seen>().unwrap().0[2], (1, 1));
+}
+
+#[test]
+fn query_does_not_see_own_changes() {
+    // Writes every entity it sees as changed, which must not make it see them again next run
+    fn writer(mut seen: ResMut<Seen>, mut query: Query<(Write<Transform>, Changed<Transform>)>) {
+        let mut count = 0;
+        for (_, (transform, _)) in query.iter_mut() {
+            transform.scale.x += 1.0;
+            count += 1;
+        }
+        seen.0.push((count, 0));
+    }
+
+    fn reader(mut seen: ResMut<Seen>, query: Query<(Read<Transform>, Changed<Transform>)>) {
+        let last = seen.0.last_mut().unwrap();
+        last.1 = query.iter().count();
+    }
+
+    let mut world = World::new();
+    world.insert((Transform::identity(),));
+
+    let mut resources = TypedTable::new();
+    resources.insert(WorldResource(world));
+    resources.insert(Seen::default());
+
+    let mut schedule = two_stage_schedule();
+    schedule.add_system_to_stage(make_label!("tests::First"), make_label!("writer"), writer);
+    schedule.add_system_to_stage(make_label!("tests::Second"), make_label!("reader"), reader);
+
+    // The reader still sees the writer's changes made after the reader last ran
+    run_frames(&mut schedule, &mut resources, 0..2);
+    assert_eq!(resources.get_ref::<Seen>().unwrap().0, vec![(1, 1), (0, 0)]);
+}
+
+#[test]
+fn commands_apply_at_stage_end() {
+    // Only spawns on the first frame
+    fn spawner(frame: Res<Frame>, mut commands: Commands) {
+        if frame.0 == 0 {
+            commands.insert((Transform::identity(),));
+        }
+    }
+
+    fn same_stage(mut seen: ResMut<Seen>, query: Query<Read<Transform>>) {
+        seen.0.push((query.iter().count(), 0));
+    }
+
+    fn later_stage(mut seen: ResMut<Seen>, query: Query<Read<Transform>>) {
+        let last = seen.0.last_mut().unwrap();
+        last.1 = query.iter().count();
+    }
+
+    let mut resources = TypedTable::new();
+    resources.insert(WorldResource(World::new()));
+    resources.insert(Seen::default());
+
+    let mut schedule = two_stage_schedule();
+    schedule.add_system_to_stage(make_label!("tests::First"), make_label!("spawner"), spawner);
+    schedule.add_system_to_stage(make_label!("tests::First"), make_label!("same"), same_stage);
+    schedule.add_system_to_stage(
+        make_label!("tests::Second"),
+        make_label!("later"),
+        later_stage,
+    );
+
+    // The spawned entity only exists once the first stage has ended, and the command isn't applied
+    // a second time on the next frame.
+    run_frames(&mut schedule, &mut resources, 0..2);
+    assert_eq!(resources.get_ref::<Seen>().unwrap().0, vec![(0, 1), (1, 1)]);
+}
@@ -50,12 +50,13 @@ use aleph_vfs::{LayerDesc, Router};
 use api::label::make_label;
 use api::platform::{
     AClipboard, AEvents, AFrameTimer, AGamepads, AKeyboard, AMouse, ARouter, AWindow, Cursor,
-    Event, KeyboardEvent, MouseEvent, WindowEvent,
+    Event, IFrameTimer, KeyboardEvent, MouseEvent, WindowEvent,
 };
 use api::plugin::{
     IPlugin, IPluginRegistrar, IQuitHandle, IRegistryAccessor, PluginDescription, Provides,
 };
 use api::schedule::CoreStage;
+use api::scheduler::FixedTimestep;
 use camino::Utf8PathBuf;
 pub use clipboard::Clipboard;
 pub use events::Events;
@@ -225,9 +226,13 @@ impl IPlugin for CorePlatform {
         let send_objects = objects.clone();
         let send_sdl = self.sdl.clone();
         let send_quit_handle = registry.quit_handle();
+        let delta_timer = objects.frame_timer.clone().unwrap();
         registry
             .core()
             .schedule
+            .stage(CoreStage::FixedUpdate.into(), |v: &mut FixedTimestep| {
+                v.set_delta_time(move || delta_timer.delta_time())
+            })
             .add_exclusive_at_start_system_to_stage(
                 CoreStage::InputCollection.into(),
                 make_label!("platform_sdl3::input_collection"),
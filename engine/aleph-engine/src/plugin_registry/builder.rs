@@ -33,7 +33,7 @@ use std::collections::{BTreeMap, BTreeSet};
 use api::ecs::world::World;
 use api::plugin::IPlugin;
 use api::schedule::CoreStage;
-use api::scheduler::{Schedule, SystemSchedule, TypedTable};
+use api::scheduler::{FixedTimestep, Schedule, SystemSchedule, TypedTable, UpdateEventsStage};
 
 use crate::core::platform::CorePlatform;
 use crate::core::rhi::CoreRhi;
@@ -84,8 +84,14 @@ impl PluginRegistryBuilder {
             self.schedule_plugin_execution(&mut init_dependencies, &mut provided_interfaces);
 
         let mut schedule = Schedule::default();
+        schedule.add_stage(CoreStage::UpdateEvents.into(), UpdateEventsStage::default());
         schedule.add_stage(CoreStage::InputCollection.into(), SystemSchedule::default());
         schedule.add_stage(CoreStage::PreUpdate.into(), SystemSchedule::default());
+
+        // The platform plugin replaces the delta time source with the frame timer when it's
+        // initialized, until then no time passes in the fixed update stage.
+        let fixed_update = FixedTimestep::with_rate(60.0, || 0.0);
+        schedule.add_stage(CoreStage::FixedUpdate.into(), fixed_update);
         schedule.add_stage(CoreStage::Update.into(), SystemSchedule::default());
         schedule.add_stage(CoreStage::PostUpdate.into(), SystemSchedule::default());
         schedule.add_stage(CoreStage::Render.into(), SystemSchedule::default());
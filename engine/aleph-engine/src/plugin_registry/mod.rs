@@ -184,7 +184,13 @@ impl PluginRegistry {
             }
         }
 
-        configs.finalize()
+        match configs.finalize() {
+            Ok(v) => v,
+            Err(v) => {
+                log::error!("Failed while reading the config object. Reason: {:?}", v);
+                panic!("Failed while reading the config object. Reason: {:?}", v);
+            }
+        }
     }
 
     ///
@@ -28,6 +28,7 @@
 //
 
 pub mod capture_previous_transforms;
+pub mod propagate_transforms;
 pub mod publish_egui_scene;
 pub mod publish_render_scene;
 pub mod render;
@@ -52,6 +52,7 @@ use mg::renderer::builder::ApplicationSurface;
 use crate::render::config::Config;
 use crate::render::core::resources::render_scene::RenderSceneResource;
 use crate::render::core::systems::capture_previous_transforms::CapturePreviousTransformsSystem;
+use crate::render::core::systems::propagate_transforms::PropagateTransformsSystem;
 use crate::render::core::systems::publish_egui_scene::PublishEguiSceneSystem;
 use crate::render::core::systems::publish_render_scene::PublishRenderSceneSystem;
 use crate::render::core::systems::render::RenderSystem;
@@ -157,6 +158,14 @@ impl IPlugin for PluginRender {
             system.register(&mut registry.core().schedule);
         }
 
+        // System to compute the world transforms of entities in a hierarchy from their local
+        // transforms. This runs at the end of the post-update stage so the render scene is always
+        // published with up-to-date world transforms.
+        {
+            let system = PropagateTransformsSystem;
+            system.register(&mut registry.core().schedule);
+        }
+
         // System to copy the simulation scene into the render scene
         {
             let system = PublishRenderSceneSystem;
@@ -27,7 +27,9 @@
 // SOFTWARE.
 //
 
+use std::cell::Cell;
 use std::collections::HashMap;
+use std::ops::Range;
 use std::sync::Arc;
 
 use aleph_alloc::instrumentation::system;
@@ -37,12 +39,19 @@ use aleph_device_allocators::{
 };
 use aleph_nstr::nstr;
 use aleph_rhi_api::*;
+use rayon::prelude::*;
 
 use crate::internal::{
     FgSystem, FrameGraphBufferDesc, FrameGraphTextureDesc, IIRNode, IRNode, PassOrderBundle,
     RenderPass, ResourceRoot, ResourceVersion, TransientResourceBundle,
 };
+use crate::pass_culling::PassCullingReport;
+use crate::persistent_resource::{PersistentResource, PersistentResourceBundle};
+use crate::queue_schedule::{
+    QUEUE_COUNT, QueueSchedule, QueueScheduleReport, queue_index, queue_scope,
+};
 use crate::render_pass::PassArgs;
+use crate::transient_pool::{TransientMemoryReport, TransientPoolPlan};
 use crate::{FrameGraphBuilder, GraphChannel, ImportBundle, ResourceRef, ResourceVariant, Result};
 
 pub struct FrameGraph<A: PassArgs = ()> {
@@ -97,9 +106,34 @@ pub struct FrameGraph<A: PassArgs = ()> {
     /// root_resources array.
     pub(crate) imported_resources: BVec<u16, FgSystem>,
 
+    /// The set of resources within the graph whose contents persist between executions of the
+    /// graph, along with the root resources that represent them.
+    pub(crate) persistent_resources: BVec<PersistentResource, FgSystem>,
+
+    /// The assignment of transient resources to the physical resources that back them. Transient
+    /// resources with disjoint lifetimes may share the same physical resource.
+    pub(crate) transient_plan: TransientPoolPlan,
+
+    /// The passes that were culled when building the graph and the lifetimes of the resources that
+    /// are left.
+    pub(crate) culling_report: PassCullingReport,
+
+    /// The split of the pass order into submissions for each queue, used by
+    /// [FrameGraph::execute_on_queues].
+    pub(crate) queue_schedule: QueueSchedule,
+
+    /// The fences used to synchronize the submissions made by [FrameGraph::execute_on_queues].
+    /// Created alongside the transient resources.
+    pub(crate) queue_fences: Option<QueueFences>,
+
     /// The transient resource bundles that the user requested by allocated for N frames in flight.
     pub(crate) transient_bundles: Option<TransientResourceBundle>,
 
+    /// The physical resources backing the persistent resources. Created alongside the transient
+    /// resources, but unlike them it is kept when the transients are reallocated so the contents
+    /// carry over.
+    pub(crate) persistent_bundle: Option<PersistentResourceBundle>,
+
     /// Per-frame deletion pool for extending the lifetime of internally managed resources like
     /// command buffers or descriptor pool.
     pub(crate) deletion_pools: BVec<DeletionPool, FgSystem>,
@@ -113,6 +147,24 @@ impl<A: PassArgs> FrameGraph<A> {
         FrameGraphBuilder::<A>::new()
     }
 
+    /// Returns the estimated memory usage of the graph's transient resources, with and without
+    /// pooling.
+    pub fn transient_memory_report(&self) -> &TransientMemoryReport {
+        &self.transient_plan.report
+    }
+
+    /// Returns the passes that were culled from the graph, and the span of the pass order that each
+    /// resource is used in.
+    pub fn pass_culling_report(&self) -> &PassCullingReport {
+        &self.culling_report
+    }
+
+    /// Returns how the graph's passes are split into submissions across the device queues when
+    /// executed with [FrameGraph::execute_on_queues].
+    pub fn queue_schedule_report(&self) -> &QueueScheduleReport {
+        &self.queue_schedule.report
+    }
+
     /// # Safety
     ///
     /// It is the caller's responsibility to ensure that none of the resources referenced by the
@@ -123,6 +175,21 @@ impl<A: PassArgs> FrameGraph<A> {
         self.transient_bundles = None;
         self.transient_bundles = Some(self.allocate_transient_resource_bundle());
 
+        if self.persistent_bundle.is_none() {
+            self.persistent_bundle = Some(PersistentResourceBundle::new(
+                self.device.as_ref(),
+                &self.root_resources,
+                &self.persistent_resources,
+            ));
+        }
+
+        if self.queue_fences.is_none() {
+            self.queue_fences = Some(QueueFences {
+                fences: std::array::from_fn(|_| self.device.create_fence(0).unwrap()),
+                values: [0; QUEUE_COUNT],
+            });
+        }
+
         self.deletion_pools.clear();
         for _ in 0..num_frames {
             self.deletion_pools.push(DeletionPool::default());
@@ -139,6 +206,9 @@ impl<A: PassArgs> FrameGraph<A> {
     /// the platform GPU API and ensuring that is safe is up to the caller. As such, to pretend this
     /// function is safe to call in anything but the most trivial examples would be incorrect. This
     /// can be used as _part_ of a safe renderer API though, just not at this level of abstraction.
+    ///
+    /// All the passes are recorded on the calling thread. See [FrameGraph::execute_parallel] to
+    /// spread the recording across the rayon thread pool.
     #[aleph_profile::function]
     pub unsafe fn execute(
         &mut self,
@@ -147,15 +217,12 @@ impl<A: PassArgs> FrameGraph<A> {
         encoder: &mut CommandEncoder,
         args: &A::Args<'_>,
     ) {
-        // TODO: parallel encode
-        //
-        // We could record in parallel by allocating command buffers ourselves and doing a parallel
-        // iteration over the execution bundles list.
         unsafe {
             self.execute_pre_assertions(import_bundle);
         }
 
         let transient_bundle = self.transient_bundles.as_ref().unwrap();
+        let persistent_bundle = self.persistent_bundle.as_ref().unwrap();
         let linear_descriptor_pool = self.linear_descriptor_pools.get();
 
         // Free all our deferred deletion resources from the last time we executed this frame. We
@@ -174,10 +241,16 @@ impl<A: PassArgs> FrameGraph<A> {
             device: self.device.as_ref(),
             import_bundle,
             transient_bundle,
+            persistent_bundle: persistent_bundle.current(),
+            has_history: persistent_bundle.has_history(),
             linear_descriptor_pool: linear_descriptor_pool.as_ref(),
         };
 
         encoder.debug_zone(Color::CYAN, nstr!("FrameGraph::execute"), |encoder| {
+            unsafe {
+                persistent_bundle.record_initial_state_barriers(encoder);
+            }
+
             let mut graph_channel = GraphChannel {
                 has_global_or_buffer_barrier: false,
                 global_barrier: GlobalBarrier::default(),
@@ -205,10 +278,7 @@ impl<A: PassArgs> FrameGraph<A> {
                         }
                         IRNode::LayoutChange(v) => {
                             let root_id = v.resource_id.root;
-                            let texture = transient_bundle
-                                .get_resource(root_id)
-                                .or_else(|| import_bundle.get_resource(root_id))
-                                .unwrap();
+                            let texture = resources.get_root(root_id).unwrap();
                             let texture = match texture {
                                 ResourceVariant::Buffer(v) => {
                                     let desc = self.device.get_buffer_desc(v);
@@ -301,50 +371,492 @@ impl<A: PassArgs> FrameGraph<A> {
         self.deletion_pools[frame_index]
             .descriptor_pools
             .push(linear_descriptor_pool.into());
+        self.persistent_bundle.as_mut().unwrap().executions += 1;
     }
 
-    fn allocate_transient_resource_bundle(&self) -> TransientResourceBundle {
-        let num_transients = self.root_resources.len() - self.imported_resources.len();
-        let mut bundle = TransientResourceBundle {
-            transients: HashMap::with_capacity(num_transients),
+    /// A variant of [FrameGraph::execute] that records the passes on the rayon thread pool. The
+    /// execution bundles are split into contiguous ranges holding roughly the same number of
+    /// passes, with each range recorded into its own command list with its own descriptor pool.
+    /// The command lists are then submitted to the general queue in a single submission, in the
+    /// order of the ranges.
+    ///
+    /// Any barriers deferred by the passes in a range are flushed at the end of the range's command
+    /// list, so they are still encoded before the passes that depend on them. As the graph makes
+    /// the submission itself, the wild card barrier that a caller of [FrameGraph::execute] would
+    /// issue after the graph is recorded at the end of the final command list.
+    ///
+    /// # Safety
+    ///
+    /// The same requirements as [FrameGraph::execute] apply. Additionally, the passes will be
+    /// executed on threads other than the calling thread.
+    #[aleph_profile::function]
+    pub unsafe fn execute_parallel(
+        &mut self,
+        frame_index: usize,
+        import_bundle: &ImportBundle,
+        submit: &FrameGraphSubmitDesc,
+        args: &A::Args<'_>,
+    ) -> std::result::Result<(), QueueSubmitError> {
+        unsafe {
+            self.execute_pre_assertions(import_bundle);
+        }
+
+        let ranges = self.split_execution_bundles(rayon::current_num_threads());
+
+        // Same as in 'execute', we know this frame index is no longer in flight
+        self.deletion_pools[frame_index].descriptor_pools.clear();
+
+        let device = self.device.as_ref();
+        let transient_bundle = self.transient_bundles.as_ref().unwrap();
+        let persistent_bundle = self.persistent_bundle.as_ref().unwrap();
+        let linear_descriptor_pools = self.linear_descriptor_pools.as_ref();
+        let execution_bundles = self.execution_bundles.as_slice();
+        let ir_nodes = self.ir_nodes.as_slice();
+        let schedule = &self.queue_schedule;
+
+        // Hand each range the passes it will record, in recording order, so the ranges can each
+        // borrow their own passes mutably.
+        let mut render_passes: Vec<_> = self.render_passes.iter_mut().map(Some).collect();
+        let ranges: Vec<_> = ranges
+            .into_iter()
+            .map(|range| {
+                let passes: Vec<&mut RenderPass<A>> = execution_bundles[range.clone()]
+                    .iter()
+                    .flat_map(|bundle| unsafe { bundle.passes.as_ref() })
+                    .map(|&pass| render_passes[ir_nodes[pass].render_pass()].take().unwrap())
+                    .collect();
+                (range, passes)
+            })
+            .collect();
+
+        let recorded: Vec<_> = ranges
+            .into_par_iter()
+            .map(|(range, passes)| {
+                let linear_descriptor_pool = linear_descriptor_pools.get();
+
+                // Safety: It is up to the caller to ensure that none of the descriptors that were
+                //         allocated into this pool were in use or can ever be used again.
+                unsafe {
+                    linear_descriptor_pool.reset();
+                }
+
+                let resources = FrameGraphResources {
+                    device,
+                    import_bundle,
+                    transient_bundle,
+                    persistent_bundle: persistent_bundle.current(),
+                    has_history: persistent_bundle.has_history(),
+                    linear_descriptor_pool: linear_descriptor_pool.as_ref(),
+                };
+
+                let mut list = device
+                    .create_command_list(&CommandListDesc {
+                        queue_type: QueueType::General,
+                        name: None,
+                    })
+                    .unwrap();
+
+                {
+                    let mut encoder = list.begin_general().unwrap();
+                    encoder.debug_zone(Color::CYAN, nstr!("FrameGraph::execute"), |encoder| {
+                        if range.start == 0 {
+                            unsafe {
+                                persistent_bundle.record_initial_state_barriers(encoder);
+                            }
+                        }
+
+                        let mut graph_channel = GraphChannel {
+                            has_global_or_buffer_barrier: false,
+                            global_barrier: GlobalBarrier::default(),
+                            texture_objects: BVec::new_in(system()),
+                            texture_barriers: BVec::new_in(system()),
+                        };
+                        let mut passes = passes.into_iter();
+                        for bundle in execution_bundles[range.clone()].iter() {
+                            unsafe {
+                                record_queue_barriers(
+                                    encoder,
+                                    &mut graph_channel,
+                                    &resources,
+                                    ir_nodes,
+                                    schedule,
+                                    QueueType::General,
+                                    bundle.barriers.as_ref(),
+                                    &[],
+                                    false,
+                                );
+                            }
+
+                            for _ in unsafe { bundle.passes.as_ref() } {
+                                let render_pass = passes.next().unwrap();
+                                if render_pass.skip {
+                                    continue;
+                                }
+
+                                unsafe {
+                                    encoder.debug_zone(
+                                        Color::RED,
+                                        render_pass.name.as_ref(),
+                                        |encoder| {
+                                            aleph_profile::scope_named!(
+                                                "FrameGraphPass",
+                                                render_pass.name.as_ref()
+                                            );
+
+                                            render_pass.pass.execute(
+                                                encoder,
+                                                &mut graph_channel,
+                                                &resources,
+                                                args,
+                                            );
+                                        },
+                                    );
+                                }
+                            }
+                        }
+
+                        // The next range is recorded into another command list, so the barriers
+                        // deferred by the last passes in this range must be flushed here.
+                        unsafe {
+                            record_queue_barriers(
+                                encoder,
+                                &mut graph_channel,
+                                &resources,
+                                ir_nodes,
+                                schedule,
+                                QueueType::General,
+                                &[],
+                                &[],
+                                false,
+                            );
+                        }
+
+                        if range.end == execution_bundles.len() {
+                            unsafe {
+                                encoder.resource_barrier(
+                                    &[GlobalBarrier {
+                                        before_sync: BarrierSync::ALL,
+                                        after_sync: BarrierSync::ALL,
+                                        before_access: BarrierAccess::COMMON,
+                                        after_access: BarrierAccess::COMMON,
+                                    }],
+                                    &[],
+                                    &[],
+                                );
+                            }
+                        }
+                    });
+
+                    unsafe {
+                        encoder.close().unwrap();
+                    }
+                }
+
+                (Cell::new(Some(list)), linear_descriptor_pool)
+            })
+            .collect();
+
+        let (command_lists, descriptor_pools): (Vec<_>, Vec<_>) = recorded.into_iter().unzip();
+        let queue = device.get_queue(QueueType::General).unwrap();
+        let result = unsafe {
+            queue.submit(&QueueSubmitDesc {
+                command_lists: &command_lists,
+                wait_fences: submit.wait_fences,
+                wait_values: submit.wait_values,
+                signal_fences: submit.signal_fences,
+                signal_values: submit.signal_values,
+                swap_image: submit.swap_image,
+            })
         };
-        for (i, transient) in self
-            .root_resources
-            .iter()
-            .enumerate()
-            .filter(|(_, v)| !v.resource_type.is_import())
-        {
-            let i = u16::try_from(i).unwrap();
-            match &transient.resource_type {
-                crate::internal::ResourceType::Buffer(v) => {
-                    let desc = BufferDesc {
-                        size: v.desc.size,
-                        cpu_access: v.desc.cpu_access,
-                        usage: transient.total_access_flags,
-                        name: v.desc.name.map(|v| unsafe { v.as_ref() }),
-                    };
-                    let buffer = self.device.create_buffer(&desc).unwrap();
-                    bundle.add_resource(i, buffer);
+
+        self.deletion_pools[frame_index]
+            .descriptor_pools
+            .extend(descriptor_pools.into_iter().map(Grave::from));
+        if result.is_ok() {
+            self.persistent_bundle.as_mut().unwrap().executions += 1;
+        }
+
+        result
+    }
+
+    /// An alternative to [FrameGraph::execute] that spreads the graph's passes across the device's
+    /// queues, running each pass on the queue it prefers (see
+    /// [crate::ResourceRegistry::set_preferred_queue]). The graph creates, records and submits its
+    /// own command lists and synchronizes the queues with its own fences.
+    ///
+    /// The first submission to each queue waits on the fences in `submit`, and on the previous
+    /// execution of the graph so that two executions in flight never share transient resources.
+    /// The final submission is always made to the general queue once every other queue has
+    /// finished, and signals the fences in `submit`. Resources are handed back to the general queue
+    /// before the graph finishes, in the state they were imported for.
+    ///
+    /// The swap image is attached to the final submission so presenting waits on the whole graph.
+    /// Passes that use the swap image must be recorded into the final submission, which can be
+    /// checked with [FrameGraph::queue_schedule_report].
+    ///
+    /// # Safety
+    ///
+    /// The same requirements as [FrameGraph::execute] apply.
+    #[aleph_profile::function]
+    pub unsafe fn execute_on_queues(
+        &mut self,
+        frame_index: usize,
+        import_bundle: &ImportBundle,
+        submit: &FrameGraphSubmitDesc,
+        args: &A::Args<'_>,
+    ) -> std::result::Result<(), QueueSubmitError> {
+        unsafe {
+            self.execute_pre_assertions(import_bundle);
+        }
+
+        let transient_bundle = self.transient_bundles.as_ref().unwrap();
+        let persistent_bundle = self.persistent_bundle.as_ref().unwrap();
+        let linear_descriptor_pool = self.linear_descriptor_pools.get();
+
+        // Same as in 'execute', we know this frame index is no longer in flight
+        self.deletion_pools[frame_index].descriptor_pools.clear();
+
+        // Safety: It is up to the caller to ensure that none of the descriptors that were allocated
+        //         into this pool were in use or can ever be used again.
+        unsafe {
+            linear_descriptor_pool.reset();
+        }
+
+        let resources = FrameGraphResources {
+            device: self.device.as_ref(),
+            import_bundle,
+            transient_bundle,
+            persistent_bundle: persistent_bundle.current(),
+            has_history: persistent_bundle.has_history(),
+            linear_descriptor_pool: linear_descriptor_pool.as_ref(),
+        };
+
+        let device = self.device.as_ref();
+        let ir_nodes = &self.ir_nodes;
+        let render_passes = &mut self.render_passes;
+        let schedule = &self.queue_schedule;
+        let fences = self.queue_fences.as_mut().unwrap();
+
+        // The persistent resources must be initialized before any queue touches them, so this
+        // gets its own submission that the first submission to the other queues waits on.
+        if !persistent_bundle.has_history() && !persistent_bundle.textures.is_empty() {
+            let mut list = device
+                .create_command_list(&CommandListDesc {
+                    queue_type: QueueType::General,
+                    name: None,
+                })
+                .unwrap();
+            {
+                let mut encoder = list.begin_general().unwrap();
+                unsafe {
+                    persistent_bundle.record_initial_state_barriers(&mut encoder);
+                    encoder.close().unwrap();
                 }
-                crate::internal::ResourceType::Texture(v) => {
-                    let desc = TextureDesc {
-                        width: v.desc.width,
-                        height: v.desc.height,
-                        depth: v.desc.depth,
-                        format: v.desc.format,
-                        dimension: v.desc.dimension,
-                        clear_value: v.desc.clear_value.clone(),
-                        array_size: v.desc.array_size,
-                        mip_levels: v.desc.mip_levels,
-                        sample_count: v.desc.sample_count,
-                        sample_quality: v.desc.sample_quality,
-                        usage: transient.total_access_flags,
-                        name: v.desc.name.map(|v| unsafe { v.as_ref() }),
+            }
+
+            let general = queue_index(QueueType::General);
+            fences.values[general] += 1;
+            let queue = device.get_queue(QueueType::General).unwrap();
+            unsafe {
+                queue.submit(&QueueSubmitDesc {
+                    command_lists: &[Some(list).into()],
+                    wait_fences: submit.wait_fences,
+                    wait_values: submit.wait_values,
+                    signal_fences: &[&fences.fences[general]],
+                    signal_values: &[fences.values[general]],
+                    swap_image: None,
+                })?;
+            }
+        }
+        let base_values = fences.values;
+        let last_batch = schedule.batches.len() - 1;
+
+        for (batch_index, batch) in schedule.batches.iter().enumerate() {
+            let mut list = device
+                .create_command_list(&CommandListDesc {
+                    queue_type: batch.queue,
+                    name: None,
+                })
+                .unwrap();
+
+            {
+                let mut encoder = match batch.queue {
+                    QueueType::General => list.begin_general(),
+                    QueueType::Compute => list.begin_compute(),
+                    QueueType::Transfer => list.begin_transfer(),
+                }
+                .unwrap();
+
+                encoder.debug_zone(Color::CYAN, nstr!("FrameGraph::execute"), |encoder| {
+                    let mut graph_channel = GraphChannel {
+                        has_global_or_buffer_barrier: false,
+                        global_barrier: GlobalBarrier::default(),
+                        texture_objects: BVec::new_in(system()),
+                        texture_barriers: BVec::new_in(system()),
                     };
-                    let texture = self.device.create_texture(&desc).unwrap();
-                    bundle.add_resource(i, texture);
+                    for step in batch.steps.iter() {
+                        unsafe {
+                            record_queue_barriers(
+                                encoder,
+                                &mut graph_channel,
+                                &resources,
+                                ir_nodes,
+                                schedule,
+                                batch.queue,
+                                &step.barriers,
+                                &step.acquires,
+                                false,
+                            );
+                        }
+
+                        for &pass in step.passes.iter() {
+                            let render_pass = &mut render_passes[ir_nodes[pass].render_pass()];
+                            if render_pass.skip {
+                                continue;
+                            }
+
+                            unsafe {
+                                encoder.debug_zone(
+                                    Color::RED,
+                                    render_pass.name.as_ref(),
+                                    |encoder| {
+                                        aleph_profile::scope_named!(
+                                            "FrameGraphPass",
+                                            render_pass.name.as_ref()
+                                        );
+
+                                        render_pass.pass.execute(
+                                            encoder,
+                                            &mut graph_channel,
+                                            &resources,
+                                            args,
+                                        );
+                                    },
+                                );
+                            }
+                        }
+                    }
+
+                    // Flush any barriers the last passes deferred, then give up the resources
+                    // that the other queues will acquire.
+                    unsafe {
+                        record_queue_barriers(
+                            encoder,
+                            &mut graph_channel,
+                            &resources,
+                            ir_nodes,
+                            schedule,
+                            batch.queue,
+                            &[],
+                            &batch.releases,
+                            true,
+                        );
+                    }
+
+                    // The transient initialization barriers have an empty 'before' scope, so
+                    // something has to stop back to back executions of the graph from overlapping
+                    // on the general queue. As we own the final submission we issue the
+                    // wild card barrier that a caller of 'execute' would issue after the graph.
+                    if batch_index == last_batch {
+                        unsafe {
+                            encoder.resource_barrier(
+                                &[GlobalBarrier {
+                                    before_sync: BarrierSync::ALL,
+                                    after_sync: BarrierSync::ALL,
+                                    before_access: BarrierAccess::COMMON,
+                                    after_access: BarrierAccess::COMMON,
+                                }],
+                                &[],
+                                &[],
+                            );
+                        }
+                    }
+                });
+
+                unsafe {
+                    encoder.close().unwrap();
                 }
-                crate::internal::ResourceType::Execution(_) => {}
+            }
+
+            let queue = queue_index(batch.queue);
+            let mut wait_fences = Vec::new();
+            let mut wait_values = Vec::new();
+            for (i, wait) in batch.waits.iter().enumerate() {
+                if let Some(wait) = wait {
+                    wait_fences.push(&fences.fences[i]);
+                    wait_values
+                        .push(base_values[i] + schedule.batches[*wait].queue_batch as u64 + 1);
+                }
+            }
+            if batch.queue_batch == 0 {
+                wait_fences.extend_from_slice(submit.wait_fences);
+                wait_values.extend_from_slice(submit.wait_values);
+
+                // The general queue's fence was last signaled by the final submission of the
+                // previous execution, which waited on all the other queues.
+                let general = queue_index(QueueType::General);
+                if batch.queue != QueueType::General && base_values[general] != 0 {
+                    wait_fences.push(&fences.fences[general]);
+                    wait_values.push(base_values[general]);
+                }
+            }
+
+            let mut signal_fences = vec![&fences.fences[queue]];
+            let mut signal_values = vec![base_values[queue] + batch.queue_batch as u64 + 1];
+            let mut swap_image = None;
+            if batch_index == last_batch {
+                signal_fences.extend_from_slice(submit.signal_fences);
+                signal_values.extend_from_slice(submit.signal_values);
+                swap_image = submit.swap_image;
+            }
+
+            let queue = device.get_queue(batch.queue).unwrap();
+            unsafe {
+                queue.submit(&QueueSubmitDesc {
+                    command_lists: &[Some(list).into()],
+                    wait_fences: &wait_fences,
+                    wait_values: &wait_values,
+                    signal_fences: &signal_fences,
+                    signal_values: &signal_values,
+                    swap_image,
+                })?;
+            }
+        }
+
+        for (value, count) in fences.values.iter_mut().zip(schedule.batch_counts) {
+            *value += count as u64;
+        }
+
+        self.deletion_pools[frame_index]
+            .descriptor_pools
+            .push(linear_descriptor_pool.into());
+        self.persistent_bundle.as_mut().unwrap().executions += 1;
+
+        Ok(())
+    }
+
+    fn allocate_transient_resource_bundle(&self) -> TransientResourceBundle {
+        let plan = &self.transient_plan;
+        let mut bundle = TransientResourceBundle {
+            transients: HashMap::with_capacity(plan.lifetimes.len()),
+        };
+        for slot in plan.slots.iter() {
+            // The physical resource is created from the description of the first occupant, with
+            // the usage of all the occupants. Occupants are only packed together if the rest of
+            // their descriptions match.
+            let first = plan.lifetimes[slot.occupants[0]].root;
+            let transient = &self.root_resources[first as usize];
+            let resource = transient
+                .resource_type
+                .create_resource(self.device.as_ref(), slot.usage);
+
+            for &occupant in slot.occupants.iter() {
+                let root = plan.lifetimes[occupant].root;
+                let resource = match &resource {
+                    ResourceVariant::Buffer(v) => ResourceVariant::from(v),
+                    ResourceVariant::Texture(v) => ResourceVariant::from(v),
+                };
+                bundle.add_resource(root, resource);
             }
         }
 
@@ -426,6 +938,40 @@ impl<A: PassArgs> FrameGraph<A> {
 }
 
 impl<A: PassArgs> FrameGraph<A> {
+    /// Splits the execution bundles into at most `max_ranges` contiguous ranges for
+    /// [FrameGraph::execute_parallel], balancing the number of passes that will be executed in
+    /// each range. Together the ranges always cover every bundle, including the import and export
+    /// barrier bundles that hold no passes.
+    pub(crate) fn split_execution_bundles(&self, max_ranges: usize) -> Vec<Range<usize>> {
+        let pass_count = |bundle: &PassOrderBundle| {
+            let passes = unsafe { bundle.passes.as_ref() };
+            passes
+                .iter()
+                .filter(|&&v| !self.render_passes[self.ir_nodes[v].render_pass()].skip)
+                .count()
+        };
+        let total: usize = self.execution_bundles.iter().map(pass_count).sum();
+        let num_ranges = max_ranges.clamp(1, total.max(1));
+        let target = total.div_ceil(num_ranges);
+
+        let mut ranges = Vec::with_capacity(num_ranges);
+        let mut start = 0;
+        let mut count = 0;
+        for (i, bundle) in self.execution_bundles.iter().enumerate() {
+            count += pass_count(bundle);
+            if count >= target && ranges.len() + 1 < num_ranges {
+                ranges.push(start..i + 1);
+                start = i + 1;
+                count = 0;
+            }
+        }
+        if start < self.execution_bundles.len() || ranges.is_empty() {
+            ranges.push(start..self.execution_bundles.len());
+        }
+
+        ranges
+    }
+
     /// Internal function that implements the debug assertions that run prior to the main execute
     /// pass in the frame graph.
     unsafe fn execute_pre_assertions(&self, import_bundle: &ImportBundle) {
@@ -482,6 +1028,11 @@ impl<A: PassArgs> FrameGraph<A> {
                     continue;
                 }
 
+                if !root_resource.final_version.is_valid() {
+                    // Resources only used by culled passes are never created
+                    continue;
+                }
+
                 let v = u16::try_from(v).unwrap();
                 let imported_resource = import_bundle.imports.get(&v);
                 let transient_resource = transient_bundle.transients.get(&v);
@@ -629,6 +1180,8 @@ pub struct FrameGraphResources<'a> {
     device: &'a dyn IDevice,
     import_bundle: &'a ImportBundle,
     transient_bundle: &'a TransientResourceBundle,
+    persistent_bundle: &'a TransientResourceBundle,
+    has_history: bool,
     linear_descriptor_pool: &'a LinearDescriptorPool,
 }
 
@@ -639,14 +1192,22 @@ impl<'a> FrameGraphResources<'a> {
 
     pub fn get<T: Into<ResourceRef>>(&self, r: T) -> Option<&ResourceVariant> {
         let r: ResourceRef = r.into();
-        let i = r.0.root_id();
+        self.get_root(r.0.root_id())
+    }
 
-        let resource = self
-            .transient_bundle
+    fn get_root(&self, i: u16) -> Option<&'a ResourceVariant> {
+        self.transient_bundle
             .get_resource(i)
-            .or_else(|| self.import_bundle.get_resource(i));
+            .or_else(|| self.persistent_bundle.get_resource(i))
+            .or_else(|| self.import_bundle.get_resource(i))
+    }
 
-        resource
+    /// Whether the persistent resources read with
+    /// [crate::ResourceRegistry::read_previous_texture] hold the contents written by the previous
+    /// execution of the graph. This is false on the first execution, where a pass should reset its
+    /// history rather than read it.
+    pub const fn has_history(&self) -> bool {
+        self.has_history
     }
 
     #[inline]
@@ -684,3 +1245,156 @@ impl<'a> FrameGraphResources<'a> {
 pub(crate) struct DeletionPool {
     pub descriptor_pools: Vec<Grave<AllocatorPoolItem<LinearDescriptorPoolFactory>>>,
 }
+
+/// Describes how [FrameGraph::execute_on_queues] synchronizes with work outside of the graph
+#[derive(Clone, Default)]
+pub struct FrameGraphSubmitDesc<'a> {
+    /// A list of fences that the first submission to each queue will wait on.
+    ///
+    /// `wait_fences.len()` and `wait_values.len()` must be equal!
+    pub wait_fences: &'a [&'a FenceHandle],
+
+    /// Associates with 'wait_fences'. Provides the value that the associated fence must become
+    /// signaled to.
+    pub wait_values: &'a [u64],
+
+    /// A list of fences that will be signaled once all the work in the graph, on every queue, has
+    /// completed executing.
+    ///
+    /// `signal_fences.len()` and `signal_values.len()` must be equal!
+    pub signal_fences: &'a [&'a FenceHandle],
+
+    /// Associates with 'signal_fences'. Provides the value that the associated fence will be
+    /// signaled with.
+    pub signal_values: &'a [u64],
+
+    /// The acquired swap chain image to associate the graph's final submission with.
+    pub swap_image: Option<&'a dyn ISwapImage>,
+}
+
+pub(crate) struct QueueFences {
+    /// A fence for each queue that is signaled by every submission the graph makes to the queue
+    pub fences: [FenceHandle; QUEUE_COUNT],
+
+    /// The value each fence was last signaled with
+    pub values: [u64; QUEUE_COUNT],
+}
+
+/// Records the barriers from the graph, and one half of a set of ownership transfers, onto an
+/// encoder for the given queue. Any barriers deferred by the previously recorded passes are
+/// flushed along with them.
+#[allow(clippy::too_many_arguments)]
+unsafe fn record_queue_barriers(
+    encoder: &mut CommandEncoder,
+    graph_channel: &mut GraphChannel,
+    resources: &FrameGraphResources,
+    ir_nodes: &[IRNode],
+    schedule: &QueueSchedule,
+    queue: QueueType,
+    barriers: &[usize],
+    transfers: &[usize],
+    release: bool,
+) {
+    let mut has_memory_barrier = std::mem::take(&mut graph_channel.has_global_or_buffer_barrier);
+    let mut memory_barrier = std::mem::take(&mut graph_channel.global_barrier);
+
+    let mut buffer_barriers = Vec::new();
+    let mut texture_barriers = Vec::new();
+    texture_barriers.extend(graph_channel.texture_barriers.drain(..));
+
+    for &barrier in barriers {
+        match &ir_nodes[barrier] {
+            IRNode::RenderPass(_) => unreachable!(),
+            IRNode::Barrier(v) => {
+                let (before_sync, before_access) =
+                    queue_scope(queue, v.before_sync, v.before_access);
+                let (after_sync, after_access) = queue_scope(queue, v.after_sync, v.after_access);
+                memory_barrier.before_sync |= before_sync;
+                memory_barrier.before_access |= before_access;
+                memory_barrier.after_sync |= after_sync;
+                memory_barrier.after_access |= after_access;
+                has_memory_barrier = true;
+            }
+            IRNode::LayoutChange(v) => {
+                let texture = match resources.get_root(v.resource_id.root).unwrap() {
+                    ResourceVariant::Buffer(_) => unreachable!(),
+                    ResourceVariant::Texture(v) => v,
+                };
+                let (before_sync, before_access) =
+                    queue_scope(queue, v.before_sync, v.before_access);
+                let (after_sync, after_access) = queue_scope(queue, v.after_sync, v.after_access);
+                texture_barriers.push(TextureBarrier {
+                    texture: Some(texture),
+                    subresource_range: v.subresource_range.clone(),
+                    before_sync,
+                    after_sync,
+                    before_access,
+                    after_access,
+                    before_layout: v.before_layout,
+                    after_layout: v.after_layout,
+                    queue_transition: None,
+                });
+            }
+        }
+    }
+
+    // The release half only makes the work from before the transfer available, and the acquire
+    // half only makes it visible to the work after. The fence wait between them provides the
+    // execution dependency.
+    for &transfer in transfers {
+        let transfer = &schedule.transfers[transfer];
+        let none = (BarrierSync::NONE, BarrierAccess::NONE);
+        let ((before_sync, before_access), (after_sync, after_access)) = if release {
+            let before = queue_scope(queue, transfer.before_sync, transfer.before_access);
+            (before, none)
+        } else {
+            let after = queue_scope(queue, transfer.after_sync, transfer.after_access);
+            (none, after)
+        };
+        let queue_transition = Some(QueueTransition {
+            before_queue: transfer.before_queue,
+            after_queue: transfer.after_queue,
+        });
+
+        match resources.get_root(transfer.root).unwrap() {
+            ResourceVariant::Buffer(v) => buffer_barriers.push(BufferBarrier {
+                buffer: Some(v),
+                offset: 0,
+                size: u64::MAX,
+                before_sync,
+                after_sync,
+                before_access,
+                after_access,
+                queue_transition,
+            }),
+            ResourceVariant::Texture(v) => {
+                let layout = transfer.layout.as_ref().unwrap();
+                texture_barriers.push(TextureBarrier {
+                    texture: Some(v),
+                    subresource_range: layout.subresource_range.clone(),
+                    before_sync,
+                    after_sync,
+                    before_access,
+                    after_access,
+                    before_layout: layout.before_layout,
+                    after_layout: layout.after_layout,
+                    queue_transition,
+                });
+            }
+        }
+    }
+
+    if has_memory_barrier || !buffer_barriers.is_empty() || !texture_barriers.is_empty() {
+        let memory_barrier = if has_memory_barrier {
+            std::slice::from_ref(&memory_barrier)
+        } else {
+            &[]
+        };
+        unsafe {
+            encoder.resource_barrier(memory_barrier, &buffer_barriers, &texture_barriers);
+        }
+    }
+
+    // Clear the texture objects from the graph channel now that we've issued the barriers
+    graph_channel.texture_objects.clear();
+}
@@ -48,6 +48,11 @@
 //!   having our pipelines fed as it encourages narrow pipes and short resource lifetimes. Open
 //!   problem to be solved when it matters.
 //!
+//! None of this is implemented yet as the RHI has no way to place resources into a shared heap.
+//! What we do instead is pool whole resources: transients with disjoint lifetimes and identical
+//! descriptions are backed by a single physical resource, and the initialization barrier of each
+//! later occupant waits on the previous one. See the `transient_pool` module for details.
+//!
 
 use std::ptr::NonNull;
 
@@ -59,8 +64,11 @@ use aleph_rhi_api::*;
 use thiserror::Error;
 
 use crate::internal::*;
+use crate::persistent_resource::PersistentResource;
+use crate::queue_schedule::QueueSchedule;
 use crate::render_pass::{CallbackRenderPass, PassArgs};
 use crate::resource::ResourceId;
+use crate::transient_pool::TransientPoolPlan;
 use crate::{FrameGraph, FrameGraphResources, GraphChannel, IRenderPass, ResourceMut, ResourceRef};
 
 #[derive(Error, Debug)]
@@ -180,6 +188,10 @@ pub struct FrameGraphBuilder<A: PassArgs = ()> {
     /// The set of resources within the graph that were imported, stored as indices into the
     /// root_resources array.
     pub(crate) imported_resources: BVec<u16, FgSystem>,
+
+    /// The set of resources within the graph whose contents persist between executions of the
+    /// graph, along with the root resources that represent them.
+    pub(crate) persistent_resources: BVec<PersistentResource, FgSystem>,
 }
 
 impl<A: PassArgs> Default for FrameGraphBuilder<A> {
@@ -190,6 +202,7 @@ impl<A: PassArgs> Default for FrameGraphBuilder<A> {
             root_resources: BVec::new_in(system()),
             resource_versions: BVec::new_in(system()),
             imported_resources: BVec::new_in(system()),
+            persistent_resources: BVec::new_in(system()),
         }
     }
 }
@@ -211,20 +224,27 @@ impl<A: PassArgs> FrameGraphBuilder<A> {
         name: &NStr,
         setup_fn: SetupFn,
     ) {
-        let (exec_fn, skip) = {
+        let (exec_fn, skip, side_effects, queue) = {
             let current_pass_index = self.render_passes.len();
             let mut resources = ResourceRegistry {
                 builder: self,
                 render_pass: current_pass_index,
                 skip: false,
+                side_effects: false,
+                queue: QueueType::General,
             };
             let exec_fn = setup_fn(&mut resources);
-            (exec_fn, resources.skip)
+            (
+                exec_fn,
+                resources.skip,
+                resources.side_effects,
+                resources.queue,
+            )
         };
 
         // Construct the CallbackRenderPass instance and handoff to add_pass
         let callback_pass = CallbackRenderPass::new(exec_fn);
-        self.add_pass_internal(name, callback_pass, skip);
+        self.add_pass_internal(name, callback_pass, skip, side_effects, queue);
     }
 
     /// Finalize the graph and fully resolve all the declared passes into a [FrameGraph]. Once the
@@ -271,6 +291,10 @@ impl<A: PassArgs> FrameGraphBuilder<A> {
         // being built
         let build_arena = Blink::new_in(BlinkAlloc::new_in(system()));
 
+        // Strip out the passes that nothing outside the graph can observe before doing any other
+        // work, so the culled passes don't affect the pass order or resource allocation.
+        let mut culling_report = self.cull_passes();
+
         self.validate_imported_resource_usages();
 
         let num_passes = self.render_passes.len();
@@ -337,7 +361,42 @@ impl<A: PassArgs> FrameGraphBuilder<A> {
         }
 
         let execution_bundles = pass_order_builder.bundles;
-        let ir_nodes = ir_builder.nodes;
+        let mut ir_nodes = ir_builder.nodes;
+
+        culling_report.record_resource_lifetimes(
+            &self.render_passes,
+            &self.root_resources,
+            &self.resource_versions,
+            &execution_bundles,
+            &ir_nodes,
+        );
+
+        // Now that the pass order is final we know the lifetimes of all the transient resources,
+        // so we can decide which ones can share memory.
+        let transient_plan = TransientPoolPlan::new(
+            &self.root_resources,
+            &self.resource_versions,
+            &execution_bundles,
+            &ir_nodes,
+            num_passes,
+        );
+        transient_plan.patch_initialization_barriers(&mut ir_nodes);
+
+        // Passes that prefer a queue the device doesn't have run on the general queue instead,
+        // then the pass order is split into submissions for each queue.
+        for pass in self.render_passes.iter_mut() {
+            if device.get_queue(pass.queue).is_none() {
+                pass.queue = QueueType::General;
+            }
+        }
+        let queue_schedule = QueueSchedule::new(
+            &self.render_passes,
+            &self.root_resources,
+            &self.resource_versions,
+            &execution_bundles,
+            &ir_nodes,
+            &transient_plan,
+        );
 
         fn take_bvec<T>(v: &mut BVec<T, FgSystem>) -> BVec<T, FgSystem> {
             let mut slot = BVec::new_in(system());
@@ -350,6 +409,7 @@ impl<A: PassArgs> FrameGraphBuilder<A> {
         let root_resources = take_bvec(&mut self.root_resources);
         let resource_versions = take_bvec(&mut self.resource_versions);
         let imported_resources = take_bvec(&mut self.imported_resources);
+        let persistent_resources = take_bvec(&mut self.persistent_resources);
 
         Ok(FrameGraph {
             _arena: Grave::new(arena),
@@ -360,7 +420,13 @@ impl<A: PassArgs> FrameGraphBuilder<A> {
             root_resources,
             resource_versions,
             imported_resources,
+            persistent_resources,
+            transient_plan,
+            culling_report,
+            queue_schedule,
+            queue_fences: None,
             transient_bundles: None,
+            persistent_bundle: None,
             deletion_pools: BVec::new_in(system()),
             linear_descriptor_pools: AllocatorPool::new(
                 LinearDescriptorPoolFactory::new(device.upgrade(), 1024),
@@ -376,6 +442,8 @@ pub struct ResourceRegistry<'a, A: PassArgs> {
     builder: &'a mut FrameGraphBuilder<A>,
     render_pass: usize,
     skip: bool,
+    side_effects: bool,
+    queue: QueueType,
 }
 
 impl<'a, A: PassArgs> ResourceRegistry<'a, A> {
@@ -704,6 +772,138 @@ impl<'a, A: PassArgs> ResourceRegistry<'a, A> {
             .create_buffer_internal(self.render_pass, desc, sync, access)
     }
 
+    /// Declares that the pass reads the contents a previous execution of the graph wrote to the
+    /// persistent texture named by 'desc'. Use 'access' to specify how the pass will use the
+    /// resource. This is the building block for temporal effects such as TAA history.
+    ///
+    /// Persistent resources are identified by 'desc.name', which must be set, and live for as long
+    /// as the [crate::FrameGraph]. The contents read here are the ones written through
+    /// [ResourceRegistry::write_current_texture] in the previous execution. Every pass that reads
+    /// the previous contents of the same persistent resource gets the same handle back.
+    ///
+    /// The contents are undefined on the first execution of the graph, which passes can detect
+    /// with [crate::FrameGraphResources::has_history]. They are also undefined if the previous
+    /// execution didn't write them.
+    ///
+    /// Like [ResourceRegistry::create_texture], the usage flags in the [TextureDesc] are ignored.
+    pub fn read_previous_texture(
+        &mut self,
+        desc: &TextureDesc,
+        access: ResourceUsageFlags,
+    ) -> ResourceRef {
+        self.builder.read_previous_texture_internal(
+            self.render_pass,
+            desc,
+            BarrierSync::NONE,
+            access,
+        )
+    }
+
+    /// See [ResourceRegistry::read_previous_texture].
+    ///
+    /// When 'sync' is equal to `BarrierSync::default()` (empty) default sync flags are chosen that
+    /// covers all possible [BarrierSync] values that are applicable to the [ResourceUsageFlags]
+    /// declared as 'access'.
+    pub fn read_previous_texture_with_sync(
+        &mut self,
+        desc: &TextureDesc,
+        sync: BarrierSync,
+        access: ResourceUsageFlags,
+    ) -> ResourceRef {
+        self.builder
+            .read_previous_texture_internal(self.render_pass, desc, sync, access)
+    }
+
+    /// Declares that the pass writes the contents of the persistent texture named by 'desc' that
+    /// the next execution of the graph will read through
+    /// [ResourceRegistry::read_previous_texture]. Use 'access' to specify how the pass will use
+    /// the resource.
+    ///
+    /// Only one pass may declare the write. Later passes in the same execution use the returned
+    /// handle like any other resource. The description must match the one given to
+    /// [ResourceRegistry::read_previous_texture] for the same name, as both are backed by the same
+    /// pair of textures that swap roles on every execution. This means any number of frames can be
+    /// in flight, as executions of a graph are serialized on the general queue.
+    ///
+    /// Like [ResourceRegistry::create_texture], the usage flags in the [TextureDesc] are ignored.
+    pub fn write_current_texture(
+        &mut self,
+        desc: &TextureDesc,
+        access: ResourceUsageFlags,
+    ) -> ResourceMut {
+        self.builder.write_current_texture_internal(
+            self.render_pass,
+            desc,
+            BarrierSync::NONE,
+            access,
+        )
+    }
+
+    /// See [ResourceRegistry::write_current_texture].
+    ///
+    /// When 'sync' is equal to `BarrierSync::default()` (empty) default sync flags are chosen that
+    /// covers all possible [BarrierSync] values that are applicable to the [ResourceUsageFlags]
+    /// declared as 'access'.
+    pub fn write_current_texture_with_sync(
+        &mut self,
+        desc: &TextureDesc,
+        sync: BarrierSync,
+        access: ResourceUsageFlags,
+    ) -> ResourceMut {
+        self.builder
+            .write_current_texture_internal(self.render_pass, desc, sync, access)
+    }
+
+    /// The buffer equivalent of [ResourceRegistry::read_previous_texture].
+    pub fn read_previous_buffer(
+        &mut self,
+        desc: &BufferDesc,
+        access: ResourceUsageFlags,
+    ) -> ResourceRef {
+        self.builder.read_previous_buffer_internal(
+            self.render_pass,
+            desc,
+            BarrierSync::NONE,
+            access,
+        )
+    }
+
+    /// The buffer equivalent of [ResourceRegistry::read_previous_texture_with_sync].
+    pub fn read_previous_buffer_with_sync(
+        &mut self,
+        desc: &BufferDesc,
+        sync: BarrierSync,
+        access: ResourceUsageFlags,
+    ) -> ResourceRef {
+        self.builder
+            .read_previous_buffer_internal(self.render_pass, desc, sync, access)
+    }
+
+    /// The buffer equivalent of [ResourceRegistry::write_current_texture].
+    pub fn write_current_buffer(
+        &mut self,
+        desc: &BufferDesc,
+        access: ResourceUsageFlags,
+    ) -> ResourceMut {
+        self.builder.write_current_buffer_internal(
+            self.render_pass,
+            desc,
+            BarrierSync::NONE,
+            access,
+        )
+    }
+
+    /// The buffer equivalent of [ResourceRegistry::write_current_texture_with_sync].
+    pub fn write_current_buffer_with_sync(
+        &mut self,
+        desc: &BufferDesc,
+        sync: BarrierSync,
+        access: ResourceUsageFlags,
+    ) -> ResourceMut {
+        self.builder
+            .write_current_buffer_internal(self.render_pass, desc, sync, access)
+    }
+
     /// Declares a new execution token with the given name. An execution token is like a transient
     /// resources that lacks a backing GPU API resource. It instead exists only for encoding an
     /// explicit execution dependency on another pass without implying any GPU synchronization.
@@ -724,6 +924,28 @@ impl<'a, A: PassArgs> ResourceRegistry<'a, A> {
     pub fn skip_execution(&mut self) {
         self.skip = true;
     }
+
+    /// Declares that the render pass has effects outside of the graph that the graph can't see,
+    /// such as writing to a resource that wasn't imported into the graph.
+    ///
+    /// Passes whose writes never reach an imported resource, directly or through the passes that
+    /// consume them, are culled when the graph is built. This flag keeps the pass, and every pass
+    /// it depends on, in the graph regardless. See [crate::PassCullingReport].
+    pub fn declare_side_effects(&mut self) {
+        self.side_effects = true;
+    }
+
+    /// Declares the queue the render pass would prefer to execute on. Defaults to
+    /// [QueueType::General].
+    ///
+    /// This only applies when the graph is executed with [FrameGraph::execute_on_queues], which
+    /// records the pass into a command list for the requested queue and synchronizes it with the
+    /// passes on other queues. [FrameGraph::execute] records every pass into the single encoder it
+    /// is given. Passes fall back to the general queue if the device doesn't provide the requested
+    /// queue, so the pass must only record commands that the general queue also supports.
+    pub fn set_preferred_queue(&mut self, queue: QueueType) {
+        self.queue = queue;
+    }
 }
 
 // =================================================================================================
@@ -737,6 +959,8 @@ impl<A: PassArgs> FrameGraphBuilder<A> {
         name: &NStr,
         pass: T,
         skip: bool,
+        side_effects: bool,
+        queue: QueueType,
     ) {
         let name = self.arena.copy_slice(name.to_bytes());
         let name = NStr::from_bytes(name).unwrap();
@@ -756,7 +980,13 @@ impl<A: PassArgs> FrameGraphBuilder<A> {
             // also, ptr just came from a properly allocated box in the same allocator.
             unsafe { BBox::from_raw_in(ptr, allocator) }
         };
-        let pass = RenderPass { pass, name, skip };
+        let pass = RenderPass {
+            pass,
+            name,
+            skip,
+            side_effects,
+            queue,
+        };
         self.render_passes.push(pass);
     }
 
@@ -766,6 +996,21 @@ impl<A: PassArgs> FrameGraphBuilder<A> {
         desc: &TextureImportDesc,
         sync: BarrierSync,
         access: ResourceUsageFlags,
+    ) -> ResourceMut {
+        let r = self.create_imported_texture_handle(render_pass, desc, sync, access);
+        self.add_imported_resource_to_list(r);
+
+        r
+    }
+
+    /// Creates the root resource for an imported texture without adding it to the list of resources
+    /// that must be provided in the [crate::ImportBundle].
+    pub(crate) fn create_imported_texture_handle(
+        &mut self,
+        render_pass: usize,
+        desc: &TextureImportDesc,
+        sync: BarrierSync,
+        access: ResourceUsageFlags,
     ) -> ResourceMut {
         debug_assert!(
             access.is_valid_texture_usage(),
@@ -806,13 +1051,25 @@ impl<A: PassArgs> FrameGraphBuilder<A> {
 
         // render pass index doesn't matter here as imported resources aren't created by a render
         // pass
-        let r = self.create_new_handle(render_pass, sync, access, r_type);
+        self.create_new_handle(render_pass, sync, access, r_type)
+    }
+
+    pub(crate) fn import_buffer_internal(
+        &mut self,
+        render_pass: usize,
+        desc: &BufferImportDesc,
+        sync: BarrierSync,
+        access: ResourceUsageFlags,
+    ) -> ResourceMut {
+        let r = self.create_imported_buffer_handle(render_pass, desc, sync, access);
         self.add_imported_resource_to_list(r);
 
         r
     }
 
-    pub(crate) fn import_buffer_internal(
+    /// Creates the root resource for an imported buffer without adding it to the list of resources
+    /// that must be provided in the [crate::ImportBundle].
+    pub(crate) fn create_imported_buffer_handle(
         &mut self,
         render_pass: usize,
         desc: &BufferImportDesc,
@@ -848,10 +1105,7 @@ impl<A: PassArgs> FrameGraphBuilder<A> {
 
         // render pass index doesn't matter here as imported resources aren't created by a render
         // pass
-        let r = self.create_new_handle(render_pass, sync, access, r_type);
-        self.add_imported_resource_to_list(r);
-
-        r
+        self.create_new_handle(render_pass, sync, access, r_type)
     }
 
     pub(crate) fn read_texture_internal<R: Into<ResourceRef>>(
@@ -2625,6 +2879,12 @@ impl<'arena> PassOrderBuilder<'arena> {
             }
         }
         debug_assert!(is_sorted(candidates.as_slice()));
+
+        // A graph can be left with nothing to schedule once all of its passes are culled
+        if candidates.is_empty() {
+            return Ok(());
+        }
+
         loop {
             // Capture the number of candidates we're trying to process in this iteration. This is
             // used later to detect graph cycles, which manifest as unresolvable dependencies
@@ -46,6 +46,12 @@ pub(crate) struct RenderPass<A: PassArgs> {
     pub pass: BBox<dyn IRenderPass<A>, FgSystem>,
     pub name: NonNull<NStr>,
     pub skip: bool,
+
+    /// Whether the pass has effects outside of the graph, which stops it from being culled
+    pub side_effects: bool,
+
+    /// The queue the pass prefers to execute on when the graph is executed across multiple queues
+    pub queue: QueueType,
 }
 
 // Safety: 'name' is a pointer into an arena on the graph, perfectly safe to share across threads as
@@ -249,6 +255,44 @@ impl ResourceType {
             }
         }
     }
+
+    /// Creates a new physical resource from the resource's description, with the given usage
+    /// flags in place of the usage the graph deduced for the resource.
+    pub(crate) fn create_resource(
+        &self,
+        device: &dyn IDevice,
+        usage: ResourceUsageFlags,
+    ) -> ResourceVariant {
+        match self {
+            ResourceType::Buffer(v) => {
+                let desc = BufferDesc {
+                    size: v.desc.size,
+                    cpu_access: v.desc.cpu_access,
+                    usage,
+                    name: v.desc.name.map(|v| unsafe { v.as_ref() }),
+                };
+                device.create_buffer(&desc).unwrap().into()
+            }
+            ResourceType::Texture(v) => {
+                let desc = TextureDesc {
+                    width: v.desc.width,
+                    height: v.desc.height,
+                    depth: v.desc.depth,
+                    format: v.desc.format,
+                    dimension: v.desc.dimension,
+                    clear_value: v.desc.clear_value.clone(),
+                    array_size: v.desc.array_size,
+                    mip_levels: v.desc.mip_levels,
+                    sample_count: v.desc.sample_count,
+                    sample_quality: v.desc.sample_quality,
+                    usage,
+                    name: v.desc.name.map(|v| unsafe { v.as_ref() }),
+                };
+                device.create_texture(&desc).unwrap().into()
+            }
+            ResourceType::Execution(_) => unreachable!(),
+        }
+    }
 }
 
 pub(crate) struct ImportedResource {
@@ -132,18 +132,25 @@ mod frame_graph;
 mod frame_graph_builder;
 mod import_bundle;
 mod internal;
+mod pass_culling;
+mod persistent_resource;
+mod queue_schedule;
 mod render_pass;
 mod resource;
 mod resource_variant;
+mod transient_pool;
 
 #[cfg(test)]
 mod tests;
 
-pub use frame_graph::{FrameGraph, FrameGraphResources};
+pub use frame_graph::{FrameGraph, FrameGraphResources, FrameGraphSubmitDesc};
 pub use frame_graph_builder::{
     BufferImportDesc, FrameGraphBuilder, ResourceRegistry, Result, TextureImportDesc,
 };
 pub use import_bundle::ImportBundle;
+pub use pass_culling::{PassCullingReport, ResourceLifetimeInfo};
+pub use queue_schedule::{QueueScheduleReport, QueueSubmissionInfo};
 pub use render_pass::{GraphChannel, IRenderPass, PassArgs};
 pub use resource::{ResourceMut, ResourceRef};
 pub use resource_variant::ResourceVariant;
+pub use transient_pool::TransientMemoryReport;
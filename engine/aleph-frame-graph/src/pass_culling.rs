@@ -0,0 +1,280 @@
+//
+//
+// This file is a part of Aleph
+//
+// https://github.com/nathanvoglsam/aleph
+//
+// MIT License
+//
+// Copyright (c) 2020 Aleph Engine
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use std::ptr::NonNull;
+
+use aleph_alloc::BVec;
+use aleph_alloc::instrumentation::system;
+
+use crate::FrameGraphBuilder;
+use crate::internal::{
+    IIRNode, IRNode, PassOrderBundle, RenderPass, ResourceRoot, ResourceType, ResourceVersion,
+    VersionIndex, VersionReaderLink,
+};
+use crate::render_pass::PassArgs;
+
+///
+/// Describes the passes that were culled from a [crate::FrameGraph] and when each of the
+/// remaining resources is used.
+///
+/// A pass is kept if something outside of the graph can observe its work. That is, if it declared
+/// side effects with [crate::ResourceRegistry::declare_side_effects], if it writes to an imported
+/// or persistent resource or if any pass that is kept depends on it. Every other pass is culled
+/// along with the transient resources that only culled passes use.
+///
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PassCullingReport {
+    /// The names of the passes that were culled, in the order they were added to the graph
+    pub culled_passes: Vec<String>,
+
+    /// The names of the resources that were only used by culled passes, and so are never created
+    pub culled_resources: Vec<String>,
+
+    /// The lifetime of every buffer and texture the graph still uses, in the order they were
+    /// declared
+    pub resources: Vec<ResourceLifetimeInfo>,
+}
+
+///
+/// The span of the final pass order that a resource is used in.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ResourceLifetimeInfo {
+    /// The name of the resource
+    pub name: String,
+
+    /// Whether the resource was imported, rather than being a transient owned by the graph
+    pub imported: bool,
+
+    /// The name of the first pass in the pass order that uses the resource
+    pub first_use: String,
+
+    /// The name of the last pass in the pass order that uses the resource
+    pub last_use: String,
+}
+
+impl PassCullingReport {
+    /// Fills out [PassCullingReport::resources] once the pass order of the graph is known
+    pub(crate) fn record_resource_lifetimes<A: PassArgs>(
+        &mut self,
+        render_passes: &[RenderPass<A>],
+        root_resources: &[ResourceRoot],
+        resource_versions: &[ResourceVersion],
+        execution_bundles: &[PassOrderBundle],
+        ir_nodes: &[IRNode],
+    ) {
+        // Map each render pass to its position in the pass order
+        let mut pass_order = vec![usize::MAX; render_passes.len()];
+        let mut position = 0;
+        for bundle in execution_bundles.iter() {
+            let passes = unsafe { bundle.passes.as_ref() };
+            for &pass in passes {
+                pass_order[ir_nodes[pass].render_pass()] = position;
+                position += 1;
+            }
+        }
+
+        // The (first, last) pass that uses each root resource
+        let mut spans: Vec<Option<(usize, usize)>> = vec![None; root_resources.len()];
+        let mut include = |root: u16, pass: usize| {
+            let span = &mut spans[root as usize];
+            let (first, last) = span.get_or_insert((pass, pass));
+            if pass_order[pass] < pass_order[*first] {
+                *first = pass;
+            }
+            if pass_order[pass] > pass_order[*last] {
+                *last = pass;
+            }
+        };
+        for version in resource_versions.iter() {
+            include(version.root_resource, version.creator_pass);
+            for read in version.reads_iter() {
+                include(version.root_resource, read.render_pass);
+            }
+        }
+
+        let pass_name = |pass: usize| unsafe { render_passes[pass].name.as_ref() }.to_string();
+        for (root, span) in root_resources.iter().zip(spans) {
+            if matches!(root.resource_type, ResourceType::Execution(_)) {
+                continue;
+            }
+            let Some((first, last)) = span else {
+                continue;
+            };
+            self.resources.push(ResourceLifetimeInfo {
+                name: resource_name(root),
+                imported: root.resource_type.is_import(),
+                first_use: pass_name(first),
+                last_use: pass_name(last),
+            });
+        }
+    }
+}
+
+impl<A: PassArgs> FrameGraphBuilder<A> {
+    /// Removes every pass whose work can't be observed from outside the graph, along with the
+    /// resource versions they create and the reads they declare. See [PassCullingReport] for the
+    /// rules that decide which passes are kept.
+    ///
+    /// Culled passes can only create the trailing versions of a resource, as a write or read of a
+    /// version by a kept pass would keep the pass that created it. This means the kept versions of
+    /// each resource still form a complete chain back to the version that created the resource.
+    /// Root resources are never removed so the root index stored in the handles given to passes
+    /// remain valid. Resources only used by culled passes are left with an invalid final version.
+    pub(crate) fn cull_passes(&mut self) -> PassCullingReport {
+        let num_passes = self.render_passes.len();
+
+        // Each pass depends on the creator of every version it reads, and on the creator of the
+        // previous version of every resource it writes.
+        //
+        // Reading the previous contents of a persistent resource is not observable on its own, so
+        // only writes to imported or persistent resources keep a pass.
+        let previous_roots: Vec<u16> = self
+            .persistent_resources
+            .iter()
+            .filter_map(|v| v.previous.map(|v| v.0.root_id()))
+            .collect();
+        let mut dependencies = vec![Vec::new(); num_passes];
+        let mut live = vec![false; num_passes];
+        for version in self.resource_versions.iter() {
+            let root = &self.root_resources[version.root_resource as usize];
+            if root.resource_type.is_import() && !previous_roots.contains(&version.root_resource) {
+                live[version.creator_pass] = true;
+            }
+            if version.previous_version.is_valid() {
+                let previous = &self.resource_versions[version.previous_version.0 as usize];
+                dependencies[version.creator_pass].push(previous.creator_pass);
+            }
+            for read in version.reads_iter() {
+                dependencies[read.render_pass].push(version.creator_pass);
+            }
+        }
+        for (i, pass) in self.render_passes.iter().enumerate() {
+            live[i] |= pass.side_effects;
+        }
+
+        let mut stack: Vec<usize> = (0..num_passes).filter(|&v| live[v]).collect();
+        while let Some(pass) = stack.pop() {
+            for &dependency in dependencies[pass].iter() {
+                if !live[dependency] {
+                    live[dependency] = true;
+                    stack.push(dependency);
+                }
+            }
+        }
+
+        let mut report = PassCullingReport::default();
+        if live.iter().all(|&v| v) {
+            return report;
+        }
+
+        // Compact the render pass list, remembering where each kept pass moved to
+        let mut pass_map = vec![usize::MAX; num_passes];
+        let render_passes = std::mem::replace(&mut self.render_passes, BVec::new_in(system()));
+        for (i, pass) in render_passes.into_iter().enumerate() {
+            if live[i] {
+                pass_map[i] = self.render_passes.len();
+                self.render_passes.push(pass);
+            } else {
+                let name = unsafe { pass.name.as_ref() };
+                report.culled_passes.push(name.to_string());
+            }
+        }
+
+        // Compact the version list, remapping the pass indices and dropping culled reads
+        let resource_versions =
+            std::mem::replace(&mut self.resource_versions, BVec::new_in(system()));
+        let mut previous_versions = Vec::with_capacity(resource_versions.len());
+        let mut version_map = vec![VersionIndex::INVALID; resource_versions.len()];
+        for (i, mut version) in resource_versions.into_iter().enumerate() {
+            previous_versions.push(version.previous_version);
+            if !live[version.creator_pass] {
+                continue;
+            }
+
+            version_map[i] = VersionIndex(self.resource_versions.len() as u32);
+            version.creator_pass = pass_map[version.creator_pass];
+            if version.previous_version.is_valid() {
+                version.previous_version = version_map[version.previous_version.0 as usize];
+            }
+
+            // Rebuild the list of reads back to front so the kept reads stay in the same order
+            let reads: Vec<VersionReaderLink> = version
+                .reads_iter()
+                .filter(|v| live[v.render_pass])
+                .cloned()
+                .collect();
+            version.reads = None;
+            version.read_count = reads.len();
+            for mut read in reads.into_iter().rev() {
+                read.next = version.reads;
+                read.render_pass = pass_map[read.render_pass];
+                version.reads = Some(NonNull::from(self.arena.put(read)));
+            }
+
+            self.resource_versions.push(version);
+        }
+
+        // The final version of a resource becomes the last version that wasn't culled
+        for root in self.root_resources.iter_mut() {
+            let mut version = root.final_version;
+            while version.is_valid() && !version_map[version.0 as usize].is_valid() {
+                version = previous_versions[version.0 as usize];
+            }
+            if version.is_valid() {
+                root.final_version = version_map[version.0 as usize];
+            } else {
+                root.final_version = VersionIndex::INVALID;
+                if !matches!(root.resource_type, ResourceType::Execution(_)) {
+                    report.culled_resources.push(resource_name(root));
+                }
+            }
+        }
+
+        // Forget the previous contents of persistent resources that only culled passes read
+        for persistent in self.persistent_resources.iter_mut() {
+            if let Some(previous) = persistent.previous.as_mut() {
+                let version = version_map[previous.0.version as usize];
+                if version.is_valid() {
+                    previous.0.version = version.0;
+                } else {
+                    persistent.previous = None;
+                }
+            }
+        }
+
+        report
+    }
+}
+
+fn resource_name(root: &ResourceRoot) -> String {
+    unsafe { root.resource_type.name() }
+        .unwrap_or("Unnamed resource")
+        .to_string()
+}
@@ -0,0 +1,358 @@
+//
+//
+// This file is a part of Aleph
+//
+// https://github.com/nathanvoglsam/aleph
+//
+// MIT License
+//
+// Copyright (c) 2020 Aleph Engine
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use std::ptr::NonNull;
+
+use aleph_rhi_api::*;
+
+use crate::internal::{ResourceRoot, ResourceType, TransientResourceBundle};
+use crate::render_pass::PassArgs;
+use crate::transient_pool::is_compatible;
+use crate::{
+    BufferImportDesc, FrameGraphBuilder, ResourceMut, ResourceRef, ResourceVariant,
+    TextureImportDesc,
+};
+
+/// The pipeline stages every execution of the graph synchronizes persistent resources with when
+/// handing them over to the next execution.
+const HANDOFF_SYNC: BarrierSync = BarrierSync::ALL;
+
+/// The access scope that persistent resources are handed over to the next execution with
+const HANDOFF_ACCESS: BarrierAccess = BarrierAccess::COMMON;
+
+/// The layout persistent textures are left in between executions of the graph
+const HANDOFF_LAYOUT: ImageLayout = ImageLayout::Common;
+
+/// A named resource whose contents are carried over from one execution of the graph to the next.
+///
+/// Within a single execution the resource is represented by two root resources, which are imported
+/// internally rather than through the [crate::ImportBundle]. The 'previous' root holds the contents
+/// written by the last execution, and the 'current' root holds the contents written by this one.
+/// Two physical resources are swapped between the roots on every execution.
+pub(crate) struct PersistentResource {
+    /// The name the resource was declared with. This is a pointer into the graph's arena, so the
+    /// same rules apply as for the names in the resource descriptions.
+    pub name: NonNull<str>,
+
+    /// The handle to the contents of the resource from the previous execution, if any pass reads
+    /// them.
+    pub previous: Option<ResourceRef>,
+
+    /// The root resource for the contents written by the current execution, if any pass writes
+    /// them.
+    pub current: Option<u16>,
+}
+
+// Safety: 'name' is just a string backed by the graph's arena, see [PersistentResource::name]
+unsafe impl Send for PersistentResource {}
+unsafe impl Sync for PersistentResource {}
+
+/// The physical resources that back the persistent resources of a graph
+pub(crate) struct PersistentResourceBundle {
+    /// The physical resources for the 'previous' and 'current' roots of each persistent resource,
+    /// for executions with an even and odd execution count respectively.
+    pub bundles: [TransientResourceBundle; 2],
+
+    /// The textures that need to be moved out of the undefined layout before the graph first
+    /// executes.
+    pub textures: Vec<(TextureHandle, TextureSubResourceSet)>,
+
+    /// The number of times the graph has executed since the resources were created
+    pub executions: u64,
+}
+
+impl PersistentResourceBundle {
+    pub fn new(
+        device: &dyn IDevice,
+        root_resources: &[ResourceRoot],
+        persistent_resources: &[PersistentResource],
+    ) -> Self {
+        let mut out = Self {
+            bundles: Default::default(),
+            textures: Vec::new(),
+            executions: 0,
+        };
+        for persistent in persistent_resources.iter() {
+            let previous = persistent.previous.map(|v| v.0.root_id());
+            let roots: Vec<u16> = previous.into_iter().chain(persistent.current).collect();
+
+            // The resources swap roles every execution, so both need the usages of both roles
+            let usage = roots.iter().fold(ResourceUsageFlags::empty(), |usage, &v| {
+                usage | root_resources[v as usize].total_access_flags
+            });
+            let resource_type = &root_resources[roots[0] as usize].resource_type;
+            let resources = [
+                resource_type.create_resource(device, usage),
+                resource_type.create_resource(device, usage),
+            ];
+
+            for (parity, bundle) in out.bundles.iter_mut().enumerate() {
+                if let Some(previous) = previous {
+                    bundle.add_resource(previous, resources[parity ^ 1].clone());
+                }
+                if let Some(current) = persistent.current {
+                    bundle.add_resource(current, resources[parity].clone());
+                }
+            }
+
+            if let ResourceType::Texture(v) = resource_type {
+                let subresource_range = TextureSubResourceSet {
+                    aspect: v.desc.format.aspect_mask(),
+                    base_mip_level: 0,
+                    num_mip_levels: v.desc.mip_levels,
+                    base_array_slice: 0,
+                    num_array_slices: v.desc.array_size,
+                };
+                for resource in resources {
+                    if let ResourceVariant::Texture(texture) = resource {
+                        out.textures.push((texture, subresource_range.clone()));
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    /// The physical resources for the roots of the persistent resources in the next execution
+    pub fn current(&self) -> &TransientResourceBundle {
+        &self.bundles[(self.executions % 2) as usize]
+    }
+
+    /// Whether the 'previous' versions of the persistent resources hold the contents written by an
+    /// earlier execution. They are undefined on the first execution after being created.
+    pub const fn has_history(&self) -> bool {
+        self.executions != 0
+    }
+
+    /// Moves freshly created persistent textures into the state the graph expects them to be left
+    /// in by the previous execution. Does nothing once the graph has executed.
+    ///
+    /// # Safety
+    ///
+    /// This records barriers, see [crate::FrameGraph::execute].
+    pub unsafe fn record_initial_state_barriers(&self, encoder: &mut CommandEncoder) {
+        if self.has_history() || self.textures.is_empty() {
+            return;
+        }
+
+        let barriers: Vec<_> = self
+            .textures
+            .iter()
+            .map(|(texture, subresource_range)| TextureBarrier {
+                texture: Some(texture),
+                subresource_range: subresource_range.clone(),
+                before_sync: BarrierSync::NONE,
+                after_sync: HANDOFF_SYNC,
+                before_access: BarrierAccess::NONE,
+                after_access: HANDOFF_ACCESS,
+                before_layout: ImageLayout::Undefined,
+                after_layout: HANDOFF_LAYOUT,
+                queue_transition: None,
+            })
+            .collect();
+        unsafe {
+            encoder.resource_barrier(&[], &[], &barriers);
+        }
+    }
+}
+
+// Internal functions exposed through ResourceRegistry
+impl<A: PassArgs> FrameGraphBuilder<A> {
+    pub(crate) fn read_previous_texture_internal(
+        &mut self,
+        render_pass: usize,
+        desc: &TextureDesc,
+        sync: BarrierSync,
+        access: ResourceUsageFlags,
+    ) -> ResourceRef {
+        let index = self.persistent_resource_index(desc.name, desc.usage);
+        if let Some(previous) = self.persistent_resources[index].previous {
+            return self.read_texture_internal(render_pass, previous, sync, access);
+        }
+
+        let r = self.create_persistent_texture_handle(render_pass, desc, sync, access);
+        self.persistent_resources[index].previous = Some(r.into());
+        self.validate_persistent_resource_descs(index);
+
+        r.into()
+    }
+
+    pub(crate) fn write_current_texture_internal(
+        &mut self,
+        render_pass: usize,
+        desc: &TextureDesc,
+        sync: BarrierSync,
+        access: ResourceUsageFlags,
+    ) -> ResourceMut {
+        let index = self.persistent_resource_index(desc.name, desc.usage);
+        self.assert_current_not_written(index);
+
+        let r = self.create_persistent_texture_handle(render_pass, desc, sync, access);
+        self.persistent_resources[index].current = Some(r.0.root_id());
+        self.validate_persistent_resource_descs(index);
+
+        r
+    }
+
+    pub(crate) fn read_previous_buffer_internal(
+        &mut self,
+        render_pass: usize,
+        desc: &BufferDesc,
+        sync: BarrierSync,
+        access: ResourceUsageFlags,
+    ) -> ResourceRef {
+        let index = self.persistent_resource_index(desc.name, desc.usage);
+        if let Some(previous) = self.persistent_resources[index].previous {
+            return self.read_buffer_internal(render_pass, previous, sync, access);
+        }
+
+        let r = self.create_persistent_buffer_handle(render_pass, desc, sync, access);
+        self.persistent_resources[index].previous = Some(r.into());
+        self.validate_persistent_resource_descs(index);
+
+        r.into()
+    }
+
+    pub(crate) fn write_current_buffer_internal(
+        &mut self,
+        render_pass: usize,
+        desc: &BufferDesc,
+        sync: BarrierSync,
+        access: ResourceUsageFlags,
+    ) -> ResourceMut {
+        let index = self.persistent_resource_index(desc.name, desc.usage);
+        self.assert_current_not_written(index);
+
+        let r = self.create_persistent_buffer_handle(render_pass, desc, sync, access);
+        self.persistent_resources[index].current = Some(r.0.root_id());
+        self.validate_persistent_resource_descs(index);
+
+        r
+    }
+
+    /// Finds the persistent resource with the given name, declaring a new one if there isn't one
+    fn persistent_resource_index(
+        &mut self,
+        name: Option<&str>,
+        usage: ResourceUsageFlags,
+    ) -> usize {
+        debug_assert!(
+            usage.is_empty(),
+            "The value of desc.usage is ignored, do not use it!"
+        );
+        let name = name.expect("Persistent resources must be named");
+
+        let existing = self
+            .persistent_resources
+            .iter()
+            .position(|v| unsafe { v.name.as_ref() } == name);
+        existing.unwrap_or_else(|| {
+            let name = NonNull::from(self.arena.copy_str(name));
+            self.persistent_resources.push(PersistentResource {
+                name,
+                previous: None,
+                current: None,
+            });
+            self.persistent_resources.len() - 1
+        })
+    }
+
+    /// The persistent roots are imported with the state every execution hands the resources over
+    /// to the next one in. The usage the physical resources are created with is deduced by the
+    /// graph like for transients, so any usage is allowed.
+    fn create_persistent_texture_handle(
+        &mut self,
+        render_pass: usize,
+        desc: &TextureDesc,
+        sync: BarrierSync,
+        access: ResourceUsageFlags,
+    ) -> ResourceMut {
+        let desc = TextureDesc {
+            usage: ResourceUsageFlags::all(),
+            ..desc.clone()
+        };
+        let import = TextureImportDesc {
+            desc: &desc,
+            before_sync: HANDOFF_SYNC,
+            before_access: HANDOFF_ACCESS,
+            before_layout: HANDOFF_LAYOUT,
+            after_sync: HANDOFF_SYNC,
+            after_access: HANDOFF_ACCESS,
+            after_layout: HANDOFF_LAYOUT,
+        };
+        self.create_imported_texture_handle(render_pass, &import, sync, access)
+    }
+
+    /// See [FrameGraphBuilder::create_persistent_texture_handle]
+    fn create_persistent_buffer_handle(
+        &mut self,
+        render_pass: usize,
+        desc: &BufferDesc,
+        sync: BarrierSync,
+        access: ResourceUsageFlags,
+    ) -> ResourceMut {
+        let desc = BufferDesc {
+            usage: ResourceUsageFlags::all(),
+            ..desc.clone()
+        };
+        let import = BufferImportDesc {
+            desc: &desc,
+            before_sync: HANDOFF_SYNC,
+            before_access: HANDOFF_ACCESS,
+            after_sync: HANDOFF_SYNC,
+            after_access: HANDOFF_ACCESS,
+        };
+        self.create_imported_buffer_handle(render_pass, &import, sync, access)
+    }
+
+    fn assert_current_not_written(&self, index: usize) {
+        let persistent = &self.persistent_resources[index];
+        assert!(
+            persistent.current.is_none(),
+            "The current version of persistent resource '{}' has already been written. Use the handle returned by the first write.",
+            unsafe { persistent.name.as_ref() }
+        );
+    }
+
+    /// Both roots are backed by the same pair of physical resources, so they must be declared with
+    /// matching descriptions.
+    fn validate_persistent_resource_descs(&self, index: usize) {
+        let persistent = &self.persistent_resources[index];
+        let (Some(previous), Some(current)) = (persistent.previous, persistent.current) else {
+            return;
+        };
+        let previous = &self.root_resources[previous.0.root_id() as usize];
+        let current = &self.root_resources[current as usize];
+        assert!(
+            is_compatible(&previous.resource_type, &current.resource_type),
+            "The previous and current versions of persistent resource '{}' were declared with different descriptions",
+            unsafe { persistent.name.as_ref() }
+        );
+    }
+}
@@ -0,0 +1,850 @@
+//
+//
+// This file is a part of Aleph
+//
+// https://github.com/nathanvoglsam/aleph
+//
+// MIT License
+//
+// Copyright (c) 2020 Aleph Engine
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use aleph_rhi_api::*;
+
+use crate::internal::{
+    IIRNode, IRNode, PassOrderBundle, RenderPass, ResourceRoot, ResourceType, ResourceVersion,
+};
+use crate::render_pass::PassArgs;
+use crate::transient_pool::TransientPoolPlan;
+
+/// The number of queue types that a graph can schedule passes onto
+pub(crate) const QUEUE_COUNT: usize = 3;
+
+/// The queue types a graph can schedule passes onto, ordered by their [queue_index]
+const QUEUES: [QueueType; QUEUE_COUNT] =
+    [QueueType::General, QueueType::Compute, QueueType::Transfer];
+
+/// Maps a queue type to its index in the per-queue arrays used by the schedule
+pub(crate) const fn queue_index(queue: QueueType) -> usize {
+    match queue {
+        QueueType::General => 0,
+        QueueType::Compute => 1,
+        QueueType::Transfer => 2,
+    }
+}
+
+/// Strips the sync flags a queue can't execute from a barrier scope.
+///
+/// A barrier recorded onto an async queue can name pipeline stages from passes that ran on another
+/// queue, which is invalid to encode. The schedule always waits on the submission that contains
+/// those passes, which already covers them, so they can be dropped from the barrier.
+pub(crate) fn queue_sync(queue: QueueType, sync: BarrierSync) -> BarrierSync {
+    let supported = match queue {
+        QueueType::General => return sync,
+        QueueType::Compute => {
+            BarrierSync::ALL
+                | BarrierSync::COMPUTE_SHADING
+                | BarrierSync::RAYTRACING
+                | BarrierSync::COPY
+                | BarrierSync::EXECUTE_INDIRECT
+                | BarrierSync::CLEAR_UNORDERED_ACCESS_VIEW
+                | BarrierSync::BUILD_RAYTRACING_ACCELERATION_STRUCTURE
+                | BarrierSync::COPY_RAYTRACING_ACCELERATION_STRUCTURE
+        }
+        QueueType::Transfer => BarrierSync::ALL | BarrierSync::COPY,
+    };
+    sync & supported
+}
+
+/// The access flag equivalent of [queue_sync]
+pub(crate) fn queue_access(queue: QueueType, access: BarrierAccess) -> BarrierAccess {
+    let supported = match queue {
+        QueueType::General => return access,
+        QueueType::Compute => {
+            BarrierAccess::CONSTANT_BUFFER_READ
+                | BarrierAccess::INDIRECT_COMMAND_READ
+                | BarrierAccess::COPY_READ
+                | BarrierAccess::COPY_WRITE
+                | BarrierAccess::RAYTRACING_ACCELERATION_STRUCTURE_READ
+                | BarrierAccess::RAYTRACING_ACCELERATION_STRUCTURE_WRITE
+                | BarrierAccess::SHADER_READ
+                | BarrierAccess::SHADER_WRITE
+                | BarrierAccess::COMMON
+        }
+        QueueType::Transfer => {
+            BarrierAccess::COPY_READ | BarrierAccess::COPY_WRITE | BarrierAccess::COMMON
+        }
+    };
+    access & supported
+}
+
+/// Applies [queue_sync] and [queue_access] to one half of a barrier. An access scope is
+/// meaningless without any sync scope so it is cleared when the sync scope becomes empty.
+pub(crate) fn queue_scope(
+    queue: QueueType,
+    sync: BarrierSync,
+    access: BarrierAccess,
+) -> (BarrierSync, BarrierAccess) {
+    let sync = queue_sync(queue, sync);
+    if sync.is_empty() {
+        (sync, BarrierAccess::NONE)
+    } else {
+        (sync, queue_access(queue, access))
+    }
+}
+
+///
+/// A single submission that a [crate::FrameGraph] makes to a device queue when executed with
+/// [crate::FrameGraph::execute_on_queues].
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QueueSubmissionInfo {
+    /// The queue the submission is made to
+    pub queue: QueueType,
+
+    /// The names of the passes recorded into the submission, in recording order
+    pub passes: Vec<String>,
+
+    /// The submissions that must finish before this submission can start executing, as indices
+    /// into [QueueScheduleReport::submissions]. Only submissions on other queues are listed as
+    /// submissions on the same queue are already ordered.
+    pub waits: Vec<usize>,
+
+    /// The number of resources whose queue ownership is acquired at the start of the submission
+    pub acquires: usize,
+
+    /// The number of resources whose queue ownership is released at the end of the submission
+    pub releases: usize,
+}
+
+///
+/// Describes how the passes of a [crate::FrameGraph] are split across the device queues.
+///
+/// Submissions are listed in the order they are submitted, which always places a submission after
+/// all the submissions it waits on.
+///
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct QueueScheduleReport {
+    pub submissions: Vec<QueueSubmissionInfo>,
+}
+
+/// Moves a resource from one queue to another. Recorded as a 'release' barrier at the end of a
+/// submission on the queue giving up the resource, and a matching 'acquire' barrier on the queue
+/// taking it.
+pub(crate) struct OwnershipTransfer {
+    /// The index of the root resource being transferred
+    pub root: u16,
+
+    /// The queue that is giving up the resource
+    pub before_queue: QueueType,
+
+    /// The queue that is taking the resource
+    pub after_queue: QueueType,
+
+    /// The layout transition performed by the transfer, or None for buffers
+    pub layout: Option<TransferLayout>,
+
+    pub before_sync: BarrierSync,
+    pub before_access: BarrierAccess,
+    pub after_sync: BarrierSync,
+    pub after_access: BarrierAccess,
+}
+
+pub(crate) struct TransferLayout {
+    pub subresource_range: TextureSubResourceSet,
+    pub before_layout: ImageLayout,
+    pub after_layout: ImageLayout,
+}
+
+/// The part of an execution bundle that is recorded into a single submission
+pub(crate) struct QueueStep {
+    /// The index of the execution bundle this step was taken from
+    pub bundle: usize,
+
+    /// Ownership transfers to acquire before the barriers, indices into
+    /// [QueueSchedule::transfers]
+    pub acquires: Vec<usize>,
+
+    /// The barrier IR nodes to record before the passes
+    pub barriers: Vec<usize>,
+
+    /// The render pass IR nodes to record
+    pub passes: Vec<usize>,
+}
+
+/// A single command list that is recorded and submitted to a queue
+pub(crate) struct QueueBatch {
+    /// The queue the batch is submitted to
+    pub queue: QueueType,
+
+    /// The index of the batch among the batches submitted to the same queue. The batch signals the
+    /// queue's fence with this value (plus one) offset by the queue's fence value at the start of
+    /// the graph execution.
+    pub queue_batch: usize,
+
+    /// For each queue, the batch (as an index into [QueueSchedule::batches]) that must have
+    /// finished before this batch can execute.
+    pub waits: [Option<usize>; QUEUE_COUNT],
+
+    pub steps: Vec<QueueStep>,
+
+    /// Ownership transfers to release at the end of the batch, indices into
+    /// [QueueSchedule::transfers]
+    pub releases: Vec<usize>,
+
+    /// Whether more steps can be appended to the batch. Sealed batches can still be given releases
+    /// as those are recorded at the very end of the batch.
+    sealed: bool,
+}
+
+impl QueueBatch {
+    fn is_empty(&self) -> bool {
+        self.steps.is_empty() && self.releases.is_empty()
+    }
+}
+
+/// The partitioning of a graph's pass order into per-queue submissions, computed once the pass
+/// order of the graph is known.
+#[derive(Default)]
+pub(crate) struct QueueSchedule {
+    /// The batches to record, in the order they must be submitted. The last batch is always on
+    /// the general queue and waits on the final batch of every other queue.
+    pub batches: Vec<QueueBatch>,
+
+    pub transfers: Vec<OwnershipTransfer>,
+
+    /// The number of batches submitted to each queue per execution
+    pub batch_counts: [usize; QUEUE_COUNT],
+
+    pub report: QueueScheduleReport,
+}
+
+impl QueueSchedule {
+    pub fn new<A: PassArgs>(
+        render_passes: &[RenderPass<A>],
+        root_resources: &[ResourceRoot],
+        resource_versions: &[ResourceVersion],
+        execution_bundles: &[PassOrderBundle],
+        ir_nodes: &[IRNode],
+        transient_plan: &TransientPoolPlan,
+    ) -> Self {
+        let mut builder = ScheduleBuilder::new(
+            render_passes,
+            root_resources,
+            resource_versions,
+            execution_bundles,
+            ir_nodes,
+            transient_plan,
+        );
+        for (i, bundle) in execution_bundles.iter().enumerate() {
+            builder.schedule_bundle(i, bundle);
+        }
+        builder.finish();
+
+        let mut schedule = Self {
+            batches: builder.batches,
+            transfers: builder.transfers,
+            batch_counts: builder.batch_counts,
+            report: Default::default(),
+        };
+        schedule.report = schedule.make_report(render_passes, ir_nodes);
+        schedule
+    }
+
+    fn make_report<A: PassArgs>(
+        &self,
+        render_passes: &[RenderPass<A>],
+        ir_nodes: &[IRNode],
+    ) -> QueueScheduleReport {
+        let submissions = self
+            .batches
+            .iter()
+            .map(|batch| {
+                let passes = batch
+                    .steps
+                    .iter()
+                    .flat_map(|v| v.passes.iter())
+                    .map(|&v| {
+                        let pass = &render_passes[ir_nodes[v].render_pass()];
+                        unsafe { pass.name.as_ref() }.to_str().to_string()
+                    })
+                    .collect();
+                QueueSubmissionInfo {
+                    queue: batch.queue,
+                    passes,
+                    waits: batch.waits.iter().flatten().copied().collect(),
+                    acquires: batch.steps.iter().map(|v| v.acquires.len()).sum(),
+                    releases: batch.releases.len(),
+                }
+            })
+            .collect();
+        QueueScheduleReport { submissions }
+    }
+}
+
+/// How a pass accesses a resource, used for ownership transfers that aren't already expressed by a
+/// barrier in the graph.
+struct PassAccess {
+    root: u16,
+    sync: BarrierSync,
+    access: BarrierAccess,
+}
+
+/// The queue that currently owns a resource
+#[derive(Copy, Clone)]
+struct Owner {
+    queue: QueueType,
+
+    /// The batch that last accessed the resource, or None when the resource is still owned by
+    /// whoever used it before the graph executed.
+    batch: Option<usize>,
+}
+
+///
+/// Walks the final pass order and assigns each pass and barrier to a batch on the queue the pass
+/// prefers.
+///
+/// A pass that depends on a pass from another queue must wait on the batch that contains it, and
+/// waits can only happen at the start of a batch. To let work overlap as much as possible a batch
+/// is closed right after any pass that another queue depends on, so the other queue only waits on
+/// the work it actually needs.
+///
+/// Every resource is owned by a single queue at a time. Using a resource on another queue
+/// transfers it, releasing it at the end of the batch that last used it and acquiring it on the
+/// new queue. Backends that don't need ownership transfers just see these as regular barriers.
+///
+struct ScheduleBuilder<'a> {
+    root_resources: &'a [ResourceRoot],
+    ir_nodes: &'a [IRNode],
+
+    /// The queue each render pass will execute on
+    pass_queues: Vec<QueueType>,
+
+    /// The position of each render pass in the pass order
+    pass_order: Vec<usize>,
+
+    /// The resources each render pass accesses
+    pass_accesses: Vec<Vec<PassAccess>>,
+
+    /// Flags the render passes that a pass on another queue depends on
+    signal_after: Vec<bool>,
+
+    /// The batch each render pass was assigned to
+    pass_batch: Vec<usize>,
+
+    /// The batch each barrier IR node was assigned to
+    barrier_batch: Vec<usize>,
+
+    /// The queue that currently owns each root resource
+    owners: Vec<Option<Owner>>,
+
+    /// The layout each texture is in at the current point of the walk
+    layouts: Vec<ImageLayout>,
+
+    /// For each transient resource, the transient that used the same physical resource before it
+    pool_previous: Vec<Option<u16>>,
+
+    /// The batch of each queue that is still accepting new steps
+    open: [Option<usize>; QUEUE_COUNT],
+
+    batches: Vec<QueueBatch>,
+    transfers: Vec<OwnershipTransfer>,
+    batch_counts: [usize; QUEUE_COUNT],
+}
+
+impl<'a> ScheduleBuilder<'a> {
+    fn new<A: PassArgs>(
+        render_passes: &[RenderPass<A>],
+        root_resources: &'a [ResourceRoot],
+        resource_versions: &[ResourceVersion],
+        execution_bundles: &[PassOrderBundle],
+        ir_nodes: &'a [IRNode],
+        transient_plan: &TransientPoolPlan,
+    ) -> Self {
+        let num_passes = render_passes.len();
+        let pass_queues: Vec<QueueType> = render_passes.iter().map(|v| v.queue).collect();
+
+        let mut pass_order = vec![usize::MAX; num_passes];
+        let mut position = 0;
+        for bundle in execution_bundles.iter() {
+            let passes = unsafe { bundle.passes.as_ref() };
+            for &pass in passes {
+                pass_order[ir_nodes[pass].render_pass()] = position;
+                position += 1;
+            }
+        }
+
+        let mut pass_accesses: Vec<Vec<PassAccess>> = (0..num_passes).map(|_| Vec::new()).collect();
+        for version in resource_versions.iter() {
+            let root = &root_resources[version.root_resource as usize];
+            let format = match &root.resource_type {
+                ResourceType::Buffer(_) => Format::default(),
+                ResourceType::Texture(v) => v.desc.format,
+                ResourceType::Execution(_) => continue,
+            };
+            pass_accesses[version.creator_pass].push(PassAccess {
+                root: version.root_resource,
+                sync: version.creator_sync,
+                access: version.creator_access.barrier_access_for_write(format),
+            });
+            for read in version.reads_iter() {
+                pass_accesses[read.render_pass].push(PassAccess {
+                    root: version.root_resource,
+                    sync: read.sync,
+                    access: read.access.barrier_access_for_read(format),
+                });
+            }
+        }
+
+        let mut signal_after = vec![false; num_passes];
+        for node in ir_nodes.iter() {
+            let IRNode::RenderPass(pass) = node else {
+                continue;
+            };
+            let queue = pass_queues[pass.render_pass];
+            let barriers = unsafe { pass.next.as_ref() };
+            for &barrier in barriers {
+                let consumers = unsafe { ir_nodes[barrier].next().as_ref() };
+                signal_after[pass.render_pass] |= consumers
+                    .iter()
+                    .filter(|&&v| ir_nodes[v].is_render_pass())
+                    .any(|&v| pass_queues[ir_nodes[v].render_pass()] != queue);
+            }
+        }
+
+        // Imported resources start out owned by the general queue, in whatever layout they were
+        // imported with. Transients aren't owned by anyone until their first use.
+        let mut owners = vec![None; root_resources.len()];
+        let mut layouts = vec![ImageLayout::Undefined; root_resources.len()];
+        for (i, root) in root_resources.iter().enumerate() {
+            let import = match &root.resource_type {
+                ResourceType::Buffer(v) => v.import.as_ref(),
+                ResourceType::Texture(v) => v.import.as_ref(),
+                ResourceType::Execution(_) => None,
+            };
+            if let Some(import) = import {
+                owners[i] = Some(Owner {
+                    queue: QueueType::General,
+                    batch: None,
+                });
+                layouts[i] = import.before_layout;
+            }
+        }
+
+        let mut pool_previous = vec![None; root_resources.len()];
+        for slot in transient_plan.slots.iter() {
+            for pair in slot.occupants.windows(2) {
+                let previous = transient_plan.lifetimes[pair[0]].root;
+                let next = transient_plan.lifetimes[pair[1]].root;
+                pool_previous[next as usize] = Some(previous);
+            }
+        }
+
+        Self {
+            root_resources,
+            ir_nodes,
+            pass_queues,
+            pass_order,
+            pass_accesses,
+            signal_after,
+            pass_batch: vec![usize::MAX; num_passes],
+            barrier_batch: vec![usize::MAX; ir_nodes.len()],
+            owners,
+            layouts,
+            pool_previous,
+            open: [None; QUEUE_COUNT],
+            batches: Vec::new(),
+            transfers: Vec::new(),
+            batch_counts: [0; QUEUE_COUNT],
+        }
+    }
+
+    fn schedule_bundle(&mut self, bundle_index: usize, bundle: &PassOrderBundle) {
+        let barriers = unsafe { bundle.barriers.as_ref() };
+        let passes = unsafe { bundle.passes.as_ref() };
+
+        // Every barrier is recorded alongside the first pass in the bundle that consumes it. The
+        // barriers that aren't consumed by a pass in the bundle, like the import and export
+        // barriers, are recorded onto the queue of the first pass to consume them or the general
+        // queue if nothing does.
+        let mut pass_barriers: Vec<Vec<usize>> = passes.iter().map(|_| Vec::new()).collect();
+        let mut loose_barriers: [Vec<usize>; QUEUE_COUNT] = Default::default();
+        for &barrier in barriers {
+            let consumers = unsafe { self.ir_nodes[barrier].next().as_ref() };
+            match passes.iter().position(|v| consumers.contains(v)) {
+                Some(i) => pass_barriers[i].push(barrier),
+                None => {
+                    let queue = consumers
+                        .iter()
+                        .filter(|&&v| self.ir_nodes[v].is_render_pass())
+                        .map(|&v| self.ir_nodes[v].render_pass())
+                        .min_by_key(|&v| self.pass_order[v])
+                        .map_or(QueueType::General, |v| self.pass_queues[v]);
+                    loose_barriers[queue_index(queue)].push(barrier);
+                }
+            }
+        }
+
+        for queue in QUEUES {
+            let barriers = std::mem::take(&mut loose_barriers[queue_index(queue)]);
+            if !barriers.is_empty() {
+                self.schedule_step(bundle_index, queue, &barriers, None);
+            }
+        }
+        for (&pass, barriers) in passes.iter().zip(pass_barriers.iter()) {
+            let queue = self.pass_queues[self.ir_nodes[pass].render_pass()];
+            self.schedule_step(bundle_index, queue, barriers, Some(pass));
+        }
+    }
+
+    fn schedule_step(
+        &mut self,
+        bundle_index: usize,
+        queue: QueueType,
+        barriers: &[usize],
+        pass: Option<usize>,
+    ) {
+        let mut waits = [None; QUEUE_COUNT];
+        let mut acquires = Vec::new();
+        let mut kept_barriers = Vec::new();
+        let mut touched = Vec::new();
+
+        for &barrier in barriers {
+            let node = &self.ir_nodes[barrier];
+            let prevs = unsafe { node.prev().as_ref() };
+            for &prev in prevs {
+                let prev = &self.ir_nodes[prev];
+                if prev.is_render_pass() && self.pass_queues[prev.render_pass()] != queue {
+                    self.wait_on(&mut waits, self.pass_batch[prev.render_pass()]);
+                }
+            }
+
+            let root = node.resource_id().root;
+            if matches!(
+                self.root_resources[root as usize].resource_type,
+                ResourceType::Execution(_)
+            ) {
+                kept_barriers.push(barrier);
+                continue;
+            }
+
+            self.wait_on_pool_previous(&mut waits, queue, root);
+            match self.foreign_owner(queue, root) {
+                Some(owner) => {
+                    let transfer = self.transfer_for_barrier(node, owner.queue, queue);
+                    acquires.push(self.add_transfer(&mut waits, owner, transfer));
+                }
+                None => kept_barriers.push(barrier),
+            }
+            if let IRNode::LayoutChange(v) = node {
+                self.layouts[root as usize] = v.after_layout;
+            }
+            touched.push(root);
+        }
+
+        if let Some(pass) = pass {
+            let node = &self.ir_nodes[pass];
+            let render_pass = node.render_pass();
+
+            // The barriers in front of the pass may have been recorded onto another queue, if an
+            // earlier pass from that queue consumes them too.
+            let prevs = unsafe { node.prev().as_ref() };
+            for &prev in prevs {
+                let batch = self.barrier_batch[prev];
+                if batch != usize::MAX && self.batches[batch].queue != queue {
+                    self.wait_on(&mut waits, batch);
+                }
+            }
+
+            for i in 0..self.pass_accesses[render_pass].len() {
+                let access = &self.pass_accesses[render_pass][i];
+                let (root, sync, access) = (access.root, access.sync, access.access);
+                if touched.contains(&root) {
+                    continue;
+                }
+
+                self.wait_on_pool_previous(&mut waits, queue, root);
+                if let Some(owner) = self.foreign_owner(queue, root) {
+                    // A resource shared between queues without a barrier in between, like a
+                    // texture read by passes on two queues. Transfer it in its current state.
+                    let transfer = OwnershipTransfer {
+                        root,
+                        before_queue: owner.queue,
+                        after_queue: queue,
+                        layout: self.current_layout(root),
+                        before_sync: BarrierSync::ALL,
+                        before_access: BarrierAccess::NONE,
+                        after_sync: sync,
+                        after_access: access,
+                    };
+                    acquires.push(self.add_transfer(&mut waits, owner, transfer));
+                }
+                touched.push(root);
+            }
+        }
+
+        let batch_index = self.batch_for(queue, &waits);
+        let batch = &mut self.batches[batch_index];
+        let step = match batch.steps.last_mut() {
+            Some(step) if step.bundle == bundle_index => step,
+            _ => {
+                batch.steps.push(QueueStep {
+                    bundle: bundle_index,
+                    acquires: Vec::new(),
+                    barriers: Vec::new(),
+                    passes: Vec::new(),
+                });
+                batch.steps.last_mut().unwrap()
+            }
+        };
+        step.acquires.extend(acquires);
+        step.barriers.extend(kept_barriers);
+        step.passes.extend(pass);
+
+        for &barrier in barriers {
+            self.barrier_batch[barrier] = batch_index;
+        }
+        for root in touched {
+            self.owners[root as usize] = Some(Owner {
+                queue,
+                batch: Some(batch_index),
+            });
+        }
+        if let Some(pass) = pass {
+            let render_pass = self.ir_nodes[pass].render_pass();
+            self.pass_batch[render_pass] = batch_index;
+            if self.signal_after[render_pass] {
+                self.batches[batch_index].sealed = true;
+            }
+        }
+    }
+
+    /// Hands every imported resource back to the general queue and joins all the other queues
+    /// onto a final general queue batch.
+    fn finish(&mut self) {
+        let mut waits = [None; QUEUE_COUNT];
+        let mut acquires = Vec::new();
+        for (i, root) in self.root_resources.iter().enumerate() {
+            let import = match &root.resource_type {
+                ResourceType::Buffer(v) => v.import.as_ref(),
+                ResourceType::Texture(v) => v.import.as_ref(),
+                ResourceType::Execution(_) => None,
+            };
+            let Some(import) = import else {
+                continue;
+            };
+            let root = i as u16;
+            if let Some(owner) = self.foreign_owner(QueueType::General, root) {
+                let transfer = OwnershipTransfer {
+                    root,
+                    before_queue: owner.queue,
+                    after_queue: QueueType::General,
+                    layout: self.current_layout(root),
+                    before_sync: BarrierSync::ALL,
+                    before_access: BarrierAccess::NONE,
+                    after_sync: import.after_sync,
+                    after_access: import.after_access,
+                };
+                acquires.push(self.add_transfer(&mut waits, owner, transfer));
+            }
+        }
+
+        for queue in [QueueType::Compute, QueueType::Transfer] {
+            let last = self.batches.iter().rposition(|v| v.queue == queue);
+            if let Some(last) = last {
+                self.wait_on(&mut waits, last);
+            }
+        }
+
+        let batch_index = self.batch_for(QueueType::General, &waits);
+        if !acquires.is_empty() {
+            self.batches[batch_index].steps.push(QueueStep {
+                bundle: usize::MAX,
+                acquires,
+                barriers: Vec::new(),
+                passes: Vec::new(),
+            });
+        }
+        debug_assert_eq!(batch_index, self.batches.len() - 1);
+
+        for batch in self.batches.iter_mut() {
+            batch.sealed = true;
+        }
+    }
+
+    /// Returns the owner of a resource if it's owned by a queue other than the given one
+    fn foreign_owner(&self, queue: QueueType, root: u16) -> Option<Owner> {
+        self.owners[root as usize].filter(|v| v.queue != queue)
+    }
+
+    /// The layout of a texture at the current point of the walk, used for transfers that don't
+    /// change the layout. None for buffers.
+    fn current_layout(&self, root: u16) -> Option<TransferLayout> {
+        match &self.root_resources[root as usize].resource_type {
+            ResourceType::Texture(v) => {
+                let layout = self.layouts[root as usize];
+                Some(TransferLayout {
+                    subresource_range: TextureSubResourceSet {
+                        aspect: v.desc.format.aspect_mask(),
+                        base_mip_level: 0,
+                        num_mip_levels: v.desc.mip_levels,
+                        base_array_slice: 0,
+                        num_array_slices: v.desc.array_size,
+                    },
+                    before_layout: layout,
+                    after_layout: layout,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Turns a barrier from the graph into an ownership transfer that performs the same transition
+    fn transfer_for_barrier(
+        &self,
+        node: &IRNode,
+        before_queue: QueueType,
+        after_queue: QueueType,
+    ) -> OwnershipTransfer {
+        let root = node.resource_id().root;
+        match node {
+            IRNode::RenderPass(_) => unreachable!(),
+            IRNode::Barrier(v) => OwnershipTransfer {
+                root,
+                before_queue,
+                after_queue,
+                layout: self.current_layout(root),
+                before_sync: v.before_sync,
+                before_access: v.before_access,
+                after_sync: v.after_sync,
+                after_access: v.after_access,
+            },
+            IRNode::LayoutChange(v) => OwnershipTransfer {
+                root,
+                before_queue,
+                after_queue,
+                layout: Some(TransferLayout {
+                    subresource_range: v.subresource_range.clone(),
+                    before_layout: v.before_layout,
+                    after_layout: v.after_layout,
+                }),
+                before_sync: v.before_sync,
+                before_access: v.before_access,
+                after_sync: v.after_sync,
+                after_access: v.after_access,
+            },
+        }
+    }
+
+    /// Records the release half of a transfer onto the batch that last used the resource and
+    /// makes the current step wait on it.
+    fn add_transfer(
+        &mut self,
+        waits: &mut [Option<usize>; QUEUE_COUNT],
+        owner: Owner,
+        transfer: OwnershipTransfer,
+    ) -> usize {
+        // Resources that haven't been used by the graph yet are released from the start of the
+        // owning queue's next batch.
+        let batch = match owner.batch {
+            Some(v) => v,
+            None => self.batch_for(owner.queue, &[None; QUEUE_COUNT]),
+        };
+        let transfer_index = self.transfers.len();
+        self.transfers.push(transfer);
+        self.batches[batch].releases.push(transfer_index);
+        self.wait_on(waits, batch);
+        transfer_index
+    }
+
+    /// Makes the current step wait on the given batch, which stops the batch from taking any more
+    /// steps as it has to signal once everything before the wait is done.
+    fn wait_on(&mut self, waits: &mut [Option<usize>; QUEUE_COUNT], batch: usize) {
+        debug_assert_ne!(batch, usize::MAX);
+        self.batches[batch].sealed = true;
+        let slot = &mut waits[queue_index(self.batches[batch].queue)];
+        *slot = Some(slot.map_or(batch, |v| v.max(batch)));
+    }
+
+    /// A transient that takes over the physical resource of another transient must wait for the previous
+    /// occupant to finish being used, even if it was used on another queue.
+    fn wait_on_pool_previous(
+        &mut self,
+        waits: &mut [Option<usize>; QUEUE_COUNT],
+        queue: QueueType,
+        root: u16,
+    ) {
+        if self.owners[root as usize].is_some() {
+            return;
+        }
+        let Some(previous) = self.pool_previous[root as usize] else {
+            return;
+        };
+        if let Some(Owner {
+            queue: previous_queue,
+            batch: Some(batch),
+        }) = self.owners[previous as usize]
+            && previous_queue != queue
+        {
+            self.wait_on(waits, batch);
+        }
+    }
+
+    /// Returns the batch a step on the given queue with the given waits should be recorded into,
+    /// starting a new batch if the queue's open batch can't take it.
+    fn batch_for(&mut self, queue: QueueType, waits: &[Option<usize>; QUEUE_COUNT]) -> usize {
+        let q = queue_index(queue);
+        if let Some(open) = self.open[q] {
+            let batch = &mut self.batches[open];
+            let needs_wait =
+                waits
+                    .iter()
+                    .zip(batch.waits.iter())
+                    .any(|(&new, &old)| match (new, old) {
+                        (Some(new), Some(old)) => new > old,
+                        (Some(_), None) => true,
+                        _ => false,
+                    });
+            if batch.is_empty() {
+                for (old, &new) in batch.waits.iter_mut().zip(waits.iter()) {
+                    *old = (*old).max(new);
+                }
+                return open;
+            }
+            if !batch.sealed && !needs_wait {
+                return open;
+            }
+        }
+
+        let index = self.batches.len();
+        self.batches.push(QueueBatch {
+            queue,
+            queue_batch: self.batch_counts[q],
+            waits: *waits,
+            steps: Vec::new(),
+            releases: Vec::new(),
+            sealed: false,
+        });
+        self.batch_counts[q] += 1;
+        self.open[q] = Some(index);
+        index
+    }
+}
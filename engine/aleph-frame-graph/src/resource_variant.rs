@@ -29,6 +29,7 @@
 
 use aleph_rhi_api::*;
 
+#[derive(Clone)]
 pub enum ResourceVariant {
     Buffer(BufferHandle),
     Texture(TextureHandle),
@@ -32,12 +32,14 @@ use std::sync::Arc;
 use aleph_nstr::nstr;
 use aleph_pin_board::PinBoard;
 use aleph_rhi_api::*;
-use aleph_rhi_null::NullContext;
+use aleph_rhi_null::{NullContext, NullTexture};
 
 use crate::frame_graph_builder::GraphBuildError;
+use crate::internal::{IRBarrierType, IRNode};
 use crate::render_pass::PassArgs;
 use crate::{
-    BufferImportDesc, FrameGraph, ImportBundle, ResourceMut, ResourceRef, TextureImportDesc,
+    BufferImportDesc, FrameGraph, FrameGraphResources, GraphChannel, ImportBundle, ResourceMut,
+    ResourceRef, ResourceVariant, TextureImportDesc,
 };
 
 fn make_null_device() -> Arc<dyn IDevice> {
@@ -125,6 +127,9 @@ pub fn test_builder() {
     });
 
     builder.add_pass(nstr!("test-pass-2"), |resources| {
+        // Nothing outside the graph reads our buffer so we need this to stop the passes being culled
+        resources.declare_side_effects();
+
         let payload = TestPassData2 {
             value: -432,
             resource: resources.read_buffer_with_sync(
@@ -1099,3 +1104,914 @@ pub fn test_usage_illegal_dependency_2() {
         Err(GraphBuildError::CyclicDependencyDetected)
     ));
 }
+
+#[test]
+pub fn test_transient_pooling() {
+    let pin_board = PinBoard::new();
+    let device = make_null_device();
+    let mut command_list = device
+        .create_command_list(&CommandListDesc {
+            queue_type: QueueType::General,
+            name: None,
+        })
+        .unwrap();
+    let mut encoder = command_list.begin_general().unwrap();
+
+    let texture_desc = |name| TextureDesc {
+        width: 1920,
+        height: 1080,
+        depth: 1,
+        format: Format::Rgba16Float,
+        dimension: TextureDimension::Texture2D,
+        clear_value: None,
+        array_size: 1,
+        mip_levels: 1,
+        sample_count: 1,
+        sample_quality: 0,
+        usage: ResourceUsageFlags::NONE,
+        name: Some(name),
+    };
+
+    let mock_texture = device
+        .create_texture(&TextureDesc {
+            usage: ResourceUsageFlags::UNORDERED_ACCESS,
+            ..texture_desc("imported-mock-texture")
+        })
+        .unwrap();
+    let mock_texture_desc = device.get_texture_desc(&mock_texture);
+
+    let mut builder = FrameGraph::<()>::builder();
+
+    // A chain of passes that each read the previous pass's output and write a new transient, like
+    // a post processing chain. 'post-0' and 'post-2' are never live at the same time, so they can
+    // share memory, while 'post-1' overlaps with both.
+    struct Chain(ResourceMut);
+    builder.add_pass(nstr!("post-pass-0"), |resources| {
+        let create = resources.create_texture_with_sync(
+            &texture_desc("post-0"),
+            BarrierSync::RENDER_TARGET,
+            ResourceUsageFlags::RENDER_TARGET,
+        );
+        pin_board.publish(Chain(create));
+        move |_encoder, _graph, _resources, _args| {}
+    });
+    for (pass_name, output_name) in [
+        (nstr!("post-pass-1"), "post-1"),
+        (nstr!("post-pass-2"), "post-2"),
+    ] {
+        builder.add_pass(pass_name, |resources| {
+            let input = pin_board.get::<Chain>().unwrap().0;
+            resources.read_texture_with_sync(
+                input,
+                BarrierSync::PIXEL_SHADING,
+                ResourceUsageFlags::SHADER_RESOURCE,
+            );
+            let create = resources.create_texture_with_sync(
+                &texture_desc(output_name),
+                BarrierSync::RENDER_TARGET,
+                ResourceUsageFlags::RENDER_TARGET,
+            );
+            pin_board.publish(Chain(create));
+            move |_encoder, _graph, _resources, _args| {}
+        });
+    }
+
+    struct Output(ResourceMut);
+    builder.add_pass(nstr!("post-resolve"), |resources| {
+        let input = pin_board.get::<Chain>().unwrap().0;
+        resources.read_texture_with_sync(
+            input,
+            BarrierSync::COMPUTE_SHADING,
+            ResourceUsageFlags::SHADER_RESOURCE,
+        );
+        let import = resources.import_texture(
+            &TextureImportDesc {
+                desc: &mock_texture_desc,
+                before_sync: BarrierSync::ALL,
+                before_access: BarrierAccess::NONE,
+                before_layout: ImageLayout::Undefined,
+                after_sync: BarrierSync::ALL,
+                after_access: BarrierAccess::SHADER_READ,
+                after_layout: ImageLayout::ShaderReadOnly,
+            },
+            ResourceUsageFlags::UNORDERED_ACCESS,
+        );
+        pin_board.publish(Output(import));
+        move |_encoder, _graph, _resources, _args| {}
+    });
+
+    let mut graph = builder.build(device.as_ref());
+
+    // 1920 * 1080 * 8 bytes, rounded up to 64KiB
+    let texture_size = (1920 * 1080 * 8u64).next_multiple_of(64 * 1024);
+    let report = graph.transient_memory_report().clone();
+    assert_eq!(report.transient_count, 3);
+    assert_eq!(report.physical_count, 2);
+    assert_eq!(report.unpooled_bytes, texture_size * 3);
+    assert_eq!(report.pooled_bytes, texture_size * 2);
+    assert_eq!(report.lifetime_peak_bytes, texture_size * 2);
+
+    // The initialization barrier for the resource that takes over 'post-0's memory must wait for
+    // the reads of 'post-0' to finish.
+    let post_2_root = graph.transient_plan.lifetimes[2].root;
+    let init_barrier = graph
+        .ir_nodes
+        .iter()
+        .find_map(|v| match v {
+            IRNode::LayoutChange(v)
+                if v.barrier_type == IRBarrierType::Initialization
+                    && v.resource_id.root == post_2_root =>
+            {
+                Some(v)
+            }
+            _ => None,
+        })
+        .unwrap();
+    assert!(
+        init_barrier
+            .before_sync
+            .contains(BarrierSync::PIXEL_SHADING)
+    );
+    assert!(
+        init_barrier
+            .before_sync
+            .contains(BarrierSync::RENDER_TARGET)
+    );
+    assert_eq!(init_barrier.before_layout, ImageLayout::Undefined);
+
+    unsafe {
+        graph.allocate_transients(1);
+    }
+
+    // 'post-0' and 'post-2' are backed by the same texture, which must support the usage of both
+    let transients = graph.transient_bundles.as_ref().unwrap();
+    let texture_id = |i: usize| {
+        let root = graph.transient_plan.lifetimes[i].root;
+        match transients.get_resource(root).unwrap() {
+            ResourceVariant::Texture(v) => device.get_texture_id(v),
+            ResourceVariant::Buffer(_) => unreachable!(),
+        }
+    };
+    assert_eq!(texture_id(0), texture_id(2));
+    assert_ne!(texture_id(0), texture_id(1));
+
+    let import = pin_board.get::<Output>().unwrap().0;
+    let mut import_bundle = ImportBundle::default();
+    import_bundle.add_resource(import, &mock_texture);
+    unsafe {
+        graph.execute(0, &import_bundle, &mut encoder, &());
+    }
+}
+
+#[test]
+pub fn test_transient_pooling_format_mismatch() {
+    let pin_board = PinBoard::new();
+    let device = make_null_device();
+
+    let texture_desc = |name, format| TextureDesc {
+        width: 1920,
+        height: 1080,
+        depth: 1,
+        format,
+        dimension: TextureDimension::Texture2D,
+        clear_value: None,
+        array_size: 1,
+        mip_levels: 1,
+        sample_count: 1,
+        sample_quality: 0,
+        usage: ResourceUsageFlags::NONE,
+        name: Some(name),
+    };
+
+    let mock_texture = device
+        .create_texture(&TextureDesc {
+            usage: ResourceUsageFlags::UNORDERED_ACCESS,
+            ..texture_desc("imported-mock-texture", Format::Rgba16Float)
+        })
+        .unwrap();
+    let mock_texture_desc = device.get_texture_desc(&mock_texture);
+
+    let mut builder = FrameGraph::<()>::builder();
+
+    // The same chain as 'test_transient_pooling', but 'post-2' has a different format of the same
+    // size. The lifetimes of 'post-0' and 'post-2' still don't overlap, but only resources with
+    // matching descriptions are pooled so they can't share a physical resource.
+    struct Chain(ResourceMut);
+    for (pass_name, output_name, format) in [
+        (nstr!("post-pass-0"), "post-0", Format::Rgba16Float),
+        (nstr!("post-pass-1"), "post-1", Format::Rgba16Float),
+        (nstr!("post-pass-2"), "post-2", Format::Rg32Float),
+    ] {
+        builder.add_pass(pass_name, |resources| {
+            if let Some(input) = pin_board.get::<Chain>() {
+                resources.read_texture_with_sync(
+                    input.0,
+                    BarrierSync::PIXEL_SHADING,
+                    ResourceUsageFlags::SHADER_RESOURCE,
+                );
+            }
+            let create = resources.create_texture_with_sync(
+                &texture_desc(output_name, format),
+                BarrierSync::RENDER_TARGET,
+                ResourceUsageFlags::RENDER_TARGET,
+            );
+            pin_board.publish(Chain(create));
+            move |_encoder, _graph, _resources, _args| {}
+        });
+    }
+
+    builder.add_pass(nstr!("post-resolve"), |resources| {
+        let input = pin_board.get::<Chain>().unwrap().0;
+        resources.read_texture_with_sync(
+            input,
+            BarrierSync::COMPUTE_SHADING,
+            ResourceUsageFlags::SHADER_RESOURCE,
+        );
+        resources.import_texture(
+            &TextureImportDesc {
+                desc: &mock_texture_desc,
+                before_sync: BarrierSync::ALL,
+                before_access: BarrierAccess::NONE,
+                before_layout: ImageLayout::Undefined,
+                after_sync: BarrierSync::ALL,
+                after_access: BarrierAccess::SHADER_READ,
+                after_layout: ImageLayout::ShaderReadOnly,
+            },
+            ResourceUsageFlags::UNORDERED_ACCESS,
+        );
+        move |_encoder, _graph, _resources, _args| {}
+    });
+
+    let mut graph = builder.build(device.as_ref());
+
+    // Both formats are 8 bytes per pixel, so every texture has the same estimated size
+    let texture_size = (1920 * 1080 * 8u64).next_multiple_of(64 * 1024);
+    let report = graph.transient_memory_report().clone();
+    assert_eq!(report.transient_count, 3);
+    assert_eq!(report.physical_count, 3);
+    assert_eq!(report.unpooled_bytes, texture_size * 3);
+    assert_eq!(report.pooled_bytes, texture_size * 3);
+    assert_eq!(report.lifetime_peak_bytes, texture_size * 2);
+
+    unsafe {
+        graph.allocate_transients(1);
+    }
+
+    let transients = graph.transient_bundles.as_ref().unwrap();
+    let texture_id = |i: usize| {
+        let root = graph.transient_plan.lifetimes[i].root;
+        match transients.get_resource(root).unwrap() {
+            ResourceVariant::Texture(v) => device.get_texture_id(v),
+            ResourceVariant::Buffer(_) => unreachable!(),
+        }
+    };
+    assert_ne!(texture_id(0), texture_id(1));
+    assert_ne!(texture_id(0), texture_id(2));
+    assert_ne!(texture_id(1), texture_id(2));
+}
+
+#[test]
+pub fn test_multi_queue_schedule() {
+    let pin_board = PinBoard::new();
+    let device = make_null_device();
+
+    let texture_desc = |name| TextureDesc {
+        width: 1920,
+        height: 1080,
+        depth: 1,
+        format: Format::Rgba16Float,
+        dimension: TextureDimension::Texture2D,
+        clear_value: None,
+        array_size: 1,
+        mip_levels: 1,
+        sample_count: 1,
+        sample_quality: 0,
+        usage: ResourceUsageFlags::NONE,
+        name: Some(name),
+    };
+
+    let mock_texture = device
+        .create_texture(&TextureDesc {
+            usage: ResourceUsageFlags::RENDER_TARGET,
+            ..texture_desc("imported-mock-texture")
+        })
+        .unwrap();
+    let mock_texture_desc = device.get_texture_desc(&mock_texture);
+
+    let mut builder = FrameGraph::<()>::builder();
+
+    // SSAO and light culling only need the depth buffer, so they can run on the compute queue
+    // while the shadow maps render on the general queue.
+    struct Depth(ResourceMut);
+    struct Shadows(ResourceMut);
+    struct Ao(ResourceMut);
+    struct LightList(ResourceMut);
+    struct Output(ResourceMut);
+    builder.add_pass(nstr!("gbuffer"), |resources| {
+        let depth = resources.create_texture_with_sync(
+            &texture_desc("depth"),
+            BarrierSync::RENDER_TARGET,
+            ResourceUsageFlags::RENDER_TARGET,
+        );
+        pin_board.publish(Depth(depth));
+        move |_encoder, _graph, _resources, _args| {}
+    });
+    builder.add_pass(nstr!("shadows"), |resources| {
+        let shadows = resources.create_texture_with_sync(
+            &texture_desc("shadows"),
+            BarrierSync::RENDER_TARGET,
+            ResourceUsageFlags::RENDER_TARGET,
+        );
+        pin_board.publish(Shadows(shadows));
+        move |_encoder, _graph, _resources, _args| {}
+    });
+    builder.add_pass(nstr!("ssao"), |resources| {
+        resources.set_preferred_queue(QueueType::Compute);
+        let depth = pin_board.get::<Depth>().unwrap().0;
+        resources.read_texture_with_sync(
+            depth,
+            BarrierSync::COMPUTE_SHADING,
+            ResourceUsageFlags::SHADER_RESOURCE,
+        );
+        let ao = resources.create_texture_with_sync(
+            &texture_desc("ao"),
+            BarrierSync::COMPUTE_SHADING,
+            ResourceUsageFlags::UNORDERED_ACCESS,
+        );
+        pin_board.publish(Ao(ao));
+        move |_encoder, _graph, _resources, _args| {}
+    });
+    builder.add_pass(nstr!("light-cull"), |resources| {
+        resources.set_preferred_queue(QueueType::Compute);
+        let depth = pin_board.get::<Depth>().unwrap().0;
+        resources.read_texture_with_sync(
+            depth,
+            BarrierSync::COMPUTE_SHADING,
+            ResourceUsageFlags::SHADER_RESOURCE,
+        );
+        let light_list = resources.create_buffer_with_sync(
+            &BufferDesc {
+                size: 4096,
+                name: Some("light-list"),
+                ..Default::default()
+            },
+            BarrierSync::COMPUTE_SHADING,
+            ResourceUsageFlags::UNORDERED_ACCESS,
+        );
+        pin_board.publish(LightList(light_list));
+        move |_encoder, _graph, _resources, _args| {}
+    });
+    builder.add_pass(nstr!("lighting"), |resources| {
+        let depth = pin_board.get::<Depth>().unwrap().0;
+        let shadows = pin_board.get::<Shadows>().unwrap().0;
+        let ao = pin_board.get::<Ao>().unwrap().0;
+        let light_list = pin_board.get::<LightList>().unwrap().0;
+        for texture in [depth, shadows, ao] {
+            resources.read_texture_with_sync(
+                texture,
+                BarrierSync::PIXEL_SHADING,
+                ResourceUsageFlags::SHADER_RESOURCE,
+            );
+        }
+        resources.read_buffer_with_sync(
+            light_list,
+            BarrierSync::PIXEL_SHADING,
+            ResourceUsageFlags::SHADER_RESOURCE,
+        );
+        let output = resources.import_texture_with_sync(
+            &TextureImportDesc {
+                desc: mock_texture_desc,
+                before_sync: BarrierSync::ALL,
+                before_access: BarrierAccess::NONE,
+                before_layout: ImageLayout::Undefined,
+                after_sync: BarrierSync::ALL,
+                after_access: BarrierAccess::SHADER_READ,
+                after_layout: ImageLayout::ShaderReadOnly,
+            },
+            BarrierSync::RENDER_TARGET,
+            ResourceUsageFlags::RENDER_TARGET,
+        );
+        pin_board.publish(Output(output));
+        move |_encoder, _graph, _resources, _args| {}
+    });
+
+    let mut graph = builder.build(device.as_ref());
+
+    let report = graph.queue_schedule_report().clone();
+    let submission_of = |pass: &str| {
+        report
+            .submissions
+            .iter()
+            .position(|v| v.passes.iter().any(|v| v == pass))
+            .unwrap()
+    };
+    let submissions = &report.submissions;
+    let gbuffer = submission_of("gbuffer");
+    let shadows = submission_of("shadows");
+    let ssao = submission_of("ssao");
+    let light_cull = submission_of("light-cull");
+    let lighting = submission_of("lighting");
+
+    assert_eq!(submissions[gbuffer].queue, QueueType::General);
+    assert_eq!(submissions[ssao].queue, QueueType::Compute);
+    assert_eq!(submissions[light_cull].queue, QueueType::Compute);
+    assert_eq!(submissions[lighting].queue, QueueType::General);
+
+    // The compute work only waits on the gbuffer, so it can overlap with the shadows
+    assert_ne!(gbuffer, shadows);
+    for compute in [ssao, light_cull] {
+        assert!(compute > gbuffer);
+        assert!(!submissions[compute].waits.contains(&shadows));
+        assert!(submissions[compute].waits.iter().all(|&v| v <= gbuffer));
+    }
+    assert!(submissions[ssao].acquires > 0);
+
+    // The lighting needs the compute results back on the general queue
+    assert!(submissions[lighting].waits.contains(&light_cull));
+    assert!(submissions[lighting].acquires > 0);
+
+    // Every submission comes after the submissions it waits on, and the last one joins every queue
+    for (i, submission) in submissions.iter().enumerate() {
+        assert!(submission.waits.iter().all(|&v| v < i));
+    }
+    let last = submissions.last().unwrap();
+    assert_eq!(last.queue, QueueType::General);
+    let last_compute = submissions
+        .iter()
+        .rposition(|v| v.queue == QueueType::Compute)
+        .unwrap();
+    assert!(
+        submissions
+            .iter()
+            .skip(last_compute + 1)
+            .any(|v| v.waits.contains(&last_compute))
+    );
+
+    unsafe {
+        graph.allocate_transients(2);
+    }
+
+    let output = pin_board.get::<Output>().unwrap().0;
+    let mut import_bundle = ImportBundle::default();
+    import_bundle.add_resource(output, &mock_texture);
+    for frame_index in [0, 1, 0] {
+        unsafe {
+            graph
+                .execute_on_queues(frame_index, &import_bundle, &Default::default(), &())
+                .unwrap();
+        }
+    }
+
+    // Recording everything onto a single encoder still works
+    let mut command_list = device
+        .create_command_list(&CommandListDesc {
+            queue_type: QueueType::General,
+            name: None,
+        })
+        .unwrap();
+    let mut encoder = command_list.begin_general().unwrap();
+    unsafe {
+        graph.execute(1, &import_bundle, &mut encoder, &());
+    }
+}
+
+#[test]
+pub fn test_parallel_execute() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let pin_board = PinBoard::new();
+    let device = make_null_device();
+
+    let mock_buffer = device
+        .create_buffer(&BufferDesc {
+            size: 256,
+            cpu_access: CpuAccessMode::None,
+            usage: ResourceUsageFlags::UNORDERED_ACCESS,
+            name: Some("imported-mock-buffer"),
+        })
+        .unwrap();
+    let mock_buffer_desc = device.get_buffer_desc(&mock_buffer).clone();
+
+    // A chain of passes that each depend on the last, so every pass lands in its own bundle
+    const PASS_COUNT: usize = 8;
+    let executions: Arc<[AtomicUsize; PASS_COUNT]> = Arc::new(Default::default());
+    let mut builder = FrameGraph::<()>::builder();
+    builder.add_pass(nstr!("pass-0"), |resources| {
+        let buffer = resources.import_buffer(
+            &BufferImportDesc {
+                desc: &mock_buffer_desc,
+                before_sync: BarrierSync::ALL,
+                before_access: BarrierAccess::NONE,
+                after_sync: BarrierSync::ALL,
+                after_access: BarrierAccess::NONE,
+            },
+            ResourceUsageFlags::UNORDERED_ACCESS,
+        );
+        pin_board.publish(Import(buffer));
+        pin_board.publish(Write(buffer));
+        let executions = executions.clone();
+        move |_encoder, _graph, _resources, _args| {
+            executions[0].fetch_add(1, Ordering::Relaxed);
+        }
+    });
+    for i in 1..PASS_COUNT {
+        let name = format!("pass-{i}\0");
+        let name = aleph_nstr::NStr::from_str(&name).unwrap();
+        builder.add_pass(name, |resources| {
+            let buffer = pin_board.get::<Write>().unwrap().0;
+            let buffer = resources.write_buffer(buffer, ResourceUsageFlags::UNORDERED_ACCESS);
+            pin_board.publish(Write(buffer));
+            let executions = executions.clone();
+            move |_encoder, graph, _resources, _args| unsafe {
+                executions[i].fetch_add(1, Ordering::Relaxed);
+                graph.deferred_global_barrier(&GlobalBarrier {
+                    before_sync: BarrierSync::COMPUTE_SHADING,
+                    after_sync: BarrierSync::COMPUTE_SHADING,
+                    before_access: BarrierAccess::SHADER_WRITE,
+                    after_access: BarrierAccess::SHADER_READ,
+                });
+            }
+        });
+    }
+
+    let mut graph = builder.build(device.as_ref());
+
+    // The ranges are contiguous, cover every bundle and are balanced by pass count
+    for max_ranges in [1, 2, 3, 4, 16] {
+        let ranges = graph.split_execution_bundles(max_ranges);
+        assert!(!ranges.is_empty());
+        assert!(ranges.len() <= max_ranges.min(PASS_COUNT));
+        assert_eq!(ranges.first().unwrap().start, 0);
+        assert_eq!(ranges.last().unwrap().end, graph.execution_bundles.len());
+        for pair in ranges.windows(2) {
+            assert_eq!(pair[0].end, pair[1].start);
+        }
+        for range in ranges.iter() {
+            assert!(!range.is_empty());
+        }
+    }
+    assert_eq!(graph.split_execution_bundles(PASS_COUNT).len(), PASS_COUNT);
+
+    unsafe {
+        graph.allocate_transients(2);
+    }
+
+    let buffer = pin_board.get::<Import>().unwrap().0;
+    let mut import_bundle = ImportBundle::default();
+    import_bundle.add_resource(buffer, &mock_buffer);
+
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(4)
+        .build()
+        .unwrap();
+    pool.install(|| {
+        for frame_index in [0, 1, 0] {
+            unsafe {
+                graph
+                    .execute_parallel(frame_index, &import_bundle, &Default::default(), &())
+                    .unwrap();
+            }
+        }
+    });
+
+    for executions in executions.iter() {
+        assert_eq!(executions.load(Ordering::Relaxed), 3);
+    }
+}
+
+#[test]
+pub fn test_pass_culling() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let pin_board = PinBoard::new();
+    let device = make_null_device();
+
+    let texture_desc = |name| TextureDesc {
+        width: 1920,
+        height: 1080,
+        depth: 1,
+        format: Format::Rgba16Float,
+        dimension: TextureDimension::Texture2D,
+        clear_value: None,
+        array_size: 1,
+        mip_levels: 1,
+        sample_count: 1,
+        sample_quality: 0,
+        usage: ResourceUsageFlags::NONE,
+        name: Some(name),
+    };
+    let buffer_desc = |name| BufferDesc {
+        size: 4096,
+        name: Some(name),
+        ..Default::default()
+    };
+
+    let mock_texture = device
+        .create_texture(&TextureDesc {
+            usage: ResourceUsageFlags::RENDER_TARGET | ResourceUsageFlags::SHADER_RESOURCE,
+            ..texture_desc("imported-mock-texture")
+        })
+        .unwrap();
+    let mock_texture_desc = device.get_texture_desc(&mock_texture);
+
+    let executions: Arc<[AtomicUsize; 6]> = Arc::new(Default::default());
+    let counter = |i: usize| {
+        let executions = executions.clone();
+        move |_encoder: &mut CommandEncoder,
+              _graph: &mut GraphChannel,
+              _resources: &FrameGraphResources,
+              _args: &()| {
+            executions[i].fetch_add(1, Ordering::Relaxed);
+        }
+    };
+
+    struct Scene(ResourceMut);
+    struct Output(ResourceMut);
+    struct Readback(ResourceMut);
+    let mut builder = FrameGraph::<()>::builder();
+    builder.add_pass(nstr!("scene"), |resources| {
+        let scene =
+            resources.create_texture(&texture_desc("scene"), ResourceUsageFlags::RENDER_TARGET);
+        pin_board.publish(Scene(scene));
+        counter(0)
+    });
+
+    // A debug view that nothing displays, so it should be culled along with its output
+    builder.add_pass(nstr!("debug-view"), |resources| {
+        let scene = pin_board.get::<Scene>().unwrap().0;
+        resources.read_texture(scene, ResourceUsageFlags::SHADER_RESOURCE);
+        resources.create_texture(
+            &texture_desc("debug-view"),
+            ResourceUsageFlags::RENDER_TARGET,
+        );
+        counter(1)
+    });
+    builder.add_pass(nstr!("present"), |resources| {
+        let scene = pin_board.get::<Scene>().unwrap().0;
+        resources.read_texture(scene, ResourceUsageFlags::SHADER_RESOURCE);
+        let output = resources.import_texture(
+            &TextureImportDesc {
+                desc: &mock_texture_desc,
+                before_sync: BarrierSync::ALL,
+                before_access: BarrierAccess::NONE,
+                before_layout: ImageLayout::Undefined,
+                after_sync: BarrierSync::ALL,
+                after_access: BarrierAccess::SHADER_READ,
+                after_layout: ImageLayout::ShaderReadOnly,
+            },
+            ResourceUsageFlags::RENDER_TARGET,
+        );
+        pin_board.publish(Output(output));
+        counter(2)
+    });
+
+    // The readback buffer is consumed on the CPU, which the graph can't see, so the pass that
+    // fills it must declare side effects to keep itself and its dependencies alive.
+    builder.add_pass(nstr!("stats"), |resources| {
+        let readback =
+            resources.create_buffer(&buffer_desc("stats"), ResourceUsageFlags::UNORDERED_ACCESS);
+        pin_board.publish(Readback(readback));
+        counter(3)
+    });
+    builder.add_pass(nstr!("stats-readback"), |resources| {
+        resources.declare_side_effects();
+        let readback = pin_board.get::<Readback>().unwrap().0;
+        resources.write_buffer(readback, ResourceUsageFlags::COPY_DEST);
+        counter(4)
+    });
+
+    // Nothing depends on this pass at all
+    builder.add_pass(nstr!("orphan"), |resources| {
+        resources.create_buffer(&buffer_desc("orphan"), ResourceUsageFlags::UNORDERED_ACCESS);
+        counter(5)
+    });
+
+    let mut graph = builder.build(device.as_ref());
+
+    let report = graph.pass_culling_report().clone();
+    assert_eq!(report.culled_passes, ["debug-view", "orphan"]);
+    assert_eq!(report.culled_resources, ["debug-view", "orphan"]);
+
+    let lifetime = |name: &str| {
+        report
+            .resources
+            .iter()
+            .find(|v| v.name == name)
+            .unwrap()
+            .clone()
+    };
+    assert_eq!(report.resources.len(), 3);
+    let scene = lifetime("scene");
+    assert!(!scene.imported);
+    assert_eq!(scene.first_use, "scene");
+    assert_eq!(scene.last_use, "present");
+    let output = lifetime("imported-mock-texture");
+    assert!(output.imported);
+    assert_eq!(output.first_use, "present");
+    assert_eq!(output.last_use, "present");
+    let stats = lifetime("stats");
+    assert_eq!(stats.first_use, "stats");
+    assert_eq!(stats.last_use, "stats-readback");
+
+    // Only the resources of the passes that are left get created
+    assert_eq!(graph.transient_memory_report().transient_count, 2);
+
+    unsafe {
+        graph.allocate_transients(1);
+    }
+
+    let output = pin_board.get::<Output>().unwrap().0;
+    let mut import_bundle = ImportBundle::default();
+    import_bundle.add_resource(output, &mock_texture);
+
+    let mut command_list = device
+        .create_command_list(&CommandListDesc {
+            queue_type: QueueType::General,
+            name: None,
+        })
+        .unwrap();
+    let mut encoder = command_list.begin_general().unwrap();
+    unsafe {
+        graph.execute(0, &import_bundle, &mut encoder, &());
+        graph
+            .execute_on_queues(0, &import_bundle, &Default::default(), &())
+            .unwrap();
+        graph
+            .execute_parallel(0, &import_bundle, &Default::default(), &())
+            .unwrap();
+    }
+
+    let executions: Vec<_> = executions
+        .iter()
+        .map(|v| v.load(Ordering::Relaxed))
+        .collect();
+    assert_eq!(executions, [3, 0, 3, 3, 3, 0]);
+}
+
+#[test]
+pub fn test_persistent_resources() {
+    use std::sync::Mutex;
+
+    let pin_board = PinBoard::new();
+    let device = make_null_device();
+
+    let texture_desc = |name| TextureDesc {
+        width: 1920,
+        height: 1080,
+        depth: 1,
+        format: Format::Rgba16Float,
+        dimension: TextureDimension::Texture2D,
+        clear_value: None,
+        array_size: 1,
+        mip_levels: 1,
+        sample_count: 1,
+        sample_quality: 0,
+        usage: ResourceUsageFlags::NONE,
+        name: Some(name),
+    };
+
+    let mock_texture = device
+        .create_texture(&TextureDesc {
+            usage: ResourceUsageFlags::RENDER_TARGET,
+            ..texture_desc("imported-mock-texture")
+        })
+        .unwrap();
+    let mock_texture_desc = device.get_texture_desc(&mock_texture);
+
+    // The has_history flag, and the addresses of the previous and current history textures, seen
+    // by each execution of the TAA pass
+    let records: Arc<Mutex<Vec<(bool, usize, usize)>>> = Default::default();
+    let address = |v: &TextureHandle| {
+        let v: *const NullTexture = v.get().downcast_ref::<NullTexture>().unwrap();
+        v as usize
+    };
+
+    struct Scene(ResourceMut);
+    struct PreviousHistory(ResourceRef);
+    struct History(ResourceMut);
+    struct Output(ResourceMut);
+    let mut builder = FrameGraph::<()>::builder();
+    builder.add_pass(nstr!("scene"), |resources| {
+        let scene =
+            resources.create_texture(&texture_desc("scene"), ResourceUsageFlags::RENDER_TARGET);
+        pin_board.publish(Scene(scene));
+        |_encoder: &mut CommandEncoder,
+         _graph: &mut GraphChannel,
+         _resources: &FrameGraphResources,
+         _args: &()| {}
+    });
+    builder.add_pass(nstr!("taa"), |resources| {
+        let scene = pin_board.get::<Scene>().unwrap().0;
+        resources.read_texture(scene, ResourceUsageFlags::SHADER_RESOURCE);
+        let previous = resources.read_previous_texture(
+            &texture_desc("taa-history"),
+            ResourceUsageFlags::SHADER_RESOURCE,
+        );
+        let current = resources.write_current_texture(
+            &texture_desc("taa-history"),
+            ResourceUsageFlags::RENDER_TARGET,
+        );
+        pin_board.publish(PreviousHistory(previous));
+        pin_board.publish(History(current));
+
+        let records = records.clone();
+        move |_encoder: &mut CommandEncoder,
+              _graph: &mut GraphChannel,
+              resources: &FrameGraphResources,
+              _args: &()| {
+            let previous = address(resources.get_texture(previous).unwrap());
+            let current = address(resources.get_texture(current).unwrap());
+            let mut records = records.lock().unwrap();
+            records.push((resources.has_history(), previous, current));
+        }
+    });
+
+    // Reads of the previous contents share a single handle, and don't keep a pass alive
+    builder.add_pass(nstr!("history-debug"), |resources| {
+        let previous = resources.read_previous_texture(
+            &texture_desc("taa-history"),
+            ResourceUsageFlags::SHADER_RESOURCE,
+        );
+        assert_eq!(previous, pin_board.get::<PreviousHistory>().unwrap().0);
+        |_encoder: &mut CommandEncoder,
+         _graph: &mut GraphChannel,
+         _resources: &FrameGraphResources,
+         _args: &()| {}
+    });
+    builder.add_pass(nstr!("present"), |resources| {
+        let history = pin_board.get::<History>().unwrap().0;
+        resources.read_texture(history, ResourceUsageFlags::SHADER_RESOURCE);
+        let output = resources.import_texture(
+            &TextureImportDesc {
+                desc: &mock_texture_desc,
+                before_sync: BarrierSync::ALL,
+                before_access: BarrierAccess::NONE,
+                before_layout: ImageLayout::Undefined,
+                after_sync: BarrierSync::ALL,
+                after_access: BarrierAccess::NONE,
+                after_layout: ImageLayout::PresentSrc,
+            },
+            ResourceUsageFlags::RENDER_TARGET,
+        );
+        pin_board.publish(Output(output));
+        |_encoder: &mut CommandEncoder,
+         _graph: &mut GraphChannel,
+         _resources: &FrameGraphResources,
+         _args: &()| {}
+    });
+
+    let mut graph = builder.build(device.as_ref());
+    assert_eq!(graph.pass_culling_report().culled_passes, ["history-debug"]);
+
+    // The history textures are owned by the graph, not allocated as transients
+    assert_eq!(graph.transient_memory_report().transient_count, 1);
+
+    unsafe {
+        graph.allocate_transients(2);
+    }
+
+    // The persistent resources are internal to the graph so only the output is imported
+    let output = pin_board.get::<Output>().unwrap().0;
+    let mut import_bundle = ImportBundle::default();
+    import_bundle.add_resource(output, &mock_texture);
+
+    let mut command_list = device
+        .create_command_list(&CommandListDesc {
+            queue_type: QueueType::General,
+            name: None,
+        })
+        .unwrap();
+    let mut encoder = command_list.begin_general().unwrap();
+    for frame in 0..3 {
+        unsafe {
+            graph.execute(frame % 2, &import_bundle, &mut encoder, &());
+        }
+    }
+    for frame in 3..5 {
+        unsafe {
+            graph
+                .execute_on_queues(frame % 2, &import_bundle, &Default::default(), &())
+                .unwrap();
+        }
+    }
+    for frame in 5..7 {
+        unsafe {
+            graph
+                .execute_parallel(frame % 2, &import_bundle, &Default::default(), &())
+                .unwrap();
+        }
+    }
+
+    let records = records.lock().unwrap();
+    assert_eq!(records.len(), 7);
+    assert!(!records[0].0);
+    for (i, &(has_history, previous, current)) in records.iter().enumerate() {
+        assert_ne!(previous, current);
+        if i != 0 {
+            assert!(has_history);
+
+            // Each execution reads the history the execution before it wrote
+            assert_eq!(previous, records[i - 1].2);
+        }
+    }
+}
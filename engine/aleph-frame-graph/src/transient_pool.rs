@@ -0,0 +1,326 @@
+//
+//
+// This file is a part of Aleph
+//
+// https://github.com/nathanvoglsam/aleph
+//
+// MIT License
+//
+// Copyright (c) 2020 Aleph Engine
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+//!
+//! Pooling of transient resources with disjoint lifetimes.
+//!
+//! Once the pass order is known we compute the first and last execution bundle each transient is
+//! used in. Transients whose lifetimes don't overlap and whose descriptions match are then backed
+//! by the same physical resource, one after another. This is resource pooling, not memory
+//! aliasing: differently shaped resources never share memory.
+//!
+//! TODO: Pack transients into shared heaps with placed resources and emit real aliasing
+//!       barriers. This needs heap and placed resource support in the RHI first. The lifetimes
+//!       computed here, and [TransientMemoryReport::lifetime_peak_bytes], are the inputs that
+//!       packing would need.
+//!
+
+use aleph_alloc::BVec;
+use aleph_rhi_api::*;
+
+use crate::internal::{
+    FgSystem, FrameGraphTextureDesc, IIRNode, IRBarrierType, IRNode, PassOrderBundle, ResourceRoot,
+    ResourceType, ResourceVersion,
+};
+
+/// The granularity that resource memory sizes are rounded up to when estimating memory usage.
+/// Matches the default placement alignment of D3D12, and is a common allocation granularity on
+/// Vulkan implementations.
+const ESTIMATE_ALIGNMENT: u64 = 64 * 1024;
+
+///
+/// Estimated memory usage of the transient resources of a [crate::FrameGraph].
+///
+/// Transient resources whose lifetimes, measured in the graph's final pass order, don't overlap
+/// and that have matching descriptions are pooled into the same physical resource. The sizes are
+/// estimated from the resource descriptions as the RHI doesn't expose memory requirements, so
+/// treat them as a guide rather than an exact measurement.
+///
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TransientMemoryReport {
+    /// The number of transient resources declared in the graph
+    pub transient_count: usize,
+
+    /// The number of physical resources created to back the transient resources
+    pub physical_count: usize,
+
+    /// The estimated memory needed if every transient resource had its own physical resource
+    pub unpooled_bytes: u64,
+
+    /// The estimated memory needed for the pooled physical resources actually created
+    pub pooled_bytes: u64,
+
+    /// The largest estimated amount of memory used by the transient resources that are live at
+    /// the same point in the pass order. This is the lower bound on the memory that packing
+    /// transients into shared heaps could achieve with the current pass order.
+    pub lifetime_peak_bytes: u64,
+}
+
+/// The lifetime and usage of a single transient resource within the final pass order
+pub(crate) struct TransientLifetime {
+    /// The index of the root resource
+    pub root: u16,
+
+    /// The index of the first execution bundle the resource is used in
+    pub first: usize,
+
+    /// The index of the last execution bundle the resource is used in
+    pub last: usize,
+
+    /// The union of the sync scopes of every use of the resource
+    pub sync: BarrierSync,
+
+    /// The union of the write accesses of every use of the resource
+    pub write_access: BarrierAccess,
+
+    /// The estimated size of the resource in memory
+    pub size: u64,
+}
+
+/// A physical resource that backs one or more transient resources, one after another
+pub(crate) struct PhysicalSlot {
+    /// The transient resources backed by this slot, ordered by their lifetimes. Indices into
+    /// [TransientPoolPlan::lifetimes].
+    pub occupants: Vec<usize>,
+
+    /// The union of the usage flags of all the occupants
+    pub usage: ResourceUsageFlags,
+}
+
+/// The assignment of transient resources to pooled physical resources, computed once the pass order of
+/// the graph is known.
+#[derive(Default)]
+pub(crate) struct TransientPoolPlan {
+    pub lifetimes: Vec<TransientLifetime>,
+    pub slots: Vec<PhysicalSlot>,
+    pub report: TransientMemoryReport,
+}
+
+impl TransientPoolPlan {
+    pub fn new(
+        root_resources: &[ResourceRoot],
+        resource_versions: &[ResourceVersion],
+        execution_bundles: &[PassOrderBundle],
+        ir_nodes: &[IRNode],
+        num_passes: usize,
+    ) -> Self {
+        // Map each render pass to the execution bundle it was scheduled into
+        let mut pass_bundles = vec![usize::MAX; num_passes];
+        for (i, bundle) in execution_bundles.iter().enumerate() {
+            let passes = unsafe { bundle.passes.as_ref() };
+            for &pass in passes {
+                pass_bundles[ir_nodes[pass].render_pass()] = i;
+            }
+        }
+
+        // Accumulate the lifetime of every transient resource from the passes that use each of
+        // its versions
+        let mut lifetime_for_root = vec![usize::MAX; root_resources.len()];
+        let mut lifetimes = Vec::new();
+        for version in resource_versions.iter() {
+            let root_index = version.root_resource as usize;
+            let root = &root_resources[root_index];
+            let format = match &root.resource_type {
+                ResourceType::Buffer(v) if v.import.is_none() => Format::default(),
+                ResourceType::Texture(v) if v.import.is_none() => v.desc.format,
+                _ => continue,
+            };
+
+            if lifetime_for_root[root_index] == usize::MAX {
+                lifetime_for_root[root_index] = lifetimes.len();
+                lifetimes.push(TransientLifetime {
+                    root: version.root_resource,
+                    first: usize::MAX,
+                    last: 0,
+                    sync: BarrierSync::NONE,
+                    write_access: BarrierAccess::NONE,
+                    size: estimate_size(&root.resource_type),
+                });
+            }
+            let lifetime = &mut lifetimes[lifetime_for_root[root_index]];
+
+            let creator_bundle = pass_bundles[version.creator_pass];
+            lifetime.first = lifetime.first.min(creator_bundle);
+            lifetime.last = lifetime.last.max(creator_bundle);
+            lifetime.sync |= version.creator_sync;
+            lifetime.write_access |= version.creator_access.barrier_access_for_write(format);
+            for read in version.reads_iter() {
+                let read_bundle = pass_bundles[read.render_pass];
+                lifetime.first = lifetime.first.min(read_bundle);
+                lifetime.last = lifetime.last.max(read_bundle);
+                lifetime.sync |= read.sync;
+            }
+        }
+
+        // Greedily pack the resources into physical slots in order of first use. A resource can
+        // reuse a slot if it is compatible with the slot's occupants and the last occupant is no
+        // longer used by the time the resource is first used. The initialization barrier for a
+        // resource executes at the start of the bundle it's first used in, so the previous
+        // occupant must be finished strictly before that bundle.
+        let mut order: Vec<usize> = (0..lifetimes.len()).collect();
+        order.sort_by_key(|&v| (lifetimes[v].first, lifetimes[v].root));
+
+        let mut slots: Vec<PhysicalSlot> = Vec::new();
+        for v in order {
+            let lifetime = &lifetimes[v];
+            let root = &root_resources[lifetime.root as usize];
+            let slot = slots.iter_mut().find(|slot| {
+                let last = &lifetimes[*slot.occupants.last().unwrap()];
+                let other = &root_resources[last.root as usize];
+                last.last < lifetime.first
+                    && is_compatible(&root.resource_type, &other.resource_type)
+            });
+            match slot {
+                Some(slot) => {
+                    slot.occupants.push(v);
+                    slot.usage |= root.total_access_flags;
+                }
+                None => slots.push(PhysicalSlot {
+                    occupants: vec![v],
+                    usage: root.total_access_flags,
+                }),
+            }
+        }
+
+        let unpooled_bytes = lifetimes.iter().map(|v| v.size).sum();
+        let pooled_bytes = slots.iter().map(|v| lifetimes[v.occupants[0]].size).sum();
+        let lifetime_peak_bytes = (0..execution_bundles.len())
+            .map(|i| {
+                lifetimes
+                    .iter()
+                    .filter(|v| v.first <= i && i <= v.last)
+                    .map(|v| v.size)
+                    .sum::<u64>()
+            })
+            .max()
+            .unwrap_or_default();
+
+        let report = TransientMemoryReport {
+            transient_count: lifetimes.len(),
+            physical_count: slots.len(),
+            unpooled_bytes,
+            pooled_bytes,
+            lifetime_peak_bytes,
+        };
+
+        Self {
+            lifetimes,
+            slots,
+            report,
+        }
+    }
+
+    /// Makes the initialization barrier of every resource that takes over a physical resource wait
+    /// on the previous occupant.
+    ///
+    /// Initialization barriers normally have an empty 'before' scope as the resource is brand new.
+    /// When the memory is shared the barrier must also wait for the previous occupant's accesses to
+    /// finish. Textures discard their contents by transitioning from the undefined layout, which
+    /// only requires waiting on execution. Buffers have no such transition, so we also make the
+    /// previous occupant's writes available so they can't land on top of the new contents.
+    pub fn patch_initialization_barriers(&self, ir_nodes: &mut BVec<IRNode, FgSystem>) {
+        for slot in self.slots.iter() {
+            for pair in slot.occupants.windows(2) {
+                let previous = &self.lifetimes[pair[0]];
+                let next = &self.lifetimes[pair[1]];
+
+                let node = ir_nodes.iter_mut().find(|v| {
+                    v.barrier_type() == IRBarrierType::Initialization
+                        && !v.is_render_pass()
+                        && v.resource_id().root == next.root
+                });
+                match node {
+                    Some(IRNode::LayoutChange(v)) => {
+                        v.before_sync |= previous.sync;
+                    }
+                    Some(IRNode::Barrier(v)) => {
+                        v.before_sync |= previous.sync;
+                        v.before_access |= previous.write_access;
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+/// Returns whether two transient resources can share the same physical resource.
+///
+/// Pooled resources reuse the same RHI resource, so their descriptions must match, ignoring name
+/// and usage. Overlapping differently shaped resources in the same memory would need placed
+/// resources, which the RHI doesn't support.
+pub(crate) fn is_compatible(a: &ResourceType, b: &ResourceType) -> bool {
+    match (a, b) {
+        (ResourceType::Buffer(a), ResourceType::Buffer(b)) => {
+            a.desc.size == b.desc.size && a.desc.cpu_access == b.desc.cpu_access
+        }
+        (ResourceType::Texture(a), ResourceType::Texture(b)) => {
+            is_texture_compatible(&a.desc, &b.desc)
+        }
+        _ => false,
+    }
+}
+
+fn is_texture_compatible(a: &FrameGraphTextureDesc, b: &FrameGraphTextureDesc) -> bool {
+    a.width == b.width
+        && a.height == b.height
+        && a.depth == b.depth
+        && a.format == b.format
+        && a.dimension == b.dimension
+        && a.clear_value == b.clear_value
+        && a.array_size == b.array_size
+        && a.mip_levels == b.mip_levels
+        && a.sample_count == b.sample_count
+        && a.sample_quality == b.sample_quality
+}
+
+/// Estimates the amount of memory a resource will need from its description
+fn estimate_size(resource_type: &ResourceType) -> u64 {
+    let size = match resource_type {
+        ResourceType::Buffer(v) => v.desc.size,
+        ResourceType::Texture(v) => {
+            let desc = &v.desc;
+            let (block_w, block_h, _) = desc.format.block_dimensions();
+            let bytes_per_block = desc.format.bytes_per_element() as u64;
+            let mut size = 0;
+            for mip in 0..desc.mip_levels.max(1) {
+                let width = (desc.width >> mip).max(1).div_ceil(block_w as u32) as u64;
+                let height = (desc.height >> mip).max(1).div_ceil(block_h as u32) as u64;
+                let depth = match desc.dimension {
+                    TextureDimension::Texture3D => (desc.depth >> mip).max(1) as u64,
+                    _ => 1,
+                };
+                size += width * height * depth * bytes_per_block;
+            }
+            size * desc.array_size.max(1) as u64 * desc.sample_count.max(1) as u64
+        }
+        ResourceType::Execution(_) => 0,
+    };
+    size.next_multiple_of(ESTIMATE_ALIGNMENT)
+}
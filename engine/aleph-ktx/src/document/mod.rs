@@ -31,6 +31,7 @@ mod file_index;
 mod level_index;
 mod super_compression_scheme;
 
+use std::borrow::Cow;
 use std::cell::Cell;
 use std::ffi::CStr;
 use std::io::{Cursor, Read, Seek, SeekFrom};
@@ -130,6 +131,11 @@ pub enum KtxReadError {
     #[error("Uncompressed size in a level index was invalid.")]
     InvalidLevelIndexUncompressedSize,
 
+    #[error(
+        "A level decompressed to a different size than its level index declared. Got '{0}', expected '{1}'."
+    )]
+    InvalidDecompressedLevelSize(u64, u64),
+
     #[error("Produced if the document describes an invalid image type.")]
     InvalidDocumentType,
 
@@ -396,6 +402,96 @@ impl<R: Read + Seek> KtxDocument<R> {
         Ok(info)
     }
 
+    /// Reads the data for the given mip level, decompressing it if the document uses the
+    /// [`SuperCompressionScheme::ZSTD`] or [`SuperCompressionScheme::ZLIB`] schemes.
+    ///
+    /// The returned buffer is exactly 'size_uncompressed' bytes long, with every layer and face of
+    /// the level packed in the order the KTX spec stores them.
+    pub fn read_level(&self, level: u32) -> Result<Vec<u8>, KtxReadError> {
+        let info = self.get_level_info(level)?;
+        let mut data = vec![0u8; info.size_uncompressed as usize];
+        self.read_level_into(level, &mut data)?;
+        Ok(data)
+    }
+
+    /// Returns the data for the given mip level out of 'data', which must hold the bytes of the
+    /// whole file the document was read from, like a memory mapped file.
+    ///
+    /// Levels that aren't supercompressed are borrowed straight out of 'data' without a copy.
+    /// Supercompressed levels are decompressed into a new buffer, laid out like [`Self::read_level`].
+    pub fn level_data<'a>(
+        &self,
+        data: &'a [u8],
+        level: u32,
+    ) -> Result<Cow<'a, [u8]>, KtxReadError> {
+        let info = self.get_level_info(level)?;
+        let src = data
+            .get(info.to_slice_range())
+            .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::UnexpectedEof))?;
+
+        if self.super_compression_scheme == SuperCompressionScheme::NONE {
+            return Ok(Cow::Borrowed(src));
+        }
+
+        let mut decompressed = vec![0u8; info.size_uncompressed as usize];
+        self.super_compression_scheme
+            .decompress(src, &mut decompressed)?;
+        Ok(Cow::Owned(decompressed))
+    }
+
+    /// Reads the data for the given mip level into 'dst', decompressing it if required. See
+    /// [`Self::read_level`].
+    ///
+    /// On success the number of bytes written into 'dst' will be returned.
+    pub fn read_level_into(&self, level: u32, dst: &mut [u8]) -> Result<usize, KtxReadError> {
+        let info = self.get_level_info(level)?;
+
+        if !matches!(
+            self.super_compression_scheme,
+            SuperCompressionScheme::NONE
+                | SuperCompressionScheme::ZSTD
+                | SuperCompressionScheme::ZLIB
+        ) {
+            return Err(KtxReadError::UnsupportedSuperCompressionScheme(
+                self.super_compression_scheme,
+            ));
+        }
+
+        let uncompressed_len = info.size_uncompressed as usize;
+        let out = dst
+            .get_mut(0..uncompressed_len)
+            .ok_or(KtxReadError::DestBufferTooSmall(uncompressed_len))?;
+
+        // Get reader from cell
+        let mut reader = self.reader.take().ok_or(KtxReadError::NoReader)?;
+
+        // Wrap the inner failible function so we can ensure we return the reader to it's slot even
+        // if we hit an error.
+        let result = self.inner_read_level(&mut reader, &info, out);
+        self.reader.set(Some(reader));
+        result.map(|_| uncompressed_len)
+    }
+
+    fn inner_read_level(
+        &self,
+        reader: &mut R,
+        info: &LevelIndex,
+        dst: &mut [u8],
+    ) -> Result<(), KtxReadError> {
+        reader.seek(SeekFrom::Start(info.offset))?;
+
+        if self.super_compression_scheme == SuperCompressionScheme::NONE {
+            // Uncompressed data can be read straight into the output buffer
+            reader.read_exact(dst)?;
+        } else {
+            let mut compressed = vec![0u8; info.size as usize];
+            reader.read_exact(&mut compressed)?;
+            self.super_compression_scheme.decompress(&compressed, dst)?;
+        }
+
+        Ok(())
+    }
+
     /// Lookup, and read out the data for, the given key in the documents key/value store.
     ///
     /// The data will be written into 'dst'. On success the number of bytes written into 'dst' will
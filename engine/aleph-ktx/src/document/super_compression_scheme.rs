@@ -28,6 +28,9 @@
 //
 
 use std::fmt::{Debug, Error, Formatter};
+use std::io::Read;
+
+use crate::KtxReadError;
 
 #[derive(Copy, Clone, PartialEq, Eq, Hash, Default, Ord, PartialOrd)]
 #[repr(transparent)]
@@ -136,6 +139,48 @@ impl SuperCompressionScheme {
     pub fn is_in_invalid_reserved(self) -> bool {
         self.0 >= 0x20000
     }
+
+    ///
+    /// Decompresses a single level's worth of data, 'src', into 'dst' using this scheme.
+    ///
+    /// 'src' should be exactly the bytes described by [`crate::LevelIndex::to_slice_range`] and
+    /// 'dst' must be exactly 'size_uncompressed' bytes long. Anything else is an error.
+    ///
+    /// # Info
+    ///
+    /// Only [`SuperCompressionScheme::NONE`], [`SuperCompressionScheme::ZSTD`] and
+    /// [`SuperCompressionScheme::ZLIB`] can be decoded. BasisLZ data must be transcoded and can't
+    /// be handled here.
+    ///
+    pub fn decompress(self, src: &[u8], dst: &mut [u8]) -> Result<(), KtxReadError> {
+        let written = match self {
+            Self::NONE => {
+                let len = src.len().min(dst.len());
+                dst[0..len].copy_from_slice(&src[0..len]);
+                src.len()
+            }
+            Self::ZSTD => zstd::bulk::decompress_to_buffer(src, dst)?,
+            Self::ZLIB => {
+                let mut decoder = flate2::read::ZlibDecoder::new(src);
+                decoder.read_exact(dst)?;
+
+                // Check that the stream ends where the level index says it should. Trailing data
+                // means the declared uncompressed size is wrong.
+                let mut trailing = [0u8; 1];
+                dst.len() + decoder.read(&mut trailing)?
+            }
+            _ => return Err(KtxReadError::UnsupportedSuperCompressionScheme(self)),
+        };
+
+        if written != dst.len() {
+            return Err(KtxReadError::InvalidDecompressedLevelSize(
+                written as u64,
+                dst.len() as u64,
+            ));
+        }
+
+        Ok(())
+    }
 }
 
 impl Debug for SuperCompressionScheme {
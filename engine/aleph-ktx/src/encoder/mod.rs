@@ -51,6 +51,7 @@ pub struct KtxDocumentDescription<'a> {
     format: VkFormat,
     generate_mips: bool,
     level_num: u32,
+    super_compression: SuperCompression,
     kvd: KeyValueEntries<'a>,
     doc_type: DocumentTypeDescription<'a>,
 }
@@ -73,6 +74,7 @@ impl<'a> KtxDocumentDescription<'a> {
             format: VkFormat::UNDEFINED,
             generate_mips: false,
             level_num: 0,
+            super_compression: SuperCompression::None,
             kvd: KeyValueEntries {
                 entries: &DEFAULT_KVD,
             },
@@ -100,6 +102,13 @@ impl<'a> KtxDocumentDescription<'a> {
         self
     }
 
+    /// Declares the supercompression scheme that should be applied to each mip level when the file
+    /// is written. Defaults to [`SuperCompression::None`].
+    pub fn super_compression(&mut self, super_compression: SuperCompression) -> &mut Self {
+        self.super_compression = super_compression;
+        self
+    }
+
     pub fn image_1d(&mut self, width: u32, level_num: u32, images: ImageSet<'a>) -> &mut Self {
         assert_ne!(width, 0);
         assert_ne!(level_num, 0);
@@ -272,16 +281,32 @@ impl<'a> KtxDocumentDescription<'a> {
             .format
             .texel_block_size_ktx()
             .expect("Can't write a format we don't have a known texel block size for");
-        let mip_padding = lcm(texel_block_size, 4) as u64;
+        let scheme = self.super_compression.scheme();
 
-        let dfd = vk2dfd(self.format.0)
-            .expect("We must always have a DFD for a VkFormat we're encoding for");
-        let dfd = bytemuck::cast_slice::<_, u8>(dfd);
+        // Supercompressed levels are opaque byte streams so the spec requires no alignment between
+        // them.
+        let mip_padding = if scheme == SuperCompressionScheme::NONE {
+            lcm(texel_block_size, 4) as u64
+        } else {
+            1
+        };
+
+        let mut dfd = vk2dfd(self.format.0)
+            .expect("We must always have a DFD for a VkFormat we're encoding for")
+            .to_vec();
+        if scheme != SuperCompressionScheme::NONE {
+            // The spec requires that supercompressed files declare all their bytesPlane fields as
+            // 0, as the bytes in the file no longer map directly to texels
+            dfd[5] = 0;
+            dfd[6] = 0;
+        }
+        let dfd = bytemuck::cast_slice::<_, u8>(&dfd);
 
         // The DFD offset is easy to calculate as it's simply the size of the header + size of the
         // level index. The level index is simply 3 u64 values per level stored so we can calculate
         // it all up front.
         let level_num = self.level_num as usize;
+        let compressed_levels = self.compress_levels(level_num)?;
         let level_index_size = 3 * size_of::<u64>() * level_num;
         let dfd_offset = HEADER_SIZE + level_index_size;
         let dfd_offset: u32 = dfd_offset.try_into().expect("DFD offset overflows u32!");
@@ -313,7 +338,7 @@ impl<'a> KtxDocumentDescription<'a> {
         dst.write_u32::<LittleEndian>(self.doc_type.encoded_layer_count())?;
         dst.write_u32::<LittleEndian>(self.doc_type.encoded_face_count())?;
         dst.write_u32::<LittleEndian>(self.level_num)?;
-        dst.write_u32::<LittleEndian>(SuperCompressionScheme::NONE.0)?;
+        dst.write_u32::<LittleEndian>(scheme.0)?;
         dst.write_u32::<LittleEndian>(dfd_offset)?;
         dst.write_u32::<LittleEndian>(dfd_bytes)?;
         dst.write_u32::<LittleEndian>(kvd_offset)?;
@@ -321,19 +346,22 @@ impl<'a> KtxDocumentDescription<'a> {
         dst.write_u64::<LittleEndian>(0)?; // sgdByteOffset (should be zero, we dont support it)
         dst.write_u64::<LittleEndian>(0)?; // sgdByteLength (should be zero, we dont support it)
 
-        // Texture data starts after kvd section, we don't support super compression so we don't
-        // need to leave space for it
+        // Texture data starts after kvd section, none of the schemes we can write use supercompression
+        // global data so we don't need to leave space for it
         let data_base_offset = kvd_offset + kvd_length;
 
-        let level_index =
-            self.doc_type
-                .resolve_level_index(level_num, data_base_offset as usize, mip_padding);
+        let level_index = self.doc_type.resolve_level_index(
+            level_num,
+            data_base_offset as usize,
+            mip_padding,
+            compressed_levels.as_deref(),
+        );
 
         // Write the calculated level index out
         for level in level_index.iter().take(level_num) {
             dst.write_u64::<LittleEndian>(level.0)?;
             dst.write_u64::<LittleEndian>(level.1)?;
-            dst.write_u64::<LittleEndian>(level.1)?;
+            dst.write_u64::<LittleEndian>(level.2)?;
         }
 
         dst.write_all(dfd)?;
@@ -349,6 +377,15 @@ impl<'a> KtxDocumentDescription<'a> {
             }
         }
 
+        if let Some(compressed_levels) = compressed_levels {
+            // Supercompressed levels have no padding between them so we can just write them out
+            // back to back, smallest first.
+            for level in compressed_levels.iter().rev() {
+                dst.write_all(level)?;
+            }
+            return Ok(());
+        }
+
         let mut accum = data_base_offset as u64;
         match self.doc_type {
             DocumentTypeDescription::Image1D { images }
@@ -447,6 +484,27 @@ impl<'a> KtxDocumentDescription<'a> {
         Ok(())
     }
 
+    /// Compresses each level with the selected supercompression scheme. Every layer and face in a
+    /// level is packed together and compressed as a single stream, as the KTX spec requires.
+    ///
+    /// Returns `None` if no supercompression was requested.
+    fn compress_levels(&self, level_num: usize) -> std::io::Result<Option<Vec<Vec<u8>>>> {
+        if self.super_compression == SuperCompression::None {
+            return Ok(None);
+        }
+
+        let mut levels = Vec::with_capacity(level_num);
+        for level_i in 0..level_num {
+            let mut level_data = Vec::new();
+            for image in self.doc_type.level_images(level_num, level_i) {
+                level_data.extend_from_slice(image);
+            }
+            levels.push(self.super_compression.compress(&level_data)?);
+        }
+
+        Ok(Some(levels))
+    }
+
     fn validate(&self) {
         assert_ne!(self.format, VkFormat::UNDEFINED);
         assert!(self.format.is_known());
@@ -565,6 +623,47 @@ impl<'a> KtxDocumentDescription<'a> {
     }
 }
 
+/// The supercompression options supported by the encoder, and the compression level to use for
+/// each.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Default)]
+pub enum SuperCompression {
+    /// Store the level data uncompressed
+    #[default]
+    None,
+
+    /// Compress each level with Zstandard at the given compression level. 0 selects zstd's default
+    /// level.
+    Zstd(i32),
+
+    /// Compress each level with Zlib at the given compression level, in the range 0-9.
+    Zlib(u32),
+}
+
+impl SuperCompression {
+    /// Returns the [`SuperCompressionScheme`] that will be declared in the file
+    pub const fn scheme(&self) -> SuperCompressionScheme {
+        match self {
+            SuperCompression::None => SuperCompressionScheme::NONE,
+            SuperCompression::Zstd(_) => SuperCompressionScheme::ZSTD,
+            SuperCompression::Zlib(_) => SuperCompressionScheme::ZLIB,
+        }
+    }
+
+    /// Compresses 'src' with the selected scheme and compression level.
+    pub fn compress(&self, src: &[u8]) -> std::io::Result<Vec<u8>> {
+        match *self {
+            SuperCompression::None => Ok(src.to_vec()),
+            SuperCompression::Zstd(level) => zstd::bulk::compress(src, level),
+            SuperCompression::Zlib(level) => {
+                let compression = flate2::Compression::new(level.min(9));
+                let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), compression);
+                encoder.write_all(src)?;
+                encoder.finish()
+            }
+        }
+    }
+}
+
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
 pub enum LevelNum {
     /// Only store a single mip level in the texture and declare that readers should generate their
@@ -734,12 +833,39 @@ impl<'a> DocumentTypeDescription<'a> {
         }
     }
 
+    const fn images(&self) -> ImageSet<'a> {
+        match self {
+            DocumentTypeDescription::Image1D { images }
+            | DocumentTypeDescription::Image2D { images }
+            | DocumentTypeDescription::Image3D { images }
+            | DocumentTypeDescription::Cube { images }
+            | DocumentTypeDescription::Array1D { images, .. }
+            | DocumentTypeDescription::Array2D { images, .. }
+            | DocumentTypeDescription::Array3D { images, .. }
+            | DocumentTypeDescription::CubeArray { images, .. } => images,
+        }
+    }
+
+    /// Yields every image in the given level in the order they are packed into the file. That is
+    /// each array layer in order, with each layer's faces in order.
+    fn level_images(&self, level_num: usize, level_i: usize) -> impl Iterator<Item = &'a [u8]> {
+        let images = self.images();
+        let layer_num_real =
+            self.encoded_layer_count().max(1) as usize * self.encoded_face_count() as usize;
+        (0..layer_num_real).map(move |layer_i| {
+            images[calculate_set_index(layer_num_real, level_num, layer_i, level_i)]
+        })
+    }
+
+    /// Resolves the (offset, size, size_uncompressed) triple for each level. If 'compressed_levels'
+    /// is provided the sizes of the compressed levels are used for 'size'.
     fn resolve_level_index(
         &self,
         level_num: usize,
         data_base_offset: usize,
         mip_padding: u64,
-    ) -> [(u64, u64); 32] {
+        compressed_levels: Option<&[Vec<u8>]>,
+    ) -> [(u64, u64, u64); 32] {
         assert!(level_num <= 32);
 
         // Implement a two pass level indexing algorithm.
@@ -760,7 +886,7 @@ impl<'a> DocumentTypeDescription<'a> {
         // This example doesn't take into account padding, but the meaning should be clear.
         // Padding will simply move the offsets a bit further along
 
-        let mut level_index = [(0u64, 0u64); 32];
+        let mut level_index = [(0u64, 0u64, 0u64); 32];
         match self {
             DocumentTypeDescription::Image1D { images }
             | DocumentTypeDescription::Image2D { images }
@@ -809,6 +935,14 @@ impl<'a> DocumentTypeDescription<'a> {
             }
         }
 
+        // The data we write is the same as the input data, unless we're supercompressing
+        for (level_i, level) in level_index.iter_mut().enumerate().take(level_num) {
+            level.2 = level.1;
+            if let Some(compressed_levels) = compressed_levels {
+                level.1 = compressed_levels[level_i].len() as u64;
+            }
+        }
+
         // Pass 2, prefix sum lengths in reverse order to get our final offsets
         let mut accum = data_base_offset as u64;
         for level_i in (0..level_num).rev() {
@@ -27,13 +27,14 @@
 // SOFTWARE.
 //
 
+use std::borrow::Cow;
 use std::io::{Cursor, Read, Seek};
 
 use aleph_vk_format::VkFormat;
 
 use crate::{
     DocumentType, ENCODER_NAME, KtxDocument, KtxDocumentDescription, KtxReadError,
-    SuperCompressionScheme, calculate_set_index,
+    SuperCompression, SuperCompressionScheme, calculate_set_index,
 };
 
 #[test]
@@ -93,7 +94,8 @@ fn round_trip_document<R: Read + Seek>(
     }
 
     if doc.super_compression_scheme() != SuperCompressionScheme::NONE {
-        // We don't support writing supercompressed data so bail here and skip the file
+        // The round trip compares raw level bytes, which only works for uncompressed data. The
+        // supercompressed writer is covered by the synthetic tests below.
         return Ok(());
     }
 
@@ -381,3 +383,139 @@ fn check_round_trip<R: Read + Seek>(
 
     Ok(())
 }
+
+/// Builds a mip chain of RGBA8 levels for a 'width' x 'height' image. The data is intentionally
+/// very compressible.
+fn make_rgba8_levels(width: u32, height: u32, level_num: u32, seed: u8) -> Vec<Vec<u8>> {
+    (0..level_num)
+        .map(|level| {
+            let w = (width >> level).max(1) as usize;
+            let h = (height >> level).max(1) as usize;
+            (0..w * h * 4)
+                .map(|i| {
+                    ((i / 64) as u8)
+                        .wrapping_add(seed)
+                        .wrapping_add(level as u8)
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn check_supercompressed_2d(compression: SuperCompression) {
+    let levels = make_rgba8_levels(64, 32, 7, 0);
+    let level_refs: Vec<&[u8]> = levels.iter().map(Vec::as_slice).collect();
+
+    let mut output = Vec::new();
+    KtxDocumentDescription::new()
+        .format(VkFormat::R8G8B8A8_UNORM)
+        .super_compression(compression)
+        .image_2d(64, 32, 7, &level_refs)
+        .write(&mut output)
+        .unwrap();
+
+    let doc = KtxDocument::from_slice(&output).unwrap();
+    assert_eq!(doc.super_compression_scheme(), compression.scheme());
+    assert_eq!(doc.level_num(), 7);
+
+    for (i, expected) in levels.iter().enumerate() {
+        let info = doc.get_level_info(i as u32).unwrap();
+        assert_eq!(info.size_uncompressed, expected.len() as u64);
+
+        let data = doc.read_level(i as u32).unwrap();
+        assert_eq!(&data, expected);
+
+        // The mapped-file path should decode identically, and only copy when it has to
+        let mapped = doc.level_data(&output, i as u32).unwrap();
+        assert_eq!(mapped.as_ref(), expected.as_slice());
+        assert_eq!(
+            matches!(mapped, Cow::Borrowed(_)),
+            compression == SuperCompression::None
+        );
+    }
+
+    // Our test data compresses well so the largest level should shrink
+    let info = doc.get_level_info(0).unwrap();
+    if compression == SuperCompression::None {
+        assert_eq!(info.size, info.size_uncompressed);
+    } else {
+        assert!(info.size < info.size_uncompressed);
+    }
+}
+
+#[test]
+fn test_write_zstd_2d() {
+    check_supercompressed_2d(SuperCompression::Zstd(3));
+}
+
+#[test]
+fn test_write_zlib_2d() {
+    check_supercompressed_2d(SuperCompression::Zlib(6));
+}
+
+#[test]
+fn test_write_uncompressed_read_level() {
+    check_supercompressed_2d(SuperCompression::None);
+}
+
+#[test]
+fn test_write_zstd_cube_array() {
+    let layer_num = 2;
+    let level_num = 3;
+    let layer_num_real = layer_num as usize * 6;
+
+    // Generate each face/layer as its own mip chain, stored in the ImageSet order
+    let chains: Vec<Vec<Vec<u8>>> = (0..layer_num_real)
+        .map(|layer| make_rgba8_levels(8, 8, level_num, layer as u8 * 17))
+        .collect();
+    let images: Vec<&[u8]> = chains.iter().flatten().map(Vec::as_slice).collect();
+
+    let mut output = Vec::new();
+    KtxDocumentDescription::new()
+        .format(VkFormat::R8G8B8A8_UNORM)
+        .super_compression(SuperCompression::Zstd(0))
+        .cube_array(8, 8, layer_num, level_num, &images)
+        .write(&mut output)
+        .unwrap();
+
+    let doc = KtxDocument::from_slice(&output).unwrap();
+    assert_eq!(doc.document_type(), DocumentType::CubeArray);
+    assert_eq!(doc.super_compression_scheme(), SuperCompressionScheme::ZSTD);
+
+    for level_i in 0..level_num as usize {
+        let data = doc.read_level(level_i as u32).unwrap();
+
+        let expected: Vec<u8> = (0..layer_num_real)
+            .flat_map(|layer| {
+                let i = calculate_set_index(layer_num_real, level_num as usize, layer, level_i);
+                images[i].iter().copied()
+            })
+            .collect();
+        assert_eq!(data, expected);
+    }
+}
+
+#[test]
+fn test_read_level_into_too_small() {
+    let levels = make_rgba8_levels(4, 4, 1, 0);
+    let level_refs: Vec<&[u8]> = levels.iter().map(Vec::as_slice).collect();
+
+    let mut output = Vec::new();
+    KtxDocumentDescription::new()
+        .format(VkFormat::R8G8B8A8_UNORM)
+        .super_compression(SuperCompression::Zlib(9))
+        .image_2d(4, 4, 1, &level_refs)
+        .write(&mut output)
+        .unwrap();
+
+    let doc = KtxDocument::from_slice(&output).unwrap();
+
+    let mut dst = [0u8; 8];
+    let err = doc.read_level_into(0, &mut dst).err().unwrap();
+    assert!(matches!(err, KtxReadError::DestBufferTooSmall(64)));
+
+    let mut dst = [0u8; 128];
+    let written = doc.read_level_into(0, &mut dst).unwrap();
+    assert_eq!(written, 64);
+    assert_eq!(&dst[0..64], levels[0].as_slice());
+}
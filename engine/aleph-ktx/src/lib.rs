@@ -46,6 +46,8 @@ pub use data_format_descriptor::{
 pub use document::{
     DocumentType, FileIndex, KtxDocument, KtxReadError, LevelIndex, SuperCompressionScheme,
 };
-pub use encoder::{ENCODER_NAME, ImageSet, KtxDocumentDescription, LevelNum, calculate_set_index};
+pub use encoder::{
+    ENCODER_NAME, ImageSet, KtxDocumentDescription, LevelNum, SuperCompression, calculate_set_index,
+};
 pub use format::{ALLOWED_FORMATS, format_type_size, is_format_prohibited};
 pub use kvd::{KtxOrientation, KtxSwizzle};
@@ -1630,7 +1630,7 @@ unsafe extern "C" {
 
     pub fn JS_SetHostPromiseRejectionTracker(rt: NonNull<JSRuntime>, cb: *mut JSHostPromiseRejectionTrackerFn, opaque: *mut c_void);
 
-    pub fn JS_SetInterruptHandler(rt: NonNull<JSRuntime>, cb: *mut JSInterruptHandlerFn, opaque: *mut c_void);
+    pub fn JS_SetInterruptHandler(rt: NonNull<JSRuntime>, cb: Option<JSInterruptHandlerFn>, opaque: *mut c_void);
     pub fn JS_SetCanBlock(rt: NonNull<JSRuntime>, can_block: bool);
     pub fn JS_SetIsHTMLDDA(ctx: NonNull<JSContext>, obj: JSValueConst);
 
@@ -28,7 +28,7 @@
 //
 
 use core::str;
-use std::ffi::{c_char, c_int};
+use std::ffi::{c_char, c_int, c_void};
 use std::fmt::{Debug, Formatter};
 use std::mem::transmute;
 use std::ops::Deref;
@@ -38,10 +38,12 @@ use aleph_nstr::NStr;
 use raw::{JSEvalOptions, JSTag, JSValue};
 
 use crate::class::ClassOpaqueContainer;
+use crate::typed_array::{buffer_slice_parts, free_boxed_slice, into_raw_boxed_slice};
 use crate::{
-    ArgValue, Atom, Class, ClassOpaque, ClassOpaqueHandle, HostFn, HostFnCombineFloat, HostFnData,
-    HostFnMagic, HostFnMapFloat, OwnPropertyNames, RefValue, Runtime, RuntimeString, WeakValue,
-    host_fn_combine_float_arg_num, host_fn_map_float_arg_num,
+    ArgValue, Atom, Class, ClassOpaque, ClassOpaqueHandle, Deserializer, HostFn,
+    HostFnCombineFloat, HostFnData, HostFnMagic, HostFnMapFloat, OwnPropertyNames,
+    PromiseResolvers, RefValue, Runtime, RuntimeString, SerdeError, Serializer, TypedArrayElement,
+    WeakValue, host_fn_combine_float_arg_num, host_fn_map_float_arg_num,
 };
 
 pub struct Context {
@@ -133,6 +135,136 @@ impl WeakContext {
         }
     }
 
+    /// Evaluates the given script as an ES module, returning the module's namespace object.
+    ///
+    /// The `filename` is used as the module's name. Any `import` statements in the module are
+    /// resolved relative to it by the runtime's [`crate::IModuleLoader`], see
+    /// [`crate::Runtime::set_module_loader`].
+    ///
+    /// # Info
+    ///
+    /// Module evaluation is asynchronous in the JS spec to support top-level await. This function
+    /// runs the runtime's pending jobs until the module has finished evaluating, like
+    /// [`WeakContext::await_promise`], so the namespace object we return is always fully
+    /// initialized. An exception thrown by the module body is returned as an error.
+    pub fn eval_module(&self, script: &NStr, filename: &NStr) -> Result<RefValue, Exception<'_>> {
+        unsafe {
+            let options = JSEvalOptions {
+                eval_flags: raw::JSEvalFlags::MODULE | raw::JSEvalFlags::COMPILE_ONLY,
+                filename: filename.to_cstr_ptr(),
+                line_num: 1,
+                ..Default::default()
+            };
+            let v = raw::JS_Eval2(self.c, script.to_cstr_ptr(), script.len(), &options);
+            if v.is_exception() {
+                return Err(self.get_exception());
+            }
+
+            // Compiling the module registers it with the context, so the module definition lives
+            // on after JS_EvalFunction consumes the value.
+            let m = NonNull::new_unchecked(v.get_ptr()).cast::<raw::JSModuleDef>();
+            let promise = raw::JS_EvalFunction(self.c, v);
+            let promise = self.maybe_exception(RefValue::new(promise))?;
+            self.await_promise(&promise)?;
+
+            let v = raw::JS_GetModuleNamespace(self.c, m);
+            self.maybe_exception(RefValue::new(v))
+        }
+    }
+
+    /// Creates a new pending promise, returning the promise object and the functions that settle
+    /// it.
+    ///
+    /// This is the building block for async host functions. A host function can create a promise,
+    /// hand the [`PromiseResolvers`] to some async operation and return the promise to the caller
+    /// immediately. The script can then `await` the promise, which will resume once the host
+    /// settles it and runs the runtime's pending jobs.
+    #[inline]
+    pub fn new_promise(&self) -> Result<(RefValue, PromiseResolvers), Exception<'_>> {
+        unsafe {
+            let mut funcs = [JSValue::UNDEFINED; 2];
+            let v = raw::JS_NewPromiseCapability(self.c, funcs.as_mut_ptr());
+            let promise = self.maybe_exception(RefValue::new(v))?;
+            let [resolve, reject] = funcs.map(RefValue::new);
+            Ok((promise, PromiseResolvers { resolve, reject }))
+        }
+    }
+
+    /// Returns the state of the given promise, or [`None`] if the value isn't a promise.
+    #[inline]
+    pub fn promise_state(&self, v: &WeakValue) -> Option<raw::JSPromiseStateEnum> {
+        unsafe {
+            if v.is_promise() {
+                Some(raw::JS_PromiseState(self.c, v.0))
+            } else {
+                None
+            }
+        }
+    }
+
+    /// Returns the value the given promise was fulfilled with, or the reason it was rejected with.
+    /// Will return 'undefined' if the promise is still pending or the value isn't a promise.
+    #[inline]
+    pub fn promise_result(&self, v: &WeakValue) -> RefValue {
+        unsafe {
+            if v.is_promise() {
+                RefValue::new(raw::JS_PromiseResult(self.c, v.0))
+            } else {
+                RefValue::new(JSValue::UNDEFINED)
+            }
+        }
+    }
+
+    /// Blocks on the given promise by running the runtime's pending jobs until the promise settles.
+    /// Returns the fulfilled value, or the rejection reason as an error. Like the JS `await`
+    /// operator, a value that isn't a promise is simply returned as is.
+    ///
+    /// # Warning
+    ///
+    /// This can only wait on work that is driven by the job queue. If the promise is waiting on
+    /// the host to settle it (i.e. it came from [`WeakContext::new_promise`]) and the job queue runs
+    /// dry before it settles then an error is thrown, as the promise can't make progress. Poll
+    /// [`WeakContext::promise_state`] alongside [`crate::Runtime::execute_pending_jobs`] for those
+    /// cases instead.
+    pub fn await_promise(&self, v: &WeakValue) -> Result<RefValue, Exception<'_>> {
+        unsafe {
+            if !v.is_promise() {
+                return Ok(v.upgrade());
+            }
+
+            let rt = raw::JS_GetRuntime(self.c).unwrap_unchecked();
+            while raw::JS_PromiseState(self.c, v.0) == raw::JSPromiseStateEnum::PENDING {
+                let mut pctx = std::ptr::null_mut();
+                let result = raw::JS_ExecutePendingJob(rt, &mut pctx);
+                if result == 0 {
+                    raw::JS_ThrowPlainError(
+                        self.c,
+                        c"Awaited promise can never settle, the job queue is empty".as_ptr(),
+                    );
+                    return Err(self.get_exception());
+                }
+                if result < 0 {
+                    if pctx == self.c.as_ptr() {
+                        return Err(self.get_exception());
+                    }
+
+                    // A job from another context threw. Clear the exception from that context so
+                    // it doesn't leak into the next call made on it.
+                    if let Some(pctx) = NonNull::new(pctx) {
+                        drop(RefValue::new(raw::JS_GetException(pctx)));
+                    }
+                }
+            }
+
+            let result = RefValue::new(raw::JS_PromiseResult(self.c, v.0));
+            if raw::JS_PromiseState(self.c, v.0) == raw::JSPromiseStateEnum::REJECTED {
+                Err(Exception::new(result, self))
+            } else {
+                Ok(result)
+            }
+        }
+    }
+
     /// Returns the global object [`RefValue`] for this context.
     #[inline]
     pub fn get_global_object(&self) -> RefValue {
@@ -185,6 +317,111 @@ impl WeakContext {
         }
     }
 
+    #[inline]
+    pub fn new_array(&self) -> Result<RefValue, Exception<'_>> {
+        unsafe {
+            let v = raw::JS_NewArray(self.c);
+            self.maybe_exception(RefValue::new(v))
+        }
+    }
+
+    /// Creates a new `ArrayBuffer` that takes ownership of the given bytes, without copying them.
+    /// The memory is handed back to Rust and freed once the buffer is garbage collected.
+    pub fn new_array_buffer(&self, data: impl Into<Box<[u8]>>) -> Result<RefValue, Exception<'_>> {
+        let (ptr, len) = into_raw_boxed_slice(data.into());
+        unsafe {
+            let v = raw::JS_NewArrayBuffer(
+                self.c,
+                ptr,
+                len,
+                free_boxed_slice::<u8>,
+                len as *mut c_void,
+                false,
+            );
+            self.maybe_exception(RefValue::new(v))
+        }
+    }
+
+    /// Creates a new `ArrayBuffer` holding a copy of the given bytes.
+    #[inline]
+    pub fn new_array_buffer_copy(&self, data: &[u8]) -> Result<RefValue, Exception<'_>> {
+        unsafe {
+            let v = raw::JS_NewArrayBufferCopy(self.c, data.as_ptr(), data.len());
+            self.maybe_exception(RefValue::new(v))
+        }
+    }
+
+    /// Creates a new `Uint8Array` that takes ownership of the given bytes, without copying them.
+    /// The memory is handed back to Rust and freed once the array's buffer is garbage collected.
+    pub fn new_uint8_array(&self, data: impl Into<Box<[u8]>>) -> Result<RefValue, Exception<'_>> {
+        let (ptr, len) = into_raw_boxed_slice(data.into());
+        unsafe {
+            let v = raw::JS_NewUint8Array(
+                self.c,
+                ptr,
+                len,
+                free_boxed_slice::<u8>,
+                len as *mut c_void,
+                false,
+            );
+            self.maybe_exception(RefValue::new(v))
+        }
+    }
+
+    /// Creates a new typed array of the type matching `T` that takes ownership of the given
+    /// elements, without copying them. The memory is handed back to Rust and freed once the
+    /// array's buffer is garbage collected.
+    pub fn new_typed_array<T: TypedArrayElement>(
+        &self,
+        data: impl Into<Box<[T]>>,
+    ) -> Result<RefValue, Exception<'_>> {
+        let (ptr, len) = into_raw_boxed_slice(data.into());
+        let buffer = unsafe {
+            let v = raw::JS_NewArrayBuffer(
+                self.c,
+                ptr.cast(),
+                len * size_of::<T>(),
+                free_boxed_slice::<T>,
+                len as *mut c_void,
+                false,
+            );
+            self.maybe_exception(RefValue::new(v))?
+        };
+        self.new_typed_array_args::<T>(&[buffer.0.0, JSValue::new_i32(0), JSValue::UNDEFINED])
+    }
+
+    /// Creates a new typed array of the type matching `T`, holding a copy of the given elements.
+    pub fn new_typed_array_copy<T: TypedArrayElement>(
+        &self,
+        data: &[T],
+    ) -> Result<RefValue, Exception<'_>> {
+        let bytes = std::ptr::slice_from_raw_parts(data.as_ptr() as *const u8, size_of_val(data));
+        // Safety: TypedArrayElement types are plain-old-data so can be viewed as bytes
+        let buffer = self.new_array_buffer_copy(unsafe { &*bytes })?;
+        self.new_typed_array_args::<T>(&[buffer.0.0, JSValue::new_i32(0), JSValue::UNDEFINED])
+    }
+
+    /// Creates a new typed array of the type matching `T` with 'len' zeroed elements.
+    pub fn new_typed_array_zeroed<T: TypedArrayElement>(
+        &self,
+        len: usize,
+    ) -> Result<RefValue, Exception<'_>> {
+        let len = JSValue::new_f64(len as f64);
+        self.new_typed_array_args::<T>(&[len, JSValue::UNDEFINED, JSValue::UNDEFINED])
+    }
+
+    /// The typed array constructor reads the offset and length args even when they're not passed,
+    /// so we must always provide all three.
+    fn new_typed_array_args<T: TypedArrayElement>(
+        &self,
+        args: &[JSValue; 3],
+    ) -> Result<RefValue, Exception<'_>> {
+        unsafe {
+            let v = raw::JS_NewTypedArray(self.c, 3, args.as_ptr(), T::TYPE);
+            self.maybe_exception(RefValue::new(v))
+        }
+    }
+
     #[inline]
     pub fn new_object_class<T: Class<Opaque: ClassOpaque>>(
         &self,
@@ -377,6 +614,44 @@ impl WeakContext {
         }
     }
 
+    #[inline]
+    pub fn get_index(&self, v: &WeakValue, index: u32) -> Result<RefValue, Exception<'_>> {
+        unsafe {
+            let v = raw::JS_GetPropertyUint32(self.c, v.0, index);
+            self.maybe_exception(RefValue::new(v))
+        }
+    }
+
+    #[inline]
+    pub fn set_index(
+        &self,
+        v: &WeakValue,
+        index: u32,
+        set: RefValue,
+    ) -> Result<bool, Exception<'_>> {
+        unsafe {
+            let set = set.detatch();
+            let result = raw::JS_SetPropertyUint32(self.c, v.0, index, set);
+            self.check_exception(result, result != 0)
+        }
+    }
+
+    /// Reads the 'length' property of the given object as an integer.
+    #[inline]
+    pub fn get_length(&self, v: &WeakValue) -> Result<i64, Exception<'_>> {
+        unsafe {
+            let mut len = 0;
+            let result = raw::JS_GetLength(self.c, v.0, &mut len);
+            self.check_exception(result, len)
+        }
+    }
+
+    /// Whether the given value is callable as a function.
+    #[inline]
+    pub fn is_function(&self, v: &WeakValue) -> bool {
+        unsafe { raw::JS_IsFunction(self.c, v.0) }
+    }
+
     #[inline]
     pub fn delete_property_str(&self, v: &WeakValue, prop: &str) -> Result<(), Exception<'_>> {
         let a = self.new_atom(prop).ok_or(Exception::undefined(self))?;
@@ -536,6 +811,22 @@ impl WeakContext {
         Ok(v)
     }
 
+    /// Converts any [`serde::Serialize`] type into a JS value, see [`crate::Serializer`].
+    #[inline]
+    pub fn serialize<T: ?Sized + serde::Serialize>(&self, v: &T) -> Result<RefValue, SerdeError> {
+        v.serialize(Serializer::new(self))
+    }
+
+    /// Converts a JS value into any [`serde::de::DeserializeOwned`] type, see
+    /// [`crate::Deserializer`].
+    #[inline]
+    pub fn deserialize<T: serde::de::DeserializeOwned>(
+        &self,
+        v: &WeakValue,
+    ) -> Result<T, SerdeError> {
+        T::deserialize(Deserializer::new(self, v))
+    }
+
     #[inline]
     pub fn atom_to_value(&self, atom: &Atom) -> Result<RefValue, Exception<'_>> {
         unsafe {
@@ -648,6 +939,156 @@ impl WeakContext {
         }
     }
 
+    /// Borrows the contents of the given `ArrayBuffer`, returning [`None`] if 'v' isn't an
+    /// `ArrayBuffer` or has been detached.
+    ///
+    /// # Warning
+    ///
+    /// The borrow keeps the buffer alive, but can't stop JS code from writing to, resizing or
+    /// detaching it. Don't run any JS code (calls, evals, pending jobs) while holding the slice.
+    pub fn borrow_array_buffer<'a>(&self, v: &'a WeakValue) -> Option<&'a [u8]> {
+        unsafe {
+            let out = self.array_buffer_parts::<u8>(v)?;
+            Some(out.as_ref())
+        }
+    }
+
+    /// Mutably borrows the contents of the given `ArrayBuffer`, returning [`None`] if 'v' isn't an
+    /// `ArrayBuffer` or has been detached.
+    ///
+    /// Similar to [`WeakContext::borrow_opaque_mut`] this performs a dynamic borrow check, only
+    /// returning a slice if 'v' holds the only reference to the buffer. Typed arrays viewing the
+    /// buffer hold a reference to it, so a buffer with live views can't be borrowed mutably.
+    ///
+    /// The same warning as [`WeakContext::borrow_array_buffer`] applies about running JS code.
+    pub fn borrow_array_buffer_mut<'a>(&self, v: &'a mut RefValue) -> Option<&'a mut [u8]> {
+        unsafe {
+            if !matches!(v.0.0.get_ref_count(), Some(1)) {
+                return None;
+            }
+            let mut out = self.array_buffer_parts::<u8>(v)?;
+            Some(out.as_mut())
+        }
+    }
+
+    /// Borrows the elements of the given typed array, returning [`None`] if 'v' isn't a typed
+    /// array with elements of type `T`, if its buffer has been detached, or if its byte offset is
+    /// not aligned for `T`.
+    ///
+    /// # Warning
+    ///
+    /// The borrow keeps the array (and its buffer) alive, but can't stop JS code from writing to,
+    /// resizing or detaching it. Don't run any JS code (calls, evals, pending jobs) while holding
+    /// the slice.
+    pub fn borrow_typed_array<'a, T: TypedArrayElement>(
+        &self,
+        v: &'a WeakValue,
+    ) -> Option<&'a [T]> {
+        unsafe {
+            let (out, _buffer) = self.typed_array_parts::<T>(v)?;
+            Some(out.as_ref())
+        }
+    }
+
+    /// Mutably borrows the elements of the given typed array, with the same failure conditions as
+    /// [`WeakContext::borrow_typed_array`].
+    ///
+    /// Similar to [`WeakContext::borrow_opaque_mut`] this performs a dynamic borrow check. A slice
+    /// is only returned if 'v' holds the only reference to the typed array _and_ the typed array
+    /// holds the only reference to its buffer. Otherwise another view (or the buffer itself) may
+    /// still be reachable from somewhere else and the borrow would not be exclusive.
+    ///
+    /// The same warning as [`WeakContext::borrow_typed_array`] applies about running JS code.
+    pub fn borrow_typed_array_mut<'a, T: TypedArrayElement>(
+        &self,
+        v: &'a mut RefValue,
+    ) -> Option<&'a mut [T]> {
+        unsafe {
+            if !matches!(v.0.0.get_ref_count(), Some(1)) {
+                return None;
+            }
+            let (mut out, buffer) = self.typed_array_parts::<T>(v)?;
+
+            // One reference from the typed array, one from our own 'buffer' handle
+            if !matches!(buffer.get_ref_count(), Some(2)) {
+                return None;
+            }
+            Some(out.as_mut())
+        }
+    }
+
+    /// Mutably borrows the elements of the given typed array without the dynamic borrow check
+    /// performed by [`WeakContext::borrow_typed_array_mut`].
+    ///
+    /// This is intended for host functions that fill in arrays passed in by a script, where the
+    /// script is guaranteed to hold other references to the array.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure no other borrow of the same memory (through this array, another
+    /// view or the buffer) is live for the lifetime of the returned slice, and that no JS code
+    /// runs while it is held.
+    #[allow(clippy::mut_from_ref)]
+    pub unsafe fn borrow_typed_array_mut_unchecked<'a, T: TypedArrayElement>(
+        &self,
+        v: &'a WeakValue,
+    ) -> Option<&'a mut [T]> {
+        unsafe {
+            let (mut out, _buffer) = self.typed_array_parts::<T>(v)?;
+            Some(out.as_mut())
+        }
+    }
+
+    /// Internal implementation for the `ArrayBuffer` borrow functions.
+    unsafe fn array_buffer_parts<T>(&self, v: &WeakValue) -> Option<NonNull<[T]>> {
+        unsafe {
+            if !v.is_array_buffer() {
+                return None;
+            }
+            let mut byte_length = 0;
+            let ptr = raw::JS_GetArrayBuffer(self.c, &mut byte_length, v.0);
+            if ptr.is_null() && raw::JS_HasException(self.c) {
+                // Detached buffers throw, we don't care about the exception. Empty buffers may
+                // have no allocation but don't throw.
+                drop(self.get_exception());
+                return None;
+            }
+            buffer_slice_parts::<T>(ptr, byte_length)
+        }
+    }
+
+    /// Internal implementation for the typed array borrow functions. Also returns the buffer
+    /// backing the array.
+    unsafe fn typed_array_parts<T: TypedArrayElement>(
+        &self,
+        v: &WeakValue,
+    ) -> Option<(NonNull<[T]>, RefValue)> {
+        unsafe {
+            if !v.typed_array_type().is_some_and(T::is_compatible) {
+                return None;
+            }
+            let mut byte_offset = 0;
+            let mut byte_length = 0;
+            let buffer = raw::JS_GetTypedArrayBuffer(
+                self.c,
+                v.0,
+                &mut byte_offset,
+                &mut byte_length,
+                std::ptr::null_mut(),
+            );
+            // Detached and out-of-bounds arrays throw, we don't care about the exception
+            let buffer = self.maybe_exception(RefValue::new(buffer)).ok()?;
+
+            let bytes = self.array_buffer_parts::<u8>(&buffer)?;
+            if byte_offset.checked_add(byte_length)? > bytes.len() {
+                return None;
+            }
+            let ptr = bytes.cast::<u8>().as_ptr().wrapping_add(byte_offset);
+            let out = buffer_slice_parts::<T>(ptr, byte_length)?;
+            Some((out, buffer))
+        }
+    }
+
     pub(crate) fn check_exception<T>(&self, r: c_int, v: T) -> Result<T, Exception<'_>> {
         if r < 0 {
             Err(self.get_exception())
@@ -682,6 +1123,10 @@ pub struct Exception<'a> {
 }
 
 impl<'a> Exception<'a> {
+    pub(crate) const fn new(v: RefValue, c: &'a WeakContext) -> Self {
+        Self { v, c }
+    }
+
     pub(crate) const fn undefined(c: &'a WeakContext) -> Self {
         Self {
             v: RefValue::new(JSValue::UNDEFINED),
@@ -694,6 +1139,14 @@ impl<'a> Exception<'a> {
         self.c
     }
 
+    /// Returns whether the exception is an 'uncatchable' error. QuickJS uses these to unwind a
+    /// script that was interrupted by the runtime's interrupt handler, see
+    /// [`Runtime::set_interrupt_handler`].
+    #[inline]
+    pub fn is_uncatchable(&self) -> bool {
+        unsafe { raw::JS_IsUncatchableError(self.v.0.0) }
+    }
+
     /// Unwrap and return the inner [`RefValue`].
     #[inline]
     pub fn into_inner(self) -> RefValue {
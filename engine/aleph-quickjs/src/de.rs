@@ -0,0 +1,492 @@
+//
+//
+// This file is a part of Aleph
+//
+// https://github.com/nathanvoglsam/aleph
+//
+// MIT License
+//
+// Copyright (c) 2020 Aleph Engine
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use raw::JSTag;
+use serde::de::value::{SeqDeserializer, StringDeserializer};
+use serde::de::{
+    DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess, SeqAccess, VariantAccess, Visitor,
+};
+use serde::forward_to_deserialize_any;
+
+use crate::{Atom, RefValue, SerdeError, WeakContext, WeakValue};
+
+/// A [`serde::Deserializer`] that reads a JS value directly into any `Deserialize` type, without
+/// going through an intermediate representation like `serde_json::Value`.
+///
+/// This is the inverse of [`crate::Serializer`]. JS has a single number type so integral numbers
+/// are visited as integers and everything else as `f64`, letting the target type decide how strict
+/// to be. Object properties with an `undefined` or function value are skipped, like
+/// `JSON.stringify` would, and functions anywhere else read as `null`. A `Uint8Array` reads as a
+/// sequence of numbers, or as a byte buffer for types that ask for one.
+///
+/// Arrays and objects can be nested at most 128 deep. Deeper values, including any value
+/// that contains itself, are an error rather than overflowing the stack.
+pub struct Deserializer<'a> {
+    ctx: &'a WeakContext,
+    v: RefValue,
+
+    /// How many arrays and objects enclose the value we're reading.
+    depth: u32,
+}
+
+/// The deepest that arrays and objects can be nested within a value passed to a [`Deserializer`].
+const MAX_DEPTH: u32 = 128;
+
+impl<'a> Deserializer<'a> {
+    /// Constructs a new [`Deserializer`] that will read from the given value.
+    #[inline]
+    pub fn new(ctx: &'a WeakContext, v: &WeakValue) -> Self {
+        Self {
+            ctx,
+            v: v.upgrade(),
+            depth: 0,
+        }
+    }
+
+    /// The depth of values nested within the one we're reading, or an error if that would exceed
+    /// [`MAX_DEPTH`].
+    fn nested_depth(&self) -> Result<u32, SerdeError> {
+        if self.depth >= MAX_DEPTH {
+            return Err(SerdeError::new(format!(
+                "JS value is nested more than {MAX_DEPTH} deep, or contains itself"
+            )));
+        }
+        Ok(self.depth + 1)
+    }
+
+    fn read_string(&self) -> String {
+        // to_c_str yields None for empty strings
+        self.ctx
+            .to_c_str(&self.v)
+            .map(|v| v.to_string())
+            .unwrap_or_default()
+    }
+
+    fn is_nullish(&self) -> bool {
+        self.v.is_null() || self.v.is_undefined()
+    }
+}
+
+impl<'de, 'a> serde::Deserializer<'de> for Deserializer<'a> {
+    type Error = SerdeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        match self.v.get_tag() {
+            JSTag::NULL | JSTag::UNDEFINED => visitor.visit_unit(),
+            JSTag::BOOL => visitor.visit_bool(self.v.get_bool().unwrap_or_default()),
+            JSTag::INT | JSTag::FLOAT64 => match self.v.get_number() {
+                Some(crate::NumberVariant::Integer(v)) => visitor.visit_i32(v),
+                Some(crate::NumberVariant::Double(v)) => {
+                    let is_integral = v.fract() == 0.0 && v.abs() < 9007199254740992.0;
+                    if is_integral {
+                        visitor.visit_i64(v as i64)
+                    } else {
+                        visitor.visit_f64(v)
+                    }
+                }
+                None => Err(SerdeError::new("Invalid JS number")),
+            },
+            JSTag::STRING => visitor.visit_string(self.read_string()),
+            JSTag::OBJECT => {
+                if self.ctx.is_function(&self.v) {
+                    visitor.visit_unit()
+                } else if self.v.is_array() {
+                    let len = self.ctx.get_length(&self.v)?;
+                    let len = u32::try_from(len)
+                        .map_err(|_| SerdeError::new("JS array length out of range"))?;
+                    let depth = self.nested_depth()?;
+                    visitor.visit_seq(ArrayAccess {
+                        ctx: self.ctx,
+                        array: self.v,
+                        index: 0,
+                        len,
+                        depth,
+                    })
+                } else if let Some(bytes) = self.uint8_array() {
+                    // Treat byte arrays like any other array unless asked for bytes explicitly
+                    let seq = SeqDeserializer::<_, SerdeError>::new(bytes.iter().copied());
+                    visitor.visit_seq(seq)
+                } else {
+                    let depth = self.nested_depth()?;
+                    visitor.visit_map(ObjectAccess::new(self.ctx, self.v, depth)?)
+                }
+            }
+            tag => Err(SerdeError::new(format!(
+                "Can't deserialize JS value with tag '{tag:?}'"
+            ))),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        if self.is_nullish() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        if self.is_nullish() {
+            visitor.visit_unit()
+        } else {
+            self.deserialize_any(visitor)
+        }
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        if self.v.is_string() {
+            // Unit variants are just the variant name
+            return visitor.visit_enum(EnumDeserializer {
+                ctx: self.ctx,
+                variant: self.read_string(),
+                value: None,
+                depth: self.depth,
+            });
+        }
+
+        if self.v.is_object() {
+            // Anything else is an object with a single key, the variant name, holding the value
+            let depth = self.nested_depth()?;
+            let mut access = ObjectAccess::new(self.ctx, self.v, depth)?;
+            if access.keys.len() == 1 {
+                let atom = access.keys.pop().unwrap();
+                let variant = self.ctx.atom_to_c_str(&atom)?.to_string();
+                let value = self.ctx.get_property(&access.object, &atom)?;
+                return visitor.visit_enum(EnumDeserializer {
+                    ctx: self.ctx,
+                    variant,
+                    value: Some(value),
+                    depth,
+                });
+            }
+        }
+
+        Err(SerdeError::new(
+            "Expected a string or an object with a single key for an enum",
+        ))
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        match self.uint8_array() {
+            Some(bytes) => visitor.visit_bytes(bytes),
+            None => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        self.deserialize_bytes(visitor)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+impl<'a> Deserializer<'a> {
+    fn uint8_array(&self) -> Option<&[u8]> {
+        // The slice borrows 'self', which holds a reference to the array so it can't be collected
+        // while borrowed. Our deserializer never runs JS code that could resize or detach the
+        // buffer.
+        self.ctx.borrow_typed_array::<u8>(&self.v)
+    }
+}
+
+struct ArrayAccess<'a> {
+    ctx: &'a WeakContext,
+    array: RefValue,
+    index: u32,
+    len: u32,
+
+    /// The depth of the array's elements.
+    depth: u32,
+}
+
+impl<'de, 'a> SeqAccess<'de> for ArrayAccess<'a> {
+    type Error = SerdeError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, SerdeError> {
+        if self.index >= self.len {
+            return Ok(None);
+        }
+        let v = self.ctx.get_index(&self.array, self.index)?;
+        self.index += 1;
+        let depth = self.depth;
+        seed.deserialize(Deserializer {
+            ctx: self.ctx,
+            v,
+            depth,
+        })
+        .map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some((self.len - self.index) as usize)
+    }
+}
+
+struct ObjectAccess<'a> {
+    ctx: &'a WeakContext,
+    object: RefValue,
+
+    /// The remaining keys to visit, in reverse order so we can pop from the back
+    keys: Vec<Atom>,
+
+    /// The value for the key we've just yielded from next_key_seed
+    value: Option<RefValue>,
+
+    /// The depth of the object's property values.
+    depth: u32,
+}
+
+impl<'a> ObjectAccess<'a> {
+    fn new(ctx: &'a WeakContext, object: RefValue, depth: u32) -> Result<Self, SerdeError> {
+        let opts =
+            raw::JSGetPropertyNameOption::STRING_MASK | raw::JSGetPropertyNameOption::ENUM_ONLY;
+        let props = ctx.get_own_property_names(&object, opts)?;
+        let mut keys: Vec<Atom> = props.iter().filter_map(|v| v.atom).collect();
+        drop(props);
+        keys.reverse();
+        Ok(Self {
+            ctx,
+            object,
+            keys,
+            value: None,
+            depth,
+        })
+    }
+}
+
+impl<'de, 'a> MapAccess<'de> for ObjectAccess<'a> {
+    type Error = SerdeError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, SerdeError> {
+        while let Some(atom) = self.keys.pop() {
+            let value = self.ctx.get_property(&self.object, &atom)?;
+            if value.is_undefined() || self.ctx.is_function(&value) {
+                continue;
+            }
+            self.value = Some(value);
+
+            let key = self.ctx.atom_to_c_str(&atom)?;
+            let key = MapKeyDeserializer { key: &key };
+            return seed.deserialize(key).map(Some);
+        }
+        Ok(None)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, SerdeError> {
+        let v = self
+            .value
+            .take()
+            .ok_or_else(|| SerdeError::new("next_value_seed called before next_key_seed"))?;
+        seed.deserialize(Deserializer {
+            ctx: self.ctx,
+            v,
+            depth: self.depth,
+        })
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.keys.len())
+    }
+}
+
+/// Object keys are always strings in JS, but we allow deserializing them as numbers too so maps
+/// with integer keys round trip.
+struct MapKeyDeserializer<'a> {
+    key: &'a str,
+}
+
+macro_rules! deserialize_parsed_key {
+    ($($method:ident => $visit:ident),* $(,)?) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+                match self.key.parse() {
+                    Ok(v) => visitor.$visit(v),
+                    Err(_) => visitor.visit_str(self.key),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de, 'a> serde::Deserializer<'de> for MapKeyDeserializer<'a> {
+    type Error = SerdeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        visitor.visit_str(self.key)
+    }
+
+    deserialize_parsed_key! {
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+        deserialize_bool => visit_bool,
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        visitor.visit_enum(self.key.into_deserializer())
+    }
+
+    forward_to_deserialize_any! {
+        i128 u128 char str string bytes byte_buf option unit unit_struct seq tuple tuple_struct
+        map struct identifier ignored_any
+    }
+}
+
+struct EnumDeserializer<'a> {
+    ctx: &'a WeakContext,
+    variant: String,
+    value: Option<RefValue>,
+    depth: u32,
+}
+
+impl<'de, 'a> EnumAccess<'de> for EnumDeserializer<'a> {
+    type Error = SerdeError;
+    type Variant = VariantDeserializer<'a>;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, VariantDeserializer<'a>), SerdeError> {
+        let variant: StringDeserializer<SerdeError> = self.variant.into_deserializer();
+        let variant = seed.deserialize(variant)?;
+        let access = VariantDeserializer {
+            ctx: self.ctx,
+            value: self.value,
+            depth: self.depth,
+        };
+        Ok((variant, access))
+    }
+}
+
+struct VariantDeserializer<'a> {
+    ctx: &'a WeakContext,
+    value: Option<RefValue>,
+    depth: u32,
+}
+
+impl<'a> VariantDeserializer<'a> {
+    fn into_value(self) -> Result<Deserializer<'a>, SerdeError> {
+        match self.value {
+            Some(v) => Ok(Deserializer {
+                ctx: self.ctx,
+                v,
+                depth: self.depth,
+            }),
+            None => Err(SerdeError::new("Expected an enum variant with a value")),
+        }
+    }
+}
+
+impl<'de, 'a> VariantAccess<'de> for VariantDeserializer<'a> {
+    type Error = SerdeError;
+
+    fn unit_variant(self) -> Result<(), SerdeError> {
+        match self.value {
+            None => Ok(()),
+            Some(v) if v.is_null() || v.is_undefined() => Ok(()),
+            Some(_) => Err(SerdeError::new("Expected a unit enum variant")),
+        }
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(
+        self,
+        seed: T,
+    ) -> Result<T::Value, SerdeError> {
+        seed.deserialize(self.into_value()?)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        serde::Deserializer::deserialize_seq(self.into_value()?, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        serde::Deserializer::deserialize_map(self.into_value()?, visitor)
+    }
+}
@@ -0,0 +1,108 @@
+//
+//
+// This file is a part of Aleph
+//
+// https://github.com/nathanvoglsam/aleph
+//
+// MIT License
+//
+// Copyright (c) 2020 Aleph Engine
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use std::ffi::{c_int, c_void};
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::ptr::NonNull;
+use std::time::{Duration, Instant};
+
+use crate::runtime::InnerRuntime;
+
+/// A limit on how long scripts are allowed to run for before they're interrupted, for use with
+/// [`crate::Runtime::set_execution_budget`].
+///
+/// QuickJS doesn't check the interrupt handler on every instruction. The engine counts down on
+/// every function call and loop iteration and polls the handler each time the counter runs out,
+/// which happens roughly once every 10,000 calls or iterations. A 'tick' is one of these polls.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum ExecutionBudget {
+    /// Interrupt any script still running once the given instant has passed.
+    Deadline(Instant),
+
+    /// Interrupt any script still running once the runtime has polled the interrupt handler the
+    /// given number of times.
+    Ticks(u64),
+}
+
+impl ExecutionBudget {
+    /// Constructs a [`ExecutionBudget::Deadline`] that expires the given duration from now.
+    #[inline]
+    pub fn timeout(duration: Duration) -> Self {
+        Self::Deadline(Instant::now() + duration)
+    }
+
+    /// Converts the budget into an interrupt handler closure that returns true once the budget is
+    /// spent.
+    pub(crate) fn into_handler(self) -> Box<dyn FnMut() -> bool> {
+        match self {
+            ExecutionBudget::Deadline(deadline) => Box::new(move || Instant::now() >= deadline),
+            ExecutionBudget::Ticks(mut remaining) => Box::new(move || {
+                if remaining == 0 {
+                    true
+                } else {
+                    remaining -= 1;
+                    false
+                }
+            }),
+        }
+    }
+}
+
+/// Installs or removes the interrupt handler trampoline on the given runtime. The `opaque` pointer
+/// must point to the [`InnerRuntime`] that owns `rt`.
+pub(crate) unsafe fn install_interrupt_hook(
+    rt: NonNull<raw::JSRuntime>,
+    opaque: *const InnerRuntime,
+    enabled: bool,
+) {
+    unsafe {
+        let cb = if enabled {
+            Some(interrupt_handler as raw::JSInterruptHandlerFn)
+        } else {
+            None
+        };
+        raw::JS_SetInterruptHandler(rt, cb, opaque as *mut c_void);
+    }
+}
+
+extern "C" fn interrupt_handler(_rt: NonNull<raw::JSRuntime>, opaque: *mut c_void) -> c_int {
+    let result = catch_unwind(AssertUnwindSafe(|| unsafe {
+        let inner = &*(opaque as *const InnerRuntime);
+
+        // A failed borrow means the handler is being replaced from inside itself, which we treat
+        // as 'keep running'.
+        match inner.1.interrupt_handler.try_borrow_mut() {
+            Ok(mut handler) => handler.as_mut().is_some_and(|f| f()),
+            Err(_) => false,
+        }
+    }));
+
+    // There's no sensible way to keep running the script if the handler panicked, so interrupt it
+    result.unwrap_or(true) as c_int
+}
@@ -33,10 +33,17 @@ pub extern crate aleph_quickjs_sys as raw;
 mod atom;
 mod class;
 mod context;
+mod de;
 mod host_function;
+mod interrupt;
+mod module_loader;
 mod own_property_names;
+mod promise;
 mod runtime;
 mod runtime_string;
+mod ser;
+mod serde_error;
+mod typed_array;
 mod value;
 
 pub use atom::Atom;
@@ -45,6 +52,7 @@ pub use class::{
     get_or_init_class_id_for,
 };
 pub use context::{Context, Exception, WeakContext};
+pub use de::Deserializer;
 pub use host_function::{
     HostFn, HostFnCombineFloat, HostFnData, HostFnMagic, HostFnMapFloat, SignatureHostFn,
     SignatureHostFnCombineFloat, SignatureHostFnData, SignatureHostFnMagic,
@@ -52,9 +60,17 @@ pub use host_function::{
     host_fn_combine_float_arg_num, host_fn_data_arg_num, host_fn_data_data_num,
     host_fn_magic_arg_num, host_fn_map_float_arg_num, this_val_arg, value_list_arg,
 };
+pub use interrupt::ExecutionBudget;
+pub use module_loader::{
+    DirectoryModuleLoader, IModuleLoader, RouterModuleLoader, resolve_module_name,
+};
 pub use own_property_names::{OwnPropertyNames, PropertyEnum};
+pub use promise::{PendingJobError, PromiseResolvers};
 pub use runtime::Runtime;
 pub use runtime_string::RuntimeString;
+pub use ser::{MapSerializer, SeqSerializer, Serializer, VariantSerializer};
+pub use serde_error::SerdeError;
+pub use typed_array::TypedArrayElement;
 pub use value::{ArgValue, NumberVariant, RefValue, Value, WeakValue};
 
 #[cfg(test)]
@@ -0,0 +1,277 @@
+//
+//
+// This file is a part of Aleph
+//
+// https://github.com/nathanvoglsam/aleph
+//
+// MIT License
+//
+// Copyright (c) 2020 Aleph Engine
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use std::ffi::{CStr, CString, c_char, c_void};
+use std::io;
+use std::io::Read;
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::path::PathBuf;
+use std::ptr::NonNull;
+use std::sync::Arc;
+
+use aleph_vfs::{IRouter, IRouterExt};
+use raw::{JSEvalFlags, JSEvalOptions};
+
+use crate::runtime::InnerRuntime;
+
+/// Interface for resolving and loading the source of ES modules requested by an `import`
+/// statement.
+///
+/// A loader is installed on a [`crate::Runtime`] with [`crate::Runtime::set_module_loader`] and is
+/// shared by every context created from that runtime. Module names are opaque strings to QuickJS,
+/// the loader is responsible for turning the name from an `import` statement into a canonical name
+/// with [`IModuleLoader::normalize`] and then fetching the source text for that canonical name with
+/// [`IModuleLoader::load`]. A module is only loaded once per context for each canonical name.
+pub trait IModuleLoader {
+    /// Resolves the module `name`, as written in an `import` statement inside the module `base`,
+    /// into a canonical module name.
+    ///
+    /// The default implementation treats module names as '/' separated paths, see
+    /// [`resolve_module_name`].
+    fn normalize(&self, base: &str, name: &str) -> io::Result<String> {
+        resolve_module_name(base, name)
+    }
+
+    /// Loads the source text for the module with the given canonical name.
+    fn load(&self, name: &str) -> io::Result<String>;
+}
+
+/// Resolves an import specifier against the name of the importing module, treating both as '/'
+/// separated paths.
+///
+/// - Names starting with `./` or `../` are resolved relative to the directory containing `base`.
+/// - All other names are resolved relative to the root.
+///
+/// The output is always an absolute path with all `.` and `..` segments collapsed. A name that
+/// tries to walk above the root is rejected.
+pub fn resolve_module_name(base: &str, name: &str) -> io::Result<String> {
+    if name.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Empty module name",
+        ));
+    }
+
+    let mut segments = Vec::new();
+    let is_relative = name.starts_with("./") || name.starts_with("../");
+    if is_relative {
+        push_segments(&mut segments, base)?;
+
+        // Strip off the importing module's file name to get the directory it lives in
+        segments.pop();
+    }
+    push_segments(&mut segments, name)?;
+
+    let mut out = String::with_capacity(name.len() + 1);
+    for segment in segments {
+        out.push('/');
+        out.push_str(segment);
+    }
+    Ok(out)
+}
+
+fn push_segments<'a>(segments: &mut Vec<&'a str>, path: &'a str) -> io::Result<()> {
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                if segments.pop().is_none() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("Module path '{path}' escapes the module root"),
+                    ));
+                }
+            }
+            v => segments.push(v),
+        }
+    }
+    Ok(())
+}
+
+/// An [`IModuleLoader`] that loads modules from a directory on the host filesystem.
+///
+/// Canonical module names are paths relative to the loader's root directory, so
+/// `import { f } from "lib/helpers.js"` would load `${ROOT}/lib/helpers.js`.
+pub struct DirectoryModuleLoader {
+    root: PathBuf,
+}
+
+impl DirectoryModuleLoader {
+    /// Constructs a new [`DirectoryModuleLoader`] that loads modules from the given directory.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+}
+
+impl IModuleLoader for DirectoryModuleLoader {
+    fn load(&self, name: &str) -> io::Result<String> {
+        // Names have been normalized so they can't contain '..' segments that would escape root.
+        let path = self.root.join(name.trim_start_matches('/'));
+        std::fs::read_to_string(path)
+    }
+}
+
+/// An [`IModuleLoader`] that loads modules from an aleph-vfs [`IRouter`].
+///
+/// Canonical module names are absolute vfs paths, so `import { f } from "/configs/common.js"`
+/// would open `/configs/common.js` in the router.
+pub struct RouterModuleLoader<R: IRouter + ?Sized> {
+    router: Arc<R>,
+}
+
+impl<R: IRouter + ?Sized> RouterModuleLoader<R> {
+    /// Constructs a new [`RouterModuleLoader`] that loads modules from the given router.
+    pub fn new(router: Arc<R>) -> Self {
+        Self { router }
+    }
+}
+
+impl<R: IRouter + ?Sized> IModuleLoader for RouterModuleLoader<R> {
+    fn load(&self, name: &str) -> io::Result<String> {
+        let file = self.router.open(name)?;
+        let mut string = String::new();
+        file.reader().read_to_string(&mut string)?;
+        Ok(string)
+    }
+}
+
+/// Installs the module loader trampolines on the given runtime. The `opaque` pointer must point to
+/// the [`InnerRuntime`] that owns `rt`.
+pub(crate) unsafe fn install_module_loader_hooks(
+    rt: NonNull<raw::JSRuntime>,
+    opaque: *const InnerRuntime,
+) {
+    unsafe {
+        raw::JS_SetModuleLoaderFunc(rt, module_normalize, module_loader, opaque as *mut c_void);
+    }
+}
+
+extern "C" fn module_normalize(
+    ctx: NonNull<raw::JSContext>,
+    module_base_name: *const c_char,
+    module_name: *const c_char,
+    opaque: *mut c_void,
+) -> *mut c_char {
+    let result = catch_unwind(AssertUnwindSafe(|| unsafe {
+        let base = CStr::from_ptr(module_base_name).to_string_lossy();
+        let name = CStr::from_ptr(module_name).to_string_lossy();
+
+        let loader = get_loader(opaque);
+        let normalized = loader
+            .ok_or_else(|| io::Error::new(io::ErrorKind::Unsupported, "No module loader"))
+            .and_then(|v| v.normalize(&base, &name));
+        match normalized {
+            Ok(v) => {
+                // QuickJS takes ownership of the string, so it must be allocated with js_malloc
+                let out = raw::js_malloc(ctx, v.len() + 1) as *mut u8;
+                if out.is_null() {
+                    raw::JS_ThrowOutOfMemory(ctx);
+                    return std::ptr::null_mut();
+                }
+                std::ptr::copy_nonoverlapping(v.as_ptr(), out, v.len());
+                out.add(v.len()).write(0);
+                out as *mut c_char
+            }
+            Err(e) => {
+                throw_reference_error(ctx, &format!("Could not resolve module '{name}': {e}"));
+                std::ptr::null_mut()
+            }
+        }
+    }));
+    result.unwrap_or_else(|_| unsafe {
+        raw::JS_ThrowPlainError(ctx, c"Native Panic".as_ptr());
+        std::ptr::null_mut()
+    })
+}
+
+extern "C" fn module_loader(
+    ctx: NonNull<raw::JSContext>,
+    module_name: *const c_char,
+    opaque: *mut c_void,
+) -> Option<NonNull<raw::JSModuleDef>> {
+    let result = catch_unwind(AssertUnwindSafe(|| unsafe {
+        let name = CStr::from_ptr(module_name).to_string_lossy();
+
+        let loader = get_loader(opaque);
+        let source = loader
+            .ok_or_else(|| io::Error::new(io::ErrorKind::Unsupported, "No module loader"))
+            .and_then(|v| v.load(&name));
+        let mut source = match source {
+            Ok(v) => v,
+            Err(e) => {
+                throw_reference_error(ctx, &format!("Could not load module '{name}': {e}"));
+                return None;
+            }
+        };
+
+        // QuickJS requires the source buffer to be null terminated
+        source.push('\0');
+        let options = JSEvalOptions {
+            eval_flags: JSEvalFlags::MODULE | JSEvalFlags::COMPILE_ONLY,
+            filename: module_name,
+            line_num: 1,
+            ..Default::default()
+        };
+        let v = raw::JS_Eval2(
+            ctx,
+            source.as_ptr() as *const c_char,
+            source.len() - 1,
+            &options,
+        );
+        if v.is_exception() {
+            return None;
+        }
+
+        // The module definition is owned by the context's module list, the value returned from
+        // the compile only eval is just a reference we can release.
+        let m = v.get_ptr();
+        raw::JS_FreeValue(ctx, v);
+        NonNull::new(m.cast())
+    }));
+    result.unwrap_or_else(|_| unsafe {
+        raw::JS_ThrowPlainError(ctx, c"Native Panic".as_ptr());
+        None
+    })
+}
+
+unsafe fn get_loader(opaque: *mut c_void) -> Option<std::rc::Rc<dyn IModuleLoader>> {
+    unsafe {
+        // We clone the loader out of the runtime so it stays alive even if the loader is replaced
+        // while it's running.
+        let inner = &*(opaque as *const InnerRuntime);
+        inner.1.module_loader.borrow().clone()
+    }
+}
+
+unsafe fn throw_reference_error(ctx: NonNull<raw::JSContext>, message: &str) {
+    unsafe {
+        let message = CString::new(message.replace('\0', "")).unwrap_or_default();
+        raw::JS_ThrowReferenceError(ctx, c"%s".as_ptr(), message.as_ptr());
+    }
+}
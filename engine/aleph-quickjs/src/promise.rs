@@ -0,0 +1,95 @@
+//
+//
+// This file is a part of Aleph
+//
+// https://github.com/nathanvoglsam/aleph
+//
+// MIT License
+//
+// Copyright (c) 2020 Aleph Engine
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use std::fmt::{Debug, Formatter};
+
+use crate::{Context, Exception, RefValue, Value, WeakContext, WeakValue};
+
+/// The pair of functions that settle a promise created with [`WeakContext::new_promise`].
+///
+/// Host code can hold on to this to settle the promise at some later point, such as when an async
+/// engine operation completes. Settling consumes the resolvers as a promise can only be settled
+/// once.
+///
+/// # Info
+///
+/// Settling a promise doesn't run any of the promise's reactions (`then` callbacks, or the
+/// continuation of an `async` function). Those are queued as jobs on the runtime, which will only
+/// run when the host calls [`crate::Runtime::execute_pending_jobs`] or awaits a promise with
+/// [`WeakContext::await_promise`].
+pub struct PromiseResolvers {
+    pub(crate) resolve: RefValue,
+    pub(crate) reject: RefValue,
+}
+
+impl PromiseResolvers {
+    /// Fulfills the promise with the given value.
+    #[inline]
+    pub fn resolve<'a>(self, ctx: &'a WeakContext, v: &WeakValue) -> Result<(), Exception<'a>> {
+        ctx.call(&self.resolve, &Value::UNDEFINED, &[v.as_arg()])?;
+        Ok(())
+    }
+
+    /// Rejects the promise with the given reason.
+    #[inline]
+    pub fn reject<'a>(self, ctx: &'a WeakContext, reason: &WeakValue) -> Result<(), Exception<'a>> {
+        ctx.call(&self.reject, &Value::UNDEFINED, &[reason.as_arg()])?;
+        Ok(())
+    }
+}
+
+/// The error returned when a job run by [`crate::Runtime::execute_pending_jobs`] throws an
+/// exception.
+///
+/// Jobs are queued on the runtime and can come from any context created from it, so this holds on
+/// to the context the job was run in alongside the exception value.
+pub struct PendingJobError {
+    pub(crate) context: Context,
+    pub(crate) exception: RefValue,
+}
+
+impl PendingJobError {
+    /// Get the context the job that threw was run in.
+    #[inline]
+    pub fn context(&self) -> &Context {
+        &self.context
+    }
+
+    /// Get the exception thrown by the job.
+    #[inline]
+    pub fn exception(&self) -> Exception<'_> {
+        Exception::new(self.exception.clone(), &self.context)
+    }
+}
+
+impl Debug for PendingJobError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&self.exception(), f)
+    }
+}
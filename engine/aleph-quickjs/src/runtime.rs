@@ -28,7 +28,7 @@
 //
 
 use std::alloc::Layout;
-use std::cell::OnceCell;
+use std::cell::{OnceCell, RefCell};
 use std::ffi::c_void;
 use std::ptr::NonNull;
 use std::rc::Rc;
@@ -36,7 +36,9 @@ use std::rc::Rc;
 use aleph_alloc::alloc::Allocator;
 use aleph_alloc::mallocator::Mallocator;
 
-use crate::{Context, WeakContext};
+use crate::interrupt::install_interrupt_hook;
+use crate::module_loader::install_module_loader_hooks;
+use crate::{Context, ExecutionBudget, IModuleLoader, PendingJobError, WeakContext};
 
 #[derive(Clone)]
 pub struct Runtime(pub(crate) Rc<InnerRuntime>);
@@ -53,7 +55,7 @@ impl Runtime {
         let rt = THREAD_RUNTIME.with(|rt| {
             let rt = rt.get_or_init(|| unsafe {
                 let rt = raw::JS_NewRuntime().unwrap();
-                Rc::new(InnerRuntime(rt))
+                InnerRuntime::new(rt)
             });
             rt.clone()
         });
@@ -132,7 +134,7 @@ impl Runtime {
                 };
                 let alloc = NonNull::from(a).cast().as_ptr();
                 let rt = raw::JS_NewRuntime2(&functions, alloc).unwrap();
-                Rc::new(InnerRuntime(rt))
+                InnerRuntime::new(rt)
             });
             rt.clone()
         });
@@ -168,6 +170,136 @@ impl Runtime {
             usage
         }
     }
+
+    /// Sets the maximum number of bytes the runtime is allowed to have allocated at once. A limit of
+    /// zero removes the limit.
+    ///
+    /// An allocation that would exceed the limit fails, which throws an 'out of memory' exception
+    /// in the script that tried to allocate. The exception is returned to the host like any other
+    /// exception.
+    #[inline]
+    pub fn set_memory_limit(&self, limit: usize) {
+        unsafe {
+            raw::JS_SetMemoryLimit(self.0.0, limit);
+        }
+    }
+
+    /// Returns the number of allocated bytes that will trigger the next automatic GC cycle.
+    #[inline]
+    pub fn gc_threshold(&self) -> usize {
+        unsafe { raw::JS_GetGCThreshold(self.0.0) }
+    }
+
+    /// Sets the number of allocated bytes that will trigger the next automatic GC cycle.
+    #[inline]
+    pub fn set_gc_threshold(&self, threshold: usize) {
+        unsafe {
+            raw::JS_SetGCThreshold(self.0.0, threshold);
+        }
+    }
+
+    /// Sets a handler that the runtime will periodically call while running scripts. If the handler
+    /// returns true the running script is interrupted.
+    ///
+    /// An interrupted script is unwound with an uncatchable 'interrupted' exception. Scripts can't
+    /// swallow it with a `try/catch` block, so it always makes its way back to the host where it
+    /// can be identified with [`crate::Exception::is_uncatchable`].
+    ///
+    /// Replaces any previously set handler, including one set by
+    /// [`Runtime::set_execution_budget`].
+    pub fn set_interrupt_handler(&self, handler: impl FnMut() -> bool + 'static) {
+        self.set_interrupt_handler_boxed(Box::new(handler));
+    }
+
+    /// Interrupts any script that runs past the given [`ExecutionBudget`].
+    ///
+    /// The budget is spent across all script execution on the runtime from the moment this is
+    /// called, so to limit individual calls the budget should be set again before each call. Once
+    /// spent, every script run on the runtime will be interrupted until the budget is replaced or
+    /// cleared with [`Runtime::clear_interrupt_handler`].
+    pub fn set_execution_budget(&self, budget: ExecutionBudget) {
+        self.set_interrupt_handler_boxed(budget.into_handler());
+    }
+
+    /// Removes the runtime's interrupt handler, if one was set.
+    pub fn clear_interrupt_handler(&self) {
+        unsafe {
+            install_interrupt_hook(self.0.0, Rc::as_ptr(&self.0), false);
+        }
+        self.0.1.interrupt_handler.take();
+    }
+
+    fn set_interrupt_handler_boxed(&self, handler: Box<dyn FnMut() -> bool>) {
+        self.0.1.interrupt_handler.replace(Some(handler));
+        unsafe {
+            install_interrupt_hook(self.0.0, Rc::as_ptr(&self.0), true);
+        }
+    }
+
+    /// Returns whether there are any jobs waiting in the runtime's job queue.
+    #[inline]
+    pub fn is_job_pending(&self) -> bool {
+        unsafe { raw::JS_IsJobPending(self.0.0) }
+    }
+
+    /// Runs a single job from the runtime's job queue, if there is one. Returns whether a job was
+    /// run.
+    ///
+    /// See [`Runtime::execute_pending_jobs`].
+    pub fn execute_pending_job(&self) -> Result<bool, PendingJobError> {
+        unsafe {
+            let mut pctx = std::ptr::null_mut();
+            let result = raw::JS_ExecutePendingJob(self.0.0, &mut pctx);
+            if result < 0 {
+                // Take our own reference to the context the job ran in so the error can outlive any
+                // other owner of the context.
+                let ctx = NonNull::new(pctx).unwrap();
+                let ctx = raw::JS_DupContext(ctx).unwrap();
+                let context = Context {
+                    ctx: WeakContext { c: ctx },
+                    r: self.clone(),
+                };
+                let exception = context.get_exception().into_inner();
+                Err(PendingJobError { context, exception })
+            } else {
+                Ok(result > 0)
+            }
+        }
+    }
+
+    /// Runs jobs from the runtime's job queue until the queue is empty. Returns the number of jobs
+    /// that were run.
+    ///
+    /// Jobs are how the JS engine runs promise reactions, which includes `then` callbacks and the
+    /// continuation of `async` functions after an `await`. No promise will ever settle its
+    /// dependents unless the host periodically drains the job queue with this function.
+    ///
+    /// Running a job may queue more jobs, which will also be run before this function returns. If
+    /// a job throws an exception this function stops and returns the exception, leaving any
+    /// remaining jobs in the queue.
+    pub fn execute_pending_jobs(&self) -> Result<usize, PendingJobError> {
+        let mut count = 0;
+        while self.execute_pending_job()? {
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Sets the [`IModuleLoader`] that will be used to resolve and load the source of modules that
+    /// are requested by `import` statements in any context created from this runtime.
+    ///
+    /// Replaces any previously set loader. Without a loader any `import` of a module that isn't
+    /// already loaded in the context will throw a `ReferenceError`.
+    #[inline]
+    pub fn set_module_loader(&self, loader: impl IModuleLoader + 'static) {
+        self.0.1.module_loader.replace(Some(Rc::new(loader)));
+    }
+
+    /// Removes the module loader from the runtime, if one was set.
+    #[inline]
+    pub fn clear_module_loader(&self) {
+        self.0.1.module_loader.take();
+    }
 }
 
 pub(crate) fn with_runtime<F, R>(f: F) -> R
@@ -177,13 +309,34 @@ where
     THREAD_RUNTIME.with(|rt| {
         let rt = rt.get_or_init(|| unsafe {
             let rt = raw::JS_NewRuntime().unwrap();
-            Rc::new(InnerRuntime(rt))
+            InnerRuntime::new(rt)
         });
         f(&rt)
     })
 }
 
-pub(crate) struct InnerRuntime(pub(crate) NonNull<raw::JSRuntime>);
+pub(crate) struct InnerRuntime(pub(crate) NonNull<raw::JSRuntime>, pub(crate) RuntimeHooks);
+
+impl InnerRuntime {
+    unsafe fn new(rt: NonNull<raw::JSRuntime>) -> Rc<Self> {
+        unsafe {
+            let out = Rc::new(Self(rt, RuntimeHooks::default()));
+
+            // The hooks get a pointer back to the InnerRuntime so they can find the Rust side
+            // state. This is stable as we never move out of the Rc.
+            install_module_loader_hooks(rt, Rc::as_ptr(&out));
+
+            out
+        }
+    }
+}
+
+/// Rust side state for the callbacks we install into the QuickJS runtime.
+#[derive(Default)]
+pub(crate) struct RuntimeHooks {
+    pub(crate) module_loader: RefCell<Option<Rc<dyn IModuleLoader>>>,
+    pub(crate) interrupt_handler: RefCell<Option<Box<dyn FnMut() -> bool>>>,
+}
 
 impl Drop for InnerRuntime {
     #[inline]
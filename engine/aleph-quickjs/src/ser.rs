@@ -0,0 +1,412 @@
+//
+//
+// This file is a part of Aleph
+//
+// https://github.com/nathanvoglsam/aleph
+//
+// MIT License
+//
+// Copyright (c) 2020 Aleph Engine
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use serde::Serialize;
+use serde::ser::{
+    SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant, SerializeTuple,
+    SerializeTupleStruct, SerializeTupleVariant,
+};
+
+use crate::{RefValue, SerdeError, Value, WeakContext};
+
+/// A [`serde::Serializer`] that builds a JS value directly from any [`Serialize`] type, without
+/// going through an intermediate representation like `serde_json::Value`.
+///
+/// The mapping follows what `JSON.parse` would produce from the `serde_json` output for the same
+/// type:
+///
+/// - Numbers become JS numbers. 64-bit integers outside the safe integer range lose precision.
+/// - `None` and `()` become `null`.
+/// - Sequences and tuples become arrays, maps and structs become plain objects.
+/// - Enums use serde's 'externally tagged' representation.
+///
+/// The exception is byte buffers (`serialize_bytes`), which become a `Uint8Array`.
+#[derive(Copy, Clone)]
+pub struct Serializer<'a> {
+    ctx: &'a WeakContext,
+}
+
+impl<'a> Serializer<'a> {
+    /// Constructs a new [`Serializer`] that will create values in the given context.
+    #[inline]
+    pub fn new(ctx: &'a WeakContext) -> Self {
+        Self { ctx }
+    }
+
+    fn new_variant_object(&self, variant: &str, v: RefValue) -> Result<RefValue, SerdeError> {
+        let object = self.ctx.new_object()?;
+        self.ctx.set_property_str(&object, variant, v)?;
+        Ok(object)
+    }
+}
+
+impl<'a> serde::Serializer for Serializer<'a> {
+    type Ok = RefValue;
+    type Error = SerdeError;
+    type SerializeSeq = SeqSerializer<'a>;
+    type SerializeTuple = SeqSerializer<'a>;
+    type SerializeTupleStruct = SeqSerializer<'a>;
+    type SerializeTupleVariant = VariantSerializer<'a, SeqSerializer<'a>>;
+    type SerializeMap = MapSerializer<'a>;
+    type SerializeStruct = MapSerializer<'a>;
+    type SerializeStructVariant = VariantSerializer<'a, MapSerializer<'a>>;
+
+    fn serialize_bool(self, v: bool) -> Result<RefValue, SerdeError> {
+        Ok(Value::new_bool(v).into_ref())
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<RefValue, SerdeError> {
+        Ok(Value::new_i32(v as i32).into_ref())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<RefValue, SerdeError> {
+        Ok(Value::new_i32(v as i32).into_ref())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<RefValue, SerdeError> {
+        Ok(Value::new_i32(v).into_ref())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<RefValue, SerdeError> {
+        Ok(Value::new_i64(v).into_ref())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<RefValue, SerdeError> {
+        Ok(Value::new_i32(v as i32).into_ref())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<RefValue, SerdeError> {
+        Ok(Value::new_i32(v as i32).into_ref())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<RefValue, SerdeError> {
+        Ok(Value::new_u32(v).into_ref())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<RefValue, SerdeError> {
+        match i64::try_from(v) {
+            Ok(v) => Ok(Value::new_i64(v).into_ref()),
+            Err(_) => Ok(Value::new_f64(v as f64).into_ref()),
+        }
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<RefValue, SerdeError> {
+        Ok(Value::new_f64(v as f64).into_ref())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<RefValue, SerdeError> {
+        Ok(Value::new_f64(v).into_ref())
+    }
+
+    fn serialize_char(self, v: char) -> Result<RefValue, SerdeError> {
+        let mut buffer = [0; 4];
+        self.serialize_str(v.encode_utf8(&mut buffer))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<RefValue, SerdeError> {
+        Ok(self.ctx.new_string(v)?)
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<RefValue, SerdeError> {
+        unsafe {
+            let v = raw::JS_NewUint8ArrayCopy(self.ctx.c, v.as_ptr(), v.len());
+            Ok(self.ctx.maybe_exception(RefValue::new(v))?)
+        }
+    }
+
+    fn serialize_none(self) -> Result<RefValue, SerdeError> {
+        self.serialize_unit()
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<RefValue, SerdeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<RefValue, SerdeError> {
+        Ok(Value::NULL.into_ref())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<RefValue, SerdeError> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<RefValue, SerdeError> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<RefValue, SerdeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<RefValue, SerdeError> {
+        let value = value.serialize(self)?;
+        self.new_variant_object(variant, value)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<SeqSerializer<'a>, SerdeError> {
+        Ok(SeqSerializer {
+            ctx: self.ctx,
+            array: self.ctx.new_array()?,
+            index: 0,
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SeqSerializer<'a>, SerdeError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SeqSerializer<'a>, SerdeError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, SerdeError> {
+        Ok(VariantSerializer {
+            ser: self,
+            variant,
+            inner: self.serialize_seq(Some(len))?,
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<MapSerializer<'a>, SerdeError> {
+        Ok(MapSerializer {
+            ctx: self.ctx,
+            object: self.ctx.new_object()?,
+            key: None,
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<MapSerializer<'a>, SerdeError> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant, SerdeError> {
+        Ok(VariantSerializer {
+            ser: self,
+            variant,
+            inner: self.serialize_map(Some(len))?,
+        })
+    }
+}
+
+/// Builds a JS array for [`Serializer`]
+pub struct SeqSerializer<'a> {
+    ctx: &'a WeakContext,
+    array: RefValue,
+    index: u32,
+}
+
+impl<'a> SeqSerializer<'a> {
+    fn push<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), SerdeError> {
+        let value = value.serialize(Serializer::new(self.ctx))?;
+        self.ctx.set_index(&self.array, self.index, value)?;
+        self.index = self
+            .index
+            .checked_add(1)
+            .ok_or_else(|| SerdeError::new("Sequence too long for a JS array"))?;
+        Ok(())
+    }
+}
+
+impl<'a> SerializeSeq for SeqSerializer<'a> {
+    type Ok = RefValue;
+    type Error = SerdeError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<RefValue, SerdeError> {
+        Ok(self.array)
+    }
+}
+
+impl<'a> SerializeTuple for SeqSerializer<'a> {
+    type Ok = RefValue;
+    type Error = SerdeError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<RefValue, SerdeError> {
+        Ok(self.array)
+    }
+}
+
+impl<'a> SerializeTupleStruct for SeqSerializer<'a> {
+    type Ok = RefValue;
+    type Error = SerdeError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<RefValue, SerdeError> {
+        Ok(self.array)
+    }
+}
+
+/// Builds a JS object for [`Serializer`]
+pub struct MapSerializer<'a> {
+    ctx: &'a WeakContext,
+    object: RefValue,
+    key: Option<String>,
+}
+
+impl<'a> MapSerializer<'a> {
+    fn insert<T: ?Sized + Serialize>(&mut self, key: &str, value: &T) -> Result<(), SerdeError> {
+        let value = value.serialize(Serializer::new(self.ctx))?;
+        self.ctx.set_property_str(&self.object, key, value)?;
+        Ok(())
+    }
+}
+
+impl<'a> SerializeMap for MapSerializer<'a> {
+    type Ok = RefValue;
+    type Error = SerdeError;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), SerdeError> {
+        // JS object keys are always strings. We accept anything that serializes to a string or a
+        // number and stringify it, like JS would.
+        let key = key.serialize(Serializer::new(self.ctx))?;
+        if !key.is_string() && !key.is_number() {
+            return Err(SerdeError::new("Map keys must be strings or numbers"));
+        }
+        let key = self.ctx.to_string(&key)?;
+        let key = match self.ctx.to_c_str(&key) {
+            Some(v) => v.to_string(),
+            // 'to_c_str' can't tell the empty string apart from a failed conversion
+            None if self.ctx.get_length(&key)? == 0 => String::new(),
+            None => return Err(SerdeError::new("Failed to convert map key to a string")),
+        };
+        self.key = Some(key);
+        Ok(())
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), SerdeError> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| SerdeError::new("serialize_value called before serialize_key"))?;
+        self.insert(&key, value)
+    }
+
+    fn end(self) -> Result<RefValue, SerdeError> {
+        Ok(self.object)
+    }
+}
+
+impl<'a> SerializeStruct for MapSerializer<'a> {
+    type Ok = RefValue;
+    type Error = SerdeError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), SerdeError> {
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Result<RefValue, SerdeError> {
+        Ok(self.object)
+    }
+}
+
+/// Wraps the output of another serializer in an externally tagged enum object for [`Serializer`]
+pub struct VariantSerializer<'a, S> {
+    ser: Serializer<'a>,
+    variant: &'static str,
+    inner: S,
+}
+
+impl<'a> SerializeTupleVariant for VariantSerializer<'a, SeqSerializer<'a>> {
+    type Ok = RefValue;
+    type Error = SerdeError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.inner.push(value)
+    }
+
+    fn end(self) -> Result<RefValue, SerdeError> {
+        self.ser.new_variant_object(self.variant, self.inner.array)
+    }
+}
+
+impl<'a> SerializeStructVariant for VariantSerializer<'a, MapSerializer<'a>> {
+    type Ok = RefValue;
+    type Error = SerdeError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), SerdeError> {
+        self.inner.insert(key, value)
+    }
+
+    fn end(self) -> Result<RefValue, SerdeError> {
+        self.ser.new_variant_object(self.variant, self.inner.object)
+    }
+}
@@ -27,15 +27,20 @@
 // SOFTWARE.
 //
 
-use std::collections::HashSet;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::io;
 use std::rc::Rc;
+use std::time::Duration;
 
 use aleph_nstr::{NStr, nstr};
 use raw::*;
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    ArgValue, NumberVariant, RefValue, Runtime, Value, WeakContext, WeakValue, make_host_fn,
-    make_host_fn_combine_float, make_host_fn_map_float, new_class,
+    ArgValue, DirectoryModuleLoader, ExecutionBudget, IModuleLoader, NumberVariant,
+    PromiseResolvers, RefValue, Runtime, Value, WeakContext, WeakValue, make_host_fn,
+    make_host_fn_combine_float, make_host_fn_map_float, new_class, resolve_module_name,
 };
 
 #[test]
@@ -701,3 +706,721 @@ pub fn string_with_internal_null_character() {
         });
     });
 }
+
+struct MapModuleLoader(HashMap<&'static str, &'static str>);
+
+impl IModuleLoader for MapModuleLoader {
+    fn load(&self, name: &str) -> io::Result<String> {
+        self.0
+            .get(name)
+            .map(|v| v.to_string())
+            .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))
+    }
+}
+
+#[test]
+pub fn resolve_module_names() {
+    assert_eq!(resolve_module_name("/a/b.js", "./c.js").unwrap(), "/a/c.js");
+    assert_eq!(resolve_module_name("/a/b.js", "../c.js").unwrap(), "/c.js");
+    assert_eq!(
+        resolve_module_name("/a/b.js", "./d/./e.js").unwrap(),
+        "/a/d/e.js"
+    );
+    assert_eq!(resolve_module_name("/a/b.js", "c.js").unwrap(), "/c.js");
+    assert_eq!(resolve_module_name("b.js", "./c.js").unwrap(), "/c.js");
+    assert_eq!(
+        resolve_module_name("/a/b.js", "/x/y.js").unwrap(),
+        "/x/y.js"
+    );
+    assert!(resolve_module_name("/a/b.js", "../../c.js").is_err());
+    assert!(resolve_module_name("/a/b.js", "").is_err());
+}
+
+#[test]
+pub fn eval_module_with_imports() {
+    std::thread::scope(|scope| {
+        scope.spawn(move || {
+            let runtime = Runtime::init_thread_runtime();
+            runtime.set_module_loader(MapModuleLoader(HashMap::from([
+                (
+                    "/lib/math.js",
+                    "export function add(a, b) { return a + b; }",
+                ),
+                (
+                    "/lib/consts.js",
+                    "import { add } from './math.js'; export const FOUR = add(2, 2);",
+                ),
+            ])));
+            let context = runtime.new_context().unwrap();
+
+            let filename = nstr!("/main.js");
+            let script =
+                nstr!("import { FOUR } from './lib/consts.js'; export const value = FOUR * 2;");
+            let namespace = context.eval_module(script, filename).unwrap();
+            assert!(namespace.is_object());
+
+            let value = context.get_property_str(&namespace, "value").unwrap();
+            assert_eq!(value.get_number(), Some(NumberVariant::Integer(8)));
+
+            runtime.clear_module_loader();
+        });
+    });
+}
+
+#[test]
+pub fn eval_module_errors() {
+    std::thread::scope(|scope| {
+        scope.spawn(move || {
+            let runtime = Runtime::init_thread_runtime();
+            runtime.set_module_loader(MapModuleLoader(HashMap::from([(
+                "/throws.js",
+                "throw new Error('oh no');",
+            )])));
+            let context = runtime.new_context().unwrap();
+
+            let filename = nstr!("/main.js");
+
+            // Missing module
+            let script = nstr!("import { x } from './missing.js';");
+            let result = context.eval_module(script, filename);
+            let message = format!("{:?}", result.err().unwrap());
+            assert!(message.contains("missing.js"), "{message}");
+
+            // Syntax error
+            let script = nstr!("export const = 1;");
+            assert!(context.eval_module(script, filename).is_err());
+
+            // Module body throws
+            let script = nstr!("import './throws.js';");
+            let result = context.eval_module(script, filename);
+            let message = format!("{:?}", result.err().unwrap());
+            assert!(message.contains("oh no"), "{message}");
+        });
+    });
+}
+
+#[test]
+pub fn eval_module_directory_loader() {
+    let root = std::env::temp_dir().join(format!("aleph-quickjs-modules-{}", std::process::id()));
+    std::fs::create_dir_all(root.join("lib")).unwrap();
+    std::fs::write(
+        root.join("lib").join("greet.js"),
+        "export const greet = (v) => `hello ${v}`;",
+    )
+    .unwrap();
+
+    std::thread::scope(|scope| {
+        scope.spawn(|| {
+            let runtime = Runtime::init_thread_runtime();
+            runtime.set_module_loader(DirectoryModuleLoader::new(&root));
+            let context = runtime.new_context().unwrap();
+
+            let filename = nstr!("/main.js");
+            let script =
+                nstr!("import { greet } from 'lib/greet.js'; export const v = greet('world');");
+            let namespace = context.eval_module(script, filename).unwrap();
+
+            let v = context.get_property_str(&namespace, "v").unwrap();
+            let v = context.to_c_str(&v).unwrap();
+            assert_eq!(v.as_ref(), "hello world");
+        });
+    });
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+pub fn await_async_function() {
+    std::thread::scope(|scope| {
+        scope.spawn(move || {
+            let runtime = Runtime::init_thread_runtime();
+            let context = runtime.new_context().unwrap();
+
+            let filename = nstr!("script.js");
+            let script = nstr!(
+                "async function double(v) { await null; return v * 2; }
+                async function fail() { await null; throw new Error('async failure'); }"
+            );
+            let _ = context.eval(script, filename, JSEvalFlags::STRICT).unwrap();
+
+            let global = context.get_global_object();
+            let double = context.get_property_str(&global, "double").unwrap();
+            let arg = Value::new_i32(21);
+            let promise = context
+                .call(&double, &Value::UNDEFINED, &[arg.as_arg()])
+                .unwrap();
+            assert!(promise.is_promise());
+            assert_eq!(
+                context.promise_state(&promise),
+                Some(JSPromiseStateEnum::PENDING)
+            );
+
+            let result = context.await_promise(&promise).unwrap();
+            assert_eq!(result.get_number(), Some(NumberVariant::Integer(42)));
+            assert_eq!(
+                context.promise_state(&promise),
+                Some(JSPromiseStateEnum::FULFILLED)
+            );
+
+            let fail = context.get_property_str(&global, "fail").unwrap();
+            let promise = context.call(&fail, &Value::UNDEFINED, &[]).unwrap();
+            let message = format!("{:?}", context.await_promise(&promise).err().unwrap());
+            assert!(message.contains("async failure"), "{message}");
+
+            // Awaiting a plain value just yields the value
+            let result = context.await_promise(&Value::new_i32(5)).unwrap();
+            assert_eq!(result.get_number(), Some(NumberVariant::Integer(5)));
+            assert_eq!(context.promise_state(&result), None);
+
+            // Exceptions thrown directly by a job are reported by the job queue
+            let script = nstr!("queueMicrotask(() => { throw new Error('job failed'); });");
+            let _ = context.eval(script, filename, JSEvalFlags::STRICT).unwrap();
+            let error = runtime.execute_pending_jobs().err().unwrap();
+            let message = format!("{error:?}");
+            assert!(message.contains("job failed"), "{message}");
+        });
+    });
+}
+
+#[test]
+pub fn async_host_function() {
+    thread_local! {
+        static PENDING: RefCell<Vec<PromiseResolvers>> = const { RefCell::new(Vec::new()) };
+    }
+
+    fn load(ctx: &WeakContext, _this: &WeakValue, _args: &[WeakValue; 0]) -> RefValue {
+        let (promise, resolvers) = ctx.new_promise().unwrap();
+        PENDING.with_borrow_mut(|v| v.push(resolvers));
+        promise
+    }
+
+    std::thread::scope(|scope| {
+        scope.spawn(move || {
+            let runtime = Runtime::init_thread_runtime();
+            let context = runtime.new_context().unwrap();
+
+            let global = context.get_global_object();
+            let func_v = context
+                .new_host_function(make_host_fn!(load), nstr!("load"))
+                .unwrap();
+            context.set_property_str(&global, "load", func_v).unwrap();
+
+            let filename = nstr!("script.js");
+            let script = nstr!(
+                "var loaded = null;
+                var failed = null;
+                (async () => { loaded = await load(); })();
+                load().catch((e) => { failed = e; });"
+            );
+            let _ = context.eval(script, filename, JSEvalFlags::STRICT).unwrap();
+
+            // Nothing can happen until the host settles the promises
+            runtime.execute_pending_jobs().unwrap();
+            assert!(!runtime.is_job_pending());
+            let loaded = context.get_property_str(&global, "loaded").unwrap();
+            assert!(loaded.is_null());
+
+            let mut pending = PENDING.with_borrow_mut(std::mem::take);
+            assert_eq!(pending.len(), 2);
+
+            let reason = context.new_string("file not found").unwrap();
+            pending.pop().unwrap().reject(&context, &reason).unwrap();
+            pending
+                .pop()
+                .unwrap()
+                .resolve(&context, &Value::new_i32(56))
+                .unwrap();
+
+            // Settling only queues the reactions, they run when we drain the job queue
+            assert!(runtime.is_job_pending());
+            let jobs = runtime.execute_pending_jobs().unwrap();
+            assert!(jobs > 0);
+
+            let loaded = context.get_property_str(&global, "loaded").unwrap();
+            assert_eq!(loaded.get_number(), Some(NumberVariant::Integer(56)));
+
+            let failed = context.get_property_str(&global, "failed").unwrap();
+            let failed = context.to_c_str(&failed).unwrap();
+            assert_eq!(failed.as_ref(), "file not found");
+
+            // A host promise that is never settled can't be awaited
+            let (promise, _resolvers) = context.new_promise().unwrap();
+            assert!(context.await_promise(&promise).is_err());
+        });
+    });
+}
+
+#[test]
+pub fn interrupt_with_deadline() {
+    std::thread::scope(|scope| {
+        scope.spawn(move || {
+            let runtime = Runtime::init_thread_runtime();
+            let context = runtime.new_context().unwrap();
+
+            let filename = nstr!("script.js");
+            runtime.set_execution_budget(ExecutionBudget::timeout(Duration::from_millis(20)));
+
+            // Scripts can't catch the interrupt to keep running
+            let script = nstr!("try { while (true) {} } catch (e) {} 'finished'");
+            let error = context
+                .eval(script, filename, JSEvalFlags::STRICT)
+                .err()
+                .unwrap();
+            assert!(error.is_uncatchable());
+            let message = format!("{error:?}");
+            assert!(message.contains("interrupted"), "{message}");
+
+            // The budget stays spent until it's cleared
+            let script = nstr!("for (let i = 0; i < 1000000; i++) {} 2 + 2");
+            assert!(context.eval(script, filename, JSEvalFlags::STRICT).is_err());
+
+            runtime.clear_interrupt_handler();
+            let result = context.eval(script, filename, JSEvalFlags::STRICT).unwrap();
+            assert_eq!(result.get_number(), Some(NumberVariant::Integer(4)));
+        });
+    });
+}
+
+#[test]
+pub fn interrupt_with_tick_budget() {
+    std::thread::scope(|scope| {
+        scope.spawn(move || {
+            let runtime = Runtime::init_thread_runtime();
+            let context = runtime.new_context().unwrap();
+
+            let polls = Rc::new(std::cell::Cell::new(0u64));
+            let handler_polls = polls.clone();
+            runtime.set_interrupt_handler(move || {
+                handler_polls.set(handler_polls.get() + 1);
+                false
+            });
+
+            // A handler that never interrupts lets the script run to completion
+            let filename = nstr!("script.js");
+            let script = nstr!("let n = 0; for (let i = 0; i < 1000000; i++) { n += 1; } n");
+            let result = context.eval(script, filename, JSEvalFlags::STRICT).unwrap();
+            assert_eq!(result.get_number(), Some(NumberVariant::Integer(1000000)));
+            assert!(polls.get() > 0);
+
+            runtime.set_execution_budget(ExecutionBudget::Ticks(2));
+            let script = nstr!("while (true) {}");
+            let error = context
+                .eval(script, filename, JSEvalFlags::STRICT)
+                .err()
+                .unwrap();
+            assert!(error.is_uncatchable());
+
+            runtime.clear_interrupt_handler();
+        });
+    });
+}
+
+#[test]
+pub fn memory_limit_and_gc_threshold() {
+    std::thread::scope(|scope| {
+        scope.spawn(move || {
+            let runtime = Runtime::init_thread_runtime();
+            let context = runtime.new_context().unwrap();
+
+            runtime.set_gc_threshold(1024 * 1024);
+            assert_eq!(runtime.gc_threshold(), 1024 * 1024);
+
+            let usage = runtime.compute_memory_usage();
+            runtime.set_memory_limit(usage.malloc_size as usize + 4 * 1024 * 1024);
+
+            let filename = nstr!("script.js");
+            let script = nstr!("let a = []; while (true) { a.push(new Array(1024).fill(1)); }");
+            let error = context
+                .eval(script, filename, JSEvalFlags::STRICT)
+                .err()
+                .unwrap();
+            assert!(!error.is_uncatchable());
+            let message = format!("{error:?}");
+            assert!(message.contains("out of memory"), "{message}");
+            drop(error);
+
+            runtime.set_memory_limit(0);
+            runtime.gc();
+            let script = nstr!("new Array(1024 * 1024).fill(1).length");
+            let result = context.eval(script, filename, JSEvalFlags::STRICT).unwrap();
+            assert_eq!(
+                result.get_number(),
+                Some(NumberVariant::Integer(1024 * 1024))
+            );
+        });
+    });
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+enum Shape {
+    Empty,
+    Circle(f32),
+    Line(i32, i32),
+    Rect { width: u32, height: u32 },
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+struct Scene {
+    name: String,
+    empty: String,
+    visible: bool,
+    id: u64,
+    offset: i64,
+    scale: f64,
+    parent: Option<u32>,
+    tags: Vec<String>,
+    layers: HashMap<u32, String>,
+    shapes: Vec<Shape>,
+    pair: (u8, char),
+}
+
+struct Bytes<'a>(&'a [u8]);
+
+impl Serialize for Bytes<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(self.0)
+    }
+}
+
+#[test]
+pub fn serde_round_trip() {
+    std::thread::scope(|scope| {
+        scope.spawn(move || {
+            let runtime = Runtime::init_thread_runtime();
+            let context = runtime.new_context().unwrap();
+
+            let scene = Scene {
+                name: "Level 1".to_string(),
+                empty: String::new(),
+                visible: true,
+                id: 1 << 40,
+                offset: -56,
+                scale: 0.5,
+                parent: None,
+                tags: vec!["a".to_string(), "b".to_string()],
+                layers: HashMap::from([(0, "background".to_string()), (7, "ui".to_string())]),
+                shapes: vec![
+                    Shape::Empty,
+                    Shape::Circle(2.5),
+                    Shape::Line(-1, 1),
+                    Shape::Rect {
+                        width: 3,
+                        height: 4,
+                    },
+                ],
+                pair: (255, 'x'),
+            };
+
+            let v = context.serialize(&scene).unwrap();
+            assert!(v.is_object());
+
+            // Check the value looks like what a script would expect
+            let global = context.get_global_object();
+            context
+                .set_property_str(&global, "scene", v.clone())
+                .unwrap();
+            let filename = nstr!("script.js");
+            let script = nstr!(
+                "[
+                    scene.name === 'Level 1',
+                    scene.parent === null,
+                    scene.id === 2 ** 40,
+                    scene.tags.length === 2,
+                    scene.layers['7'] === 'ui',
+                    scene.shapes[0] === 'Empty',
+                    scene.shapes[1].Circle === 2.5,
+                    scene.shapes[2].Line[0] === -1,
+                    scene.shapes[3].Rect.height === 4,
+                ].every((v) => v)"
+            );
+            let result = context.eval(script, filename, JSEvalFlags::STRICT).unwrap();
+            assert_eq!(result.get_bool(), Some(true));
+
+            let back: Scene = context.deserialize(&v).unwrap();
+            assert_eq!(back, scene);
+
+            let bytes = context.serialize(&Bytes(&[1, 2, 3])).unwrap();
+            let script = nstr!("(v) => v instanceof Uint8Array && v[2] === 3");
+            let check = context.eval(script, filename, JSEvalFlags::STRICT).unwrap();
+            let result = context
+                .call(&check, &Value::UNDEFINED, &[bytes.as_arg()])
+                .unwrap();
+            assert_eq!(result.get_bool(), Some(true));
+            let back: serde_json::Value = context.deserialize(&bytes).unwrap();
+            assert_eq!(back, serde_json::json!([1, 2, 3]));
+        });
+    });
+}
+
+#[test]
+pub fn serde_deserialize_from_script() {
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Window {
+        width: u32,
+        height: u32,
+        title: Option<String>,
+    }
+
+    std::thread::scope(|scope| {
+        scope.spawn(move || {
+            let runtime = Runtime::init_thread_runtime();
+            let context = runtime.new_context().unwrap();
+
+            let filename = nstr!("script.js");
+            let script = nstr!("({ width: 1280, height: 720 * 1.0, title: undefined, extra: [] })");
+            let v = context.eval(script, filename, JSEvalFlags::STRICT).unwrap();
+            let window: Window = context.deserialize(&v).unwrap();
+            assert_eq!(
+                window,
+                Window {
+                    width: 1280,
+                    height: 720,
+                    title: None
+                }
+            );
+
+            let script = nstr!("({ width: 1.5, height: 720 })");
+            let v = context.eval(script, filename, JSEvalFlags::STRICT).unwrap();
+            assert!(context.deserialize::<Window>(&v).is_err());
+
+            let script = nstr!("({ a: 1, b: 'two', c: [true, null, 3.5], d: undefined })");
+            let v = context.eval(script, filename, JSEvalFlags::STRICT).unwrap();
+            let json: serde_json::Value = context.deserialize(&v).unwrap();
+            assert_eq!(
+                json,
+                serde_json::json!({ "a": 1, "b": "two", "c": [true, null, 3.5] })
+            );
+        });
+    });
+}
+
+#[test]
+pub fn serde_deserialize_cycles_and_functions() {
+    std::thread::scope(|scope| {
+        scope.spawn(move || {
+            let runtime = Runtime::init_thread_runtime();
+            let context = runtime.new_context().unwrap();
+
+            // Values that contain themselves are an error, not a stack overflow
+            let filename = nstr!("script.js");
+            let script = nstr!("(() => { const v = { a: [] }; v.a.push(v); return v; })()");
+            let v = context.eval(script, filename, JSEvalFlags::STRICT).unwrap();
+            assert!(context.deserialize::<serde_json::Value>(&v).is_err());
+
+            // As is anything nested too deeply to deserialize recursively
+            let script =
+                nstr!("(() => { let v = 1; for (let i = 0; i < 200; i++) v = [v]; return v; })()");
+            let v = context.eval(script, filename, JSEvalFlags::STRICT).unwrap();
+            assert!(context.deserialize::<serde_json::Value>(&v).is_err());
+
+            // Function valued properties are skipped and functions in arrays read as null, like
+            // JSON.stringify would
+            let script = nstr!("({ a: 1, f() {}, g: () => 2, h: [function () {}, 3] })");
+            let v = context.eval(script, filename, JSEvalFlags::STRICT).unwrap();
+            let json: serde_json::Value = context.deserialize(&v).unwrap();
+            assert_eq!(json, serde_json::json!({ "a": 1, "h": [null, 3] }));
+        });
+    });
+}
+
+#[test]
+pub fn serde_map_keys() {
+    std::thread::scope(|scope| {
+        scope.spawn(move || {
+            let runtime = Runtime::init_thread_runtime();
+            let context = runtime.new_context().unwrap();
+
+            // The empty string is a valid key, not a failed conversion
+            let map = HashMap::from([(String::new(), 1), ("a".to_string(), 2)]);
+            let v = context.serialize(&map).unwrap();
+            let back: HashMap<String, i32> = context.deserialize(&v).unwrap();
+            assert_eq!(back, map);
+
+            // Keys that JS can't use as property names are an error, not an empty key
+            let map = HashMap::from([(true, 1)]);
+            assert!(context.serialize(&map).is_err());
+            let map = HashMap::from([((1, 2), 1)]);
+            assert!(context.serialize(&map).is_err());
+        });
+    });
+}
+
+#[test]
+pub fn typed_array_wrap_and_borrow() {
+    std::thread::scope(|scope| {
+        scope.spawn(move || {
+            let runtime = Runtime::init_thread_runtime();
+            let context = runtime.new_context().unwrap();
+
+            let filename = nstr!("script.js");
+            let script =
+                nstr!("(v) => { for (let i = 0; i < v.length; i++) v[i] *= 2; return v; }");
+            let double = context.eval(script, filename, JSEvalFlags::STRICT).unwrap();
+
+            let mut array = context.new_typed_array_copy(&[1.0f32, 2.5, -4.0]).unwrap();
+            assert!(array.typed_array_type() == Some(JSTypedArrayEnum::FLOAT32));
+            context
+                .call(&double, &Value::UNDEFINED, &[array.as_arg()])
+                .unwrap();
+            assert_eq!(
+                context.borrow_typed_array::<f32>(&array),
+                Some([2.0f32, 5.0, -8.0].as_slice())
+            );
+            assert!(context.borrow_typed_array::<u32>(&array).is_none());
+
+            // We hold the only reference to both the array and its buffer
+            let elements = context.borrow_typed_array_mut::<f32>(&mut array).unwrap();
+            elements[1] = 10.0;
+            let script = nstr!("(v) => v[1]");
+            let get = context.eval(script, filename, JSEvalFlags::STRICT).unwrap();
+            let result = context
+                .call(&get, &Value::UNDEFINED, &[array.as_arg()])
+                .unwrap();
+            assert_eq!(result.get_number().unwrap().normalize(), 10.0);
+
+            let buffer = context.new_array_buffer_copy(&[1, 2, 3, 4]).unwrap();
+            assert!(buffer.is_array_buffer());
+            assert_eq!(
+                context.borrow_array_buffer(&buffer),
+                Some([1u8, 2, 3, 4].as_slice())
+            );
+            assert!(context.borrow_typed_array::<u8>(&buffer).is_none());
+
+            let empty = context.new_typed_array_copy::<u16>(&[]).unwrap();
+            assert_eq!(
+                context.borrow_typed_array::<u16>(&empty),
+                Some([].as_slice())
+            );
+
+            // Zeroed arrays can be filled in place
+            let mut positions = context.new_typed_array_zeroed::<f64>(4).unwrap();
+            let elements = context
+                .borrow_typed_array_mut::<f64>(&mut positions)
+                .unwrap();
+            elements.copy_from_slice(&[0.5, 1.5, 2.5, 3.5]);
+            let script = nstr!("(v) => v.reduce((a, b) => a + b)");
+            let sum = context.eval(script, filename, JSEvalFlags::STRICT).unwrap();
+            let result = context
+                .call(&sum, &Value::UNDEFINED, &[positions.as_arg()])
+                .unwrap();
+            assert_eq!(result.get_number().unwrap().normalize(), 8.0);
+
+            // Views created by scripts can be borrowed too, honoring their offset and length
+            let script = nstr!("new Int32Array(new ArrayBuffer(16), 4, 2).fill(7)");
+            let view = context.eval(script, filename, JSEvalFlags::STRICT).unwrap();
+            assert_eq!(
+                context.borrow_typed_array::<i32>(&view),
+                Some([7, 7].as_slice())
+            );
+
+            let script = nstr!("new Uint8Array([1, 2, 3, 4, 5])");
+            let bytes = context.eval(script, filename, JSEvalFlags::STRICT).unwrap();
+            assert_eq!(context.borrow_typed_array::<u8>(&bytes).unwrap().len(), 5);
+        });
+    });
+}
+
+#[test]
+pub fn typed_array_wrap_owned() {
+    std::thread::scope(|scope| {
+        scope.spawn(move || {
+            let runtime = Runtime::init_thread_runtime();
+            let context = runtime.new_context().unwrap();
+
+            // The buffers wrap the Rust allocation rather than a copy of it
+            let bytes = vec![1u8, 2, 3, 4];
+            let ptr = bytes.as_ptr();
+            let buffer = context.new_array_buffer(bytes).unwrap();
+            assert!(buffer.is_array_buffer());
+            let borrowed = context.borrow_array_buffer(&buffer).unwrap();
+            assert_eq!(borrowed, [1, 2, 3, 4]);
+            assert_eq!(borrowed.as_ptr(), ptr);
+
+            let bytes: Box<[u8]> = Box::new([5, 6, 7]);
+            let ptr = bytes.as_ptr();
+            let array = context.new_uint8_array(bytes).unwrap();
+            assert!(array.typed_array_type() == Some(JSTypedArrayEnum::UINT8));
+            let borrowed = context.borrow_typed_array::<u8>(&array).unwrap();
+            assert_eq!(borrowed, [5, 6, 7]);
+            assert_eq!(borrowed.as_ptr(), ptr);
+
+            let elements = vec![0.5f64, 1.5, 2.5];
+            let ptr = elements.as_ptr();
+            let array = context.new_typed_array(elements).unwrap();
+            assert!(array.typed_array_type() == Some(JSTypedArrayEnum::FLOAT64));
+            let filename = nstr!("script.js");
+            let script = nstr!("(v) => v.reduce((a, b) => a + b)");
+            let sum = context.eval(script, filename, JSEvalFlags::STRICT).unwrap();
+            let result = context
+                .call(&sum, &Value::UNDEFINED, &[array.as_arg()])
+                .unwrap();
+            assert_eq!(result.get_number().unwrap().normalize(), 4.5);
+            let borrowed = context.borrow_typed_array::<f64>(&array).unwrap();
+            assert_eq!(borrowed.as_ptr(), ptr);
+
+            let empty = context.new_typed_array(Vec::<u32>::new()).unwrap();
+            assert_eq!(
+                context.borrow_typed_array::<u32>(&empty),
+                Some([].as_slice())
+            );
+
+            // Dropping the last references hands the memory back to Rust to be freed
+            drop((buffer, array, empty));
+            context.gc();
+        });
+    });
+}
+
+#[test]
+pub fn typed_array_borrow_rules() {
+    std::thread::scope(|scope| {
+        scope.spawn(move || {
+            let runtime = Runtime::init_thread_runtime();
+            let context = runtime.new_context().unwrap();
+            let global = context.get_global_object();
+
+            // Another reference to the array itself blocks a mutable borrow
+            let mut array = context.new_typed_array_copy(&[1u32, 2, 3]).unwrap();
+            let other = array.clone();
+            assert!(context.borrow_typed_array_mut::<u32>(&mut array).is_none());
+            drop(other);
+            assert!(context.borrow_typed_array_mut::<u32>(&mut array).is_some());
+
+            // So does another reference to the buffer behind it
+            let filename = nstr!("script.js");
+            let script = nstr!("(v) => { globalThis.alias = new Uint32Array(v.buffer); }");
+            let alias = context.eval(script, filename, JSEvalFlags::STRICT).unwrap();
+            context
+                .call(&alias, &Value::UNDEFINED, &[array.as_arg()])
+                .unwrap();
+            assert!(context.borrow_typed_array_mut::<u32>(&mut array).is_none());
+            assert!(context.borrow_typed_array::<u32>(&array).is_some());
+
+            let aliased = context.get_property_str(&global, "alias").unwrap();
+            let elements = unsafe { context.borrow_typed_array_mut_unchecked::<u32>(&aliased) };
+            elements.unwrap()[0] = 42;
+            assert_eq!(context.borrow_typed_array::<u32>(&array).unwrap()[0], 42);
+
+            let mut buffer = context.new_array_buffer_copy(&[0; 8]).unwrap();
+            context.borrow_array_buffer_mut(&mut buffer).unwrap()[3] = 9;
+            assert_eq!(context.borrow_array_buffer(&buffer).unwrap()[3], 9);
+
+            // Detached buffers can't be borrowed at all, nor can arrays viewing them
+            let script = nstr!("(b) => { b.transfer(64); }");
+            let detach = context.eval(script, filename, JSEvalFlags::STRICT).unwrap();
+            context
+                .call(&detach, &Value::UNDEFINED, &[buffer.as_arg()])
+                .unwrap();
+            assert!(context.borrow_array_buffer(&buffer).is_none());
+            assert!(context.borrow_array_buffer_mut(&mut buffer).is_none());
+
+            let script = nstr!("alias.buffer");
+            let aliased_buffer = context.eval(script, filename, JSEvalFlags::STRICT).unwrap();
+            context
+                .call(&detach, &Value::UNDEFINED, &[aliased_buffer.as_arg()])
+                .unwrap();
+            assert!(context.borrow_typed_array::<u32>(&array).is_none());
+            assert!(context.borrow_typed_array::<u32>(&aliased).is_none());
+        });
+    });
+}
@@ -0,0 +1,136 @@
+//
+//
+// This file is a part of Aleph
+//
+// https://github.com/nathanvoglsam/aleph
+//
+// MIT License
+//
+// Copyright (c) 2020 Aleph Engine
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use std::ffi::c_void;
+use std::ptr::NonNull;
+
+use raw::JSTypedArrayEnum;
+
+/// Marker trait for the element types that JS typed arrays can be viewed as.
+///
+/// # Safety
+///
+/// Implementors must be plain-old-data types where every bit pattern is a valid value, with a size
+/// that matches the element size of the typed array identified by [`TypedArrayElement::TYPE`].
+pub unsafe trait TypedArrayElement: Copy + 'static {
+    /// The typed array type with elements of this type.
+    const TYPE: JSTypedArrayEnum;
+
+    /// Returns whether a typed array of the given type can be viewed as elements of this type.
+    #[inline]
+    fn is_compatible(v: JSTypedArrayEnum) -> bool {
+        v == Self::TYPE
+    }
+}
+
+unsafe impl TypedArrayElement for u8 {
+    const TYPE: JSTypedArrayEnum = JSTypedArrayEnum::UINT8;
+
+    #[inline]
+    fn is_compatible(v: JSTypedArrayEnum) -> bool {
+        // Uint8ClampedArray only differs from Uint8Array when JS writes to it
+        v == JSTypedArrayEnum::UINT8 || v == JSTypedArrayEnum::UINT8C
+    }
+}
+
+unsafe impl TypedArrayElement for i8 {
+    const TYPE: JSTypedArrayEnum = JSTypedArrayEnum::INT8;
+}
+
+unsafe impl TypedArrayElement for u16 {
+    const TYPE: JSTypedArrayEnum = JSTypedArrayEnum::UINT16;
+}
+
+unsafe impl TypedArrayElement for i16 {
+    const TYPE: JSTypedArrayEnum = JSTypedArrayEnum::INT16;
+}
+
+unsafe impl TypedArrayElement for u32 {
+    const TYPE: JSTypedArrayEnum = JSTypedArrayEnum::UINT32;
+}
+
+unsafe impl TypedArrayElement for i32 {
+    const TYPE: JSTypedArrayEnum = JSTypedArrayEnum::INT32;
+}
+
+unsafe impl TypedArrayElement for u64 {
+    const TYPE: JSTypedArrayEnum = JSTypedArrayEnum::BIG_UINT64;
+}
+
+unsafe impl TypedArrayElement for i64 {
+    const TYPE: JSTypedArrayEnum = JSTypedArrayEnum::BIG_INT64;
+}
+
+unsafe impl TypedArrayElement for f32 {
+    const TYPE: JSTypedArrayEnum = JSTypedArrayEnum::FLOAT32;
+}
+
+unsafe impl TypedArrayElement for f64 {
+    const TYPE: JSTypedArrayEnum = JSTypedArrayEnum::FLOAT64;
+}
+
+/// Builds a slice from a pointer + byte length pair that came from an array buffer, checking that
+/// the pointer is suitably aligned for `T`.
+pub(crate) unsafe fn buffer_slice_parts<T>(
+    ptr: *mut u8,
+    byte_length: usize,
+) -> Option<NonNull<[T]>> {
+    let len = byte_length / size_of::<T>();
+    if len == 0 {
+        // Empty and detached buffers may hand back a null pointer
+        return Some(NonNull::slice_from_raw_parts(NonNull::dangling(), 0));
+    }
+    let ptr = NonNull::new(ptr)?.cast::<T>();
+    if !ptr.is_aligned() {
+        return None;
+    }
+    Some(NonNull::slice_from_raw_parts(ptr, len))
+}
+
+/// Leaks a boxed slice so its memory can be handed to QuickJS, returning the pointer and element
+/// count that [`free_boxed_slice`] needs to reclaim it.
+pub(crate) fn into_raw_boxed_slice<T>(data: Box<[T]>) -> (*mut T, usize) {
+    let len = data.len();
+    (Box::into_raw(data).cast::<T>(), len)
+}
+
+/// The free callback for array buffers that wrap memory from [`into_raw_boxed_slice`]. The element
+/// count is smuggled through the opaque pointer so the box can be rebuilt with the layout it was
+/// allocated with.
+pub(crate) extern "C" fn free_boxed_slice<T>(
+    _rt: NonNull<raw::JSRuntime>,
+    opaque: *mut c_void,
+    ptr: *mut c_void,
+) {
+    let len = opaque as usize;
+    unsafe {
+        let slice = std::ptr::slice_from_raw_parts_mut(ptr.cast::<T>(), len);
+        drop(Box::from_raw(slice));
+    }
+}
@@ -413,6 +413,28 @@ impl WeakValue {
         unsafe { raw::JS_IsArray(self.0) }
     }
 
+    /// Returns true if 'self' is a promise
+    #[inline]
+    pub fn is_promise(&self) -> bool {
+        // Safety: This wrapper type is guaranteed to contain a live JS object
+        unsafe { raw::JS_IsPromise(self.0) }
+    }
+
+    /// Returns true if 'self' is an `ArrayBuffer`
+    #[inline]
+    pub fn is_array_buffer(&self) -> bool {
+        // Safety: This wrapper type is guaranteed to contain a live JS object
+        unsafe { raw::JS_IsArrayBuffer(self.0) }
+    }
+
+    /// Returns the element type of 'self' if it is a typed array, or [`None`] if it isn't.
+    #[inline]
+    pub fn typed_array_type(&self) -> Option<raw::JSTypedArrayEnum> {
+        // Safety: This wrapper type is guaranteed to contain a live JS object
+        let v = unsafe { raw::JS_GetTypedArrayType(self.0) };
+        if v.0 < 0 { None } else { Some(v) }
+    }
+
     /// Returns the reference count of the value, if it is a reference type. Pure value types like
     /// 'number' return [`None`].
     #[inline(always)]
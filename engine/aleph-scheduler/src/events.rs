@@ -0,0 +1,357 @@
+//
+//
+// This file is a part of Aleph
+//
+// https://github.com/nathanvoglsam/aleph
+//
+// MIT License
+//
+// Copyright (c) 2020 Aleph Engine
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use std::collections::HashSet;
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use aleph_object_system::nstr::{NStr, nstr};
+use aleph_object_system::uuid::{Uuid, uuid};
+use aleph_object_system::{
+    IObject, Object, ObjectDescription, get_object_destructor_for, object_destructor,
+};
+use aleph_typed_table::TypedTable;
+
+use crate::{
+    AccessDescriptor, ResMut, ScheduleArgs, Stage, SystemParam, SystemParamFetch, SystemParamState,
+};
+
+///
+/// A double-buffered queue of events of type `T`, used to send messages between systems.
+///
+/// Systems send events with an [`EventWriter`] and receive them with an [`EventReader`]. Each
+/// reader tracks its own cursor, so every reader sees every event exactly once no matter how many
+/// other readers there are.
+///
+/// Events live for two calls to [`Events::update`], which should happen once per frame at the
+/// frame boundary, with an [`UpdateEventsStage`] or the [`update_events`] system. This gives a
+/// reader that runs before the writer in a frame, like one in an earlier stage, a chance to see the
+/// events sent in the previous frame. Events that a reader doesn't get to in time are dropped.
+///
+/// Writers declare exclusive access to the resource, and readers declare shared access that comes
+/// after every writer. Within a stage the scheduler always runs the readers after the writers, so
+/// events are delivered in the frame they're sent.
+///
+/// # Usage
+///
+/// Event types must implement [`IObject`] (see `unsafe_impl_iobject!`), which is used to derive a
+/// unique ID for the `Events<T>` resource. Registering the event type with an
+/// [`UpdateEventsStage`] that runs first in the frame creates the resource and updates it.
+///
+/// ```ignore
+/// schedule.add_events::<Collision>(UPDATE_EVENTS_STAGE);
+/// ```
+///
+pub struct Events<T> {
+    /// Events sent before the most recent update
+    previous: Vec<T>,
+
+    /// The ID of the first event in `previous`
+    previous_start: u64,
+
+    /// Events sent since the most recent update
+    current: Vec<T>,
+
+    /// The ID of the first event in `current`
+    current_start: u64,
+}
+
+impl<T> Default for Events<T> {
+    fn default() -> Self {
+        Self {
+            previous: Vec::new(),
+            previous_start: 0,
+            current: Vec::new(),
+            current_start: 0,
+        }
+    }
+}
+
+impl<T> Events<T> {
+    /// Sends an event, which will be visible to every [`EventReader`] until it is dropped by the
+    /// second call to [`Events::update`].
+    #[inline]
+    pub fn send(&mut self, event: T) {
+        self.current.push(event);
+    }
+
+    /// Sends every event in the iterator. See [`Events::send`].
+    #[inline]
+    pub fn send_batch(&mut self, events: impl IntoIterator<Item = T>) {
+        self.current.extend(events);
+    }
+
+    /// Swaps the event buffers, dropping the events sent before the previous update. Should be
+    /// called once per frame.
+    pub fn update(&mut self) {
+        std::mem::swap(&mut self.previous, &mut self.current);
+        self.current.clear();
+        self.previous_start = self.current_start;
+        self.current_start = self.previous_start + self.previous.len() as u64;
+    }
+
+    /// Drops all events in both buffers. Readers will not see any of the dropped events.
+    pub fn clear(&mut self) {
+        self.current_start = self.end();
+        self.previous_start = self.current_start;
+        self.previous.clear();
+        self.current.clear();
+    }
+
+    /// Returns the number of events currently stored, across both buffers.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.previous.len() + self.current.len()
+    }
+
+    /// Returns whether there are no events currently stored.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Iterates over all the stored events, oldest first, without affecting any reader.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.previous.iter().chain(self.current.iter())
+    }
+
+    /// Returns the ID that the next event sent will be given
+    #[inline]
+    fn end(&self) -> u64 {
+        self.current_start + self.current.len() as u64
+    }
+
+    /// Returns all the stored events with an ID of `cursor` or greater
+    fn events_from(&self, cursor: u64) -> (&[T], &[T]) {
+        let previous_skip = cursor.saturating_sub(self.previous_start) as usize;
+        let current_skip = cursor.saturating_sub(self.current_start) as usize;
+        let previous = self.previous.get(previous_skip..).unwrap_or_default();
+        let current = self.current.get(current_skip..).unwrap_or_default();
+        (previous, current)
+    }
+}
+
+/// Mixed into the ID of an event type to produce the ID of its [`Events`] resource, so the two
+/// never collide.
+const EVENTS_ID_SALT: u128 = uuid!("01a14e78-aada-7e02-a803-3eb84b99c65a").as_u128();
+
+// SAFETY: The ID is unique for each event type as long as the event type's own ID is unique, and
+//         the description matches the type.
+unsafe impl<T: IObject + Send + Sync + 'static> IObject for Events<T> {
+    const ID: Uuid = Uuid::from_u128(T::ID.as_u128() ^ EVENTS_ID_SALT);
+    const SIZE: usize = size_of::<Self>();
+    const ALIGN: usize = align_of::<Self>();
+    const NAME: &'static NStr = nstr!("aleph_scheduler::events::Events");
+    const DESC: &'static ObjectDescription = &ObjectDescription {
+        id: Self::ID,
+        size: Self::SIZE,
+        align: Self::ALIGN,
+        name: Self::NAME,
+        destructor: get_object_destructor_for::<Self>(),
+        destructor_arc: object_destructor::<Arc<Object<Self>>>,
+    };
+}
+
+/// A system that calls [`Events::update`] on the `Events<T>` resource. Should be added to a
+/// schedule so it runs exactly once per frame.
+///
+/// An [`UpdateEventsStage`] does the same for any number of event types, without needing a system
+/// label for each of them.
+pub fn update_events<T: IObject + Send + Sync + 'static>(mut events: ResMut<Events<T>>) {
+    events.update();
+}
+
+///
+/// A [`Stage`] that calls [`Events::update`] on the `Events` resource of every event type
+/// registered with it. Should be placed so it runs once per frame, at the frame boundary.
+///
+/// The `Events` resources are inserted when the stage runs if they don't already exist, so
+/// registering an event type with the stage is all that's needed to start using it.
+///
+#[derive(Default)]
+pub struct UpdateEventsStage {
+    /// The ID of every registered `Events` resource
+    registered: HashSet<Uuid>,
+
+    /// Updates a single `Events` resource, one for each registered event type
+    updaters: Vec<fn(&mut TypedTable)>,
+}
+
+impl UpdateEventsStage {
+    /// Registers the event type `T` so its `Events<T>` resource is updated every time the stage
+    /// runs. Registering the same type more than once has no effect.
+    pub fn add_events<T: IObject + Send + Sync + 'static>(&mut self) -> &mut Self {
+        fn update<T: IObject + Send + Sync + 'static>(resources: &mut TypedTable) {
+            resources.get_or_insert_with(Events::<T>::default).update();
+        }
+
+        if self.registered.insert(Events::<T>::ID) {
+            self.updaters.push(update::<T>);
+        }
+        self
+    }
+}
+
+impl<A: ScheduleArgs> Stage<A> for UpdateEventsStage {
+    fn run(&mut self, _args: &A::Args<'_>, resources: &mut TypedTable) {
+        for update in self.updaters.iter() {
+            update(resources);
+        }
+    }
+}
+
+// ============================================================================================== //
+
+/// A [`SystemParam`] that allows a system to send events of type `T`. See [`Events`].
+pub struct EventWriter<'w, T> {
+    events: &'w mut Events<T>,
+}
+
+impl<'w, T> EventWriter<'w, T> {
+    /// Sends an event. See [`Events::send`].
+    #[inline]
+    pub fn send(&mut self, event: T) {
+        self.events.send(event);
+    }
+
+    /// Sends every event in the iterator. See [`Events::send`].
+    #[inline]
+    pub fn send_batch(&mut self, events: impl IntoIterator<Item = T>) {
+        self.events.send_batch(events);
+    }
+}
+
+/// An internal type that handles loading the pointer [`EventWriter`] will provide to a system
+/// function.
+pub struct EventWriterState<T>(PhantomData<T>);
+
+impl<'a, T: IObject + Send + Sync + 'static> SystemParam for EventWriter<'a, T> {
+    type Fetch = EventWriterState<T>;
+}
+
+unsafe impl<T: IObject + Send + Sync + 'static> SystemParamState for EventWriterState<T> {
+    #[inline]
+    fn init(access: &mut dyn AccessDescriptor) -> Self {
+        access.writes_resource::<Events<T>>();
+        Self(Default::default())
+    }
+}
+
+impl<'a, T: IObject + Send + Sync + 'static> SystemParamFetch<'a> for EventWriterState<T> {
+    type Item = EventWriter<'a, T>;
+
+    #[inline]
+    unsafe fn get_param(_state: &'a mut Self, resources: &'a TypedTable) -> Self::Item {
+        unsafe {
+            let mut events = resources
+                .get_raw::<Events<T>>()
+                .expect("Events resource has not been inserted");
+            EventWriter {
+                events: events.as_mut(),
+            }
+        }
+    }
+}
+
+// ============================================================================================== //
+
+/// A [`SystemParam`] that allows a system to receive events of type `T`. See [`Events`].
+///
+/// Each system has its own cursor, which is advanced as events are read. Events sent before a
+/// system first runs are still visible to it, as long as they haven't been dropped yet.
+pub struct EventReader<'w, T> {
+    events: &'w Events<T>,
+    cursor: &'w mut u64,
+}
+
+impl<'w, T> EventReader<'w, T> {
+    /// Iterates over all the events this reader hasn't seen yet, oldest first, and marks them as
+    /// read.
+    pub fn read(&mut self) -> impl Iterator<Item = &'w T> + use<'w, T> {
+        let (previous, current) = self.events.events_from(*self.cursor);
+        *self.cursor = self.events.end();
+        previous.iter().chain(current.iter())
+    }
+
+    /// Returns the number of events this reader hasn't seen yet.
+    pub fn len(&self) -> usize {
+        let (previous, current) = self.events.events_from(*self.cursor);
+        previous.len() + current.len()
+    }
+
+    /// Returns whether this reader has seen all the stored events.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Marks all the stored events as read without reading them.
+    #[inline]
+    pub fn clear(&mut self) {
+        *self.cursor = self.events.end();
+    }
+}
+
+/// An internal type that stores the cursor of an [`EventReader`] between executions of a system.
+pub struct EventReaderState<T> {
+    cursor: u64,
+    _phantom: PhantomData<fn() -> T>,
+}
+
+impl<'a, T: IObject + Send + Sync + 'static> SystemParam for EventReader<'a, T> {
+    type Fetch = EventReaderState<T>;
+}
+
+unsafe impl<T: IObject + Send + Sync + 'static> SystemParamState for EventReaderState<T> {
+    #[inline]
+    fn init(access: &mut dyn AccessDescriptor) -> Self {
+        // Reading after the writers means events are seen in the frame they're sent, no matter
+        // what order the systems were added in.
+        access.reads_resource_after_writers::<Events<T>>();
+        Self {
+            cursor: 0,
+            _phantom: Default::default(),
+        }
+    }
+}
+
+impl<'a, T: IObject + Send + Sync + 'static> SystemParamFetch<'a> for EventReaderState<T> {
+    type Item = EventReader<'a, T>;
+
+    #[inline]
+    unsafe fn get_param(state: &'a mut Self, resources: &'a TypedTable) -> Self::Item {
+        let events = resources
+            .get_ref::<Events<T>>()
+            .expect("Events resource has not been inserted");
+        EventReader {
+            events,
+            cursor: &mut state.cursor,
+        }
+    }
+}
@@ -0,0 +1,225 @@
+//
+//
+// This file is a part of Aleph
+//
+// https://github.com/nathanvoglsam/aleph
+//
+// MIT License
+//
+// Copyright (c) 2020 Aleph Engine
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use aleph_object_system::unsafe_impl_iobject;
+use aleph_typed_table::TypedTable;
+
+use crate::introspection::StageDescription;
+use crate::{Schedule, ScheduleArgs, Stage};
+
+///
+/// A resource published by a [`FixedTimestep`] stage that describes the state of the fixed update
+/// loop.
+///
+/// The resource is updated before every step of the child schedule, and once more after the last
+/// step of the frame so systems that run afterwards see the final interpolation alpha.
+///
+#[derive(Clone, Debug, Default)]
+pub struct FixedTime {
+    /// The length of a single fixed step, in seconds.
+    pub step: f64,
+
+    /// How far the accumulated time has progressed towards the next step, in the range `[0, 1)`.
+    ///
+    /// Rendering code should use this to interpolate between the previous and current state of
+    /// anything simulated in fixed steps. It is only meaningful after the fixed steps for the
+    /// frame have finished.
+    pub alpha: f64,
+
+    /// The number of steps that have been run so far this frame.
+    pub steps_this_frame: u32,
+
+    /// The total number of steps that have been run since the stage was created.
+    pub tick: u64,
+}
+unsafe_impl_iobject!(FixedTime, "01a14e73-4f60-7441-9010-76801f19e51b");
+
+///
+/// A [`Stage`] that runs a child [`Schedule`] zero or more times per frame, at a fixed tick rate.
+///
+/// Every frame the stage reads the frame's delta time from a user provided function, typically
+/// wrapping `IFrameTimer::delta_time`, and adds it to an accumulator. The child schedule is then
+/// run once for every whole step the accumulator holds.
+///
+/// To stop a slow frame from requiring more steps, which makes the next frame slower still, at
+/// most [`FixedTimestep::max_steps`] steps are run per frame. Any whole steps left over once the
+/// limit is reached are discarded.
+///
+/// The state of the loop is published into the resources table as a [`FixedTime`] resource.
+///
+/// # Usage
+///
+/// ```ignore
+/// let timer = registry.get_interface::<dyn IFrameTimer>().unwrap();
+/// let mut fixed = FixedTimestep::with_rate(60.0, move || timer.delta_time());
+/// fixed
+///     .schedule_mut()
+///     .add_stage(make_label!("physics"), SystemSchedule::default());
+/// schedule.add_stage_after(CoreStage::PreUpdate.into(), make_label!("fixed"), fixed);
+/// ```
+///
+pub struct FixedTimestep<A: ScheduleArgs = ()> {
+    /// The schedule to run on each fixed step
+    schedule: Schedule<A>,
+
+    /// Yields the time, in seconds, that passed since the previous frame
+    delta_time: Box<dyn FnMut() -> f64>,
+
+    /// The length of a fixed step in seconds
+    step: f64,
+
+    /// The maximum number of steps to run in a single frame
+    max_steps: u32,
+
+    /// The time accumulated that hasn't been consumed by a step yet
+    accumulator: f64,
+
+    /// The total number of steps that have been run
+    tick: u64,
+}
+
+impl<A: ScheduleArgs> FixedTimestep<A> {
+    /// The default value for [`FixedTimestep::max_steps`].
+    pub const DEFAULT_MAX_STEPS: u32 = 8;
+
+    /// Constructs a new stage that runs an empty schedule with a fixed step of `step` seconds.
+    /// `delta_time` is called once per frame to get the time that passed since the last frame.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `step` is not finite and positive.
+    pub fn new(step: f64, delta_time: impl FnMut() -> f64 + 'static) -> Self {
+        assert!(
+            step.is_finite() && step > 0.0,
+            "Fixed timestep must be finite and positive"
+        );
+        Self {
+            schedule: Schedule::default(),
+            delta_time: Box::new(delta_time),
+            step,
+            max_steps: Self::DEFAULT_MAX_STEPS,
+            accumulator: 0.0,
+            tick: 0,
+        }
+    }
+
+    /// Constructs a new stage that runs an empty schedule `rate` times per second. See
+    /// [`FixedTimestep::new`].
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `rate` is not finite and positive.
+    pub fn with_rate(rate: f64, delta_time: impl FnMut() -> f64 + 'static) -> Self {
+        assert!(
+            rate.is_finite() && rate > 0.0,
+            "Fixed timestep rate must be finite and positive"
+        );
+        Self::new(1.0 / rate, delta_time)
+    }
+
+    /// Replaces the function used to get the frame's delta time. Useful when the stage has to be
+    /// created before the frame timer exists.
+    pub fn set_delta_time(&mut self, delta_time: impl FnMut() -> f64 + 'static) -> &mut Self {
+        self.delta_time = Box::new(delta_time);
+        self
+    }
+
+    /// Sets the maximum number of steps that will be run in a single frame.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `max_steps` is zero.
+    pub fn max_steps(&mut self, max_steps: u32) -> &mut Self {
+        assert_ne!(max_steps, 0, "max_steps must not be zero");
+        self.max_steps = max_steps;
+        self
+    }
+
+    /// Returns the length of a single fixed step, in seconds.
+    #[inline]
+    pub fn step(&self) -> f64 {
+        self.step
+    }
+
+    /// Returns the interpolation alpha. See [`FixedTime::alpha`].
+    #[inline]
+    pub fn alpha(&self) -> f64 {
+        self.accumulator / self.step
+    }
+
+    /// Returns the schedule that is run on each fixed step.
+    #[inline]
+    pub fn schedule(&self) -> &Schedule<A> {
+        &self.schedule
+    }
+
+    /// Returns the schedule that is run on each fixed step, so stages and systems can be added.
+    #[inline]
+    pub fn schedule_mut(&mut self) -> &mut Schedule<A> {
+        &mut self.schedule
+    }
+
+    fn publish(&self, resources: &mut TypedTable, steps_this_frame: u32) {
+        let state = resources.get_or_insert_with(FixedTime::default);
+        state.step = self.step;
+        state.alpha = self.alpha();
+        state.steps_this_frame = steps_this_frame;
+        state.tick = self.tick;
+    }
+}
+
+impl<A: ScheduleArgs> Stage<A> for FixedTimestep<A> {
+    fn run(&mut self, args: &A::Args<'_>, resources: &mut TypedTable) {
+        // A negative delta would make the accumulator run backwards, which is never meaningful
+        let delta = (self.delta_time)();
+        self.accumulator += delta.max(0.0);
+
+        let mut steps = 0;
+        while self.accumulator >= self.step && steps < self.max_steps {
+            self.accumulator -= self.step;
+            self.tick += 1;
+            steps += 1;
+
+            self.publish(resources, steps);
+            self.schedule.run(args, resources);
+        }
+
+        // We hit the step limit, drop the backlog of whole steps but keep the partial step so the
+        // interpolation alpha stays continuous.
+        if self.accumulator >= self.step {
+            self.accumulator %= self.step;
+        }
+
+        self.publish(resources, steps);
+    }
+
+    fn describe(&mut self) -> StageDescription {
+        StageDescription::Schedule(self.schedule.introspect())
+    }
+}
@@ -0,0 +1,598 @@
+//
+//
+// This file is a part of Aleph
+//
+// https://github.com/nathanvoglsam/aleph
+//
+// MIT License
+//
+// Copyright (c) 2020 Aleph Engine
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use std::fmt::{Display, Formatter};
+use std::io::Write;
+
+use aleph_label::Label;
+use aleph_object_system::uuid::Uuid;
+use serde::{Serialize, Serializer};
+
+///
+/// A snapshot of the structure of a [`crate::Schedule`]: its stages, the systems inside them and
+/// the dependency edges the scheduler built between those systems.
+///
+/// Produced by [`crate::Schedule::introspect`]. The description can be written out as GraphViz or
+/// JSON for inspection in external tools, or used to produce a [`ConflictReport`].
+///
+#[derive(Clone, Debug, Default)]
+pub struct ScheduleDescription {
+    /// The stages of the schedule, in execution order, paired with the label they were registered
+    /// with
+    pub stages: Vec<(Label, StageDescription)>,
+}
+
+///
+/// Describes the structure of a single [`crate::Stage`].
+///
+#[derive(Clone, Debug, Default)]
+pub enum StageDescription {
+    /// The stage does not expose any information about its contents
+    #[default]
+    Opaque,
+
+    /// The stage runs a nested schedule
+    Schedule(ScheduleDescription),
+
+    /// The stage is a [`crate::SystemSchedule`]
+    Systems(SystemScheduleDescription),
+}
+
+///
+/// Describes the systems and dependency graph of each of the channels of a
+/// [`crate::SystemSchedule`].
+///
+#[derive(Clone, Debug, Default)]
+pub struct SystemScheduleDescription {
+    /// The single threaded systems that run before the parallel systems
+    pub exclusive_at_start: ChannelDescription,
+
+    /// The systems that run in parallel with each other
+    pub parallel: ChannelDescription,
+
+    /// The single threaded systems that run after the parallel systems
+    pub exclusive_at_end: ChannelDescription,
+}
+
+impl SystemScheduleDescription {
+    /// Iterates over the channels of the stage, in execution order, paired with their name.
+    pub fn channels(&self) -> impl Iterator<Item = (&'static str, &ChannelDescription)> {
+        [
+            ("exclusive_at_start", &self.exclusive_at_start),
+            ("parallel", &self.parallel),
+            ("exclusive_at_end", &self.exclusive_at_end),
+        ]
+        .into_iter()
+    }
+}
+
+///
+/// Describes the systems in a single channel of a [`crate::SystemSchedule`] and the edges of the
+/// dependency graph built between them.
+///
+#[derive(Clone, Debug, Default)]
+pub struct ChannelDescription {
+    /// The systems in the channel, in the order they were registered
+    pub systems: Vec<SystemDescription>,
+
+    /// The edges of the channel's dependency graph
+    pub edges: Vec<DependencyEdge>,
+}
+
+///
+/// Describes the access a system declared. All lists are sorted so the output is deterministic.
+///
+#[derive(Clone, Debug, Default)]
+pub struct SystemDescription {
+    /// The label the system was registered with
+    pub label: Label,
+
+    /// The resources the system reads
+    pub resource_reads: Vec<Uuid>,
+
+    /// The resources the system writes
+    pub resource_writes: Vec<Uuid>,
+
+    /// The (resource, component) pairs the system reads
+    pub component_reads: Vec<(Uuid, u32)>,
+
+    /// The (resource, component) pairs the system writes
+    pub component_writes: Vec<(Uuid, u32)>,
+
+    /// The labels of the systems this system explicitly runs before
+    pub runs_before: Vec<Label>,
+
+    /// The labels of the systems this system explicitly runs after
+    pub runs_after: Vec<Label>,
+}
+
+///
+/// An edge in a channel's dependency graph. The system at `after` can only begin once the system
+/// at `before` has finished.
+///
+#[derive(Clone, Debug)]
+pub struct DependencyEdge {
+    /// Index into [`ChannelDescription::systems`] of the system that runs first
+    pub before: usize,
+
+    /// Index into [`ChannelDescription::systems`] of the system that runs second
+    pub after: usize,
+
+    /// Every reason the scheduler had for creating the edge
+    pub reasons: Vec<DependencyReason>,
+}
+
+impl DependencyEdge {
+    /// Returns whether the edge was only created because of conflicting access, and not because
+    /// the user asked for the systems to be ordered.
+    pub fn is_conflict_only(&self) -> bool {
+        !self
+            .reasons
+            .iter()
+            .any(|v| matches!(v, DependencyReason::Explicit))
+    }
+}
+
+///
+/// The kind of conflicting access that requires two systems to be ordered.
+///
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum AccessHazard {
+    /// Both systems write the resource
+    WriteAfterWrite,
+
+    /// The first system reads the resource and the second writes it
+    WriteAfterRead,
+
+    /// The first system writes the resource and the second reads it
+    ReadAfterWrite,
+}
+
+impl AccessHazard {
+    fn as_str(self) -> &'static str {
+        match self {
+            AccessHazard::WriteAfterWrite => "write_after_write",
+            AccessHazard::WriteAfterRead => "write_after_read",
+            AccessHazard::ReadAfterWrite => "read_after_write",
+        }
+    }
+}
+
+///
+/// The reason the scheduler created a [`DependencyEdge`].
+///
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum DependencyReason {
+    /// One of the systems declared `runs_before` or `runs_after` on the other
+    Explicit,
+
+    /// Both systems access the same resource and at least one of them writes it
+    Resource {
+        resource: Uuid,
+        hazard: AccessHazard,
+    },
+
+    /// Access to a component type inside the resource conflicts. `component` is `None` when the
+    /// conflict is between access to a single component and access to the whole resource.
+    Component {
+        resource: Uuid,
+        component: Option<u32>,
+    },
+}
+
+impl Display for DependencyReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DependencyReason::Explicit => write!(f, "explicit ordering"),
+            DependencyReason::Resource { resource, hazard } => {
+                write!(f, "{} on resource {resource}", hazard.as_str())
+            }
+            DependencyReason::Component {
+                resource,
+                component: Some(component),
+            } => write!(f, "component {component} of resource {resource}"),
+            DependencyReason::Component {
+                resource,
+                component: None,
+            } => write!(f, "components of resource {resource}"),
+        }
+    }
+}
+
+///
+/// Lists the pairs of parallel systems that the scheduler serialized only because their access
+/// conflicts, and not because they were explicitly ordered.
+///
+/// These are the places to look at when a stage isn't getting as much parallelism as expected.
+/// Exclusive systems run one at a time regardless of their access, so they are not included.
+///
+#[derive(Clone, Debug, Default)]
+pub struct ConflictReport {
+    pub entries: Vec<ConflictReportEntry>,
+}
+
+///
+/// A single pair of systems in a [`ConflictReport`].
+///
+#[derive(Clone, Debug)]
+pub struct ConflictReportEntry {
+    /// The labels of the stages containing the systems, outermost first
+    pub stage_path: Vec<Label>,
+
+    /// The label of the system that runs first
+    pub before: Label,
+
+    /// The label of the system that runs second
+    pub after: Label,
+
+    /// The conflicts between the two systems
+    pub reasons: Vec<DependencyReason>,
+}
+
+impl Display for ConflictReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for entry in self.entries.iter() {
+            for (i, stage) in entry.stage_path.iter().enumerate() {
+                if i != 0 {
+                    write!(f, "/")?;
+                }
+                write!(f, "{stage}")?;
+            }
+            write!(f, ": {} -> {} (", entry.before, entry.after)?;
+            for (i, reason) in entry.reasons.iter().enumerate() {
+                if i != 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{reason}")?;
+            }
+            writeln!(f, ")")?;
+        }
+        Ok(())
+    }
+}
+
+impl ScheduleDescription {
+    /// Produces a report of all systems that were serialized only because of conflicting access.
+    /// See [`ConflictReport`].
+    pub fn conflict_report(&self) -> ConflictReport {
+        fn walk(
+            schedule: &ScheduleDescription,
+            stage_path: &mut Vec<Label>,
+            entries: &mut Vec<ConflictReportEntry>,
+        ) {
+            for (label, stage) in schedule.stages.iter() {
+                stage_path.push(*label);
+                match stage {
+                    StageDescription::Opaque => {}
+                    StageDescription::Schedule(v) => walk(v, stage_path, entries),
+                    StageDescription::Systems(v) => {
+                        let channel = &v.parallel;
+                        for edge in channel.edges.iter().filter(|v| v.is_conflict_only()) {
+                            entries.push(ConflictReportEntry {
+                                stage_path: stage_path.clone(),
+                                before: channel.systems[edge.before].label,
+                                after: channel.systems[edge.after].label,
+                                reasons: edge.reasons.clone(),
+                            });
+                        }
+                    }
+                }
+                stage_path.pop();
+            }
+        }
+
+        let mut entries = Vec::new();
+        walk(self, &mut Vec::new(), &mut entries);
+        ConflictReport { entries }
+    }
+
+    /// Writes the schedule out as a GraphViz digraph named `graph_name`.
+    ///
+    /// Each stage and channel is drawn as a cluster, and each dependency edge is labeled with the
+    /// reasons the scheduler had for creating it.
+    pub fn graph_viz(&self, graph_name: &str, writer: &mut impl Write) -> std::io::Result<()> {
+        writeln!(writer, "digraph {graph_name} {{")?;
+        writeln!(writer, "    compound=true;")?;
+        writeln!(writer, "    node [shape=box];")?;
+        let mut next_id = 0;
+        self.write_graph_viz_stages(writer, &mut next_id, 1)?;
+        writeln!(writer, "}}")?;
+        Ok(())
+    }
+
+    fn write_graph_viz_stages(
+        &self,
+        writer: &mut impl Write,
+        next_id: &mut usize,
+        depth: usize,
+    ) -> std::io::Result<()> {
+        let indent = "    ".repeat(depth);
+        for (label, stage) in self.stages.iter() {
+            let stage_id = *next_id;
+            *next_id += 1;
+
+            writeln!(writer, "{indent}subgraph cluster{stage_id} {{")?;
+            writeln!(writer, "{indent}    label=\"{}\";", escape(label.to_str()))?;
+            match stage {
+                StageDescription::Opaque => {
+                    writeln!(
+                        writer,
+                        "{indent}    node{stage_id} [label=\"(opaque)\",style=dashed];"
+                    )?;
+                }
+                StageDescription::Schedule(v) => {
+                    v.write_graph_viz_stages(writer, next_id, depth + 1)?;
+                }
+                StageDescription::Systems(v) => {
+                    for (name, channel) in v.channels() {
+                        if channel.systems.is_empty() {
+                            continue;
+                        }
+
+                        let channel_id = *next_id;
+                        *next_id += 1;
+                        writeln!(writer, "{indent}    subgraph cluster{channel_id} {{")?;
+                        writeln!(writer, "{indent}        label=\"{name}\";")?;
+                        writeln!(writer, "{indent}        style=filled;")?;
+
+                        // Node IDs are allocated as a contiguous block so edges can be mapped from
+                        // the system index
+                        let base = *next_id;
+                        *next_id += channel.systems.len();
+                        for (i, system) in channel.systems.iter().enumerate() {
+                            let label = escape(system.label.to_str());
+                            writeln!(
+                                writer,
+                                "{indent}        node{} [label=\"{label}\"];",
+                                base + i
+                            )?;
+                        }
+                        for edge in channel.edges.iter() {
+                            write!(
+                                writer,
+                                "{indent}        node{} -> node{} [label=\"",
+                                base + edge.before,
+                                base + edge.after
+                            )?;
+                            for (i, reason) in edge.reasons.iter().enumerate() {
+                                if i != 0 {
+                                    write!(writer, "\\n")?;
+                                }
+                                write!(writer, "{}", escape(&reason.to_string()))?;
+                            }
+                            if edge.is_conflict_only() {
+                                writeln!(writer, "\",color=red];")?;
+                            } else {
+                                writeln!(writer, "\"];")?;
+                            }
+                        }
+                        writeln!(writer, "{indent}    }}")?;
+                    }
+                }
+            }
+            writeln!(writer, "{indent}}}")?;
+        }
+        Ok(())
+    }
+
+    /// Writes the schedule out as a JSON document.
+    ///
+    /// The document is an object with a `stages` array. Every stage has a `label` and a `kind`,
+    /// which is one of `opaque`, `schedule` (with a nested `schedule` object) or `systems` (with
+    /// a `channels` object). Edges refer to systems by label.
+    pub fn json(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        serde_json::to_writer(&mut *writer, &ScheduleReport::new(self))?;
+        writeln!(writer)
+    }
+}
+
+// ============================================================================================== //
+
+// The types below mirror the description types in the layout of the JSON document written by
+// `ScheduleDescription::json`.
+
+#[derive(Serialize)]
+struct ScheduleReport<'a> {
+    stages: Vec<StageReport<'a>>,
+}
+
+impl<'a> ScheduleReport<'a> {
+    fn new(schedule: &'a ScheduleDescription) -> Self {
+        let stages = schedule
+            .stages
+            .iter()
+            .map(|(label, stage)| StageReport {
+                label: label.to_str(),
+                kind: match stage {
+                    StageDescription::Opaque => StageKindReport::Opaque,
+                    StageDescription::Schedule(v) => StageKindReport::Schedule {
+                        schedule: ScheduleReport::new(v),
+                    },
+                    StageDescription::Systems(v) => StageKindReport::Systems {
+                        channels: ChannelsReport {
+                            exclusive_at_start: ChannelReport::new(&v.exclusive_at_start),
+                            parallel: ChannelReport::new(&v.parallel),
+                            exclusive_at_end: ChannelReport::new(&v.exclusive_at_end),
+                        },
+                    },
+                },
+            })
+            .collect();
+        Self { stages }
+    }
+}
+
+#[derive(Serialize)]
+struct StageReport<'a> {
+    label: &'a str,
+
+    #[serde(flatten)]
+    kind: StageKindReport<'a>,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum StageKindReport<'a> {
+    Opaque,
+    Schedule { schedule: ScheduleReport<'a> },
+    Systems { channels: ChannelsReport<'a> },
+}
+
+#[derive(Serialize)]
+struct ChannelsReport<'a> {
+    exclusive_at_start: ChannelReport<'a>,
+    parallel: ChannelReport<'a>,
+    exclusive_at_end: ChannelReport<'a>,
+}
+
+#[derive(Serialize)]
+struct ChannelReport<'a> {
+    systems: Vec<SystemReport<'a>>,
+    edges: Vec<EdgeReport<'a>>,
+}
+
+impl<'a> ChannelReport<'a> {
+    fn new(channel: &'a ChannelDescription) -> Self {
+        let labels = |v: &'a [Label]| v.iter().map(|v| v.to_str()).collect();
+        let uuids = |v: &[Uuid]| v.iter().copied().map(UuidReport).collect();
+        let components = |v: &[(Uuid, u32)]| {
+            v.iter()
+                .map(|&(resource, component)| ComponentReport {
+                    resource: UuidReport(resource),
+                    component,
+                })
+                .collect()
+        };
+
+        let systems = channel
+            .systems
+            .iter()
+            .map(|v| SystemReport {
+                label: v.label.to_str(),
+                resource_reads: uuids(&v.resource_reads),
+                resource_writes: uuids(&v.resource_writes),
+                component_reads: components(&v.component_reads),
+                component_writes: components(&v.component_writes),
+                runs_before: labels(&v.runs_before),
+                runs_after: labels(&v.runs_after),
+            })
+            .collect();
+        let edges = channel
+            .edges
+            .iter()
+            .map(|v| EdgeReport {
+                before: channel.systems[v.before].label.to_str(),
+                after: channel.systems[v.after].label.to_str(),
+                reasons: v.reasons.iter().copied().map(ReasonReport::from).collect(),
+            })
+            .collect();
+        Self { systems, edges }
+    }
+}
+
+#[derive(Serialize)]
+struct SystemReport<'a> {
+    label: &'a str,
+    resource_reads: Vec<UuidReport>,
+    resource_writes: Vec<UuidReport>,
+    component_reads: Vec<ComponentReport>,
+    component_writes: Vec<ComponentReport>,
+    runs_before: Vec<&'a str>,
+    runs_after: Vec<&'a str>,
+}
+
+#[derive(Serialize)]
+struct ComponentReport {
+    resource: UuidReport,
+    component: u32,
+}
+
+#[derive(Serialize)]
+struct EdgeReport<'a> {
+    before: &'a str,
+    after: &'a str,
+    reasons: Vec<ReasonReport>,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum ReasonReport {
+    Explicit,
+    Resource {
+        resource: UuidReport,
+        hazard: &'static str,
+    },
+    Component {
+        resource: UuidReport,
+        component: Option<u32>,
+    },
+}
+
+impl From<DependencyReason> for ReasonReport {
+    fn from(value: DependencyReason) -> Self {
+        match value {
+            DependencyReason::Explicit => ReasonReport::Explicit,
+            DependencyReason::Resource { resource, hazard } => ReasonReport::Resource {
+                resource: UuidReport(resource),
+                hazard: hazard.as_str(),
+            },
+            DependencyReason::Component {
+                resource,
+                component,
+            } => ReasonReport::Component {
+                resource: UuidReport(resource),
+                component,
+            },
+        }
+    }
+}
+
+/// Serializes a [`Uuid`] as its hyphenated string form
+struct UuidReport(Uuid);
+
+impl Serialize for UuidReport {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&self.0)
+    }
+}
+
+/// Escapes a string so it can be embedded in a double quoted GraphViz string
+fn escape(v: &str) -> String {
+    let mut out = String::with_capacity(v.len());
+    for c in v.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out
+}
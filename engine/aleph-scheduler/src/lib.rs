@@ -54,15 +54,32 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
+mod events;
+mod fixed_timestep;
+mod introspection;
 mod stage;
 mod system;
 mod system_schedule;
 
+#[cfg(test)]
+mod tests;
+
 use std::collections::HashMap;
 use std::hash::Hash;
 
 use aleph_label::Label;
+use aleph_object_system::IObject;
 pub use aleph_typed_table::TypedTable;
+pub use events::{
+    EventReader, EventReaderState, EventWriter, EventWriterState, Events, UpdateEventsStage,
+    update_events,
+};
+pub use fixed_timestep::{FixedTime, FixedTimestep};
+pub use introspection::{
+    AccessHazard, ChannelDescription, ConflictReport, ConflictReportEntry, DependencyEdge,
+    DependencyReason, ScheduleDescription, StageDescription, SystemDescription,
+    SystemScheduleDescription,
+};
 pub use stage::{AccessDescriptor, Stage};
 pub use system::{
     AlreadyWasSystem, ExplicitDependencies, IntoSystem, Res, ResMut, ResMutState, ResState,
@@ -273,6 +290,18 @@ impl<A: ScheduleArgs> Schedule<A> {
         })
     }
 
+    /// Registers the event type `T` with the [`UpdateEventsStage`] with the `target` label, so its
+    /// [`Events`] resource is created and updated every time the stage runs.
+    ///
+    /// # Panics
+    ///
+    /// - Will panic if the stage with [`Label`] of `target` does not exist.
+    /// - Will panic if the stage is not of type [`UpdateEventsStage`].
+    #[inline]
+    pub fn add_events<T: IObject + Send + Sync + 'static>(&mut self, target: Label) -> &mut Self {
+        self.stage(target, |v: &mut UpdateEventsStage| v.add_events::<T>())
+    }
+
     /// Looks up the [`Stage`] that was registered with the [`Label`] provided in `label` and passes
     /// a downcasted reference into the closure provided in `func`.
     ///
@@ -327,6 +356,24 @@ impl<A: ScheduleArgs> Schedule<A> {
             .map(move |label| (*label, self.stages[label].as_ref()))
     }
 
+    /// Produces a description of all the stages of the schedule, recursing into nested stages.
+    ///
+    /// The description can be exported with [`ScheduleDescription::graph_viz`] and
+    /// [`ScheduleDescription::json`], or checked for lost parallelism with
+    /// [`ScheduleDescription::conflict_report`]. Stages with out of date execution graphs will
+    /// rebuild them, which is why this needs `&mut self`.
+    pub fn introspect(&mut self) -> ScheduleDescription {
+        let stages = self
+            .stage_order
+            .iter()
+            .map(|label| {
+                let stage = self.stages.get_mut(label).unwrap();
+                (*label, stage.describe())
+            })
+            .collect();
+        ScheduleDescription { stages }
+    }
+
     fn index_from_label(&self, target: Label) -> usize {
         self.stage_order
             .iter()
@@ -357,6 +404,10 @@ impl<A: ScheduleArgs> Stage<A> for Schedule<A> {
         // If we pass the above check then we can continue on and execute the schedule
         self.run_once(args, resources);
     }
+
+    fn describe(&mut self) -> StageDescription {
+        StageDescription::Schedule(self.introspect())
+    }
 }
 
 ///
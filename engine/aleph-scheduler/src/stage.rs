@@ -35,6 +35,7 @@ use aleph_object_system::uuid::Uuid;
 use aleph_typed_table::TypedTable;
 
 use crate::ScheduleArgs;
+use crate::introspection::StageDescription;
 
 ///
 /// The interface expected of an execution stage
@@ -42,6 +43,15 @@ use crate::ScheduleArgs;
 pub trait Stage<A: ScheduleArgs>: Any + 'static {
     /// This will be called by a scheduler exactly once during an execution cycle.
     fn run(&mut self, args: &A::Args<'_>, resources: &mut TypedTable);
+
+    /// Describes the contents of the stage for debugging and tooling. Stages that contain systems
+    /// or other stages should override this so they can be inspected with
+    /// [`crate::Schedule::introspect`].
+    ///
+    /// Takes `&mut self` so stages can bring any lazily built state up to date first.
+    fn describe(&mut self) -> StageDescription {
+        StageDescription::Opaque
+    }
 }
 
 impl<A: ScheduleArgs> dyn Stage<A> {
@@ -99,6 +109,30 @@ pub trait AccessDescriptor: 'static {
     /// Caller uses this to declare a exclusive/write access to the given resource
     fn writes_resource_with_id(&mut self, resource: Uuid);
 
+    /// Caller uses this to declare a shared/read access to the given resource, like
+    /// [`AccessDescriptor::reads_resource_with_id`], that must happen after every other system in
+    /// the same stage that writes the resource. Plain reads are ordered against writes in the order
+    /// the systems were added instead.
+    ///
+    /// This can order a system before one that was added earlier, so it can create a cycle with
+    /// the system's other access. Building a stage whose systems depend on each other in a cycle
+    /// panics.
+    fn reads_resource_after_writers_with_id(&mut self, resource: Uuid);
+
+    /// Caller uses this to declare a shared/read access to a single component type stored inside
+    /// the given resource, rather than the whole resource.
+    ///
+    /// Component access implies shared access to the resource itself, so it conflicts with
+    /// exclusive access to the resource. Access to different component types of the same resource
+    /// never conflicts.
+    fn reads_component_with_id(&mut self, resource: Uuid, component: u32);
+
+    /// Caller uses this to declare a exclusive/write access to a single component type stored
+    /// inside the given resource, rather than the whole resource.
+    ///
+    /// Conflicts with any access to the whole resource, and any access to the same component type.
+    fn writes_component_with_id(&mut self, resource: Uuid, component: u32);
+
     /// Caller uses this to declare the label of another system that `self` should run before
     fn runs_before_label(&mut self, system: Label);
 
@@ -119,6 +153,12 @@ impl dyn AccessDescriptor {
         self.writes_resource_with_id(T::ID);
     }
 
+    /// Generic wrapper around [`AccessDescriptor::reads_resource_after_writers_with_id`] that uses a
+    /// generic parameter to get the ID.
+    pub fn reads_resource_after_writers<T: IObject + Send + Sync + 'static>(&mut self) {
+        self.reads_resource_after_writers_with_id(T::ID);
+    }
+
     /// Generic wrapper around [`AccessDescriptor::runs_before_label`] that handles boxing the label
     pub fn runs_before(&mut self, system: Label) {
         self.runs_before_label(system)
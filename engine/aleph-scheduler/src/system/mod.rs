@@ -116,6 +116,16 @@ pub trait System: Any + 'static {
         //         function. See the documentation of System::execute for more info.
         unsafe { self.execute(input, resources) }
     }
+
+    /// Will be called by a scheduler at a sync point, some time after [`System::execute`], with
+    /// exclusive access to the resources. This allows a system to apply work it deferred while
+    /// executing, like structural changes to the ECS world recorded into a command buffer.
+    ///
+    /// The default implementation does nothing.
+    #[inline]
+    fn apply_deferred(&mut self, resources: &mut TypedTable) {
+        let _ = resources;
+    }
 }
 
 // ============================================================================================== //
@@ -207,6 +217,11 @@ impl<S: System> System for RunsBeforeSystem<S> {
     ) -> Self::Out {
         unsafe { self.s.execute(input, resources) }
     }
+
+    #[inline]
+    fn apply_deferred(&mut self, resources: &mut TypedTable) {
+        self.s.apply_deferred(resources)
+    }
 }
 
 // ============================================================================================== //
@@ -236,6 +251,11 @@ impl<S: System> System for RunsAfterSystem<S> {
     ) -> Self::Out {
         unsafe { self.s.execute(input, resources) }
     }
+
+    #[inline]
+    fn apply_deferred(&mut self, resources: &mut TypedTable) {
+        self.s.apply_deferred(resources)
+    }
 }
 
 // ============================================================================================== //
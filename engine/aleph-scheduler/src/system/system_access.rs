@@ -85,6 +85,13 @@ pub trait SystemParam: Sized {
 #[allow(clippy::missing_safety_doc)]
 pub unsafe trait SystemParamState: Send + Sync + 'static {
     fn init(access: &mut dyn AccessDescriptor) -> Self;
+
+    /// Called at the scheduler's sync point to apply any work the parameter deferred while the
+    /// system was executing. See [`System::apply_deferred`].
+    #[inline]
+    fn apply(&mut self, resources: &mut TypedTable) {
+        let _ = resources;
+    }
 }
 
 // ============================================================================================== //
@@ -172,7 +179,7 @@ impl<'a, T: IObject + Send + Sync + 'static> SystemParam for ResMut<'a, T> {
 unsafe impl<T: IObject + Send + Sync + 'static> SystemParamState for ResMutState<T> {
     #[inline]
     fn init(access: &mut dyn AccessDescriptor) -> Self {
-        access.reads_resource::<T>();
+        access.writes_resource::<T>();
         Self(Default::default())
     }
 }
@@ -220,7 +227,13 @@ impl<Param: SystemParam + 'static, F: SystemParamFunction<Param>> System
 
     #[inline]
     fn declare_access(&mut self, access: &mut dyn AccessDescriptor) {
-        self.state = Some(Param::Fetch::init(access));
+        // Access is re-declared every time the schedule's graph is rebuilt. Only the first state is
+        // kept so parameters that carry data between executions, like event reader cursors, don't
+        // lose it.
+        let state = Param::Fetch::init(access);
+        if self.state.is_none() {
+            self.state = Some(state);
+        }
     }
 
     #[inline]
@@ -231,6 +244,13 @@ impl<Param: SystemParam + 'static, F: SystemParamFunction<Param>> System
     ) -> Self::Out {
         unsafe { self.f.run(self.state.as_mut().unwrap(), resources) }
     }
+
+    #[inline]
+    fn apply_deferred(&mut self, resources: &mut TypedTable) {
+        if let Some(state) = self.state.as_mut() {
+            state.apply(resources);
+        }
+    }
 }
 
 impl<Param: SystemParam + 'static, F: SystemParamFunction<Param>> IntoSystem<(), (), Param> for F {
@@ -339,6 +359,12 @@ macro_rules! impl_param_for_tuple {
             fn init(access: &mut dyn $crate::AccessDescriptor) -> Self {
                 (($($name::init(access),)*))
             }
+
+            #[inline]
+            fn apply(&mut self, resources: &mut $crate::TypedTable) {
+                let ($($name,)*) = self;
+                $($name.apply(resources);)*
+            }
         }
     };
 }
@@ -47,6 +47,16 @@ pub struct SystemAccessDescriptor {
     /// Stores all resources that are written by a given system
     pub resource_writes: HashSet<Uuid>,
 
+    /// Stores the resources in `resource_reads` that must be read after every system that writes
+    /// them, rather than in the order the systems were added
+    pub resource_reads_after_writers: HashSet<Uuid>,
+
+    /// Stores all the (resource, component) pairs that are read by a given system
+    pub component_reads: HashSet<(Uuid, u32)>,
+
+    /// Stores all the (resource, component) pairs that are written by a given system
+    pub component_writes: HashSet<(Uuid, u32)>,
+
     /// Stores the labels of all systems that must run before the system this descriptor is for
     pub runs_before: HashSet<Label>,
 
@@ -60,14 +70,64 @@ impl SystemAccessDescriptor {
             label,
             resource_reads: Default::default(),
             resource_writes: Default::default(),
+            resource_reads_after_writers: Default::default(),
+            component_reads: Default::default(),
+            component_writes: Default::default(),
             runs_before: Default::default(),
             runs_after: Default::default(),
         }
     }
 
+    /// Returns whether the system accesses any individual component of the given resource
+    pub fn accesses_components_of(&self, resource: Uuid) -> bool {
+        self.component_reads.iter().any(|v| v.0 == resource) || self.writes_components_of(resource)
+    }
+
+    /// Returns whether the system writes any individual component of the given resource
+    pub fn writes_components_of(&self, resource: Uuid) -> bool {
+        self.component_writes.iter().any(|v| v.0 == resource)
+    }
+
+    /// Returns the conflicts between `self` and `other` that involve access to individual
+    /// components, as (resource, component) pairs. The component is `None` when access to a
+    /// component conflicts with access to the whole resource.
+    pub fn component_conflicts(&self, other: &SystemAccessDescriptor) -> Vec<(Uuid, Option<u32>)> {
+        fn one_way(
+            a: &SystemAccessDescriptor,
+            b: &SystemAccessDescriptor,
+            out: &mut Vec<(Uuid, Option<u32>)>,
+        ) {
+            for v in a.resource_writes.iter() {
+                if b.accesses_components_of(*v) {
+                    out.push((*v, None));
+                }
+            }
+            for v in a.resource_reads.iter() {
+                if b.writes_components_of(*v) {
+                    out.push((*v, None));
+                }
+            }
+            for v in a.component_writes.iter() {
+                if b.component_reads.contains(v) || b.component_writes.contains(v) {
+                    out.push((v.0, Some(v.1)));
+                }
+            }
+        }
+
+        let mut out = Vec::new();
+        one_way(self, other, &mut out);
+        one_way(other, self, &mut out);
+        out.sort();
+        out.dedup();
+        out
+    }
+
     pub fn clear(&mut self) {
         self.resource_reads.clear();
         self.resource_writes.clear();
+        self.resource_reads_after_writers.clear();
+        self.component_reads.clear();
+        self.component_writes.clear();
         self.runs_before.clear();
         self.runs_after.clear();
     }
@@ -76,7 +136,7 @@ impl SystemAccessDescriptor {
 impl AccessDescriptor for SystemAccessDescriptor {
     fn reads_resource_with_id(&mut self, resource: Uuid) {
         assert!(
-            !self.resource_writes.contains(&resource),
+            !self.resource_writes.contains(&resource) && !self.writes_components_of(resource),
             "System \"{:#?}\" wants shared for resource \"{:?}\" that is already being used",
             self.label,
             resource
@@ -89,9 +149,14 @@ impl AccessDescriptor for SystemAccessDescriptor {
         );
     }
 
+    fn reads_resource_after_writers_with_id(&mut self, resource: Uuid) {
+        self.reads_resource_with_id(resource);
+        self.resource_reads_after_writers.insert(resource);
+    }
+
     fn writes_resource_with_id(&mut self, resource: Uuid) {
         assert!(
-            !self.resource_reads.contains(&resource),
+            !self.resource_reads.contains(&resource) && !self.accesses_components_of(resource),
             "System \"{:#?}\" wants exclusive for resource \"{:?}\" that is already being used",
             self.label,
             resource
@@ -104,6 +169,49 @@ impl AccessDescriptor for SystemAccessDescriptor {
         );
     }
 
+    fn reads_component_with_id(&mut self, resource: Uuid, component: u32) {
+        assert!(
+            !self.resource_writes.contains(&resource),
+            "System \"{:#?}\" wants shared for a component of resource \"{:?}\" that is already being used",
+            self.label,
+            resource
+        );
+        assert!(
+            !self.component_writes.contains(&(resource, component)),
+            "System \"{:#?}\" wants shared for component \"{:?}\" of resource \"{:?}\" that is already being used",
+            self.label,
+            component,
+            resource
+        );
+
+        // Unlike resources, the same component may be read more than once. Two queries in a
+        // system reading the same component is perfectly reasonable.
+        self.component_reads.insert((resource, component));
+    }
+
+    fn writes_component_with_id(&mut self, resource: Uuid, component: u32) {
+        assert!(
+            !self.resource_reads.contains(&resource) && !self.resource_writes.contains(&resource),
+            "System \"{:#?}\" wants exclusive for a component of resource \"{:?}\" that is already being used",
+            self.label,
+            resource
+        );
+        assert!(
+            !self.component_reads.contains(&(resource, component)),
+            "System \"{:#?}\" wants exclusive for component \"{:?}\" of resource \"{:?}\" that is already being used",
+            self.label,
+            component,
+            resource
+        );
+        assert!(
+            self.component_writes.insert((resource, component)),
+            "System \"{:#?}\" requested exclusive access for component \"{:?}\" of resource \"{:?}\" more than once",
+            self.label,
+            component,
+            resource
+        );
+    }
+
     fn runs_before_label(&mut self, system: Label) {
         self.runs_before.insert(system);
     }
@@ -35,6 +35,7 @@ mod system_channel;
 use aleph_label::Label;
 use aleph_typed_table::TypedTable;
 
+use crate::introspection::{StageDescription, SystemScheduleDescription};
 use crate::system::{IntoSystem, System};
 use crate::system_schedule::system_cell::{ExclusiveSystemCell, SystemCell};
 use crate::system_schedule::system_channel::SystemChannel;
@@ -113,6 +114,23 @@ impl<A: ScheduleArgs> SystemSchedule<A> {
         self.exclusive_at_start.execute_exclusive(args, resources);
         self.parallel_systems.execute_parallel(args, resources);
         self.exclusive_at_end.execute_exclusive(args, resources);
+
+        // The end of the stage is a sync point, so systems can now apply any work they deferred
+        // while running, like structural changes to the ECS world.
+        self.exclusive_at_start.apply_deferred(resources);
+        self.parallel_systems.apply_deferred(resources);
+        self.exclusive_at_end.apply_deferred(resources);
+    }
+
+    /// Produces a description of the systems in the stage and the execution graph built between
+    /// them, rebuilding the graph first if it is out of date.
+    pub fn introspect(&mut self) -> SystemScheduleDescription {
+        self.check_dirty();
+        SystemScheduleDescription {
+            exclusive_at_start: self.exclusive_at_start.describe(),
+            parallel: self.parallel_systems.describe(),
+            exclusive_at_end: self.exclusive_at_end.describe(),
+        }
     }
 }
 
@@ -120,6 +138,10 @@ impl<A: ScheduleArgs> Stage<A> for SystemSchedule<A> {
     fn run(&mut self, args: &A::Args<'_>, resources: &mut TypedTable) {
         self.run_once(args, resources)
     }
+
+    fn describe(&mut self) -> StageDescription {
+        StageDescription::Systems(self.introspect())
+    }
 }
 
 impl<A: ScheduleArgs> SystemSchedule<A> {
@@ -27,11 +27,12 @@
 // SOFTWARE.
 //
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 use aleph_label::Label;
 
 use crate::ScheduleArgs;
+use crate::introspection::DependencyReason;
 use crate::system::System;
 use crate::system_schedule::access_descriptor::SystemAccessDescriptor;
 use crate::system_schedule::system_cell::{ExclusiveSystemCell, SystemCell};
@@ -82,4 +83,8 @@ pub struct GraphEdges {
 
     /// A set of indices to the systems that execute after `system`
     pub successors: HashSet<usize>,
+
+    /// Maps the index of each predecessor to the reasons the edge from it was created. Only used
+    /// for introspection.
+    pub reasons: HashMap<usize, Vec<DependencyReason>>,
 }
@@ -61,6 +61,8 @@ pub trait GenericSystemCell<A: ScheduleArgs> {
     unsafe fn execute(&self, args: &A::Args<'_>, resources: &TypedTable);
 
     fn execute_safe(&self, args: &A::Args<'_>, resources: &mut TypedTable);
+
+    fn apply_deferred(&self, resources: &mut TypedTable);
 }
 
 impl<A: ScheduleArgs> GenericSystemCell<A> for SystemCell<A> {
@@ -83,6 +85,12 @@ impl<A: ScheduleArgs> GenericSystemCell<A> for SystemCell<A> {
         system.execute_safe(args, resources);
         self.store(Some(system));
     }
+
+    fn apply_deferred(&self, resources: &mut TypedTable) {
+        let mut system = self.take().unwrap();
+        system.apply_deferred(resources);
+        self.store(Some(system));
+    }
 }
 
 impl<A: ScheduleArgs> GenericSystemCell<A> for ExclusiveSystemCell<A> {
@@ -105,4 +113,10 @@ impl<A: ScheduleArgs> GenericSystemCell<A> for ExclusiveSystemCell<A> {
         system.execute_safe(args, resources);
         self.set(Some(system));
     }
+
+    fn apply_deferred(&self, resources: &mut TypedTable) {
+        let mut system = self.take().unwrap();
+        system.apply_deferred(resources);
+        self.set(Some(system));
+    }
 }
@@ -41,7 +41,11 @@ use crossbeam::sync::WaitGroup;
 use rayon::prelude::*;
 
 use crate::ScheduleArgs;
+use crate::introspection::{
+    AccessHazard, ChannelDescription, DependencyEdge, DependencyReason, SystemDescription,
+};
 use crate::system::{IntoSystem, System};
+use crate::system_schedule::access_descriptor::SystemAccessDescriptor;
 use crate::system_schedule::system_box::SystemBox;
 use crate::system_schedule::system_cell::{ExclusiveSystemCell, GenericSystemCell, SystemCell};
 
@@ -270,11 +274,20 @@ impl<A: ScheduleArgs, C: GenericSystemCell<A>> SystemChannel<A, C> {
         }
     }
 
+    /// Applies the deferred work of every system in the channel, in the order the systems were
+    /// registered.
+    pub fn apply_deferred(&mut self, resources: &mut TypedTable) {
+        for system in self.systems.iter() {
+            system.system.apply_deferred(resources);
+        }
+    }
+
     /// Used for clearing all the edges from all the nodes prior to a graph rebuild
     pub fn clear_graph_nodes(&mut self) {
         self.systems.iter_mut().for_each(|v| {
             v.edges.predecessors.clear();
             v.edges.successors.clear();
+            v.edges.reasons.clear();
         });
         self.root_systems.clear();
     }
@@ -296,10 +309,7 @@ impl<A: ScheduleArgs, C: GenericSystemCell<A>> SystemChannel<A, C> {
                 let before = self.system_label_map.get(before).copied().unwrap();
 
                 // Mark ourselves as a predecessor to that system
-                self.systems[before].edges.predecessors.insert(i);
-
-                // Add the target system to our successor set
-                self.systems[i].edges.successors.insert(before);
+                self.add_edge(i, before, DependencyReason::Explicit);
             }
             self.systems[i].access.runs_before = runs_before;
 
@@ -310,10 +320,7 @@ impl<A: ScheduleArgs, C: GenericSystemCell<A>> SystemChannel<A, C> {
                 let after = self.system_label_map.get(after).copied().unwrap();
 
                 // Mark ourselves as a successor to that system
-                self.systems[after].edges.successors.insert(i);
-
-                // Add the target system to our predecessor set
-                self.systems[i].edges.predecessors.insert(after);
+                self.add_edge(after, i, DependencyReason::Explicit);
             }
             self.systems[i].access.runs_after = runs_after;
         }
@@ -323,6 +330,8 @@ impl<A: ScheduleArgs, C: GenericSystemCell<A>> SystemChannel<A, C> {
         let mut last_resource_write: HashMap<Uuid, usize> = HashMap::new();
         let mut last_resource_reads: HashMap<Uuid, Vec<usize>> = HashMap::new();
 
+        self.handle_reads_after_writers();
+
         for system_index in 0..self.systems.len() {
             self.handle_writes(
                 &mut last_resource_write,
@@ -335,6 +344,8 @@ impl<A: ScheduleArgs, C: GenericSystemCell<A>> SystemChannel<A, C> {
                 &mut last_resource_reads,
                 system_index,
             );
+
+            self.handle_component_access(system_index);
         }
 
         for (i, system) in self.systems.iter().enumerate() {
@@ -342,6 +353,131 @@ impl<A: ScheduleArgs, C: GenericSystemCell<A>> SystemChannel<A, C> {
                 self.root_systems.push(i);
             }
         }
+
+        // A cycle would never let any of its systems run, so both executors would wait forever
+        if let Some(cycle) = self.find_cycle() {
+            let labels: Vec<&str> = cycle
+                .iter()
+                .chain(cycle.first())
+                .map(|&v| self.systems[v].access.label.to_str())
+                .collect();
+            panic!("Systems have a cyclic dependency: {}.", labels.join(" -> "));
+        }
+    }
+
+    /// Searches the graph for a cycle, returning the systems in the first one found in the order
+    /// they depend on each other. Each system runs after the one before it, and the first runs
+    /// after the last.
+    fn find_cycle(&self) -> Option<Vec<usize>> {
+        #[derive(Copy, Clone, PartialEq, Eq)]
+        enum Visit {
+            New,
+            OnPath,
+            Done,
+        }
+
+        fn visit<A, T>(
+            channel: &SystemChannel<A, T>,
+            system: usize,
+            state: &mut [Visit],
+            path: &mut Vec<usize>,
+        ) -> Option<Vec<usize>> {
+            state[system] = Visit::OnPath;
+            path.push(system);
+
+            // Sorted so the same graph always reports the same cycle
+            let mut successors: Vec<usize> = channel.systems[system]
+                .edges
+                .successors
+                .iter()
+                .copied()
+                .collect();
+            successors.sort();
+            for successor in successors {
+                match state[successor] {
+                    Visit::OnPath => {
+                        let start = path.iter().position(|&v| v == successor).unwrap();
+                        return Some(path[start..].to_vec());
+                    }
+                    Visit::New => {
+                        if let Some(cycle) = visit(channel, successor, state, path) {
+                            return Some(cycle);
+                        }
+                    }
+                    Visit::Done => {}
+                }
+            }
+
+            path.pop();
+            state[system] = Visit::Done;
+            None
+        }
+
+        let mut state = vec![Visit::New; self.systems.len()];
+        let mut path = Vec::new();
+        for system in 0..self.systems.len() {
+            if state[system] == Visit::New
+                && let Some(cycle) = visit(self, system, &mut state, &mut path)
+            {
+                return Some(cycle);
+            }
+        }
+        None
+    }
+
+    /// Adds an edge to the graph so the system at `after` runs after the system at `before`,
+    /// recording the reason the edge was needed.
+    fn add_edge(&mut self, before: usize, after: usize, reason: DependencyReason) {
+        self.systems[after].edges.predecessors.insert(before);
+        self.systems[before].edges.successors.insert(after);
+
+        let reasons = self.systems[after].edges.reasons.entry(before).or_default();
+        if !reasons.contains(&reason) {
+            reasons.push(reason);
+        }
+    }
+
+    /// Produces a description of the systems in the channel and the current execution graph
+    /// between them. The graph must already have been built.
+    pub fn describe(&self) -> ChannelDescription {
+        fn sorted<T: Ord + Copy>(v: impl Iterator<Item = T>) -> Vec<T> {
+            let mut v: Vec<T> = v.collect();
+            v.sort();
+            v
+        }
+
+        let systems = self
+            .systems
+            .iter()
+            .map(|v| SystemDescription {
+                label: v.access.label,
+                resource_reads: sorted(v.access.resource_reads.iter().copied()),
+                resource_writes: sorted(v.access.resource_writes.iter().copied()),
+                component_reads: sorted(v.access.component_reads.iter().copied()),
+                component_writes: sorted(v.access.component_writes.iter().copied()),
+                runs_before: sorted(v.access.runs_before.iter().copied()),
+                runs_after: sorted(v.access.runs_after.iter().copied()),
+            })
+            .collect();
+
+        let mut edges = Vec::new();
+        for (after, system) in self.systems.iter().enumerate() {
+            for before in sorted(system.edges.predecessors.iter().copied()) {
+                let reasons = system
+                    .edges
+                    .reasons
+                    .get(&before)
+                    .map(|v| sorted(v.iter().copied()))
+                    .unwrap_or_default();
+                edges.push(DependencyEdge {
+                    before,
+                    after,
+                    reasons,
+                });
+            }
+        }
+
+        ChannelDescription { systems, edges }
     }
 
     pub fn handle_writes(
@@ -356,6 +492,7 @@ impl<A: ScheduleArgs, C: GenericSystemCell<A>> SystemChannel<A, C> {
             last_resource_write,
             last_resource_reads,
             system_index,
+            |resource, hazard| DependencyReason::Resource { resource, hazard },
         );
         self.systems[system_index].access.resource_writes = writes;
     }
@@ -366,21 +503,30 @@ impl<A: ScheduleArgs, C: GenericSystemCell<A>> SystemChannel<A, C> {
         last_write: &mut HashMap<T, usize>,
         last_reads: &mut HashMap<T, Vec<usize>>,
         system_index: usize,
+        make_reason: impl Fn(T, AccessHazard) -> DependencyReason,
     ) where
         TB: ToOwned<Owned = T> + ?Sized + Eq + Hash + 'a,
         T: Eq + Hash + Borrow<TB>,
         I: Iterator<Item = &'a TB>,
     {
         for write in writes {
-            last_write.insert(write.to_owned(), system_index);
+            // Two writes conflict with each other, so the previous writer must finish first. Any
+            // reads in between are handled below.
+            if let Some(prev) = last_write.insert(write.to_owned(), system_index)
+                && prev != system_index
+            {
+                let reason = make_reason(write.to_owned(), AccessHazard::WriteAfterWrite);
+                self.add_edge(prev, system_index, reason);
+            }
 
             match last_reads.get_mut(write) {
                 None => {}
                 Some(reads) => {
                     for read in reads.iter().copied() {
                         if read != system_index {
-                            self.systems[system_index].edges.predecessors.insert(read);
-                            self.systems[read].edges.successors.insert(system_index);
+                            let reason =
+                                make_reason(write.to_owned(), AccessHazard::WriteAfterRead);
+                            self.add_edge(read, system_index, reason);
                         }
                     }
                     reads.clear();
@@ -389,6 +535,70 @@ impl<A: ScheduleArgs, C: GenericSystemCell<A>> SystemChannel<A, C> {
         }
     }
 
+    /// Orders the system after every earlier system whose component access conflicts with it.
+    ///
+    /// Access to a whole resource conflicts with access to the components inside it, which can't
+    /// be expressed with the per-key tracking used for resources. Instead, systems that declare
+    /// component access are compared pairwise with all the systems before them. Conflicts purely
+    /// between whole resources are already ordered by [`SystemChannel::handle_writes`] and
+    /// [`SystemChannel::handle_reads`].
+    pub fn handle_component_access(&mut self, system_index: usize) {
+        fn has_component_access(access: &SystemAccessDescriptor) -> bool {
+            !access.component_reads.is_empty() || !access.component_writes.is_empty()
+        }
+
+        let this_has_component_access = has_component_access(&self.systems[system_index].access);
+        for other_index in 0..system_index {
+            let this = &self.systems[system_index].access;
+            let other = &self.systems[other_index].access;
+
+            // Conflicts between two systems that only access whole resources are already handled
+            if !this_has_component_access && !has_component_access(other) {
+                continue;
+            }
+
+            for (resource, component) in this.component_conflicts(other) {
+                let reason = DependencyReason::Component {
+                    resource,
+                    component,
+                };
+                self.add_edge(other_index, system_index, reason);
+            }
+        }
+    }
+
+    /// Orders every system that reads a resource after its writers, with
+    /// [`AccessDescriptor::reads_resource_after_writers_with_id`], after every system that writes
+    /// the resource, wherever they are in the channel.
+    ///
+    /// These reads are skipped by [`SystemChannel::handle_reads`], and so are never ordered before
+    /// a later writer.
+    ///
+    /// [`AccessDescriptor::reads_resource_after_writers_with_id`]: crate::AccessDescriptor::reads_resource_after_writers_with_id
+    pub fn handle_reads_after_writers(&mut self) {
+        for reader in 0..self.systems.len() {
+            let reads =
+                std::mem::take(&mut self.systems[reader].access.resource_reads_after_writers);
+            for writer in 0..self.systems.len() {
+                for &resource in reads.iter() {
+                    if writer != reader
+                        && self.systems[writer]
+                            .access
+                            .resource_writes
+                            .contains(&resource)
+                    {
+                        let reason = DependencyReason::Resource {
+                            resource,
+                            hazard: AccessHazard::ReadAfterWrite,
+                        };
+                        self.add_edge(writer, reader, reason);
+                    }
+                }
+            }
+            self.systems[reader].access.resource_reads_after_writers = reads;
+        }
+    }
+
     pub fn handle_reads(
         &mut self,
         last_resource_write: &mut HashMap<Uuid, usize>,
@@ -396,11 +606,20 @@ impl<A: ScheduleArgs, C: GenericSystemCell<A>> SystemChannel<A, C> {
         system_index: usize,
     ) {
         let reads = std::mem::take(&mut self.systems[system_index].access.resource_reads);
+        let after_writers = &self.systems[system_index]
+            .access
+            .resource_reads_after_writers;
+        let reads_in_order: Vec<Uuid> = reads
+            .iter()
+            .filter(|v| !after_writers.contains(v))
+            .copied()
+            .collect();
         self.handle_reads_generic(
-            reads.iter(),
+            reads_in_order.iter(),
             last_resource_write,
             last_resource_reads,
             system_index,
+            |resource, hazard| DependencyReason::Resource { resource, hazard },
         );
         self.systems[system_index].access.resource_reads = reads;
     }
@@ -411,6 +630,7 @@ impl<A: ScheduleArgs, C: GenericSystemCell<A>> SystemChannel<A, C> {
         last_write: &mut HashMap<T, usize>,
         last_reads: &mut HashMap<T, Vec<usize>>,
         system_index: usize,
+        make_reason: impl Fn(T, AccessHazard) -> DependencyReason,
     ) where
         TB: ToOwned<Owned = T> + ?Sized + Hash + Eq + 'a,
         T: Eq + Hash + Borrow<TB>,
@@ -432,8 +652,8 @@ impl<A: ScheduleArgs, C: GenericSystemCell<A>> SystemChannel<A, C> {
                 None => {}
                 Some(write) => {
                     if write != system_index {
-                        self.systems[system_index].edges.predecessors.insert(write);
-                        self.systems[write].edges.successors.insert(system_index);
+                        let reason = make_reason(read.to_owned(), AccessHazard::ReadAfterWrite);
+                        self.add_edge(write, system_index, reason);
                     }
                 }
             }
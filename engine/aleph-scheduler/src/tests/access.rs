@@ -0,0 +1,86 @@
+//
+//
+// This file is a part of Aleph
+//
+// https://github.com/nathanvoglsam/aleph
+//
+// MIT License
+//
+// Copyright (c) 2020 Aleph Engine
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use aleph_label::make_label;
+use aleph_object_system::{IObject, unsafe_impl_iobject};
+
+use crate::{AccessHazard, DependencyReason, ResMut, Schedule, SystemSchedule, TypedTable};
+
+#[derive(Default)]
+struct Order(Vec<u32>);
+unsafe_impl_iobject!(Order, "019a1c2d-3e4f-7a5b-8c6d-7e8f9a0b1c27");
+
+fn push_1(mut order: ResMut<Order>) {
+    order.0.push(1);
+}
+
+fn push_2(mut order: ResMut<Order>) {
+    order.0.push(2);
+}
+
+#[test]
+fn res_mut_declares_a_write() {
+    let mut stage = SystemSchedule::<()>::default();
+    stage.add_system(make_label!("push_1"), push_1);
+
+    let channel = stage.introspect().parallel;
+    assert_eq!(channel.systems[0].resource_writes, [Order::ID]);
+    assert!(channel.systems[0].resource_reads.is_empty());
+}
+
+#[test]
+fn writers_of_a_resource_are_ordered() {
+    let mut stage = SystemSchedule::<()>::default();
+    stage
+        .add_system(make_label!("push_1"), push_1)
+        .add_system(make_label!("push_2"), push_2);
+
+    let channel = stage.introspect().parallel;
+    assert_eq!(channel.edges.len(), 1);
+    assert_eq!(channel.edges[0].before, 0);
+    assert_eq!(channel.edges[0].after, 1);
+    assert_eq!(
+        channel.edges[0].reasons,
+        [DependencyReason::Resource {
+            resource: Order::ID,
+            hazard: AccessHazard::WriteAfterWrite,
+        }]
+    );
+
+    // The writers never run concurrently, and always run in the order they were added
+    let mut schedule = Schedule::default();
+    schedule.add_stage(make_label!("tests::Update"), stage);
+    let mut resources = TypedTable::default();
+    resources.insert(Order::default());
+    for _ in 0..16 {
+        schedule.run_once(&(), &mut resources);
+    }
+    let order = resources.get_ref::<Order>().unwrap();
+    assert_eq!(order.0, [1, 2].repeat(16));
+}
@@ -0,0 +1,225 @@
+//
+//
+// This file is a part of Aleph
+//
+// https://github.com/nathanvoglsam/aleph
+//
+// MIT License
+//
+// Copyright (c) 2020 Aleph Engine
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use aleph_label::make_label;
+use aleph_object_system::unsafe_impl_iobject;
+
+use super::{Frame, run_frames};
+use crate::{
+    EventReader, EventWriter, Events, Res, ResMut, Schedule, SystemSchedule, TypedTable,
+    UpdateEventsStage,
+};
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+struct Ping(u32);
+unsafe_impl_iobject!(Ping, "019a1c2d-3e4f-7a5b-8c6d-7e8f9a0b1c21");
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+struct Pong(u32);
+unsafe_impl_iobject!(Pong, "019a1c2d-3e4f-7a5b-8c6d-7e8f9a0b1c28");
+
+#[derive(Default)]
+struct Received(Vec<u32>);
+unsafe_impl_iobject!(Received, "019a1c2d-3e4f-7a5b-8c6d-7e8f9a0b1c22");
+
+#[derive(Default)]
+struct ReceivedLate(Vec<u32>);
+unsafe_impl_iobject!(ReceivedLate, "019a1c2d-3e4f-7a5b-8c6d-7e8f9a0b1c23");
+
+fn writer(frame: Res<Frame>, mut events: EventWriter<Ping>) {
+    events.send(Ping(frame.0));
+}
+
+fn reader(mut received: ResMut<Received>, mut events: EventReader<Ping>) {
+    received.0.extend(events.read().map(|v| v.0));
+}
+
+/// A schedule with an [`UpdateEventsStage`] for [`Ping`] followed by two system stages
+fn event_schedule() -> Schedule {
+    let mut schedule = Schedule::default();
+    schedule.add_stage(make_label!("tests::Events"), UpdateEventsStage::default());
+    schedule.add_stage(make_label!("tests::First"), SystemSchedule::default());
+    schedule.add_stage(make_label!("tests::Second"), SystemSchedule::default());
+    schedule.add_events::<Ping>(make_label!("tests::Events"));
+    schedule
+}
+
+#[test]
+fn events_live_for_two_updates() {
+    let mut events = Events::default();
+    events.send(Ping(0));
+    assert_eq!(events.iter().copied().collect::<Vec<_>>(), [Ping(0)]);
+
+    events.update();
+    events.send(Ping(1));
+    assert_eq!(
+        events.iter().copied().collect::<Vec<_>>(),
+        [Ping(0), Ping(1)]
+    );
+
+    events.update();
+    assert_eq!(events.iter().copied().collect::<Vec<_>>(), [Ping(1)]);
+
+    events.update();
+    assert!(events.is_empty());
+}
+
+#[test]
+fn writer_runs_before_reader() {
+    let mut schedule = event_schedule();
+
+    // The reader is added first but must still run after the writer
+    schedule.add_system_to_stage(make_label!("tests::First"), make_label!("reader"), reader);
+    schedule.add_system_to_stage(make_label!("tests::First"), make_label!("writer"), writer);
+
+    let mut resources = TypedTable::default();
+    resources.insert(Received::default());
+
+    run_frames(&mut schedule, &mut resources, 0..1);
+    assert_eq!(resources.get_ref::<Received>().unwrap().0, [0]);
+
+    run_frames(&mut schedule, &mut resources, 1..3);
+    assert_eq!(resources.get_ref::<Received>().unwrap().0, [0, 1, 2]);
+}
+
+#[test]
+fn events_are_seen_next_frame() {
+    let mut schedule = event_schedule();
+
+    // The reader's stage runs before the writer's, so it only sees the previous frame's events
+    schedule.add_system_to_stage(make_label!("tests::First"), make_label!("reader"), reader);
+    schedule.add_system_to_stage(make_label!("tests::Second"), make_label!("writer"), writer);
+
+    let mut resources = TypedTable::default();
+    resources.insert(Received::default());
+
+    run_frames(&mut schedule, &mut resources, 0..1);
+    assert!(resources.get_ref::<Received>().unwrap().0.is_empty());
+
+    run_frames(&mut schedule, &mut resources, 1..3);
+    assert_eq!(resources.get_ref::<Received>().unwrap().0, [0, 1]);
+}
+
+#[test]
+fn reader_cursors_are_per_system() {
+    // Skips the first frame without marking the events as read
+    fn late_reader(
+        frame: Res<Frame>,
+        mut received: ResMut<ReceivedLate>,
+        mut events: EventReader<Ping>,
+    ) {
+        if frame.0 != 0 {
+            received.0.extend(events.read().map(|v| v.0));
+        }
+    }
+
+    let mut schedule = event_schedule();
+    schedule.add_system_to_stage(make_label!("tests::First"), make_label!("writer"), writer);
+    schedule.add_system_to_stage(make_label!("tests::Second"), make_label!("reader"), reader);
+    schedule.add_system_to_stage(
+        make_label!("tests::Second"),
+        make_label!("late_reader"),
+        late_reader,
+    );
+
+    let mut resources = TypedTable::default();
+    resources.insert(Received::default());
+    resources.insert(ReceivedLate::default());
+
+    run_frames(&mut schedule, &mut resources, 0..1);
+    assert_eq!(resources.get_ref::<Received>().unwrap().0, [0]);
+    assert!(resources.get_ref::<ReceivedLate>().unwrap().0.is_empty());
+
+    // The event from the first frame is still alive, and the late reader's cursor is independent
+    // of the other reader's
+    run_frames(&mut schedule, &mut resources, 1..3);
+    assert_eq!(resources.get_ref::<Received>().unwrap().0, [0, 1, 2]);
+    assert_eq!(resources.get_ref::<ReceivedLate>().unwrap().0, [0, 1, 2]);
+}
+
+#[test]
+fn update_events_stage_updates_once() {
+    let mut schedule = event_schedule();
+
+    // Registering the same type again must not update it twice per run
+    schedule.add_events::<Ping>(make_label!("tests::Events"));
+
+    let mut resources = TypedTable::default();
+    schedule.run_once(&(), &mut resources);
+    resources.get_mut::<Events<Ping>>().unwrap().send(Ping(0));
+
+    schedule.run_once(&(), &mut resources);
+    assert_eq!(resources.get_ref::<Events<Ping>>().unwrap().len(), 1);
+
+    schedule.run_once(&(), &mut resources);
+    assert!(resources.get_ref::<Events<Ping>>().unwrap().is_empty());
+}
+
+#[test]
+#[should_panic(expected = "Systems have a cyclic dependency: ping -> pong -> ping.")]
+fn ping_pong_events_are_a_cycle() {
+    fn ping(mut pings: EventWriter<Ping>, mut pongs: EventReader<Pong>) {
+        pings.send_batch(pongs.read().map(|v| Ping(v.0)));
+    }
+
+    fn pong(mut pongs: EventWriter<Pong>, mut pings: EventReader<Ping>) {
+        pongs.send_batch(pings.read().map(|v| Pong(v.0)));
+    }
+
+    // Each system reads events the other writes, so each must run after the other
+    let mut schedule = event_schedule();
+    schedule.add_events::<Pong>(make_label!("tests::Events"));
+    schedule.add_system_to_stage(make_label!("tests::First"), make_label!("ping"), ping);
+    schedule.add_system_to_stage(make_label!("tests::First"), make_label!("pong"), pong);
+
+    let mut resources = TypedTable::default();
+    run_frames(&mut schedule, &mut resources, 0..1);
+}
+
+#[test]
+#[should_panic(expected = "cyclic dependency")]
+fn reader_before_writer_of_its_resource_is_a_cycle() {
+    fn counting_writer(received: Res<Received>, mut events: EventWriter<Ping>) {
+        events.send(Ping(received.0.len() as u32));
+    }
+
+    // The reader runs after the writer because it reads the writer's events, but the writer also
+    // reads 'Received' after the reader, which was added first, writes it.
+    let mut schedule = event_schedule();
+    schedule.add_system_to_stage(make_label!("tests::First"), make_label!("reader"), reader);
+    schedule.add_system_to_stage(
+        make_label!("tests::First"),
+        make_label!("writer"),
+        counting_writer,
+    );
+
+    let mut resources = TypedTable::default();
+    resources.insert(Received::default());
+    run_frames(&mut schedule, &mut resources, 0..1);
+}
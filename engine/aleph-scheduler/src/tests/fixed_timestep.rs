@@ -0,0 +1,164 @@
+//
+//
+// This file is a part of Aleph
+//
+// https://github.com/nathanvoglsam/aleph
+//
+// MIT License
+//
+// Copyright (c) 2020 Aleph Engine
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use std::cell::Cell;
+use std::rc::Rc;
+
+use aleph_label::make_label;
+use aleph_object_system::unsafe_impl_iobject;
+
+use crate::{FixedTime, FixedTimestep, Res, ResMut, Stage, SystemSchedule, TypedTable};
+
+#[derive(Default)]
+struct Steps(u32);
+unsafe_impl_iobject!(Steps, "019a1c2d-3e4f-7a5b-8c6d-7e8f9a0b1c24");
+
+/// Builds a stage stepping 4 times a second, with a delta time that can be changed between runs
+/// through the returned cell. The child schedule counts its runs in the [`Steps`] resource.
+fn counting_stage() -> (FixedTimestep, Rc<Cell<f64>>) {
+    fn count(mut steps: ResMut<Steps>) {
+        steps.0 += 1;
+    }
+
+    let delta = Rc::new(Cell::new(0.0));
+    let source = delta.clone();
+    let mut stage = FixedTimestep::with_rate(4.0, move || source.get());
+    stage
+        .schedule_mut()
+        .add_stage(make_label!("tests::Fixed"), SystemSchedule::default())
+        .add_system_to_stage(make_label!("tests::Fixed"), make_label!("count"), count);
+    (stage, delta)
+}
+
+/// Runs the stage once with the given delta time and returns the published [`FixedTime`] and the
+/// total number of times the child schedule has run.
+fn run(
+    stage: &mut FixedTimestep,
+    delta: &Cell<f64>,
+    resources: &mut TypedTable,
+    v: f64,
+) -> (FixedTime, u32) {
+    delta.set(v);
+    stage.run(&(), resources);
+    let time = resources.get_ref::<FixedTime>().unwrap().clone();
+    (time, resources.get_ref::<Steps>().unwrap().0)
+}
+
+#[test]
+fn fixed_timestep_accumulates_partial_steps() {
+    let (mut stage, delta) = counting_stage();
+    let mut resources = TypedTable::default();
+    resources.insert(Steps::default());
+
+    // Half a step isn't enough to run anything
+    let (time, steps) = run(&mut stage, &delta, &mut resources, 0.125);
+    assert_eq!(steps, 0);
+    assert_eq!(time.step, 0.25);
+    assert_eq!(time.steps_this_frame, 0);
+    assert_eq!(time.alpha, 0.5);
+
+    // The other half completes the step
+    let (time, steps) = run(&mut stage, &delta, &mut resources, 0.125);
+    assert_eq!(steps, 1);
+    assert_eq!(time.steps_this_frame, 1);
+    assert_eq!(time.tick, 1);
+    assert_eq!(time.alpha, 0.0);
+
+    // Two and a half steps
+    let (time, steps) = run(&mut stage, &delta, &mut resources, 0.625);
+    assert_eq!(steps, 3);
+    assert_eq!(time.steps_this_frame, 2);
+    assert_eq!(time.tick, 3);
+    assert_eq!(time.alpha, 0.5);
+
+    // Negative deltas are ignored
+    let (time, steps) = run(&mut stage, &delta, &mut resources, -1.0);
+    assert_eq!(steps, 3);
+    assert_eq!(time.steps_this_frame, 0);
+    assert_eq!(time.alpha, 0.5);
+}
+
+#[test]
+fn fixed_timestep_clamps_to_max_steps() {
+    let (mut stage, delta) = counting_stage();
+    stage.max_steps(3);
+    let mut resources = TypedTable::default();
+    resources.insert(Steps::default());
+
+    // Eight and a half steps, only three are run and the rest of the whole steps are dropped
+    let (time, steps) = run(&mut stage, &delta, &mut resources, 2.125);
+    assert_eq!(steps, 3);
+    assert_eq!(time.steps_this_frame, 3);
+    assert_eq!(time.tick, 3);
+    assert_eq!(time.alpha, 0.5);
+    assert_eq!(stage.alpha(), 0.5);
+
+    // The partial step was kept
+    let (time, steps) = run(&mut stage, &delta, &mut resources, 0.125);
+    assert_eq!(steps, 4);
+    assert_eq!(time.steps_this_frame, 1);
+    assert_eq!(time.alpha, 0.0);
+}
+
+#[test]
+fn fixed_timestep_publishes_before_each_step() {
+    fn record(time: Res<FixedTime>, mut steps: ResMut<Steps>) {
+        steps.0 += 1;
+        assert_eq!(time.steps_this_frame, steps.0);
+    }
+
+    let mut stage = FixedTimestep::with_rate(4.0, || 0.75);
+    stage
+        .schedule_mut()
+        .add_stage(make_label!("tests::Fixed"), SystemSchedule::default())
+        .add_system_to_stage(make_label!("tests::Fixed"), make_label!("record"), record);
+
+    let mut resources = TypedTable::default();
+    resources.insert(Steps::default());
+    stage.run(&(), &mut resources);
+    assert_eq!(resources.get_ref::<Steps>().unwrap().0, 3);
+}
+
+#[test]
+#[should_panic]
+fn fixed_timestep_rejects_zero_rate() {
+    FixedTimestep::<()>::with_rate(0.0, || 0.0);
+}
+
+#[test]
+#[should_panic]
+fn fixed_timestep_rejects_infinite_rate() {
+    FixedTimestep::<()>::with_rate(f64::INFINITY, || 0.0);
+}
+
+#[test]
+#[should_panic]
+fn fixed_timestep_rejects_nan_rate() {
+    FixedTimestep::<()>::with_rate(f64::NAN, || 0.0);
+}
@@ -0,0 +1,200 @@
+//
+//
+// This file is a part of Aleph
+//
+// https://github.com/nathanvoglsam/aleph
+//
+// MIT License
+//
+// Copyright (c) 2020 Aleph Engine
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use aleph_label::make_label;
+use aleph_object_system::{IObject, unsafe_impl_iobject};
+use serde_json::{Value, json};
+
+use crate::{
+    AccessHazard, DependencyReason, ExplicitDependencies, FixedTimestep, IntoSystem, Res, ResMut,
+    Schedule, ScheduleDescription, SystemSchedule, UpdateEventsStage,
+};
+
+struct ResourceA;
+unsafe_impl_iobject!(ResourceA, "019a1c2d-3e4f-7a5b-8c6d-7e8f9a0b1c25");
+
+struct ResourceB;
+unsafe_impl_iobject!(ResourceB, "019a1c2d-3e4f-7a5b-8c6d-7e8f9a0b1c26");
+
+fn write_a(_: ResMut<ResourceA>) {}
+
+fn read_b(_: Res<ResourceB>) {}
+
+fn write_b(_: ResMut<ResourceB>) {}
+
+/// Builds a schedule with an opaque stage, a system stage and a fixed timestep stage with a nested
+/// system stage.
+///
+/// In "tests::Update" the two writers of `ResourceA` conflict, while `read_b` and `write_b` also
+/// conflict but are explicitly ordered. In "tests::Fixed" the two writers of `ResourceB` conflict.
+fn describe_schedule() -> ScheduleDescription {
+    let mut fixed = FixedTimestep::new(1.0, || 0.0);
+    fixed
+        .schedule_mut()
+        .add_stage(make_label!("tests::\"Inner\""), SystemSchedule::default())
+        .add_system_to_stage(make_label!("tests::\"Inner\""), make_label!("b_1"), write_b)
+        .add_system_to_stage(make_label!("tests::\"Inner\""), make_label!("b_2"), write_b);
+
+    let mut schedule = Schedule::default();
+    schedule
+        .add_stage(make_label!("tests::Events"), UpdateEventsStage::default())
+        .add_stage(make_label!("tests::Update"), SystemSchedule::default())
+        .add_stage(make_label!("tests::Fixed"), fixed);
+
+    let read = read_b.system().runs_after(make_label!("a_2"));
+    let write = write_b.system().runs_after(make_label!("read_b"));
+    schedule
+        .add_system_to_stage(make_label!("tests::Update"), make_label!("a_1"), write_a)
+        .add_system_to_stage(make_label!("tests::Update"), make_label!("a_2"), write_a)
+        .add_system_to_stage(make_label!("tests::Update"), make_label!("read_b"), read)
+        .add_system_to_stage(make_label!("tests::Update"), make_label!("write_b"), write);
+
+    schedule.introspect()
+}
+
+#[test]
+fn conflict_report_lists_only_conflicts() {
+    let report = describe_schedule().conflict_report();
+    assert_eq!(report.entries.len(), 2);
+
+    let entry = &report.entries[0];
+    assert_eq!(entry.stage_path, [make_label!("tests::Update")]);
+    assert_eq!(entry.before, make_label!("a_1"));
+    assert_eq!(entry.after, make_label!("a_2"));
+    assert_eq!(
+        entry.reasons,
+        [DependencyReason::Resource {
+            resource: ResourceA::ID,
+            hazard: AccessHazard::WriteAfterWrite,
+        }]
+    );
+
+    let entry = &report.entries[1];
+    assert_eq!(
+        entry.stage_path,
+        [make_label!("tests::Fixed"), make_label!("tests::\"Inner\"")]
+    );
+    assert_eq!(entry.before, make_label!("b_1"));
+    assert_eq!(entry.after, make_label!("b_2"));
+
+    let text = report.to_string();
+    assert!(text.contains("tests::Update: a_1 -> a_2 (write_after_write on resource"));
+    assert!(text.contains("tests::Fixed/tests::\"Inner\": b_1 -> b_2"));
+}
+
+#[test]
+fn graph_viz_output() {
+    let mut out = Vec::new();
+    describe_schedule().graph_viz("test", &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+
+    assert!(out.starts_with("digraph test {\n"));
+    assert!(out.ends_with("}\n"));
+    assert_eq!(out.matches('{').count(), out.matches('}').count());
+
+    // Every stage is a cluster, and labels are escaped
+    assert!(out.contains("label=\"tests::Events\";"));
+    assert!(out.contains("[label=\"(opaque)\",style=dashed];"));
+    assert!(out.contains("label=\"tests::Update\";"));
+    assert!(out.contains("label=\"tests::\\\"Inner\\\"\";"));
+    assert!(out.contains("label=\"parallel\";"));
+    assert!(out.contains("[label=\"read_b\"];"));
+
+    // Only edges that exist because of conflicts are highlighted
+    let edges: Vec<&str> = out.lines().filter(|v| v.contains("->")).collect();
+    assert_eq!(edges.len(), 4);
+    let conflicts = edges.iter().filter(|v| v.ends_with(",color=red];")).count();
+    assert_eq!(conflicts, 2);
+    assert!(
+        edges
+            .iter()
+            .any(|v| v.contains("explicit ordering\\nwrite_after_read") && v.ends_with("\"];"))
+    );
+}
+
+#[test]
+fn json_output() {
+    let mut out = Vec::new();
+    describe_schedule().json(&mut out).unwrap();
+    let out: Value = serde_json::from_slice(&out).unwrap();
+
+    let stages = out["stages"].as_array().unwrap();
+    assert_eq!(stages.len(), 3);
+    assert_eq!(
+        stages[0],
+        json!({"label": "tests::Events", "kind": "opaque"})
+    );
+
+    assert_eq!(stages[1]["label"], "tests::Update");
+    assert_eq!(stages[1]["kind"], "systems");
+    let channels = &stages[1]["channels"];
+    assert_eq!(
+        channels["exclusive_at_start"],
+        json!({"systems": [], "edges": []})
+    );
+    assert_eq!(
+        channels["exclusive_at_end"],
+        json!({"systems": [], "edges": []})
+    );
+
+    let parallel = &channels["parallel"];
+    assert_eq!(
+        parallel["systems"][2],
+        json!({
+            "label": "read_b",
+            "resource_reads": [ResourceB::ID.to_string()],
+            "resource_writes": [],
+            "component_reads": [],
+            "component_writes": [],
+            "runs_before": [],
+            "runs_after": ["a_2"],
+        })
+    );
+    let edges = parallel["edges"].as_array().unwrap();
+    assert_eq!(edges.len(), 3);
+    assert!(edges.contains(&json!({
+        "before": "a_1",
+        "after": "a_2",
+        "reasons": [{
+            "kind": "resource",
+            "resource": ResourceA::ID.to_string(),
+            "hazard": "write_after_write",
+        }],
+    })));
+    assert!(edges.contains(&json!({
+        "before": "a_2",
+        "after": "read_b",
+        "reasons": [{"kind": "explicit"}],
+    })));
+
+    assert_eq!(stages[2]["kind"], "schedule");
+    let inner = &stages[2]["schedule"]["stages"][0];
+    assert_eq!(inner["label"], "tests::\"Inner\"");
+    assert_eq!(inner["channels"]["parallel"]["edges"][0]["before"], "b_1");
+}
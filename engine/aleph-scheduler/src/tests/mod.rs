@@ -0,0 +1,50 @@
+//
+//
+// This file is a part of Aleph
+//
+// https://github.com/nathanvoglsam/aleph
+//
+// MIT License
+//
+// Copyright (c) 2020 Aleph Engine
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+mod access;
+mod events;
+mod fixed_timestep;
+mod introspection;
+
+use std::ops::Range;
+
+use aleph_object_system::unsafe_impl_iobject;
+
+use crate::{Schedule, TypedTable};
+
+/// The index of the frame being run, inserted by [`run_frames`] before each frame
+struct Frame(u32);
+unsafe_impl_iobject!(Frame, "019a1c2d-3e4f-7a5b-8c6d-7e8f9a0b1c20");
+
+fn run_frames(schedule: &mut Schedule, resources: &mut TypedTable, frames: Range<u32>) {
+    for frame in frames {
+        resources.insert(Frame(frame));
+        schedule.run_once(&(), resources);
+    }
+}
@@ -0,0 +1,778 @@
+//
+//
+// This file is a part of Aleph
+//
+// https://github.com/nathanvoglsam/aleph
+//
+// MIT License
+//
+// Copyright (c) 2020 Aleph Engine
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use std::cell::RefCell;
+use std::fs::File;
+use std::io;
+use std::num::NonZero;
+use std::path::Path;
+use std::ptr::NonNull;
+use std::rc::{Rc, Weak};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::SystemTime;
+
+use aleph_alloc::BBox;
+use aleph_alloc::instrumentation::IAllocationCategory;
+use aleph_gen_arena::{GenArena, Handle, HandleType, RawHandle};
+use camino::Utf8PathBuf;
+use crossbeam::channel::{SendError, Sender};
+
+use crate::async_io::{ISender, IoQueue};
+use crate::file::{AsyncReadResponse, IAsyncVFile, VFile, VFileVtable};
+use crate::metadata::{DirEntry, FileType, Metadata};
+use crate::pack::{PackCompressionScheme, PackEntry, PackIndex};
+use crate::path::VPath;
+use crate::{ILayer, Vfs, VfsSystem, box_layer};
+
+struct ArchiveVFile;
+aleph_gen_arena::make_handle_id!(ArchiveVFile);
+
+/// Source of unique IDs for [`ArchiveLayer`] instances, used to key the per-thread pack file
+/// handles.
+static NEXT_LAYER_ID: AtomicU64 = AtomicU64::new(0);
+
+/// An [`ILayer`] that mounts a single pack file, as produced by
+/// [`PackWriter`](crate::pack::PackWriter).
+///
+/// The pack's index is read when the layer is installed. Uncompressed entries are read directly
+/// out of the pack. Compressed entries must be decompressed in full before they can be read, so
+/// [`ILayer::query_entity`] will eagerly decompress the entry into memory.
+pub struct ArchiveLayer {
+    /// The pack file in the filesystem that this layer is mounting into the vfs.
+    pack_path: Utf8PathBuf,
+
+    /// The pack's index. Only populated once the layer is installed.
+    index: Option<PackIndex>,
+
+    /// The modification time of the pack file when it was installed. Reported as the modification
+    /// time of every entity in the pack.
+    modified: Option<SystemTime>,
+
+    /// The [`IoQueue`] to push async file requests onto.
+    io_queue: Option<Arc<IoQueue>>,
+
+    /// Unique ID for this layer, keys the per-thread pack file handles in [`HANDLES`].
+    id: u64,
+}
+
+impl ArchiveLayer {
+    pub fn new(pack_path: Utf8PathBuf) -> BBox<dyn ILayer, VfsSystem> {
+        box_layer(Self {
+            pack_path,
+            index: None,
+            modified: None,
+            io_queue: None,
+            id: NEXT_LAYER_ID.fetch_add(1, Ordering::Relaxed),
+        })
+    }
+
+    pub fn new_with_io_queue(
+        pack_path: Utf8PathBuf,
+        io_queue: Arc<IoQueue>,
+    ) -> BBox<dyn ILayer, VfsSystem> {
+        box_layer(Self {
+            pack_path,
+            index: None,
+            modified: None,
+            io_queue: Some(io_queue),
+            id: NEXT_LAYER_ID.fetch_add(1, Ordering::Relaxed),
+        })
+    }
+
+    fn index(&self) -> io::Result<&PackIndex> {
+        self.index.as_ref().ok_or_else(|| {
+            io::Error::other("Can't query an ArchiveLayer that hasn't been installed")
+        })
+    }
+
+    /// Gets this thread's handle to the pack file, opening a new one if this thread doesn't
+    /// already have one open.
+    ///
+    /// Handles are shared between every [`VFile`] open on a thread, and are closed once all of
+    /// them have been dropped.
+    fn thread_file(&self) -> io::Result<Rc<File>> {
+        HANDLES.with_borrow_mut(|handles| {
+            handles.retain(|(_, v)| v.strong_count() > 0);
+
+            let existing = handles
+                .iter()
+                .find(|(id, _)| *id == self.id)
+                .and_then(|(_, v)| v.upgrade());
+            if let Some(file) = existing {
+                return Ok(file);
+            }
+
+            let file = Vfs::with(|| -> io::Result<_> {
+                let file = std::fs::OpenOptions::new()
+                    .read(true)
+                    .write(false)
+                    .create(false)
+                    .open(&self.pack_path)?;
+                Ok(Rc::new(file))
+            })?;
+            handles.push((self.id, Rc::downgrade(&file)));
+            Ok(file)
+        })
+    }
+}
+
+impl ILayer for ArchiveLayer {
+    fn install(&mut self, _mount_name: &str) -> io::Result<()> {
+        // We want a fully normalized, canonical path for 'pack_path' so the async io queue's
+        // handle cache doesn't see the same pack under different names.
+        self.pack_path = self.pack_path.canonicalize_utf8()?;
+
+        let metadata = self.pack_path.metadata()?;
+        if !metadata.is_file() {
+            return Err(io::Error::new(
+                io::ErrorKind::IsADirectory,
+                "Can't mount directories as an ArchiveLayer",
+            ));
+        }
+
+        self.modified = metadata.modified().ok();
+
+        let index = Vfs::with(|| -> io::Result<_> {
+            let file = File::open(&self.pack_path)?;
+            let mut reader = io::BufReader::new(file);
+            PackIndex::read(&mut reader)
+        })?;
+        self.index = Some(index);
+
+        // Prime the handle cache, or error out if we failed to open the file.
+        if let Some(io_queue) = self.io_queue.as_ref() {
+            io_queue.open(self.pack_path.as_std_path())?;
+        }
+
+        Ok(())
+    }
+
+    fn query_entity(&self, path: &VPath) -> io::Result<VFile<'_>> {
+        let entry = *self.index()?.get(path)?;
+        let file = self.thread_file()?;
+
+        let pooled = match entry.compression {
+            PackCompressionScheme::None => PooledFile::Stored {
+                file,
+                offset: entry.offset,
+                len: entry.size,
+            },
+            scheme => Vfs::with(|| -> io::Result<_> {
+                let mut compressed = vec![0u8; entry.size as usize];
+                read_exact_at(&file, &mut compressed, entry.offset)?;
+                let data = scheme.decompress(&compressed, entry.size_uncompressed)?;
+                Ok(PooledFile::Decompressed { data })
+            })?,
+        };
+
+        let handle = POOL.with_borrow_mut(|pool| pool.alloc(pooled));
+        let handle = handle.to_bare_handle().into_int();
+
+        let out = VFile {
+            handle,
+            vtable: &VTABLE,
+            _no_send: Default::default(),
+            _vfs: Default::default(),
+        };
+
+        Ok(out)
+    }
+
+    fn query_entity_async_io(&self, path: &VPath) -> io::Result<Arc<dyn IAsyncVFile>> {
+        let io_queue = match self.io_queue.as_ref() {
+            Some(io_queue) => io_queue,
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "Can't open async io for an archive layer with no async io queue.",
+                ));
+            }
+        };
+        let entry = *self.index()?.get(path)?;
+
+        let file = Vfs::with(|| {
+            let path: Arc<VPath> = {
+                let arc: Arc<str> = Arc::from(path.to_str());
+                unsafe { Arc::from_raw(Arc::into_raw(arc) as *const VPath) }
+            };
+
+            let out = AsyncVFile {
+                queue: io_queue.clone(),
+                virtual_path: path,
+                path: Arc::from(self.pack_path.as_std_path()),
+                entry,
+                decompressed: Default::default(),
+            };
+            Arc::new(out)
+        });
+
+        Ok(file)
+    }
+
+    fn metadata(&self, path: &VPath) -> io::Result<Metadata> {
+        match self.index()?.get(path) {
+            Ok(entry) => Ok(Metadata::new(
+                FileType::File,
+                entry.size_uncompressed,
+                self.modified,
+            )),
+            Err(e) if e.kind() == io::ErrorKind::IsADirectory => {
+                Ok(Metadata::new(FileType::Directory, 0, self.modified))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn read_dir(&self, path: &VPath) -> io::Result<Vec<DirEntry>> {
+        let index = self.index()?;
+        Vfs::with(|| index.read_dir(path))
+    }
+}
+
+static VTABLE: VFileVtable = VFileVtable {
+    read_at: read_at_vfile,
+    size: size_vfile,
+    close: close_vfile,
+};
+
+fn read_at_vfile(handle: NonZero<u64>, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+    let handle = RawHandle::from_int(handle);
+    let handle = Handle::from_bare_handle(handle);
+
+    POOL.with_borrow_mut(|pool| match pool.get_mut(handle).unwrap() {
+        PooledFile::Stored {
+            file,
+            offset: base,
+            len,
+        } => {
+            // Clamp the read to the entry's range so we can't read into neighbouring entries
+            let available = len.saturating_sub(offset);
+            let n = (buf.len() as u64).min(available) as usize;
+            if n == 0 {
+                return Ok(0);
+            }
+            read_at(file, &mut buf[..n], *base + offset)
+        }
+        PooledFile::Decompressed { data } => {
+            let offset = usize::try_from(offset)
+                .unwrap_or(usize::MAX)
+                .min(data.len());
+            let src = &data[offset..];
+            let n = buf.len().min(src.len());
+            buf[..n].copy_from_slice(&src[..n]);
+            Ok(n)
+        }
+    })
+}
+
+fn size_vfile(handle: NonZero<u64>) -> io::Result<u64> {
+    let handle = RawHandle::from_int(handle);
+    let handle = Handle::from_bare_handle(handle);
+    POOL.with_borrow(|pool| match pool.get_ref(handle).unwrap() {
+        PooledFile::Stored { len, .. } => Ok(*len),
+        PooledFile::Decompressed { data } => Ok(data.len() as u64),
+    })
+}
+
+fn close_vfile(handle: NonZero<u64>) {
+    let handle = RawHandle::from_int(handle);
+    let handle = Handle::from_bare_handle(handle);
+    POOL.with_borrow_mut(|pool| {
+        let _ = pool.free(handle);
+    })
+}
+
+fn read_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+    cfg_select! {
+        unix => {
+            use std::os::unix::fs::FileExt;
+            file.read_at(buf, offset)
+        }
+        windows => {
+            use std::os::windows::fs::FileExt;
+            file.seek_read(buf, offset)
+        }
+        _ => {
+            unimplemented!()
+        }
+    }
+}
+
+fn read_exact_at(file: &File, mut buf: &mut [u8], mut offset: u64) -> io::Result<()> {
+    while !buf.is_empty() {
+        match read_at(file, buf, offset) {
+            Ok(0) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "failed to fill whole buffer",
+                ));
+            }
+            Ok(n) => {
+                let tmp = buf;
+                buf = &mut tmp[n..];
+                offset += n as u64;
+            }
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+enum PooledFile {
+    /// An uncompressed entry, read directly from the pack through the thread's shared handle.
+    Stored {
+        file: Rc<File>,
+        offset: u64,
+        len: u64,
+    },
+
+    /// A compressed entry that has been decompressed into memory.
+    Decompressed { data: Vec<u8> },
+}
+
+type Pool = RefCell<GenArena<PooledFile, Handle<ArchiveVFile>, VfsSystem>>;
+thread_local! {
+    static POOL: Pool = const { RefCell::new(GenArena::new_in()) };
+
+    /// This thread's open handles to pack files, keyed by the ID of the [`ArchiveLayer`] that
+    /// opened them. Only weak references are held so the handles close when the last [`VFile`]
+    /// using them is dropped.
+    static HANDLES: RefCell<Vec<(u64, Weak<File>)>> = const { RefCell::new(Vec::new()) };
+}
+
+struct AsyncVFile {
+    queue: Arc<IoQueue>,
+    virtual_path: Arc<VPath>,
+    path: Arc<Path>,
+    entry: PackEntry,
+
+    /// The decompressed contents of a compressed entry, filled in by the first ranged read so
+    /// later reads don't have to read and decompress the whole entry again.
+    decompressed: Arc<OnceLock<Vec<u8>>>,
+}
+
+impl AsyncVFile {
+    /// Serves a ranged read of a compressed entry, from the cached decompressed data if we have
+    /// it or by queueing a read of the whole entry if we don't.
+    fn read_compressed(
+        &self,
+        buf: NonNull<[u8]>,
+        offset: u64,
+        exact: bool,
+        sender: Sender<AsyncReadResponse>,
+        cookie: u64,
+    ) -> Result<(), SendError<()>> {
+        match self.decompressed.get() {
+            Some(data) => {
+                let response = read_response(&self.virtual_path, buf, offset, exact, data, cookie);
+                match sender.send(response) {
+                    Ok(_) => Ok(()),
+                    Err(_) => Err(SendError(())),
+                }
+            }
+            None => {
+                let request = EntryRequest::Read { buf, offset, exact };
+                self.read_entry(sender, cookie, request)
+            }
+        }
+    }
+
+    /// Queues a read of the entire entry, handing the data to an [`EntrySender`] that will
+    /// decompress it and complete the given `request`.
+    fn read_entry(
+        &self,
+        sender: Sender<AsyncReadResponse>,
+        cookie: u64,
+        request: EntryRequest,
+    ) -> Result<(), SendError<()>> {
+        let mut data = vec![0u8; self.entry.size as usize];
+        let dst = NonNull::from(data.as_mut_slice());
+        let entry_sender = EntrySender {
+            path: self.virtual_path.clone(),
+            sender,
+            entry: self.entry,
+            data: Mutex::new(Some(data)),
+            request,
+            cache: self.decompressed.clone(),
+        };
+
+        // Safety: 'dst' points into the heap allocation of 'data', which is owned by
+        //         'entry_sender'. The allocation is not touched until the queue sends a completion
+        //         message through 'entry_sender', which ends the borrow.
+        unsafe {
+            self.queue.async_read_exact(
+                self.path.clone(),
+                dst,
+                self.entry.offset,
+                Arc::new(entry_sender),
+                [cookie, 0, 0, 0],
+            )
+        }
+    }
+}
+
+impl IAsyncVFile for AsyncVFile {
+    unsafe fn read_at(
+        &self,
+        buf: NonNull<[u8]>,
+        offset: u64,
+        sender: Sender<AsyncReadResponse>,
+        cookie: u64,
+    ) -> Result<(), SendError<()>> {
+        if self.entry.compression != PackCompressionScheme::None {
+            return self.read_compressed(buf, offset, false, sender, cookie);
+        }
+
+        // Clamp the read to the entry's range so we can't read into neighbouring entries
+        let offset_in_entry = offset.min(self.entry.size);
+        let available = self.entry.size - offset_in_entry;
+        let len = (buf.len() as u64).min(available) as usize;
+        let dst = NonNull::slice_from_raw_parts(buf.cast::<u8>(), len);
+
+        unsafe {
+            let remap_sender = RemapSender {
+                path: self.virtual_path.clone(),
+                sender,
+                buf,
+                offset,
+            };
+            self.queue.async_read(
+                self.path.clone(),
+                dst,
+                self.entry.offset + offset_in_entry,
+                Arc::new(remap_sender),
+                [cookie, 0, 0, 0],
+            )
+        }
+    }
+
+    unsafe fn read_exact_at(
+        &self,
+        buf: NonNull<[u8]>,
+        offset: u64,
+        sender: Sender<AsyncReadResponse>,
+        cookie: u64,
+    ) -> Result<(), SendError<()>> {
+        if self.entry.compression != PackCompressionScheme::None {
+            return self.read_compressed(buf, offset, true, sender, cookie);
+        }
+
+        // A read past the end of the entry can never succeed, and must not be allowed to read
+        // into neighbouring entries.
+        let end = offset.checked_add(buf.len() as u64);
+        if end.is_none_or(|v| v > self.entry.size) {
+            let result = sender.send(AsyncReadResponse::ReadFail {
+                path: self.virtual_path.clone(),
+                buf,
+                offset,
+                err: io::Error::new(io::ErrorKind::UnexpectedEof, "failed to fill whole buffer"),
+                cookie,
+            });
+            return match result {
+                Ok(_) => Ok(()),
+                Err(_) => Err(SendError(())),
+            };
+        }
+
+        unsafe {
+            let remap_sender = RemapSender {
+                path: self.virtual_path.clone(),
+                sender,
+                buf,
+                offset,
+            };
+            self.queue.async_read_exact(
+                self.path.clone(),
+                buf,
+                self.entry.offset + offset,
+                Arc::new(remap_sender),
+                [cookie, 0, 0, 0],
+            )
+        }
+    }
+
+    fn load(&self, sender: Sender<AsyncReadResponse>, cookie: u64) -> Result<(), SendError<()>> {
+        if let Some(data) = self.decompressed.get() {
+            let result = sender.send(AsyncReadResponse::LoadSuccess {
+                path: self.virtual_path.clone(),
+                data: data.clone(),
+                cookie,
+            });
+            return match result {
+                Ok(_) => Ok(()),
+                Err(_) => Err(SendError(())),
+            };
+        }
+        self.read_entry(sender, cookie, EntryRequest::Load)
+    }
+}
+
+/// This is an internal [`ISender`] implementation used for reads of uncompressed entries. Remaps
+/// the read of the pack file back into a read of the virtual file, restoring the caller's buffer
+/// and offset.
+struct RemapSender {
+    path: Arc<VPath>,
+    sender: Sender<AsyncReadResponse>,
+
+    /// The buffer the caller gave us, which may be longer than the clamped buffer we gave the
+    /// queue.
+    buf: NonNull<[u8]>,
+
+    /// The offset the caller requested, relative to the start of the entry.
+    offset: u64,
+}
+
+// Safety: 'buf' is never dereferenced by the sender, it is only handed back to the client that
+//         gave it to us. The client is responsible for it being valid to send across threads.
+unsafe impl Send for RemapSender {}
+unsafe impl Sync for RemapSender {}
+
+impl ISender for RemapSender {
+    fn send_success(
+        &self,
+        opaque: [u64; 4],
+        _file: Arc<Path>,
+        _buf: NonNull<[u8]>,
+        _offset: u64,
+        bytes_transferred: usize,
+    ) -> Result<(), SendError<()>> {
+        let result = self.sender.send(AsyncReadResponse::ReadSuccess {
+            path: self.path.clone(),
+            buf: self.buf,
+            offset: self.offset,
+            bytes_transferred,
+            cookie: opaque[0],
+        });
+        match result {
+            Ok(_) => Ok(()),
+            Err(_) => Err(SendError(())),
+        }
+    }
+
+    fn send_fail(
+        &self,
+        opaque: [u64; 4],
+        _file: Arc<Path>,
+        _buf: NonNull<[u8]>,
+        _offset: u64,
+        err: io::Error,
+    ) -> Result<(), SendError<()>> {
+        let result = self.sender.send(AsyncReadResponse::ReadFail {
+            path: self.path.clone(),
+            buf: self.buf,
+            offset: self.offset,
+            err,
+            cookie: opaque[0],
+        });
+        match result {
+            Ok(_) => Ok(()),
+            Err(_) => Err(SendError(())),
+        }
+    }
+
+    fn send_load_success(
+        &self,
+        _opaque: [u64; 4],
+        _file: Arc<Path>,
+        _data: Vec<u8>,
+    ) -> Result<(), SendError<()>> {
+        unreachable!("RemapSender is never used for load requests")
+    }
+
+    fn send_load_fail(
+        &self,
+        _opaque: [u64; 4],
+        _file: Arc<Path>,
+        _err: io::Error,
+    ) -> Result<(), SendError<()>> {
+        unreachable!("RemapSender is never used for load requests")
+    }
+}
+
+/// The client request that an [`EntrySender`] completes once the whole entry has been read.
+enum EntryRequest {
+    /// Hand the whole (decompressed) entry to the client.
+    Load,
+
+    /// Copy a range of the decompressed entry into the client's buffer.
+    Read {
+        buf: NonNull<[u8]>,
+        offset: u64,
+        exact: bool,
+    },
+}
+
+/// This is an internal [`ISender`] implementation that owns the buffer a whole entry is read into.
+/// Once the read completes the entry is decompressed, if needed, and used to complete the client's
+/// original request.
+struct EntrySender {
+    path: Arc<VPath>,
+    sender: Sender<AsyncReadResponse>,
+    entry: PackEntry,
+
+    /// The buffer the io queue is reading the stored entry into.
+    data: Mutex<Option<Vec<u8>>>,
+
+    request: EntryRequest,
+
+    /// The owning [`AsyncVFile`]'s cache of the decompressed entry. Filled in when completing a
+    /// [`EntryRequest::Read`], loads hand their data straight to the client instead.
+    cache: Arc<OnceLock<Vec<u8>>>,
+}
+
+// Safety: The client buffer in 'request' is only written to once the queue completes our read,
+//         which the client has transferred ownership to us for until we send the completion
+//         message. 'data' is only accessed by the queue until the completion message.
+unsafe impl Send for EntrySender {}
+unsafe impl Sync for EntrySender {}
+
+impl EntrySender {
+    fn complete(&self, data: io::Result<Vec<u8>>, cookie: u64) -> Result<(), SendError<()>> {
+        let response = match (&self.request, data) {
+            (EntryRequest::Load, Ok(data)) => AsyncReadResponse::LoadSuccess {
+                path: self.path.clone(),
+                data,
+                cookie,
+            },
+            (EntryRequest::Load, Err(err)) => AsyncReadResponse::LoadFail {
+                path: self.path.clone(),
+                err,
+                cookie,
+            },
+            (EntryRequest::Read { buf, offset, exact }, Ok(data)) => {
+                // Only compressed entries are read whole for ranged reads, so these are the only
+                // entries that will land in the cache.
+                let data = self.cache.get_or_init(|| data);
+                read_response(&self.path, *buf, *offset, *exact, data, cookie)
+            }
+            (EntryRequest::Read { buf, offset, .. }, Err(err)) => AsyncReadResponse::ReadFail {
+                path: self.path.clone(),
+                buf: *buf,
+                offset: *offset,
+                err,
+                cookie,
+            },
+        };
+
+        match self.sender.send(response) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(SendError(())),
+        }
+    }
+}
+
+/// Builds the response for a ranged read of `data`, copying the requested range into `buf`.
+fn read_response(
+    path: &Arc<VPath>,
+    buf: NonNull<[u8]>,
+    offset: u64,
+    exact: bool,
+    data: &[u8],
+    cookie: u64,
+) -> AsyncReadResponse {
+    let start = usize::try_from(offset)
+        .unwrap_or(usize::MAX)
+        .min(data.len());
+    let src = &data[start..];
+    if exact && src.len() < buf.len() {
+        return AsyncReadResponse::ReadFail {
+            path: path.clone(),
+            buf,
+            offset,
+            err: io::Error::new(io::ErrorKind::UnexpectedEof, "failed to fill whole buffer"),
+            cookie,
+        };
+    }
+
+    let n = src.len().min(buf.len());
+
+    // Safety: The client transferred ownership of 'buf' to us for the duration of the request.
+    unsafe {
+        let dst = buf.cast::<u8>().as_ptr();
+        std::ptr::copy_nonoverlapping(src.as_ptr(), dst, n);
+    }
+
+    AsyncReadResponse::ReadSuccess {
+        path: path.clone(),
+        buf,
+        offset,
+        bytes_transferred: n,
+        cookie,
+    }
+}
+
+impl ISender for EntrySender {
+    fn send_success(
+        &self,
+        opaque: [u64; 4],
+        _file: Arc<Path>,
+        _buf: NonNull<[u8]>,
+        _offset: u64,
+        _bytes_transferred: usize,
+    ) -> Result<(), SendError<()>> {
+        let data = self.data.lock().unwrap().take().unwrap();
+        let data = match self.entry.compression {
+            PackCompressionScheme::None => Ok(data),
+            scheme => scheme.decompress(&data, self.entry.size_uncompressed),
+        };
+        self.complete(data, opaque[0])
+    }
+
+    fn send_fail(
+        &self,
+        opaque: [u64; 4],
+        _file: Arc<Path>,
+        _buf: NonNull<[u8]>,
+        _offset: u64,
+        err: io::Error,
+    ) -> Result<(), SendError<()>> {
+        let _ = self.data.lock().unwrap().take();
+        self.complete(Err(err), opaque[0])
+    }
+
+    fn send_load_success(
+        &self,
+        _opaque: [u64; 4],
+        _file: Arc<Path>,
+        _data: Vec<u8>,
+    ) -> Result<(), SendError<()>> {
+        unreachable!("EntrySender is never used for load requests")
+    }
+
+    fn send_load_fail(
+        &self,
+        _opaque: [u64; 4],
+        _file: Arc<Path>,
+        _err: io::Error,
+    ) -> Result<(), SendError<()>> {
+        unreachable!("EntrySender is never used for load requests")
+    }
+}
@@ -35,16 +35,19 @@ use std::num::NonZero;
 use std::path::Path;
 use std::ptr::NonNull;
 use std::sync::Arc;
+use std::time::SystemTime;
 
-use aleph_alloc::BBox;
-use aleph_alloc::instrumentation::IAllocationCategory;
+use aleph_alloc::instrumentation::{IAllocationCategory, system};
+use aleph_alloc::{BBox, BHashMap};
 use aleph_gen_arena::{GenArena, Handle, HandleType, RawHandle};
 use camino::Utf8PathBuf;
 use crossbeam::channel::{SendError, Sender};
 
 use crate::async_io::{ISender, IoQueue};
 use crate::file::{AsyncReadResponse, IAsyncVFile, VFile, VFileVtable};
-use crate::path::{Component, VPath};
+use crate::metadata::{DirEntry, FileType, Metadata};
+use crate::path::{Component, VPath, VPathBuf, normalize_layer_path};
+use crate::watch::{ChangeEvent, ChangeKind, ILayerWatcher, box_watcher};
 use crate::{ILayer, Vfs, VfsSystem, box_layer};
 
 struct LocalVFile;
@@ -75,6 +78,43 @@ impl DirectoryLayer {
             io_queue: Some(io_queue),
         })
     }
+
+    /// Maps a path within the layer to the path of the matching entity in the mounted directory.
+    fn resolve_path(&self, path: &VPath) -> io::Result<Utf8PathBuf> {
+        // clone and pre-reserve space for the path + some slop for redundant characters
+        let mut combined = self.mounted_path.clone();
+        combined.reserve(path.len() + 8);
+
+        // Push the path in
+        for component in path.components() {
+            match component {
+                // We just skip a root segment, an absolute path here is absolute _within_ the
+                // layer mount.
+                //
+                // If we don't get a root component at all we're okay because relative paths
+                // within this call are relative to the layer mount's root, not the outer vfs
+                // root.
+                Component::Root => continue,
+                Component::Segment(seg) => {
+                    // We intentionally skip ".." components. No good can come from trying to
+                    // handle '..'. It's not a sane name for a file, so they only real time
+                    // you'll see this is either a bug or someone intentionally trying to break
+                    // things.
+                    //
+                    // We skip "." components too because they are likely to cause problems too.
+                    if matches!(seg, ".." | ".") {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidFilename,
+                            "Path contains '..' | '.' segments.",
+                        ));
+                    }
+                    combined.push(seg);
+                }
+            }
+        }
+
+        Ok(combined)
+    }
 }
 
 impl ILayer for DirectoryLayer {
@@ -97,38 +137,8 @@ impl ILayer for DirectoryLayer {
     }
 
     fn query_entity(&self, path: &VPath) -> io::Result<VFile<'_>> {
-        let file = Vfs::with(|| {
-            // clone and pre-reserve space for the path + some slop for redundant characters
-            let mut combined = self.mounted_path.clone();
-            combined.reserve(path.len() + 8);
-
-            // Push the path in
-            for component in path.components() {
-                match component {
-                    // We just skip a root segment, an absolute path here is absolute _within_ the
-                    // layer mount.
-                    //
-                    // If we don't get a root component at all we're okay because relative paths
-                    // within this call are relative to the layer mount's root, not the outer vfs
-                    // root.
-                    Component::Root => continue,
-                    Component::Segment(seg) => {
-                        // We intentionally skip ".." components. No good can come from trying to
-                        // handle '..'. It's not a sane name for a file, so they only real time
-                        // you'll see this is either a bug or someone intentionally trying to break
-                        // things.
-                        //
-                        // We skip "." components too because they are likely to cause problems too.
-                        if matches!(seg, ".." | ".") {
-                            return Err(io::Error::new(
-                                io::ErrorKind::InvalidFilename,
-                                "Path contains '..' | '.' segments.",
-                            ));
-                        }
-                        combined.push(seg);
-                    }
-                }
-            }
+        let file = Vfs::with(|| -> io::Result<_> {
+            let combined = self.resolve_path(path)?;
 
             // First we open a standard file handle for synchronous file IO
             let file = std::fs::OpenOptions::new()
@@ -177,38 +187,8 @@ impl ILayer for DirectoryLayer {
                 ));
             }
         };
-        let file = Vfs::with(|| {
-            // clone and pre-reserve space for the path + some slop for redundant characters
-            let mut combined = self.mounted_path.clone();
-            combined.reserve(path.len() + 8);
-
-            // Push the path in
-            for component in path.components() {
-                match component {
-                    // We just skip a root segment, an absolute path here is absolute _within_ the
-                    // layer mount.
-                    //
-                    // If we don't get a root component at all we're okay because relative paths
-                    // within this call are relative to the layer mount's root, not the outer vfs
-                    // root.
-                    Component::Root => continue,
-                    Component::Segment(seg) => {
-                        // We intentionally skip ".." components. No good can come from trying to
-                        // handle '..'. It's not a sane name for a file, so they only real time
-                        // you'll see this is either a bug or someone intentionally trying to break
-                        // things.
-                        //
-                        // We skip "." components too because they are likely to cause problems too.
-                        if matches!(seg, ".." | ".") {
-                            return Err(io::Error::new(
-                                io::ErrorKind::InvalidFilename,
-                                "Path contains '..' | '.' segments.",
-                            ));
-                        }
-                        combined.push(seg);
-                    }
-                }
-            }
+        let file = Vfs::with(|| -> io::Result<_> {
+            let combined = self.resolve_path(path)?;
 
             let path: Arc<VPath> = {
                 let arc: Arc<str> = Arc::from(path.to_str());
@@ -230,6 +210,222 @@ impl ILayer for DirectoryLayer {
 
         Ok(file)
     }
+
+    fn metadata(&self, path: &VPath) -> io::Result<Metadata> {
+        let combined = Vfs::with(|| self.resolve_path(path))?;
+        let metadata = combined.metadata()?;
+        convert_metadata(&metadata)
+    }
+
+    fn read_dir(&self, path: &VPath) -> io::Result<Vec<DirEntry>> {
+        Vfs::with(|| {
+            let combined = self.resolve_path(path)?;
+
+            let mut entries = Vec::new();
+            for entry in combined.read_dir()? {
+                let entry = entry?;
+
+                // Names that aren't valid UTF-8 can't be addressed through a VPath, so there's no
+                // point listing them.
+                let name = match entry.file_name().into_string() {
+                    Ok(v) => v,
+                    Err(_) => continue,
+                };
+
+                // Follow symlinks so links to files and directories are listed as their target,
+                // matching what 'query_entity' would see. Anything that isn't a file or
+                // directory can't be represented in the vfs so is skipped.
+                let file_type = match std::fs::metadata(entry.path()) {
+                    Ok(v) if v.is_file() => FileType::File,
+                    Ok(v) if v.is_dir() => FileType::Directory,
+                    _ => continue,
+                };
+
+                entries.push(DirEntry::new(name, file_type));
+            }
+
+            Ok(entries)
+        })
+    }
+
+    fn watch(&self, path: &VPath) -> io::Result<BBox<dyn ILayerWatcher + '_, VfsSystem>> {
+        Vfs::with(|| {
+            let root = self.resolve_path(path)?;
+
+            // Fail early if there's nothing to watch
+            root.metadata()?;
+
+            let mut watcher = DirectoryWatcher {
+                root,
+                prefix: normalize_layer_path(path)?,
+                files: BHashMap::new_in(system()),
+            };
+            watcher.files = watcher.scan()?;
+
+            Ok(box_watcher(watcher))
+        })
+    }
+}
+
+/// A polling [`ILayerWatcher`] for a [`DirectoryLayer`].
+///
+/// Takes a snapshot of the size and modification time of every file in the watched subtree, and
+/// diffs it against a fresh snapshot on every poll. Symlinks to files are followed, symlinks to
+/// directories are not to avoid walking into cycles.
+struct DirectoryWatcher {
+    /// The path in the OS filesystem that is being watched.
+    root: Utf8PathBuf,
+
+    /// The normalized layer path of 'root'. Prepended to the paths of all files found when
+    /// scanning 'root'.
+    prefix: VPathBuf,
+
+    /// The snapshot from the last scan.
+    files: BHashMap<VPathBuf, FileStamp, VfsSystem>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+struct FileStamp {
+    modified: Option<SystemTime>,
+    len: u64,
+}
+
+impl FileStamp {
+    fn new(metadata: &std::fs::Metadata) -> Self {
+        Self {
+            modified: metadata.modified().ok(),
+            len: metadata.len(),
+        }
+    }
+}
+
+impl DirectoryWatcher {
+    fn scan(&self) -> io::Result<BHashMap<VPathBuf, FileStamp, VfsSystem>> {
+        let mut files = BHashMap::new_in(system());
+
+        // The watched root can come and go, if it's gone then everything inside it is gone too
+        match std::fs::metadata(&self.root) {
+            Ok(v) if v.is_file() => {
+                files.insert(self.prefix.clone(), FileStamp::new(&v));
+            }
+            Ok(v) if v.is_dir() => {
+                Self::scan_dir(self.root.as_std_path(), self.prefix.to_str(), &mut files)?;
+            }
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+
+        Ok(files)
+    }
+
+    fn scan_dir(
+        dir: &Path,
+        prefix: &str,
+        files: &mut BHashMap<VPathBuf, FileStamp, VfsSystem>,
+    ) -> io::Result<()> {
+        // Files can be removed while we're scanning, which we treat the same as if the file was
+        // never there. The next poll will pick up anything we miss.
+        let iter = match dir.read_dir() {
+            Ok(v) => v,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        for entry in iter {
+            let entry = match entry {
+                Ok(v) => v,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            };
+
+            // Names that aren't valid UTF-8 can't be addressed through a VPath
+            let name = match entry.file_name().into_string() {
+                Ok(v) => v,
+                Err(_) => continue,
+            };
+            let path = if prefix.is_empty() {
+                name
+            } else {
+                format!("{prefix}/{name}")
+            };
+
+            let file_type = match entry.file_type() {
+                Ok(v) => v,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            };
+            if file_type.is_dir() {
+                Self::scan_dir(&entry.path(), &path, files)?;
+                continue;
+            }
+
+            match std::fs::metadata(entry.path()) {
+                Ok(v) if v.is_file() => {
+                    files.insert(VPathBuf::from(path), FileStamp::new(&v));
+                }
+                Ok(_) => {}
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ILayerWatcher for DirectoryWatcher {
+    fn poll(&mut self, events: &mut Vec<ChangeEvent>) -> io::Result<()> {
+        Vfs::with(|| {
+            let files = self.scan()?;
+
+            let first = events.len();
+            for (path, stamp) in files.iter() {
+                let kind = match self.files.get(path) {
+                    None => ChangeKind::Created,
+                    Some(old) if old != stamp => ChangeKind::Modified,
+                    Some(_) => continue,
+                };
+                events.push(ChangeEvent {
+                    path: path.clone(),
+                    kind,
+                });
+            }
+            for path in self.files.keys() {
+                if !files.contains_key(path) {
+                    events.push(ChangeEvent {
+                        path: path.clone(),
+                        kind: ChangeKind::Removed,
+                    });
+                }
+            }
+
+            // Hash map order is arbitrary, sort so events come out in a stable order
+            events[first..].sort_by(|a, b| a.path.cmp(&b.path));
+
+            self.files = files;
+            Ok(())
+        })
+    }
+}
+
+/// Converts OS file metadata into vfs [`Metadata`], rejecting anything that isn't a file or
+/// directory.
+fn convert_metadata(metadata: &std::fs::Metadata) -> io::Result<Metadata> {
+    let file_type = if metadata.is_file() {
+        FileType::File
+    } else if metadata.is_dir() {
+        FileType::Directory
+    } else {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "DirectoryLayer only supports files and directories.",
+        ));
+    };
+    let len = if metadata.is_file() {
+        metadata.len()
+    } else {
+        0
+    };
+    Ok(Metadata::new(file_type, len, metadata.modified().ok()))
 }
 
 static VTABLE: VFileVtable = VFileVtable {
@@ -27,10 +27,15 @@
 // SOFTWARE.
 //
 
+pub mod archive_layer;
 pub mod async_io;
 pub mod directory_layer;
 pub mod file;
+pub mod metadata;
+pub mod overlay_layer;
+pub mod pack;
 pub mod path;
+pub mod watch;
 
 #[cfg(test)]
 mod tests;
@@ -42,7 +47,9 @@ use aleph_alloc::instrumentation::{IAllocationCategory, system};
 use aleph_alloc::{BBox, BHashMap};
 
 use crate::file::{IAsyncVFile, VFile};
+use crate::metadata::{DirEntry, FileType, Metadata};
 use crate::path::{Component, Components, VPath};
+use crate::watch::{ILayerWatcher, Watcher};
 
 /// The top level interface to our 'vfs' implementation.
 ///
@@ -63,7 +70,7 @@ use crate::path::{Component, Components, VPath};
 /// file system.
 ///
 /// You could source data from:
-/// - A zip archive
+/// - An archive, like a pack file with [`archive_layer::ArchiveLayer`]
 /// - A folder on disk
 /// - A remote server over the network
 ///
@@ -94,21 +101,30 @@ use crate::path::{Component, Components, VPath};
 /// # Limitations
 ///
 /// This is not a general purpose file system, and is heavily simplified down to the bare essentials
-/// for storing game assets. All layers and files are read-only. You can't open directories to
-/// enumerate the files and folders they contain. There is no file metadata like mtime. There are no
-/// symlinks.
+/// for storing game assets. All layers and files are read-only. There are no symlinks.
 ///
 /// You get 3 fundamental operations:
 /// - open
 /// - read
 /// - close
 ///
+/// Along with some basic queries over the tree. Directories can be enumerated with
+/// [`IRouterExt::read_dir`], and [`IRouterExt::metadata`] provides the type, size and (where the
+/// layer tracks it) modification time of an entity without opening it. The root of the tree is a
+/// directory containing one entry for each mount.
+///
+/// Layers backed by mutable storage can also be watched for changes with [`IRouterExt::watch`],
+/// which is intended to drive hot reloading of assets during development.
+///
 /// We only allow mounting layers at the root. Two layers 'A' and 'B' can be made available as '/A'
 /// and '/B', but never '/A/B' or '/C/A' to prevent mount points from overlapping. This is an
 /// intentional restriction to simplify the implementation. Overlapping mounts would complicate
-/// mapping a virtual file to the source and reduce runtime efficiency. This prevents using overlays
-/// to 'patch' files with replacements. However, we see this is a potential footgun that can be
-/// resolved at higher layers of the engine.
+/// mapping a virtual file to the source and reduce runtime efficiency.
+///
+/// Patching files with replacements is instead an opt-in feature of a single mount. An
+/// [`overlay_layer::OverlayLayer`] stacks multiple layers under one mount name and resolves each
+/// path to the highest priority layer that contains it. Only mounts that ask for an overlay pay
+/// for searching multiple layers.
 ///
 /// All mounted files are immutable. We don't allow mutating the data through this interface, but
 /// outside processes or other code could modify the mounted files. This will cause problems, and we
@@ -189,6 +205,89 @@ impl Router {
         }
     }
 
+    /// The core implementation of `metadata` with the generic args stripped away so we don't
+    /// monomorph the whole method body for every type that implements `AsRef<VPath>`.
+    fn ___metadata(&self, path: &VPath) -> io::Result<Metadata> {
+        if Self::is_root(path) {
+            return Ok(Metadata::directory());
+        }
+
+        let mut components = path.components();
+
+        let layer_name = Self::parse_target_layer(&mut components)?;
+
+        // Try and find the layer mounted at the given name
+        if let Some(layer) = self.layers.get(layer_name) {
+            layer.metadata(components.as_path())
+        } else {
+            Err(io::Error::new(io::ErrorKind::NotFound, "No such file."))
+        }
+    }
+
+    /// The core implementation of `read_dir` with the generic args stripped away so we don't
+    /// monomorph the whole method body for every type that implements `AsRef<VPath>`.
+    fn ___read_dir(&self, path: &VPath) -> io::Result<Vec<DirEntry>> {
+        // The root directory contains our mount points
+        if Self::is_root(path) {
+            return Vfs::with(|| {
+                let entries = self
+                    .layers
+                    .keys()
+                    .map(|v| DirEntry::new(v.clone(), FileType::Directory))
+                    .collect();
+                Ok(entries)
+            });
+        }
+
+        let mut components = path.components();
+
+        let layer_name = Self::parse_target_layer(&mut components)?;
+
+        // Try and find the layer mounted at the given name
+        if let Some(layer) = self.layers.get(layer_name) {
+            layer.read_dir(components.as_path())
+        } else {
+            Err(io::Error::new(io::ErrorKind::NotFound, "No such file."))
+        }
+    }
+
+    /// The core implementation of `watch` with the generic args stripped away so we don't
+    /// monomorph the whole method body for every type that implements `AsRef<VPath>`.
+    fn ___watch(&self, path: &VPath) -> io::Result<Watcher<'_>> {
+        // Watching the root watches every layer that supports it
+        if Self::is_root(path) {
+            let mut watchers = Vec::new();
+            for (mount_name, layer) in self.layers.iter() {
+                match layer.watch(VPath::new("")) {
+                    Ok(v) => watchers.push((mount_name.clone(), v)),
+                    Err(e) if e.kind() == io::ErrorKind::Unsupported => {}
+                    Err(e) => return Err(e),
+                }
+            }
+            return Ok(Watcher { watchers });
+        }
+
+        let mut components = path.components();
+
+        let layer_name = Self::parse_target_layer(&mut components)?;
+
+        // Try and find the layer mounted at the given name
+        if let Some((mount_name, layer)) = self.layers.get_key_value(layer_name) {
+            let watcher = layer.watch(components.as_path())?;
+            let watchers = vec![(mount_name.clone(), watcher)];
+            Ok(Watcher { watchers })
+        } else {
+            Err(io::Error::new(io::ErrorKind::NotFound, "No such file."))
+        }
+    }
+
+    /// Whether the path refers to the root of the tree, which is the only directory that doesn't
+    /// belong to a layer.
+    fn is_root(path: &VPath) -> bool {
+        let mut components = path.components();
+        matches!(components.next(), Some(Component::Root)) && components.next().is_none()
+    }
+
     fn parse_target_layer<'a>(components: &'a mut Components) -> io::Result<&'a str> {
         let layer_name = match components.next() {
             // The empty path categorically doesn't refer to any elements, so bail
@@ -241,6 +340,18 @@ impl IRouter for Router {
     fn __open_async(&self, path: &VPath) -> io::Result<Arc<dyn IAsyncVFile>> {
         self.___open_async(path)
     }
+
+    fn __metadata(&self, path: &VPath) -> io::Result<Metadata> {
+        self.___metadata(path)
+    }
+
+    fn __read_dir(&self, path: &VPath) -> io::Result<Vec<DirEntry>> {
+        self.___read_dir(path)
+    }
+
+    fn __watch(&self, path: &VPath) -> io::Result<Watcher<'_>> {
+        self.___watch(path)
+    }
 }
 
 /// 'ABI' level trait that exposes the interface for [`Router`] as a trait object. See
@@ -257,6 +368,24 @@ pub trait IRouter: Send + Sync + 'static {
     ///
     /// Use [`Router::open_async`] or [`IRouter::open_async`] instead.
     fn __open_async(&self, path: &VPath) -> io::Result<Arc<dyn IAsyncVFile>>;
+
+    /// The core implementation of [`Router::metadata`] with the generic args stripped away so we
+    /// don't monomorph the whole method body for every type that implements `AsRef<VPath>`.
+    ///
+    /// Use [`Router::metadata`] or [`IRouter::metadata`] instead.
+    fn __metadata(&self, path: &VPath) -> io::Result<Metadata>;
+
+    /// The core implementation of [`Router::read_dir`] with the generic args stripped away so we
+    /// don't monomorph the whole method body for every type that implements `AsRef<VPath>`.
+    ///
+    /// Use [`Router::read_dir`] or [`IRouter::read_dir`] instead.
+    fn __read_dir(&self, path: &VPath) -> io::Result<Vec<DirEntry>>;
+
+    /// The core implementation of [`Router::watch`] with the generic args stripped away so we
+    /// don't monomorph the whole method body for every type that implements `AsRef<VPath>`.
+    ///
+    /// Use [`Router::watch`] or [`IRouter::watch`] instead.
+    fn __watch(&self, path: &VPath) -> io::Result<Watcher<'_>>;
 }
 
 /// An extension over [`IRouter`] that providers neater interfaces. We need this layer because we
@@ -271,6 +400,32 @@ pub trait IRouterExt: IRouter + Send + Sync + 'static {
     fn open_async<P: AsRef<VPath>>(&self, path: P) -> io::Result<Arc<dyn IAsyncVFile>> {
         self.__open_async(path.as_ref())
     }
+
+    /// Queries the [`Metadata`] of the file or directory at the given path.
+    fn metadata<P: AsRef<VPath>>(&self, path: P) -> io::Result<Metadata> {
+        self.__metadata(path.as_ref())
+    }
+
+    /// Lists the entries of the directory at the given path. The order of the entries is
+    /// unspecified.
+    fn read_dir<P: AsRef<VPath>>(&self, path: P) -> io::Result<Vec<DirEntry>> {
+        self.__read_dir(path.as_ref())
+    }
+
+    /// Returns whether a file or directory exists at the given path. Errors other than
+    /// [`io::ErrorKind::NotFound`] are propagated, like [`std::fs::exists`].
+    fn exists<P: AsRef<VPath>>(&self, path: P) -> io::Result<bool> {
+        match self.__metadata(path.as_ref()) {
+            Ok(_) => Ok(true),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Starts watching the file or directory at the given path for changes. See [`Watcher`].
+    fn watch<P: AsRef<VPath>>(&self, path: P) -> io::Result<Watcher<'_>> {
+        self.__watch(path.as_ref())
+    }
 }
 
 impl<T: IRouter + ?Sized> IRouterExt for T {}
@@ -308,6 +463,43 @@ pub trait ILayer: Send + Sync + 'static {
     fn query_entity(&self, path: &VPath) -> io::Result<VFile<'_>>;
 
     fn query_entity_async_io(&self, path: &VPath) -> io::Result<Arc<dyn IAsyncVFile>>;
+
+    /// Query the [`Metadata`] of the entity at the given 'path'.
+    ///
+    /// 'path' follows the same rules as [`ILayer::query_entity`]. The empty path, or a lone root,
+    /// refers to the root directory of the layer.
+    fn metadata(&self, path: &VPath) -> io::Result<Metadata>;
+
+    /// List the entries of the directory at the given 'path'. The order of the entries is
+    /// unspecified.
+    ///
+    /// 'path' follows the same rules as [`ILayer::metadata`].
+    fn read_dir(&self, path: &VPath) -> io::Result<Vec<DirEntry>>;
+
+    /// Test whether an entity exists at the given 'path'. Errors other than
+    /// [`io::ErrorKind::NotFound`] are propagated.
+    ///
+    /// 'path' follows the same rules as [`ILayer::metadata`].
+    fn exists(&self, path: &VPath) -> io::Result<bool> {
+        match self.metadata(path) {
+            Ok(_) => Ok(true),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Start watching the file or directory at the given 'path' for changes.
+    ///
+    /// 'path' follows the same rules as [`ILayer::metadata`]. Layers that can't change should
+    /// return an [`io::ErrorKind::Unsupported`] error, which is what the default implementation
+    /// does.
+    fn watch(&self, path: &VPath) -> io::Result<BBox<dyn ILayerWatcher + '_, VfsSystem>> {
+        let _ = path;
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "This layer doesn't support watching for changes.",
+        ))
+    }
 }
 
 /// Utility for boxing a layer implementation into the tagged box types we use for allocation
@@ -0,0 +1,120 @@
+//
+//
+// This file is a part of Aleph
+//
+// https://github.com/nathanvoglsam/aleph
+//
+// MIT License
+//
+// Copyright (c) 2020 Aleph Engine
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use std::time::SystemTime;
+
+/// The type of an entity within a vfs.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum FileType {
+    /// A regular file that can be opened and read.
+    File,
+
+    /// A directory that can be enumerated with [`crate::IRouterExt::read_dir`].
+    Directory,
+}
+
+/// Analogue of [`std::fs::Metadata`]. Describes an entity within a vfs without needing to open it.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Metadata {
+    file_type: FileType,
+    len: u64,
+    modified: Option<SystemTime>,
+}
+
+impl Metadata {
+    /// Constructs a new [`Metadata`]. Intended for use by [`crate::ILayer`] implementations.
+    pub const fn new(file_type: FileType, len: u64, modified: Option<SystemTime>) -> Self {
+        Self {
+            file_type,
+            len,
+            modified,
+        }
+    }
+
+    /// Constructs [`Metadata`] describing a directory with no modification time.
+    pub const fn directory() -> Self {
+        Self::new(FileType::Directory, 0, None)
+    }
+
+    /// The type of the entity.
+    pub const fn file_type(&self) -> FileType {
+        self.file_type
+    }
+
+    /// Whether the entity is a regular file.
+    pub const fn is_file(&self) -> bool {
+        matches!(self.file_type, FileType::File)
+    }
+
+    /// Whether the entity is a directory.
+    pub const fn is_dir(&self) -> bool {
+        matches!(self.file_type, FileType::Directory)
+    }
+
+    /// The size of the file, in bytes. This is always the size of the data a reader will see, not
+    /// the size of any compressed representation in the backing storage. Directories have a length
+    /// of 0.
+    pub const fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Whether the entity has a length of 0.
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The last modification time of the entity, if the layer it came from tracks one.
+    pub const fn modified(&self) -> Option<SystemTime> {
+        self.modified
+    }
+}
+
+/// Analogue of [`std::fs::DirEntry`]. A single entry yielded when enumerating a directory.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct DirEntry {
+    name: String,
+    file_type: FileType,
+}
+
+impl DirEntry {
+    /// Constructs a new [`DirEntry`]. Intended for use by [`crate::ILayer`] implementations.
+    pub const fn new(name: String, file_type: FileType) -> Self {
+        Self { name, file_type }
+    }
+
+    /// The name of the entry within its parent directory. This is a single path segment.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The type of the entry.
+    pub const fn file_type(&self) -> FileType {
+        self.file_type
+    }
+}
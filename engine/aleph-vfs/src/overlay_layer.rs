@@ -0,0 +1,357 @@
+//
+//
+// This file is a part of Aleph
+//
+// https://github.com/nathanvoglsam/aleph
+//
+// MIT License
+//
+// Copyright (c) 2020 Aleph Engine
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use std::cmp::Reverse;
+use std::io;
+use std::sync::Arc;
+
+use aleph_alloc::instrumentation::{IAllocationCategory, system};
+use aleph_alloc::{BBox, BHashSet, BVec};
+
+use crate::file::{IAsyncVFile, VFile};
+use crate::metadata::{DirEntry, Metadata};
+use crate::path::{VPath, VPathBuf, normalize_layer_path};
+use crate::watch::{ChangeEvent, ChangeKind, ILayerWatcher, box_watcher};
+use crate::{ILayer, Vfs, VfsSystem, box_layer};
+
+/// Describes a single layer within an [`OverlayLayer`].
+pub struct OverlayEntry {
+    /// The priority of the layer within the overlay. When multiple layers contain the same path
+    /// the layer with the highest priority wins. Layers with equal priority are searched in the
+    /// order they were given to [`OverlayLayer::new`].
+    pub priority: i32,
+
+    /// The fs layer to stack into the overlay.
+    pub layer: BBox<dyn ILayer, VfsSystem>,
+
+    /// A list of 'whiteout' paths, relative to the root of the overlay. A whiteout hides the path
+    /// from all layers with a lower priority than this one. Whiting out a directory hides every
+    /// file inside it.
+    ///
+    /// A whiteout never hides files within its own layer.
+    pub whiteouts: Vec<VPathBuf>,
+}
+
+/// An [`ILayer`] implementation that stacks multiple layers on top of each other under a single
+/// mount point.
+///
+/// This is the opt-in mechanism for overlapping mounts. The [`crate::Router`] still only allows a
+/// single layer per mount name, but that layer can be an overlay that searches a list of layers in
+/// priority order. This allows mods and patches to replace individual files of a base package
+/// without copying the whole package.
+///
+/// Queries are resolved against each layer from highest to lowest priority. The first layer that
+/// contains the path is used. A layer that reports [`io::ErrorKind::NotFound`] is skipped, any
+/// other error is returned immediately. Layers can also _hide_ paths in the layers beneath them
+/// with whiteouts, see [`OverlayEntry::whiteouts`].
+///
+/// All layers within an overlay should be configured the same way for async IO. A layer with no
+/// async io support will fail the query rather than fall through to the layers below it.
+pub struct OverlayLayer {
+    /// The stacked layers, sorted from highest priority to lowest.
+    layers: BVec<OverlayStackEntry, VfsSystem>,
+}
+
+struct OverlayStackEntry {
+    priority: i32,
+
+    layer: BBox<dyn ILayer, VfsSystem>,
+
+    /// The set of normalized whiteout paths. Normalized paths have no root and no redundant
+    /// separators.
+    whiteouts: BHashSet<VPathBuf, VfsSystem>,
+}
+
+impl OverlayLayer {
+    /// Construct a new overlay from the given set of layers. The layers can be provided in any
+    /// order, they will be sorted by [`OverlayEntry::priority`].
+    ///
+    /// # Errors
+    ///
+    /// Will fail with [`io::ErrorKind::InvalidFilename`] if any whiteout path contains '.' or '..'
+    /// segments.
+    pub fn new<I: IntoIterator<Item = OverlayEntry>>(
+        entries: I,
+    ) -> io::Result<BBox<dyn ILayer, VfsSystem>> {
+        Ok(box_layer(Self::__new(entries.into_iter())?))
+    }
+
+    fn __new<I: Iterator<Item = OverlayEntry>>(entries: I) -> io::Result<Self> {
+        Vfs::with(|| {
+            let mut layers = BVec::new_in(system());
+            for entry in entries {
+                let mut whiteouts = BHashSet::with_hasher_in(Default::default(), system());
+                for whiteout in entry.whiteouts.iter() {
+                    whiteouts.insert(normalize_layer_path(whiteout)?);
+                }
+                layers.push(OverlayStackEntry {
+                    priority: entry.priority,
+                    layer: entry.layer,
+                    whiteouts,
+                });
+            }
+
+            // Stable sort, so equal priorities keep the order they were given in
+            layers.sort_by_key(|v| Reverse(v.priority));
+
+            Ok(Self { layers })
+        })
+    }
+
+    /// Walks the layers in priority order, calling 'query' on each one until a layer returns
+    /// anything other than [`io::ErrorKind::NotFound`] or the path is hidden by a whiteout.
+    fn resolve<'a, T>(
+        &'a self,
+        path: &VPath,
+        query: impl Fn(&'a dyn ILayer) -> io::Result<T>,
+    ) -> io::Result<T> {
+        // Only pay for normalizing the path if there's a whiteout that could hide it
+        let has_whiteouts = self.layers.iter().any(|v| !v.whiteouts.is_empty());
+        let normalized = if has_whiteouts {
+            Some(Vfs::with(|| normalize_layer_path(path))?)
+        } else {
+            None
+        };
+
+        for entry in self.layers.iter() {
+            match query(entry.layer.as_ref()) {
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                v => return v,
+            }
+
+            if let Some(normalized) = normalized.as_ref()
+                && entry.is_hidden(normalized)
+            {
+                break;
+            }
+        }
+
+        Err(io::Error::new(io::ErrorKind::NotFound, "No such file."))
+    }
+}
+
+impl OverlayLayer {
+    /// Whether the given normalized path is contained in, or whited out by, any layer with a
+    /// higher priority than the layer at 'index'.
+    fn shadowed(&self, index: usize, path: &VPath) -> bool {
+        self.layers[..index]
+            .iter()
+            .any(|v| v.is_hidden(path) || v.layer.exists(path).unwrap_or(false))
+    }
+
+    /// Whether the given normalized path resolves to any layer with a lower priority than the
+    /// layer at 'index', taking whiteouts into account.
+    fn visible_below(&self, index: usize, path: &VPath) -> bool {
+        for (i, entry) in self.layers.iter().enumerate() {
+            if i > index && entry.layer.exists(path).unwrap_or(false) {
+                return true;
+            }
+            if entry.is_hidden(path) {
+                return false;
+            }
+        }
+        false
+    }
+}
+
+impl OverlayStackEntry {
+    /// Returns whether the given normalized path, or any of its parent directories, has been
+    /// whited out by this layer.
+    fn is_hidden(&self, path: &VPath) -> bool {
+        if self.whiteouts.is_empty() {
+            return false;
+        }
+
+        let path = path.to_str();
+        let prefixes = path
+            .match_indices('/')
+            .map(|(i, _)| &path[..i])
+            .chain(std::iter::once(path));
+        for prefix in prefixes {
+            if self.whiteouts.contains(VPath::new(prefix)) {
+                return true;
+            }
+        }
+
+        false
+    }
+}
+
+impl ILayer for OverlayLayer {
+    fn install(&mut self, mount_name: &str) -> io::Result<()> {
+        for entry in self.layers.iter_mut() {
+            entry.layer.install(mount_name)?;
+        }
+        Ok(())
+    }
+
+    fn query_entity(&self, path: &VPath) -> io::Result<VFile<'_>> {
+        self.resolve(path, |layer| layer.query_entity(path))
+    }
+
+    fn query_entity_async_io(&self, path: &VPath) -> io::Result<Arc<dyn IAsyncVFile>> {
+        self.resolve(path, |layer| layer.query_entity_async_io(path))
+    }
+
+    fn metadata(&self, path: &VPath) -> io::Result<Metadata> {
+        self.resolve(path, |layer| layer.metadata(path))
+    }
+
+    fn read_dir(&self, path: &VPath) -> io::Result<Vec<DirEntry>> {
+        Vfs::with(|| {
+            let normalized = normalize_layer_path(path)?;
+
+            // Directory listings are merged across every layer that contains the directory. The
+            // highest priority layer decides the type of an entry that appears in multiple layers.
+            let mut names: BHashSet<String, VfsSystem> =
+                BHashSet::with_hasher_in(Default::default(), system());
+            let mut entries = Vec::new();
+            let mut found = false;
+            for (i, entry) in self.layers.iter().enumerate() {
+                match entry.layer.read_dir(path) {
+                    Ok(v) => {
+                        found = true;
+                        for child in v {
+                            // Skip anything whited out by a layer above this one
+                            let mut child_path = normalized.clone();
+                            child_path.push(child.name());
+                            let child_path = normalize_layer_path(&child_path)?;
+                            let hidden = self.layers[..i].iter().any(|v| v.is_hidden(&child_path));
+                            if hidden {
+                                continue;
+                            }
+
+                            if names.insert(child.name().to_string()) {
+                                entries.push(child);
+                            }
+                        }
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                    Err(e) => return Err(e),
+                }
+
+                if entry.is_hidden(&normalized) {
+                    break;
+                }
+            }
+
+            if found {
+                Ok(entries)
+            } else {
+                Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    "No such directory.",
+                ))
+            }
+        })
+    }
+
+    fn watch(&self, path: &VPath) -> io::Result<BBox<dyn ILayerWatcher + '_, VfsSystem>> {
+        Vfs::with(|| {
+            // Watch every layer that contains the path. Layers that don't contain it or can't be
+            // watched are skipped.
+            let mut watchers = Vec::new();
+            let mut unsupported = 0;
+            for (i, entry) in self.layers.iter().enumerate() {
+                match entry.layer.watch(path) {
+                    Ok(v) => watchers.push((i, v)),
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                    Err(e) if e.kind() == io::ErrorKind::Unsupported => unsupported += 1,
+                    Err(e) => return Err(e),
+                }
+            }
+
+            if watchers.is_empty() {
+                return if unsupported == self.layers.len() {
+                    Err(io::Error::new(
+                        io::ErrorKind::Unsupported,
+                        "No layer in the overlay supports watching for changes.",
+                    ))
+                } else {
+                    Err(io::Error::new(io::ErrorKind::NotFound, "No such file."))
+                };
+            }
+
+            Ok(box_watcher(OverlayWatcher {
+                overlay: self,
+                watchers,
+            }))
+        })
+    }
+}
+
+/// [`ILayerWatcher`] for an [`OverlayLayer`]. Polls the watchers of each layer, and translates
+/// their events into what is visible through the overlay.
+///
+/// Changes to files shadowed by a higher priority layer, or hidden by a whiteout, are dropped.
+/// Creating or removing a file that shadows a file in a lower priority layer is reported as a
+/// modification, as the path still resolves but to different contents.
+struct OverlayWatcher<'a> {
+    overlay: &'a OverlayLayer,
+
+    /// The watchers for each layer, paired with the index of the layer in the overlay.
+    watchers: Vec<(usize, BBox<dyn ILayerWatcher + 'a, VfsSystem>)>,
+}
+
+impl<'a> ILayerWatcher for OverlayWatcher<'a> {
+    fn poll(&mut self, events: &mut Vec<ChangeEvent>) -> io::Result<()> {
+        Vfs::with(|| {
+            let overlay = self.overlay;
+            let mut seen: BHashSet<VPathBuf, VfsSystem> =
+                BHashSet::with_hasher_in(Default::default(), system());
+            let mut layer_events = Vec::new();
+            for (index, watcher) in self.watchers.iter_mut() {
+                layer_events.clear();
+                watcher.poll(&mut layer_events)?;
+
+                for event in layer_events.drain(..) {
+                    if seen.contains(&event.path) || overlay.shadowed(*index, &event.path) {
+                        continue;
+                    }
+
+                    let kind = match event.kind {
+                        ChangeKind::Created | ChangeKind::Removed
+                            if overlay.visible_below(*index, &event.path) =>
+                        {
+                            ChangeKind::Modified
+                        }
+                        v => v,
+                    };
+
+                    seen.insert(event.path.clone());
+                    events.push(ChangeEvent {
+                        path: event.path,
+                        kind,
+                    });
+                }
+            }
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,541 @@
+//
+//
+// This file is a part of Aleph
+//
+// https://github.com/nathanvoglsam/aleph
+//
+// MIT License
+//
+// Copyright (c) 2020 Aleph Engine
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+//! The on-disk format for vfs 'pack' archives, as consumed by
+//! [`ArchiveLayer`](crate::archive_layer::ArchiveLayer).
+//!
+//! A pack is a single file containing the data for many virtual files along with an index that
+//! maps each virtual path to the byte range that holds its data. All integers are little endian.
+//!
+//! ```text
+//! +----------------------+
+//! | magic    [u8; 8]     |  b"ALEPHPAK"
+//! | version  u32         |
+//! | reserved u32         |
+//! +----------------------+
+//! | entry data ...       |  Concatenated, possibly compressed, file data
+//! +----------------------+
+//! | index entries ...    |  See 'PackEntry'
+//! +----------------------+
+//! | index_offset u64     |  Footer, the last 32 bytes of the file
+//! | index_size   u64     |
+//! | entry_count  u64     |
+//! | magic        [u8; 8] |
+//! +----------------------+
+//! ```
+//!
+//! The index is written at the end so a pack can be streamed out in a single pass. Each index
+//! entry is laid out as:
+//!
+//! ```text
+//! path_len          u32
+//! path              [u8; path_len]  UTF-8, normalized with no leading separator
+//! compression       u32
+//! offset            u64             Offset from the start of the pack to the entry's data
+//! size              u64             Size of the data stored in the pack
+//! size_uncompressed u64             Size of the data once decompressed
+//! ```
+
+use std::io;
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use aleph_alloc::instrumentation::system;
+use aleph_alloc::{BHashMap, BHashSet, BVec};
+
+use crate::VfsSystem;
+use crate::metadata::{DirEntry, FileType};
+use crate::path::{VPath, VPathBuf, normalize_layer_path};
+
+/// The magic bytes found at the start and the end of every pack file.
+pub const PACK_MAGIC: [u8; 8] = *b"ALEPHPAK";
+
+/// The version of the pack format that this crate reads and writes.
+pub const PACK_VERSION: u32 = 1;
+
+/// Size of the header at the start of the pack, in bytes.
+pub const PACK_HEADER_SIZE: u64 = 16;
+
+/// Size of the footer at the end of the pack, in bytes.
+pub const PACK_FOOTER_SIZE: u64 = 32;
+
+/// Size of an index entry with an empty path, the smallest an entry can be, in bytes.
+const PACK_MIN_ENTRY_SIZE: u64 = 32;
+
+/// The largest ratio of decompressed to compressed size a zstd frame can achieve. The best case is
+/// a run of RLE blocks, where 4 bytes of block header and payload expand to a full 128KiB block.
+const PACK_MAX_ZSTD_RATIO: u64 = (128 * 1024) / 4;
+
+/// The compression scheme that was applied to a single entry within a pack.
+#[repr(u32)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Default)]
+pub enum PackCompressionScheme {
+    /// The entry is stored as-is. These entries can be read directly from the pack at arbitrary
+    /// offsets.
+    #[default]
+    None = 0,
+
+    /// The entry is stored as a single zstd frame.
+    Zstd = 1,
+}
+
+impl PackCompressionScheme {
+    pub const fn from_raw(v: u32) -> Option<Self> {
+        match v {
+            0 => Some(Self::None),
+            1 => Some(Self::Zstd),
+            _ => None,
+        }
+    }
+
+    /// Whether `size` bytes of data compressed with this scheme could possibly decompress to
+    /// `size_uncompressed` bytes.
+    pub const fn is_plausible_size(self, size: u64, size_uncompressed: u64) -> bool {
+        match self {
+            PackCompressionScheme::None => size == size_uncompressed,
+            PackCompressionScheme::Zstd => {
+                size_uncompressed <= size.saturating_mul(PACK_MAX_ZSTD_RATIO)
+            }
+        }
+    }
+
+    /// Decompresses `src`, which is expected to hold exactly `size_uncompressed` bytes once
+    /// decompressed.
+    ///
+    /// `size_uncompressed` usually comes from the pack's index, so it is checked against the
+    /// largest size `src` could decompress to before it is trusted with an allocation.
+    pub fn decompress(self, src: &[u8], size_uncompressed: u64) -> io::Result<Vec<u8>> {
+        if !self.is_plausible_size(src.len() as u64, size_uncompressed) {
+            return Err(invalid_data(
+                "Pack entry claims an impossible decompressed size",
+            ));
+        }
+
+        let out = match self {
+            PackCompressionScheme::None => src.to_vec(),
+            PackCompressionScheme::Zstd => {
+                let capacity = usize::try_from(size_uncompressed)
+                    .map_err(|_| io::Error::other("Pack entry too large for address space"))?;
+                zstd::bulk::decompress(src, capacity)?
+            }
+        };
+
+        if out.len() as u64 != size_uncompressed {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Pack entry decompressed to an unexpected size",
+            ));
+        }
+
+        Ok(out)
+    }
+}
+
+/// The compression to request when adding an entry with [`PackWriter::add_entry`].
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Default)]
+pub enum PackCompression {
+    /// Store the entry uncompressed.
+    #[default]
+    None,
+
+    /// Compress the entry with zstd, at the given compression level.
+    Zstd(i32),
+}
+
+/// A single entry in a pack's index.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct PackEntry {
+    /// The compression scheme applied to the entry's data.
+    pub compression: PackCompressionScheme,
+
+    /// Offset from the start of the pack file to the first byte of the entry's data.
+    pub offset: u64,
+
+    /// The number of bytes the entry occupies in the pack file.
+    pub size: u64,
+
+    /// The size of the entry once it has been decompressed. Will equal `size` for uncompressed
+    /// entries.
+    pub size_uncompressed: u64,
+}
+
+/// The index of a pack file, mapping normalized virtual paths to the entries that hold their data.
+pub struct PackIndex {
+    entries: BHashMap<VPathBuf, PackEntry, VfsSystem>,
+
+    /// The set of directories implied by the entry paths. Packs don't store directories
+    /// explicitly, every ancestor of an entry is considered a directory. Always contains the empty
+    /// path, which names the root of the pack.
+    directories: BHashSet<VPathBuf, VfsSystem>,
+}
+
+impl PackIndex {
+    /// Reads the index of the pack that `reader` is positioned over. The reader's cursor position
+    /// is unspecified after the call returns.
+    pub fn read<R: Read + Seek>(reader: &mut R) -> io::Result<Self> {
+        let mut header = [0u8; PACK_HEADER_SIZE as usize];
+        reader.seek(SeekFrom::Start(0))?;
+        reader.read_exact(&mut header)?;
+        if header[0..8] != PACK_MAGIC {
+            return Err(invalid_data("Pack header has invalid magic"));
+        }
+        let version = u32::from_le_bytes(header[8..12].try_into().unwrap());
+        if version != PACK_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Unsupported pack version",
+            ));
+        }
+
+        let len = reader.seek(SeekFrom::End(0))?;
+        if len < PACK_HEADER_SIZE + PACK_FOOTER_SIZE {
+            return Err(invalid_data("Pack file too small"));
+        }
+
+        let mut footer = [0u8; PACK_FOOTER_SIZE as usize];
+        reader.seek(SeekFrom::Start(len - PACK_FOOTER_SIZE))?;
+        reader.read_exact(&mut footer)?;
+        if footer[24..32] != PACK_MAGIC {
+            return Err(invalid_data("Pack footer has invalid magic"));
+        }
+        let index_offset = u64::from_le_bytes(footer[0..8].try_into().unwrap());
+        let index_size = u64::from_le_bytes(footer[8..16].try_into().unwrap());
+        let entry_count = u64::from_le_bytes(footer[16..24].try_into().unwrap());
+
+        let data_end = len - PACK_FOOTER_SIZE;
+        if index_offset < PACK_HEADER_SIZE
+            || index_offset
+                .checked_add(index_size)
+                .is_none_or(|v| v != data_end)
+        {
+            return Err(invalid_data("Pack index out of bounds"));
+        }
+
+        // Every entry takes up space in the index, so this bounds the count before we trust it
+        // with an allocation.
+        if entry_count > index_size / PACK_MIN_ENTRY_SIZE {
+            return Err(invalid_data("Pack entry count doesn't fit in the index"));
+        }
+
+        let mut index = vec![0u8; index_size as usize];
+        reader.seek(SeekFrom::Start(index_offset))?;
+        reader.read_exact(&mut index)?;
+
+        let mut entries = BHashMap::with_capacity_in(entry_count as usize, system());
+        let mut directories = BHashSet::new_in(system());
+        directories.insert(VPathBuf::new());
+        let mut cursor = index.as_slice();
+        for _ in 0..entry_count {
+            let path_len = take_u32(&mut cursor)? as usize;
+            let path = take_bytes(&mut cursor, path_len)?;
+            let path = std::str::from_utf8(path)
+                .map_err(|_| invalid_data("Pack entry path is not valid UTF-8"))?;
+            let path = normalize_layer_path(VPath::new(path))?;
+
+            let compression = PackCompressionScheme::from_raw(take_u32(&mut cursor)?)
+                .ok_or_else(|| invalid_data("Pack entry has unknown compression scheme"))?;
+            let offset = take_u64(&mut cursor)?;
+            let size = take_u64(&mut cursor)?;
+            let size_uncompressed = take_u64(&mut cursor)?;
+
+            // Entries must live entirely within the data region of the pack
+            if offset < PACK_HEADER_SIZE
+                || offset.checked_add(size).is_none_or(|v| v > index_offset)
+            {
+                return Err(invalid_data("Pack entry out of bounds"));
+            }
+            if !compression.is_plausible_size(size, size_uncompressed) {
+                return Err(invalid_data(
+                    "Pack entry claims an impossible decompressed size",
+                ));
+            }
+
+            let entry = PackEntry {
+                compression,
+                offset,
+                size,
+                size_uncompressed,
+            };
+            let mut parent = path.to_str();
+            while let Some((v, _)) = parent.rsplit_once('/') {
+                parent = v;
+                if !directories.insert(VPathBuf::from(parent)) {
+                    break;
+                }
+            }
+
+            if entries.insert(path, entry).is_some() {
+                return Err(invalid_data("Pack contains duplicate entries"));
+            }
+        }
+
+        if !cursor.is_empty() {
+            return Err(invalid_data("Pack index has trailing bytes"));
+        }
+
+        // A file can't also be a directory
+        if entries.keys().any(|v| directories.contains(v)) {
+            return Err(invalid_data("Pack entry is also used as a directory"));
+        }
+
+        Ok(Self {
+            entries,
+            directories,
+        })
+    }
+
+    /// Looks up the entry for the given path. The path will be normalized before the lookup, so
+    /// leading and redundant separators are ignored.
+    ///
+    /// Returns an [`io::ErrorKind::IsADirectory`] error if the path names a directory within the
+    /// pack.
+    pub fn get(&self, path: &VPath) -> io::Result<&PackEntry> {
+        let path = normalize_layer_path(path)?;
+        if let Some(entry) = self.entries.get(&path) {
+            return Ok(entry);
+        }
+        if self.directories.contains(&path) {
+            return Err(io::Error::new(
+                io::ErrorKind::IsADirectory,
+                "ArchiveLayer can only open files.",
+            ));
+        }
+        Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "File not found in pack",
+        ))
+    }
+
+    /// Whether the given path names a directory within the pack.
+    pub fn is_directory(&self, path: &VPath) -> bool {
+        match normalize_layer_path(path) {
+            Ok(path) => self.directories.contains(&path),
+            Err(_) => false,
+        }
+    }
+
+    /// Lists the direct children of the directory at the given path.
+    pub fn read_dir(&self, path: &VPath) -> io::Result<Vec<DirEntry>> {
+        let path = normalize_layer_path(path)?;
+        if !self.directories.contains(&path) {
+            return if self.entries.contains_key(&path) {
+                Err(io::Error::new(
+                    io::ErrorKind::NotADirectory,
+                    "Not a directory.",
+                ))
+            } else {
+                Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    "No such directory.",
+                ))
+            };
+        }
+
+        let files = self.entries.keys().map(|v| (v, FileType::File));
+        let directories = self.directories.iter().map(|v| (v, FileType::Directory));
+        let entries = files
+            .chain(directories)
+            .filter_map(|(v, file_type)| {
+                let (parent, name) = v.to_str().rsplit_once('/').unwrap_or(("", v.to_str()));
+                if !name.is_empty() && parent == path.to_str() {
+                    Some(DirEntry::new(name.to_string(), file_type))
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        Ok(entries)
+    }
+
+    /// The number of entries in the pack.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the pack contains no entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Iterate over every entry in the pack, in an unspecified order.
+    pub fn iter(&self) -> impl Iterator<Item = (&VPath, &PackEntry)> {
+        self.entries.iter().map(|(k, v)| (k.as_ref(), v))
+    }
+}
+
+/// Writes a pack file in a single pass.
+///
+/// Entries are streamed into the underlying writer as they are added, the index is only written
+/// when [`PackWriter::finish`] is called. A pack that is never finished is not valid.
+pub struct PackWriter<W: Write> {
+    writer: W,
+    cursor: u64,
+    paths: BVec<VPathBuf, VfsSystem>,
+    entries: BHashMap<VPathBuf, PackEntry, VfsSystem>,
+    directories: BHashSet<VPathBuf, VfsSystem>,
+}
+
+impl<W: Write> PackWriter<W> {
+    /// Creates a new writer, immediately writing the pack header into `writer`.
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        writer.write_all(&PACK_MAGIC)?;
+        writer.write_all(&PACK_VERSION.to_le_bytes())?;
+        writer.write_all(&0u32.to_le_bytes())?;
+        Ok(Self {
+            writer,
+            cursor: PACK_HEADER_SIZE,
+            paths: BVec::new_in(system()),
+            entries: BHashMap::new_in(system()),
+            directories: BHashSet::new_in(system()),
+        })
+    }
+
+    /// Appends a new entry to the pack with the given path and contents.
+    ///
+    /// If compressing the entry doesn't make it any smaller then the entry will be stored
+    /// uncompressed instead. Adding the same path twice is an error.
+    pub fn add_entry(
+        &mut self,
+        path: &VPath,
+        data: &[u8],
+        compression: PackCompression,
+    ) -> io::Result<()> {
+        let path = normalize_layer_path(path)?;
+        if path.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidFilename,
+                "Pack entries can't have an empty path",
+            ));
+        }
+        if self.entries.contains_key(&path) || self.directories.contains(&path) {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                "Pack already contains an entry with this path",
+            ));
+        }
+
+        // None of the entry's ancestors can be files
+        let mut parents: BVec<VPathBuf, VfsSystem> = BVec::new_in(system());
+        let mut parent = path.to_str();
+        while let Some((v, _)) = parent.rsplit_once('/') {
+            parent = v;
+            if self.entries.contains_key(VPath::new(parent)) {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotADirectory,
+                    "Pack entry path has a file as an ancestor",
+                ));
+            }
+            parents.push(VPathBuf::from(parent));
+        }
+
+        let compressed = match compression {
+            PackCompression::None => None,
+            PackCompression::Zstd(level) => {
+                let compressed = zstd::bulk::compress(data, level)?;
+                if compressed.len() < data.len() {
+                    Some((PackCompressionScheme::Zstd, compressed))
+                } else {
+                    None
+                }
+            }
+        };
+        let (scheme, stored) = match &compressed {
+            Some((scheme, compressed)) => (*scheme, compressed.as_slice()),
+            None => (PackCompressionScheme::None, data),
+        };
+
+        self.writer.write_all(stored)?;
+        let entry = PackEntry {
+            compression: scheme,
+            offset: self.cursor,
+            size: stored.len() as u64,
+            size_uncompressed: data.len() as u64,
+        };
+        self.cursor += entry.size;
+
+        self.directories.extend(parents);
+        self.paths.push(path.clone());
+        self.entries.insert(path, entry);
+
+        Ok(())
+    }
+
+    /// Writes the index and footer, completing the pack. Returns the inner writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        let index_offset = self.cursor;
+        let mut index_size = 0u64;
+        for path in self.paths.iter() {
+            let entry = &self.entries[path];
+            let path = path.to_str().as_bytes();
+
+            self.writer.write_all(&(path.len() as u32).to_le_bytes())?;
+            self.writer.write_all(path)?;
+            self.writer
+                .write_all(&(entry.compression as u32).to_le_bytes())?;
+            self.writer.write_all(&entry.offset.to_le_bytes())?;
+            self.writer.write_all(&entry.size.to_le_bytes())?;
+            self.writer
+                .write_all(&entry.size_uncompressed.to_le_bytes())?;
+
+            index_size += 4 + path.len() as u64 + 4 + 8 + 8 + 8;
+        }
+
+        self.writer.write_all(&index_offset.to_le_bytes())?;
+        self.writer.write_all(&index_size.to_le_bytes())?;
+        self.writer
+            .write_all(&(self.paths.len() as u64).to_le_bytes())?;
+        self.writer.write_all(&PACK_MAGIC)?;
+        self.writer.flush()?;
+
+        Ok(self.writer)
+    }
+}
+
+fn invalid_data(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn take_bytes<'a>(cursor: &mut &'a [u8], len: usize) -> io::Result<&'a [u8]> {
+    if cursor.len() < len {
+        return Err(invalid_data("Pack index truncated"));
+    }
+    let (head, tail) = cursor.split_at(len);
+    *cursor = tail;
+    Ok(head)
+}
+
+fn take_u32(cursor: &mut &[u8]) -> io::Result<u32> {
+    let bytes = take_bytes(cursor, 4)?;
+    Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+fn take_u64(cursor: &mut &[u8]) -> io::Result<u64> {
+    let bytes = take_bytes(cursor, 8)?;
+    Ok(u64::from_le_bytes(bytes.try_into().unwrap()))
+}
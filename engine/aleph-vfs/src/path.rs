@@ -28,6 +28,7 @@
 //
 
 use std::borrow::{Borrow, Cow};
+use std::io;
 use std::ops::Deref;
 
 pub const SEPARATOR: char = '/';
@@ -190,6 +191,12 @@ impl<'a> From<&'a str> for VPathBuf {
     }
 }
 
+impl From<String> for VPathBuf {
+    fn from(value: String) -> Self {
+        VPathBuf(value)
+    }
+}
+
 // == DEREF TRAITS == //
 
 impl Deref for VPathBuf {
@@ -918,6 +925,30 @@ impl<'a> Iterator for ReverseComponents<'a> {
 
 impl std::iter::FusedIterator for ReverseComponents<'_> {}
 
+/// Normalizes a path within a layer to a canonical form that can be used as a key. Strips root and
+/// redundant separators, and rejects '.' and '..' segments.
+pub(crate) fn normalize_layer_path(path: &VPath) -> io::Result<VPathBuf> {
+    let mut out = String::with_capacity(path.len());
+    for component in path.components() {
+        match component {
+            Component::Root => continue,
+            Component::Segment(seg) => {
+                if matches!(seg, ".." | ".") {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidFilename,
+                        "Path contains '..' | '.' segments.",
+                    ));
+                }
+                if !out.is_empty() {
+                    out.push('/');
+                }
+                out.push_str(seg);
+            }
+        }
+    }
+    Ok(VPathBuf::from(out))
+}
+
 #[cfg(test)]
 mod tests {
     #[test]
@@ -29,15 +29,22 @@
 
 use std::io;
 use std::io::Read;
+use std::ptr::NonNull;
 
 use camino::Utf8PathBuf;
 use crossbeam::channel::unbounded;
 
+use crate::archive_layer::ArchiveLayer;
 use crate::async_io::IoQueue;
 use crate::async_io::top_level_handle_cache::TopLevelHandleCache;
 use crate::directory_layer::DirectoryLayer;
 use crate::file::AsyncReadResponse;
-use crate::{IRouterExt, LayerDesc, Router};
+use crate::metadata::{DirEntry, FileType};
+use crate::overlay_layer::{OverlayEntry, OverlayLayer};
+use crate::pack::{PackCompression, PackCompressionScheme, PackIndex, PackWriter};
+use crate::path::{VPath, VPathBuf};
+use crate::watch::{ChangeEvent, ChangeKind};
+use crate::{ILayer, IRouterExt, LayerDesc, Router};
 
 #[test]
 pub fn empty_vfs_finds_no_files() {
@@ -264,3 +271,726 @@ pub fn async_read_test() {
         _ => panic!("Unexpected response"),
     }
 }
+
+fn sorted_dir(router: &Router, path: &str) -> io::Result<Vec<DirEntry>> {
+    let mut entries = router.read_dir(path)?;
+    entries.sort_by(|a, b| a.name().cmp(b.name()));
+    Ok(entries)
+}
+
+#[test]
+pub fn directory_metadata_and_read_dir() {
+    let layers = [
+        LayerDesc {
+            mount_name: "package_a",
+            layer: DirectoryLayer::new(Utf8PathBuf::from("./test-data/package_a")),
+        },
+        LayerDesc {
+            mount_name: "package_b",
+            layer: DirectoryLayer::new(Utf8PathBuf::from("./test-data/package_b")),
+        },
+    ];
+
+    let router = Router::new(layers).unwrap();
+
+    // The root lists the mounts
+    assert!(router.metadata("/").unwrap().is_dir());
+    assert_eq!(
+        sorted_dir(&router, "/").unwrap(),
+        [
+            DirEntry::new("package_a".to_string(), FileType::Directory),
+            DirEntry::new("package_b".to_string(), FileType::Directory),
+        ]
+    );
+
+    let metadata = router.metadata("/package_b/file.txt").unwrap();
+    assert!(metadata.is_file());
+    assert_eq!(metadata.len(), "Hello, from Package B!".len() as u64);
+    assert!(metadata.modified().is_some());
+
+    assert!(router.metadata("/package_b").unwrap().is_dir());
+    assert!(router.metadata("/package_b/folder/").unwrap().is_dir());
+
+    assert_eq!(
+        sorted_dir(&router, "/package_b").unwrap(),
+        [
+            DirEntry::new("file.txt".to_string(), FileType::File),
+            DirEntry::new("folder".to_string(), FileType::Directory),
+        ]
+    );
+    assert_eq!(
+        sorted_dir(&router, "package_b/folder").unwrap(),
+        [DirEntry::new("file.txt".to_string(), FileType::File)]
+    );
+
+    assert!(router.exists("/package_a/file.txt").unwrap());
+    assert!(!router.exists("/package_a/folder").unwrap());
+    assert!(!router.exists("/package_c").unwrap());
+
+    let err = router.exists("/package_a/../package_b").err().unwrap();
+    assert!(matches!(err.kind(), io::ErrorKind::InvalidFilename));
+
+    let err = router.read_dir("/package_a/no").err().unwrap();
+    assert!(matches!(err.kind(), io::ErrorKind::NotFound));
+}
+
+#[test]
+pub fn overlay_read_dir_merges_layers() {
+    let overlay = OverlayLayer::new([
+        OverlayEntry {
+            priority: 10,
+            layer: DirectoryLayer::new(Utf8PathBuf::from("./test-data/package_a")),
+            whiteouts: vec![VPathBuf::from("folder")],
+        },
+        OverlayEntry {
+            priority: 0,
+            layer: DirectoryLayer::new(Utf8PathBuf::from("./test-data/package_b")),
+            whiteouts: Vec::new(),
+        },
+        OverlayEntry {
+            priority: -10,
+            layer: DirectoryLayer::new(Utf8PathBuf::from("./test-data/package_b")),
+            whiteouts: Vec::new(),
+        },
+    ])
+    .unwrap();
+    let layers = [LayerDesc {
+        mount_name: "package",
+        layer: overlay,
+    }];
+
+    let router = Router::new(layers).unwrap();
+
+    // 'file.txt' is in every layer but should only be listed once, 'folder' is whited out
+    assert_eq!(
+        sorted_dir(&router, "/package").unwrap(),
+        [DirEntry::new("file.txt".to_string(), FileType::File)]
+    );
+
+    let err = router.read_dir("/package/folder").err().unwrap();
+    assert!(matches!(err.kind(), io::ErrorKind::NotFound));
+    assert!(!router.exists("/package/folder/file.txt").unwrap());
+
+    let metadata = router.metadata("/package/file.txt").unwrap();
+    assert_eq!(metadata.len(), "Hello, World!".len() as u64);
+}
+
+fn read_string(router: &Router, path: &str) -> io::Result<String> {
+    let file = router.open(path)?;
+    let mut reader = file.reader();
+    let mut string = String::new();
+    reader.read_to_string(&mut string)?;
+    Ok(string)
+}
+
+#[test]
+pub fn overlay_resolves_highest_priority() {
+    // Give the layers in the 'wrong' order to check they get sorted by priority
+    let overlay = OverlayLayer::new([
+        OverlayEntry {
+            priority: 0,
+            layer: DirectoryLayer::new(Utf8PathBuf::from("./test-data/package_b")),
+            whiteouts: Vec::new(),
+        },
+        OverlayEntry {
+            priority: 10,
+            layer: DirectoryLayer::new(Utf8PathBuf::from("./test-data/package_a")),
+            whiteouts: Vec::new(),
+        },
+    ])
+    .unwrap();
+    let layers = [LayerDesc {
+        mount_name: "package",
+        layer: overlay,
+    }];
+
+    let router = Router::new(layers).unwrap();
+
+    // Both layers have 'file.txt', so the highest priority wins
+    assert_eq!(
+        read_string(&router, "/package/file.txt").unwrap(),
+        "Hello, World!"
+    );
+
+    // Only the lower layer has 'folder/file.txt' so we fall through to it
+    assert_eq!(
+        read_string(&router, "/package/folder/file.txt").unwrap(),
+        "Hello, Subdir!"
+    );
+
+    let err = router.open("/package/folder/no.txt").err().unwrap();
+    assert!(matches!(err.kind(), io::ErrorKind::NotFound));
+
+    // Errors other than 'NotFound' stop the search
+    let err = router.open("/package/folder").err().unwrap();
+    assert!(matches!(err.kind(), io::ErrorKind::IsADirectory));
+}
+
+#[test]
+pub fn overlay_whiteouts_hide_lower_layers() {
+    let overlay = OverlayLayer::new([
+        OverlayEntry {
+            priority: 10,
+            layer: DirectoryLayer::new(Utf8PathBuf::from("./test-data/package_a")),
+            whiteouts: vec![VPathBuf::from("/folder//"), VPathBuf::from("file.txt")],
+        },
+        OverlayEntry {
+            priority: 0,
+            layer: DirectoryLayer::new(Utf8PathBuf::from("./test-data/package_b")),
+            whiteouts: Vec::new(),
+        },
+    ])
+    .unwrap();
+    let layers = [LayerDesc {
+        mount_name: "package",
+        layer: overlay,
+    }];
+
+    let router = Router::new(layers).unwrap();
+
+    // A whiteout doesn't hide a file in its own layer
+    assert_eq!(
+        read_string(&router, "/package/file.txt").unwrap(),
+        "Hello, World!"
+    );
+
+    // Whiting out a directory hides everything inside it
+    let err = router.open("/package/folder/file.txt").err().unwrap();
+    assert!(matches!(err.kind(), io::ErrorKind::NotFound));
+}
+
+#[test]
+pub fn overlay_rejects_dot_segments() {
+    let overlay = |whiteout: &str| {
+        OverlayLayer::new([OverlayEntry {
+            priority: 0,
+            layer: DirectoryLayer::new(Utf8PathBuf::from("./test-data/package_a")),
+            whiteouts: vec![VPathBuf::from(whiteout)],
+        }])
+    };
+
+    let err = overlay("folder/../file.txt").err().unwrap();
+    assert!(matches!(err.kind(), io::ErrorKind::InvalidFilename));
+    let err = overlay("./file.txt").err().unwrap();
+    assert!(matches!(err.kind(), io::ErrorKind::InvalidFilename));
+
+    // Queries that could escape a whiteout are rejected too
+    let overlay = overlay("folder").unwrap();
+    let err = overlay
+        .metadata(VPath::new("other/../folder/file.txt"))
+        .err()
+        .unwrap();
+    assert!(matches!(err.kind(), io::ErrorKind::InvalidFilename));
+    let err = overlay.read_dir(VPath::new("folder/..")).err().unwrap();
+    assert!(matches!(err.kind(), io::ErrorKind::InvalidFilename));
+}
+
+#[test]
+pub fn overlay_async_read_test() {
+    let queue = IoQueue::new(TopLevelHandleCache::new(2));
+    let overlay = OverlayLayer::new([
+        OverlayEntry {
+            priority: 1,
+            layer: DirectoryLayer::new_with_io_queue(
+                Utf8PathBuf::from("./test-data/package_a"),
+                queue.clone(),
+            ),
+            whiteouts: Vec::new(),
+        },
+        OverlayEntry {
+            priority: 0,
+            layer: DirectoryLayer::new_with_io_queue(
+                Utf8PathBuf::from("./test-data/package_b"),
+                queue,
+            ),
+            whiteouts: Vec::new(),
+        },
+    ])
+    .unwrap();
+    let layers = [LayerDesc {
+        mount_name: "package",
+        layer: overlay,
+    }];
+
+    let router = Router::new(layers).unwrap();
+
+    let (sender, receiver) = unbounded();
+
+    let file = router.open_async("/package/folder/file.txt").unwrap();
+    file.load(sender.clone(), 1).unwrap();
+    let file = router.open_async("/package/file.txt").unwrap();
+    file.load(sender, 2).unwrap();
+
+    for _ in 0..2 {
+        match receiver.recv().unwrap() {
+            AsyncReadResponse::LoadSuccess { data, cookie, .. } => {
+                let data = String::from_utf8(data).unwrap();
+                match cookie {
+                    1 => assert_eq!(data, "Hello, Subdir!"),
+                    2 => assert_eq!(data, "Hello, World!"),
+                    _ => panic!("Unexpected cookie"),
+                }
+            }
+            _ => panic!("Unexpected response"),
+        }
+    }
+}
+
+/// Writes a small pack into the system temp dir for the archive tests. Contains an uncompressed
+/// 'file.txt' and a zstd compressed 'folder/file.txt'.
+fn write_test_pack(name: &str) -> Utf8PathBuf {
+    let path = std::env::temp_dir().join(format!("aleph-vfs-{}-{}.pack", std::process::id(), name));
+    let path = Utf8PathBuf::from_path_buf(path).unwrap();
+
+    let file = std::fs::File::create(&path).unwrap();
+    let mut writer = PackWriter::new(io::BufWriter::new(file)).unwrap();
+    writer
+        .add_entry(
+            VPath::new("/file.txt"),
+            b"Hello, World!",
+            PackCompression::None,
+        )
+        .unwrap();
+    writer
+        .add_entry(
+            VPath::new("folder//file.txt"),
+            compressible_data().as_bytes(),
+            PackCompression::Zstd(3),
+        )
+        .unwrap();
+    writer.finish().unwrap();
+
+    path
+}
+
+fn compressible_data() -> String {
+    "Hello, Compressed! ".repeat(64)
+}
+
+#[test]
+pub fn pack_index_round_trip() {
+    let path = write_test_pack("index");
+
+    let mut file = std::fs::File::open(&path).unwrap();
+    let index = PackIndex::read(&mut file).unwrap();
+    assert_eq!(index.len(), 2);
+
+    let entry = index.get(VPath::new("file.txt")).unwrap();
+    assert_eq!(entry.compression, PackCompressionScheme::None);
+    assert_eq!(entry.size, 13);
+    assert_eq!(entry.size_uncompressed, 13);
+
+    let entry = index.get(VPath::new("/folder/file.txt")).unwrap();
+    assert_eq!(entry.compression, PackCompressionScheme::Zstd);
+    assert!(entry.size < entry.size_uncompressed);
+    assert_eq!(entry.size_uncompressed, compressible_data().len() as u64);
+
+    assert!(index.is_directory(VPath::new("/folder")));
+    let err = index.get(VPath::new("folder")).err().unwrap();
+    assert!(matches!(err.kind(), io::ErrorKind::IsADirectory));
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+pub fn pack_writer_rejects_conflicts() {
+    let mut writer = PackWriter::new(Vec::new()).unwrap();
+    writer
+        .add_entry(VPath::new("a/b"), b"b", PackCompression::None)
+        .unwrap();
+
+    let err = writer
+        .add_entry(VPath::new("/a/b"), b"b", PackCompression::None)
+        .err()
+        .unwrap();
+    assert!(matches!(err.kind(), io::ErrorKind::AlreadyExists));
+
+    let err = writer
+        .add_entry(VPath::new("a"), b"a", PackCompression::None)
+        .err()
+        .unwrap();
+    assert!(matches!(err.kind(), io::ErrorKind::AlreadyExists));
+
+    let err = writer
+        .add_entry(VPath::new("a/b/c"), b"c", PackCompression::None)
+        .err()
+        .unwrap();
+    assert!(matches!(err.kind(), io::ErrorKind::NotADirectory));
+
+    let err = writer
+        .add_entry(VPath::new("a/../c"), b"c", PackCompression::None)
+        .err()
+        .unwrap();
+    assert!(matches!(err.kind(), io::ErrorKind::InvalidFilename));
+
+    // Compression that doesn't shrink the entry falls back to storing it
+    writer
+        .add_entry(VPath::new("tiny"), b"x", PackCompression::Zstd(3))
+        .unwrap();
+    let pack = writer.finish().unwrap();
+    let index = PackIndex::read(&mut io::Cursor::new(pack)).unwrap();
+    let entry = index.get(VPath::new("tiny")).unwrap();
+    assert_eq!(entry.compression, PackCompressionScheme::None);
+}
+
+#[test]
+pub fn pack_index_rejects_bad_entry_count() {
+    let mut writer = PackWriter::new(Vec::new()).unwrap();
+    writer
+        .add_entry(VPath::new("a"), b"a", PackCompression::None)
+        .unwrap();
+    let mut pack = writer.finish().unwrap();
+
+    // Claim far more entries than the index could possibly hold
+    let count_offset = pack.len() - 16;
+    pack[count_offset..count_offset + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+    let result = PackIndex::read(&mut io::Cursor::new(pack));
+    assert!(result.is_err_and(|v| v.kind() == io::ErrorKind::InvalidData));
+}
+
+#[test]
+pub fn pack_index_rejects_impossible_sizes() {
+    let mut writer = PackWriter::new(Vec::new()).unwrap();
+    writer
+        .add_entry(
+            VPath::new("a"),
+            compressible_data().as_bytes(),
+            PackCompression::Zstd(3),
+        )
+        .unwrap();
+    let pack = writer.finish().unwrap();
+    assert!(PackIndex::read(&mut io::Cursor::new(pack.clone())).is_ok());
+
+    // Claim the entry decompresses to far more than zstd could ever expand it to. The index is
+    // the entry (45 bytes with a 1 byte path) followed by the 32 byte footer.
+    let mut pack = pack;
+    let size_offset = pack.len() - 32 - 8;
+    pack[size_offset..size_offset + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+    let result = PackIndex::read(&mut io::Cursor::new(pack));
+    assert!(result.is_err_and(|v| v.kind() == io::ErrorKind::InvalidData));
+
+    // Decompression checks the claimed size before allocating for it
+    let result = PackCompressionScheme::Zstd.decompress(&[0; 4], u64::MAX);
+    assert!(result.is_err_and(|v| v.kind() == io::ErrorKind::InvalidData));
+    let result = PackCompressionScheme::None.decompress(&[0; 4], 5);
+    assert!(result.is_err_and(|v| v.kind() == io::ErrorKind::InvalidData));
+}
+
+#[test]
+pub fn archive_mount() {
+    let path = write_test_pack("mount");
+    let layers = [LayerDesc {
+        mount_name: "pack",
+        layer: ArchiveLayer::new(path.clone()),
+    }];
+
+    let router = Router::new(layers).unwrap();
+
+    assert_eq!(
+        read_string(&router, "/pack/file.txt").unwrap(),
+        "Hello, World!"
+    );
+    assert_eq!(
+        read_string(&router, "/pack/folder/file.txt").unwrap(),
+        compressible_data()
+    );
+
+    // Reads must be clamped to the entry, and not spill into the neighbouring entry
+    let file = router.open("/pack/file.txt").unwrap();
+    assert_eq!(file.len().unwrap(), 13);
+    let mut buf = [0u8; 64];
+    assert_eq!(file.read_at(&mut buf, 7).unwrap(), 6);
+    assert_eq!(&buf[..6], b"World!");
+    assert_eq!(file.read_at(&mut buf, 100).unwrap(), 0);
+
+    let err = router.open("/pack/folder").err().unwrap();
+    assert!(matches!(err.kind(), io::ErrorKind::IsADirectory));
+
+    let err = router.open("/pack/no.txt").err().unwrap();
+    assert!(matches!(err.kind(), io::ErrorKind::NotFound));
+
+    let err = router.open("/pack/folder/../file.txt").err().unwrap();
+    assert!(matches!(err.kind(), io::ErrorKind::InvalidFilename));
+
+    let err = router.open_async("/pack/file.txt").err().unwrap();
+    assert!(matches!(err.kind(), io::ErrorKind::Unsupported));
+
+    // Packs are immutable so can't be watched
+    let err = router.watch("/pack").err().unwrap();
+    assert!(matches!(err.kind(), io::ErrorKind::Unsupported));
+    assert!(router.watch("/").unwrap().poll().unwrap().is_empty());
+
+    // Metadata reports the decompressed size of compressed entries
+    let metadata = router.metadata("/pack/folder/file.txt").unwrap();
+    assert!(metadata.is_file());
+    assert_eq!(metadata.len(), compressible_data().len() as u64);
+    assert!(router.metadata("/pack/folder").unwrap().is_dir());
+    assert!(!router.exists("/pack/no.txt").unwrap());
+
+    assert_eq!(
+        sorted_dir(&router, "/pack").unwrap(),
+        [
+            DirEntry::new("file.txt".to_string(), FileType::File),
+            DirEntry::new("folder".to_string(), FileType::Directory),
+        ]
+    );
+    assert_eq!(
+        sorted_dir(&router, "/pack/folder").unwrap(),
+        [DirEntry::new("file.txt".to_string(), FileType::File)]
+    );
+    let err = router.read_dir("/pack/file.txt").err().unwrap();
+    assert!(matches!(err.kind(), io::ErrorKind::NotADirectory));
+
+    drop(file);
+    drop(router);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+pub fn archive_async_read_test() {
+    let path = write_test_pack("async");
+    let queue = IoQueue::new(TopLevelHandleCache::new(2));
+    let layers = [LayerDesc {
+        mount_name: "pack",
+        layer: ArchiveLayer::new_with_io_queue(path.clone(), queue),
+    }];
+
+    let router = Router::new(layers).unwrap();
+
+    let (sender, receiver) = unbounded();
+
+    let stored = router.open_async("/pack/file.txt").unwrap();
+    let compressed = router.open_async("/pack/folder/file.txt").unwrap();
+    stored.load(sender.clone(), 1).unwrap();
+    compressed.load(sender.clone(), 2).unwrap();
+
+    for _ in 0..2 {
+        match receiver.recv().unwrap() {
+            AsyncReadResponse::LoadSuccess { data, cookie, .. } => {
+                let data = String::from_utf8(data).unwrap();
+                match cookie {
+                    1 => assert_eq!(data, "Hello, World!"),
+                    2 => assert_eq!(data, compressible_data()),
+                    _ => panic!("Unexpected cookie"),
+                }
+            }
+            _ => panic!("Unexpected response"),
+        }
+    }
+
+    // Reads are clamped to the end of the entry
+    let mut buf = [0u8; 64];
+    unsafe {
+        stored
+            .read_at(NonNull::from(&mut buf[..]), 7, sender.clone(), 3)
+            .unwrap();
+    }
+    match receiver.recv().unwrap() {
+        AsyncReadResponse::ReadSuccess {
+            buf: response_buf,
+            offset,
+            bytes_transferred,
+            cookie,
+            ..
+        } => {
+            assert_eq!(response_buf.len(), 64);
+            assert_eq!(offset, 7);
+            assert_eq!(bytes_transferred, 6);
+            assert_eq!(cookie, 3);
+        }
+        _ => panic!("Unexpected response"),
+    }
+    assert_eq!(&buf[..6], b"World!");
+
+    // An exact read past the end of the entry fails
+    unsafe {
+        stored
+            .read_exact_at(NonNull::from(&mut buf[..]), 0, sender.clone(), 4)
+            .unwrap();
+    }
+    match receiver.recv().unwrap() {
+        AsyncReadResponse::ReadFail { err, cookie, .. } => {
+            assert!(matches!(err.kind(), io::ErrorKind::UnexpectedEof));
+            assert_eq!(cookie, 4);
+        }
+        _ => panic!("Unexpected response"),
+    }
+
+    // Ranged reads of compressed entries are served from the decompressed data
+    let mut buf = [0u8; 10];
+    unsafe {
+        compressed
+            .read_exact_at(NonNull::from(&mut buf[..]), 19, sender.clone(), 5)
+            .unwrap();
+    }
+    match receiver.recv().unwrap() {
+        AsyncReadResponse::ReadSuccess {
+            bytes_transferred,
+            cookie,
+            ..
+        } => {
+            assert_eq!(bytes_transferred, 10);
+            assert_eq!(cookie, 5);
+        }
+        _ => panic!("Unexpected response"),
+    }
+    assert_eq!(&buf, b"Hello, Com");
+
+    // The decompressed entry is cached, so later reads complete without going through the queue
+    let mut buf = [0u8; 7];
+    unsafe {
+        compressed
+            .read_at(NonNull::from(&mut buf[..]), 38, sender, 6)
+            .unwrap();
+    }
+    match receiver.try_recv().unwrap() {
+        AsyncReadResponse::ReadSuccess {
+            bytes_transferred,
+            cookie,
+            ..
+        } => {
+            assert_eq!(bytes_transferred, 7);
+            assert_eq!(cookie, 6);
+        }
+        _ => panic!("Unexpected response"),
+    }
+    assert_eq!(&buf, b"Hello, ");
+
+    drop(router);
+    std::fs::remove_file(&path).unwrap();
+}
+
+/// Creates a fresh, empty directory in the system temp dir for tests that need to mutate files.
+fn make_test_dir(name: &str) -> Utf8PathBuf {
+    let path = std::env::temp_dir().join(format!("aleph-vfs-{}-{}", std::process::id(), name));
+    let path = Utf8PathBuf::from_path_buf(path).unwrap();
+    let _ = std::fs::remove_dir_all(&path);
+    std::fs::create_dir_all(&path).unwrap();
+    path
+}
+
+fn change(path: &str, kind: ChangeKind) -> ChangeEvent {
+    ChangeEvent {
+        path: VPathBuf::from(path),
+        kind,
+    }
+}
+
+#[test]
+pub fn directory_watch_test() {
+    let dir = make_test_dir("watch");
+    std::fs::write(dir.join("a.txt"), "a").unwrap();
+
+    let layers = [LayerDesc {
+        mount_name: "pkg",
+        layer: DirectoryLayer::new(dir.clone()),
+    }];
+    let router = Router::new(layers).unwrap();
+
+    let mut watcher = router.watch("/").unwrap();
+    let mut file_watcher = router.watch("/pkg/a.txt").unwrap();
+    assert!(watcher.poll().unwrap().is_empty());
+
+    std::fs::create_dir(dir.join("sub")).unwrap();
+    std::fs::write(dir.join("sub/b.txt"), "b").unwrap();
+    assert_eq!(
+        watcher.poll().unwrap(),
+        [change("/pkg/sub/b.txt", ChangeKind::Created)]
+    );
+    assert!(watcher.poll().unwrap().is_empty());
+
+    std::fs::write(dir.join("a.txt"), "a but longer").unwrap();
+    assert_eq!(
+        watcher.poll().unwrap(),
+        [change("/pkg/a.txt", ChangeKind::Modified)]
+    );
+
+    std::fs::remove_dir_all(dir.join("sub")).unwrap();
+    std::fs::remove_file(dir.join("a.txt")).unwrap();
+    assert_eq!(
+        watcher.poll().unwrap(),
+        [
+            change("/pkg/a.txt", ChangeKind::Removed),
+            change("/pkg/sub/b.txt", ChangeKind::Removed),
+        ]
+    );
+
+    // Watching a single file only reports that file
+    assert_eq!(
+        file_watcher.poll().unwrap(),
+        [change("/pkg/a.txt", ChangeKind::Removed)]
+    );
+
+    let err = router.watch("/pkg/no.txt").err().unwrap();
+    assert!(matches!(err.kind(), io::ErrorKind::NotFound));
+
+    drop(watcher);
+    drop(file_watcher);
+    drop(router);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+pub fn overlay_watch_test() {
+    let upper = make_test_dir("watch-upper");
+    let lower = make_test_dir("watch-lower");
+    std::fs::write(lower.join("f.txt"), "lower").unwrap();
+
+    let overlay = OverlayLayer::new([
+        OverlayEntry {
+            priority: 1,
+            layer: DirectoryLayer::new(upper.clone()),
+            whiteouts: vec![VPathBuf::from("hidden.txt")],
+        },
+        OverlayEntry {
+            priority: 0,
+            layer: DirectoryLayer::new(lower.clone()),
+            whiteouts: Vec::new(),
+        },
+    ])
+    .unwrap();
+    let layers = [LayerDesc {
+        mount_name: "pkg",
+        layer: overlay,
+    }];
+    let router = Router::new(layers).unwrap();
+
+    let mut watcher = router.watch("/pkg").unwrap();
+    assert!(watcher.poll().unwrap().is_empty());
+
+    // Shadowing a lower file changes what the path resolves to
+    std::fs::write(upper.join("f.txt"), "upper").unwrap();
+    assert_eq!(
+        watcher.poll().unwrap(),
+        [change("/pkg/f.txt", ChangeKind::Modified)]
+    );
+
+    // Changes to a shadowed file, or a whited out file, aren't visible
+    std::fs::write(lower.join("f.txt"), "lower but longer").unwrap();
+    std::fs::write(lower.join("hidden.txt"), "hidden").unwrap();
+    assert!(watcher.poll().unwrap().is_empty());
+
+    // Removing the upper file reveals the lower one again
+    std::fs::remove_file(upper.join("f.txt")).unwrap();
+    assert_eq!(
+        watcher.poll().unwrap(),
+        [change("/pkg/f.txt", ChangeKind::Modified)]
+    );
+
+    std::fs::remove_file(lower.join("f.txt")).unwrap();
+    std::fs::write(lower.join("g.txt"), "g").unwrap();
+    let mut events = watcher.poll().unwrap();
+    events.sort_by(|a, b| a.path.cmp(&b.path));
+    assert_eq!(
+        events,
+        [
+            change("/pkg/f.txt", ChangeKind::Removed),
+            change("/pkg/g.txt", ChangeKind::Created),
+        ]
+    );
+
+    drop(watcher);
+    drop(router);
+    std::fs::remove_dir_all(&upper).unwrap();
+    std::fs::remove_dir_all(&lower).unwrap();
+}
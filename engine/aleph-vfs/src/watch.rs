@@ -0,0 +1,121 @@
+//
+//
+// This file is a part of Aleph
+//
+// https://github.com/nathanvoglsam/aleph
+//
+// MIT License
+//
+// Copyright (c) 2020 Aleph Engine
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use std::io;
+
+use aleph_alloc::BBox;
+use aleph_alloc::instrumentation::system;
+
+use crate::VfsSystem;
+use crate::path::VPathBuf;
+
+/// The kind of change a [`ChangeEvent`] describes.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum ChangeKind {
+    /// A file was created.
+    Created,
+
+    /// The contents of an existing file changed.
+    Modified,
+
+    /// A file was removed.
+    Removed,
+}
+
+/// Describes a change to a single file within a vfs.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct ChangeEvent {
+    /// The path of the file that changed.
+    ///
+    /// Events produced by a [`Watcher`] carry the full vfs path, including the mount point. Events
+    /// produced by an [`ILayerWatcher`] carry a normalized path relative to the root of the layer,
+    /// with no leading separator.
+    pub path: VPathBuf,
+
+    /// What happened to the file.
+    pub kind: ChangeKind,
+}
+
+/// Interface for watching a subtree of a single [`crate::ILayer`] for changes. Created with
+/// [`crate::ILayer::watch`].
+pub trait ILayerWatcher: Send {
+    /// Checks for any changes since the watcher was created, or since the last call to `poll`.
+    /// An event for each changed file is pushed onto `events`.
+    ///
+    /// Only files are reported. Creating or removing a directory is reported as events for the
+    /// files inside it.
+    fn poll(&mut self, events: &mut Vec<ChangeEvent>) -> io::Result<()>;
+}
+
+/// Utility for boxing a watcher implementation into the tagged box types we use for allocation
+/// tracking.
+pub fn box_watcher<'a, T: ILayerWatcher + 'a>(
+    watcher: T,
+) -> BBox<dyn ILayerWatcher + 'a, VfsSystem> {
+    let (ptr, allocator) = BBox::into_raw_with_allocator(BBox::new_in(watcher, system()));
+    let ptr: *mut (dyn ILayerWatcher + 'a) = ptr;
+    unsafe { BBox::from_raw_in(ptr, allocator) }
+}
+
+/// Watches a subtree of a [`crate::Router`] for changes, created with
+/// [`crate::IRouterExt::watch`].
+///
+/// Watching is pull based, [`Watcher::poll`] must be called to find out what changed. This fits
+/// naturally into a frame loop. How expensive a poll is depends on the layers being watched, some
+/// layers may need to scan the whole watched subtree so it's wise to poll on a timer rather than
+/// every frame.
+///
+/// Layers that can't change, like packs, don't support watching. Watching the root of the vfs will
+/// watch every mount that supports it and skip the rest.
+pub struct Watcher<'vfs> {
+    pub(crate) watchers: Vec<(String, BBox<dyn ILayerWatcher + 'vfs, VfsSystem>)>,
+}
+
+impl<'vfs> Watcher<'vfs> {
+    /// Checks for any changes since the watcher was created, or since the last call to `poll`.
+    /// Returns an event for each changed file, with paths given as absolute vfs paths.
+    pub fn poll(&mut self) -> io::Result<Vec<ChangeEvent>> {
+        let mut events = Vec::new();
+        let mut layer_events = Vec::new();
+        for (mount_name, watcher) in self.watchers.iter_mut() {
+            layer_events.clear();
+            watcher.poll(&mut layer_events)?;
+
+            for event in layer_events.drain(..) {
+                let mut path = VPathBuf::from(format!("/{mount_name}"));
+                path.push(&event.path);
+                events.push(ChangeEvent {
+                    path,
+                    kind: event.kind,
+                });
+            }
+        }
+        Ok(events)
+    }
+}
@@ -27,9 +27,14 @@
 // SOFTWARE.
 //
 
+use std::io::BufWriter;
+
 use aleph_target::Platform;
+use aleph_vfs::pack::{PackCompression, PackWriter};
+use aleph_vfs::path::VPath;
 use anyhow::anyhow;
-use clap::ArgMatches;
+use camino::{Utf8Path, Utf8PathBuf};
+use clap::{Arg, ArgMatches};
 
 use crate::commands::{ISubcommand, arch_arg, config_arg, platform_arg};
 use crate::project::AlephProject;
@@ -48,15 +53,62 @@ impl ISubcommand for Bundle {
             copy build artefacts into project dirs and generate a 'bundle' for the target \
             platform. On iOS an App Bundle.\
         ";
+        let pack = Arg::new("pack")
+            .long("pack")
+            .help("A directory to pack into a vfs pack file.")
+            .long_help(
+                "A directory to pack into a vfs pack file. When given, only the pack is produced \
+                and no platform bundle is built.",
+            );
+        let pack_output = Arg::new("pack-output")
+            .long("pack-output")
+            .help("The output pack file.")
+            .long_help(
+                "The output pack file. If unspecified the filename is derived from the packed \
+                directory's name.",
+            );
+        let compression = Arg::new("compression")
+            .long("compression")
+            .value_parser(["none", "zstd"])
+            .default_value("zstd")
+            .help("The compression scheme to apply to each entry in the pack.");
+        let compression_level = Arg::new("compression-level")
+            .long("compression-level")
+            .value_parser(clap::value_parser!(i32))
+            .help("The compression level to use with the selected compression scheme.")
+            .long_help("The compression level to use with the selected compression scheme. zstd accepts 1-22. If unspecified a sensible default is used.");
         clap::Command::new(self.name())
             .about("Bundles the game for the requested platform/architecture/config")
             .long_about(LONG)
             .arg(platform_arg())
             .arg(arch_arg())
             .arg(config_arg())
+            .arg(pack)
+            .arg(pack_output)
+            .arg(compression)
+            .arg(compression_level)
     }
 
     fn exec(&mut self, project: &AlephProject, mut matches: ArgMatches) -> anyhow::Result<()> {
+        if let Some(pack_dir) = matches.remove_one::<String>("pack") {
+            let pack_dir = Utf8PathBuf::from(pack_dir);
+            let output = match matches.remove_one::<String>("pack-output") {
+                Some(v) => Utf8PathBuf::from(v),
+                None => pack_dir.with_extension("pack"),
+            };
+
+            let compression_level: Option<i32> = matches.remove_one("compression-level");
+            let compression: String = matches
+                .remove_one("compression")
+                .expect("compression has a default");
+            let compression = match compression.as_str() {
+                "zstd" => PackCompression::Zstd(compression_level.unwrap_or(19).clamp(1, 22)),
+                _ => PackCompression::None,
+            };
+
+            return Self::pack(&pack_dir, &output, compression);
+        }
+
         let platform_arg: String = matches
             .remove_one("platform")
             .expect("platform should have a default");
@@ -109,6 +161,53 @@ impl ISubcommand for Bundle {
 }
 
 impl Bundle {
+    /// Packs every file inside `dir` into a single vfs pack file at `output`. Entries are named
+    /// by their path relative to `dir`.
+    fn pack(dir: &Utf8Path, output: &Utf8Path, compression: PackCompression) -> anyhow::Result<()> {
+        if !dir.is_dir() {
+            return Err(anyhow!("Pack input \"{}\" is not a directory", dir));
+        }
+
+        let mut files = Vec::new();
+        Self::collect_files(dir, &mut files)?;
+
+        // Sort so the same input directory always produces the same pack
+        files.sort();
+
+        let file = std::fs::File::create(output)?;
+        let mut writer = PackWriter::new(BufWriter::new(file))?;
+        for file in files.iter() {
+            let name = file.strip_prefix(dir)?;
+            let name = name
+                .components()
+                .map(|v| v.as_str())
+                .collect::<Vec<_>>()
+                .join("/");
+
+            log::info!("Packing \"{}\"", name);
+            let data = std::fs::read(file)?;
+            writer.add_entry(VPath::new(&name), &data, compression)?;
+        }
+        writer.finish()?;
+
+        log::info!("Wrote {} entries to \"{}\"", files.len(), output);
+
+        Ok(())
+    }
+
+    fn collect_files(dir: &Utf8Path, files: &mut Vec<Utf8PathBuf>) -> anyhow::Result<()> {
+        for entry in dir.read_dir_utf8()? {
+            let entry = entry?;
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                Self::collect_files(entry.path(), files)?;
+            } else if file_type.is_file() {
+                files.push(entry.into_path());
+            }
+        }
+        Ok(())
+    }
+
     fn windows(
         &self,
         _project: &AlephProject,
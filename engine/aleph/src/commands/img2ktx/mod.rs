@@ -33,7 +33,7 @@ use aleph_image::{
     ColorType, DowncastImageBuffer, DynamicImageBuffer, DynamicTextureBuffer, IPixelStorage,
     ImageBuffer, PixelFormat, TextureBuffer, TextureType,
 };
-use aleph_ktx::{KtxDocumentDescription, VkFormat};
+use aleph_ktx::{KtxDocumentDescription, SuperCompression, VkFormat};
 use anyhow::anyhow;
 use camino::Utf8Path;
 use clap::parser::Values;
@@ -70,11 +70,23 @@ impl ISubcommand for Image2Ktx {
             .long("is-cube")
             .help("Whether the input image set describes a cube map.")
             .long_help("Whether the input image set describes a cube map. Must provide six images, ordered by +X, -X, +Y, -Y, +Z, -Z");
+        let compression = Arg::new("compression")
+            .long("compression")
+            .value_parser(["none", "zstd", "zlib"])
+            .default_value("none")
+            .help("The supercompression scheme to apply to each mip level.");
+        let compression_level = Arg::new("compression-level")
+            .long("compression-level")
+            .value_parser(clap::value_parser!(u32))
+            .help("The compression level to use with the selected supercompression scheme.")
+            .long_help("The compression level to use with the selected supercompression scheme. zstd accepts 1-22, zlib accepts 0-9. If unspecified a sensible default is used.");
         Command::new(self.name())
             .about("Converts the given input image into the KTX2 format")
             .arg(input)
             .arg(output)
             .arg(is_cube)
+            .arg(compression)
+            .arg(compression_level)
     }
 
     fn exec(&mut self, _project: &AlephProject, mut matches: ArgMatches) -> anyhow::Result<()> {
@@ -96,6 +108,16 @@ impl ISubcommand for Image2Ktx {
 
         let is_cube = matches.get_flag("is-cube");
 
+        let compression_level: Option<u32> = matches.remove_one("compression-level");
+        let compression: String = matches
+            .remove_one("compression")
+            .expect("compression has a default");
+        let compression = match compression.as_str() {
+            "zstd" => SuperCompression::Zstd(compression_level.unwrap_or(19).min(22) as i32),
+            "zlib" => SuperCompression::Zlib(compression_level.unwrap_or(9).min(9)),
+            _ => SuperCompression::None,
+        };
+
         // Make sure we have enough input images to encode a cubemap(array)
         if is_cube {
             if input_files.len() % 6 != 0 {
@@ -145,6 +167,7 @@ impl ISubcommand for Image2Ktx {
 
         // Setup mip state in common code to keep the match arms shorter
         let mut ktx = KtxDocumentDescription::new();
+        ktx.super_compression(compression);
 
         match images.get_color_type() {
             ColorType::R8Unorm => ktx.format(VkFormat::R8_UNORM),
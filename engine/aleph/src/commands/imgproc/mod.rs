@@ -299,11 +299,10 @@ where
         let level = u32::try_from(level).unwrap();
 
         let src = doc
-            .get_level_info(level)
-            .inspect_err(|e| log::error!("Failed to get level {level} in KTX doc: {e:?}"))?;
-        let src = &data[src.to_slice_range()];
+            .level_data(data, level)
+            .inspect_err(|e| log::error!("Failed to read level {level} in KTX doc: {e:?}"))?;
 
-        let img = load_from_ktx::<R, C, P>(src, doc, layer);
+        let img = load_from_ktx::<R, C, P>(&src, doc, layer);
         images.push(img);
     }
     Ok(images)
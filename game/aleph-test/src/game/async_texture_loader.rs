@@ -202,11 +202,11 @@ fn load_on_threadpool(
                 let num_rows = desc.num_rows_for_level(level);
                 let row_bytes = desc.row_bytes_for_level(level);
                 let row_bytes_padded = desc.upload_row_bytes_for_level(level);
-                let src = doc
-                    .get_level_info(level)
-                    .inspect_err(|e| log::error!("Failed to get level {level} in KTX doc: {e:?}"))
+                let level_data = doc
+                    .level_data(&data, level)
+                    .inspect_err(|e| log::error!("Failed to read level {level} in KTX doc: {e:?}"))
                     .ok()?;
-                let mut src = &data[src.to_slice_range()];
+                let mut src = level_data.as_ref();
                 let dst = upload.data.offset_for_level(level)?;
                 let mut dst = &mut upload.buffer.bytes_mut()[dst..];
                 for _ in 0..num_rows {
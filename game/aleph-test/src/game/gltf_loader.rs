@@ -31,9 +31,10 @@ use std::sync::Arc;
 
 use aleph_device_allocators::UploadBumpAllocator;
 use aleph_engine::any::AnyArc;
-use aleph_engine::interfaces::components::{StaticMesh, Transform};
+use aleph_engine::interfaces::components::{LocalTransform, StaticMesh, Transform};
 use aleph_engine::interfaces::ecs::World;
-use aleph_engine::interfaces::math::{Mat4, Rotor3, ToDouble, Vec3, Vec4};
+use aleph_engine::interfaces::ecs::entity::EntityHandle;
+use aleph_engine::interfaces::math::{Rotor3, ToDouble, Vec3};
 use aleph_engine::interfaces::renderer::{
     BufferHandle, BufferObject, BufferObjectDesc, BufferUploadDesc, Material, MaterialBinding,
     MaterialInstanceHandle, MaterialInstanceObject, PollCompleteError, Renderer, ResourceCommand,
@@ -278,46 +279,39 @@ pub fn load_scene(
         mesh_table: &[Vec<(BufferHandle, BufferHandle)>],
         tex_table: &[Option<TextureStreamingRequest>],
         mat_table: &[MaterialInstanceHandle],
-        parent_transform: Mat4,
+        parent: Option<EntityHandle>,
         node: &gltf::Node,
     ) {
-        let [col1, col2, col3, col4] = node.transform().matrix();
-        let self_transform = Mat4::new(
-            Vec4::from(col1),
-            Vec4::from(col2),
-            Vec4::from(col3),
-            Vec4::from(col4),
-        );
-
-        let world_transform = parent_transform * self_transform;
+        let (t, r, s) = node.transform().decomposed();
+        let local_transform = LocalTransform {
+            position: Vec3::from(t).to_double(),
+            rotation: Rotor3::from_quaternion_array(r),
+            scale: Vec3::from(s),
+        };
 
-        let (t, r, s) = gltf::scene::Transform::Matrix {
-            matrix: [
-                *world_transform.cols[0].as_array(),
-                *world_transform.cols[1].as_array(),
-                *world_transform.cols[2].as_array(),
-                *world_transform.cols[3].as_array(),
-            ],
+        // Every node gets an entity so the node hierarchy is preserved. The world transforms are
+        // computed from the local transforms by the transform propagation system.
+        let entity = world.extend_one((local_transform, Transform::identity()));
+        if let Some(parent) = parent {
+            world.set_parent(entity, parent).unwrap();
         }
-        .decomposed();
 
         if let Some(mesh) = node.mesh() {
             for (prim, (idx, vtx)) in mesh.primitives().zip(mesh_table[mesh.index()].iter()) {
                 let mat = prim.material().index().unwrap();
                 match prim.material().alpha_mode() {
                     AlphaMode::Opaque => {
-                        let transform = Transform {
-                            position: Vec3::from(t).to_double(),
-                            rotation: Rotor3::from_quaternion_array(r),
-                            scale: Vec3::from(s),
-                        };
-
                         let static_mesh = StaticMesh {
                             vtx: *vtx,
                             idx: *idx,
                             material_instance: mat_table[mat],
                         };
-                        let _entity = world.extend_one((transform, static_mesh));
+                        let primitive = world.extend_one((
+                            LocalTransform::identity(),
+                            Transform::identity(),
+                            static_mesh,
+                        ));
+                        world.set_parent(primitive, entity).unwrap();
                     }
                     #[allow(unreachable_patterns)]
                     _ => {}
@@ -333,13 +327,12 @@ pub fn load_scene(
                 mesh_table,
                 tex_table,
                 mat_table,
-                world_transform,
+                Some(entity),
                 &node,
             );
         }
     }
 
-    let root_transform = Mat4::identity();
     if let Some(scene) = document.default_scene() {
         for node in scene.nodes() {
             process_node(
@@ -349,7 +342,7 @@ pub fn load_scene(
                 &mesh_table,
                 &tex_table,
                 &mat_table,
-                root_transform,
+                None,
                 &node,
             );
         }